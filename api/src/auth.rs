@@ -13,6 +13,8 @@
 // limitations under the License.
 
 use crate::router::{Handler, HandlerObj, ResponseFuture};
+use crate::tokens::{ApiTokens, AuthContext, Scope};
+use crate::util::to_base64;
 use crate::web::response;
 
 use futures::future::ok;
@@ -86,6 +88,170 @@ impl Handler<Full<Bytes>> for BasicAuthURIMiddleware {
     }
 }
 
+/// A legacy basic auth secret, the scopes it grants and where it's valid.
+struct BasicAuthSecret {
+    api_basic_auth: String,
+    context: AuthContext,
+    target_uri: Option<String>,
+}
+
+// API Token Authentication Middleware
+//
+// Authenticates the caller with a bearer API token (or one of the basic
+// auth secrets), enforces the scope of the v1 routes and attaches the caller
+// to the request, the v2 handlers checking the scope of each method.
+pub struct TokenAuthMiddleware {
+    tokens: ApiTokens,
+    basic_auths: Vec<BasicAuthSecret>,
+    api_secret_set: bool,
+    foreign_api_secret_set: bool,
+}
+
+impl TokenAuthMiddleware {
+    /// The api secret keeps granting everything and the foreign api secret
+    /// the foreign API, anonymous callers keep the access they had on each
+    /// path with those secrets alone.
+    pub fn new(
+        tokens: ApiTokens,
+        api_secret: Option<String>,
+        foreign_api_secret: Option<String>,
+    ) -> TokenAuthMiddleware {
+        let basic_auth =
+            |secret: &str| "Basic ".to_string() + &to_base64(&format!("epic:{}", secret));
+        let mut basic_auths = vec![];
+        if let Some(secret) = &api_secret {
+            basic_auths.push(BasicAuthSecret {
+                api_basic_auth: basic_auth(secret),
+                context: AuthContext {
+                    name: "api_secret".to_owned(),
+                    scopes: Scope::all(),
+                },
+                target_uri: None,
+            });
+        }
+        if let Some(secret) = &foreign_api_secret {
+            basic_auths.push(BasicAuthSecret {
+                api_basic_auth: basic_auth(secret),
+                context: AuthContext {
+                    name: "foreign_api_secret".to_owned(),
+                    scopes: Scope::foreign(),
+                },
+                target_uri: Some("/v2/foreign".to_owned()),
+            });
+        }
+        TokenAuthMiddleware {
+            tokens,
+            basic_auths,
+            api_secret_set: api_secret.is_some(),
+            foreign_api_secret_set: foreign_api_secret.is_some(),
+        }
+    }
+
+    /// What a caller without credentials can do on the provided path: the api
+    /// secret guards the v1 and v2 owner APIs, the foreign api secret the v2
+    /// foreign API, the other paths are open.
+    fn anonymous(&self, path: &str) -> AuthContext {
+        let guarded = if is_under(path, "/v2/foreign") {
+            self.foreign_api_secret_set
+        } else if is_under(path, "/v1") || is_under(path, "/v2/owner") {
+            self.api_secret_set
+        } else {
+            false
+        };
+        AuthContext {
+            name: "anonymous".to_owned(),
+            scopes: if guarded { vec![] } else { Scope::all() },
+        }
+    }
+
+    /// The caller, None if the provided credentials are invalid.
+    fn authenticate(&self, req: &Request<hyper::body::Incoming>) -> Option<AuthContext> {
+        let path = req.uri().path();
+        let auth = match req.headers().get(AUTHORIZATION) {
+            Some(auth) => auth.as_bytes(),
+            None => return Some(self.anonymous(path)),
+        };
+        if let Some(token) = auth.strip_prefix(b"Bearer ") {
+            let token = std::str::from_utf8(token).ok()?;
+            return self.tokens.authenticate(token.trim()).map(|t| AuthContext {
+                name: t.name.clone(),
+                scopes: t.scopes.clone(),
+            });
+        }
+        self.basic_auths
+            .iter()
+            .filter(|b| match &b.target_uri {
+                Some(uri) => is_under(path, uri),
+                None => true,
+            })
+            .find(|b| auth.ct_eq(b.api_basic_auth.as_bytes()).unwrap_u8() == 1)
+            .map(|b| b.context.clone())
+    }
+}
+
+impl Handler<Full<Bytes>> for TokenAuthMiddleware {
+    fn call(
+        &self,
+        mut req: Request<hyper::body::Incoming>,
+        mut handlers: Box<dyn Iterator<Item = HandlerObj>>,
+    ) -> ResponseFuture {
+        let next_handler = match handlers.next() {
+            Some(h) => h,
+            None => return response(StatusCode::INTERNAL_SERVER_ERROR, "no handler found"),
+        };
+        if req.method().as_str() == "OPTIONS" {
+            return next_handler.call(req, handlers);
+        }
+        let context = match self.authenticate(&req) {
+            Some(context) => context,
+            None => return unauthorized_response(&EPIC_BASIC_REALM),
+        };
+        if let Some(scope) = Scope::for_route(req.uri().path()) {
+            if !context.allows(scope) {
+                if req.headers().contains_key(AUTHORIZATION) {
+                    return forbidden_response();
+                }
+                return unauthorized_response(&EPIC_BASIC_REALM);
+            }
+            if scope.is_admin() {
+                info!(
+                    "API audit: {} called {} {}",
+                    context.name,
+                    req.method(),
+                    req.uri().path()
+                );
+            }
+        }
+        req.extensions_mut().insert(context);
+        next_handler.call(req, handlers)
+    }
+}
+
+/// Whether the path is the provided prefix or one of its subpaths.
+fn is_under(path: &str, prefix: &str) -> bool {
+    path == prefix || path.starts_with(&format!("{}/", prefix))
+}
+
+fn forbidden_response() -> ResponseFuture {
+    let body = boxed_body(
+        r#"{
+            "jsonrpc": "2.0",
+            "error": {
+                "code": -32600,
+                "message": "Forbidden"
+            },
+            "id": null
+        }"#
+        .to_string(),
+    );
+    let response = Response::builder()
+        .status(StatusCode::FORBIDDEN)
+        .header("content-type", "application/json")
+        .body(body)
+        .unwrap();
+    Box::pin(ok(response))
+}
+
 fn unauthorized_response(basic_realm: &HeaderValue) -> ResponseFuture {
     let body = boxed_body(
         r#"{
@@ -106,3 +272,31 @@ fn unauthorized_response(basic_realm: &HeaderValue) -> ResponseFuture {
         .unwrap();
     Box::pin(ok(response))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_anonymous_scopes() {
+        let open = |auth: &TokenAuthMiddleware, path: &str| !auth.anonymous(path).scopes.is_empty();
+
+        // api secret alone: the foreign API stays open
+        let auth = TokenAuthMiddleware::new(ApiTokens::default(), Some("s".to_owned()), None);
+        assert!(!open(&auth, "/v1/chain"));
+        assert!(!open(&auth, "/v1/pool/push_tx"));
+        assert!(!open(&auth, "/v2/owner"));
+        assert!(open(&auth, "/v2/foreign"));
+        assert!(open(&auth, "/v2/tor"));
+
+        // foreign api secret alone: only the foreign API is guarded
+        let auth = TokenAuthMiddleware::new(ApiTokens::default(), None, Some("f".to_owned()));
+        assert!(open(&auth, "/v1/chain"));
+        assert!(open(&auth, "/v2/owner"));
+        assert!(!open(&auth, "/v2/foreign"));
+        assert!(open(&auth, "/v2/foreignx"));
+
+        let auth = TokenAuthMiddleware::new(ApiTokens::default(), None, None);
+        assert!(open(&auth, "/v1/chain") && open(&auth, "/v2/foreign"));
+    }
+}
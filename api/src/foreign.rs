@@ -19,18 +19,21 @@ use crate::core::core::hash::Hash;
 use crate::core::core::transaction::Transaction;
 use crate::core::core::Block;
 use crate::handlers::blocks_api::{BlockHandler, HeaderHandler};
-use crate::handlers::chain_api::{ChainHandler, KernelHandler, OutputHandler};
+use crate::handlers::chain_api::{
+	ChainHandler, FoundationLevyHandler, KernelHandler, OutputHandler,
+};
 use crate::handlers::mining_api::{
 	BlockTemplate, CoinbaseData, FinalizedBlockTemplate, MiningHandler,
 };
-use crate::handlers::pool_api::PoolHandler;
+use crate::handlers::pool_api::{PoolHandler, TxStatusHandler};
 use crate::handlers::transactions_api::TxHashSetHandler;
 use crate::handlers::version_api::VersionHandler;
 use crate::pool::{self, BlockChain, PoolAdapter, PoolEntry};
 use crate::rest::*;
 use crate::types::{
-	BlockHeaderPrintable, BlockPrintable, LocatedTxKernel, OutputListing, OutputPrintable, Tip,
-	Version,
+	BlockHeaderPrintable, BlockListing, BlockPrintable, BlockStats, ForkScheduleInfo,
+	FoundationLevyAudit, LocatedTxKernel, NetworkStats, OutputListing, OutputPrintable, Supply,
+	Tip, TxStatus, Version,
 };
 use crate::util::RwLock;
 use epic_core::core::TxKernel;
@@ -137,6 +140,52 @@ where
 		block_handler.get_block(&hash, true, true)
 	}
 
+	/// Gets summaries of the blocks between two heights, for block explorers.
+	/// Results are paginated, at most `max` blocks (capped to 1000) are
+	/// returned out of at most 1000 heights walked, `next_height` tells where
+	/// the next page starts.
+	///
+	/// # Arguments
+	/// * `start_height` - height of the first block, inclusive.
+	/// * `end_height` - height of the last block, inclusive.
+	/// * `max` - maximum number of blocks returned.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`BlockListing`](types/struct.BlockListing.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_block_summaries(
+		&self,
+		start_height: u64,
+		end_height: u64,
+		max: u64,
+	) -> Result<BlockListing, Error> {
+		let block_handler = BlockHandler {
+			chain: self.chain.clone(),
+		};
+		block_handler.get_block_summaries(start_height, end_height, max)
+	}
+
+	/// Gets weight, cut-through and fee statistics of the block at a given height.
+	///
+	/// # Arguments
+	/// * `height` - block height.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`BlockStats`](types/struct.BlockStats.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_block_stats(&self, height: u64) -> Result<BlockStats, Error> {
+		let block_handler = BlockHandler {
+			chain: self.chain.clone(),
+		};
+		block_handler.get_block_stats(height)
+	}
+
 	/// Returns a block template for mining (header, txs, difficulty, etc.)
 	///
 	/// # Returns
@@ -215,6 +264,92 @@ where
 		chain_handler.get_tip()
 	}
 
+	/// Per proof of work algorithm mining statistics over the last blocks:
+	/// block count against the policy share, average solve time, difficulty
+	/// trend and estimated hashrate.
+	///
+	/// # Arguments
+	/// * `window` - number of blocks to look at, at most a week of blocks.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`NetworkStats`](types/struct.NetworkStats.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_network_stats(&self, window: u64) -> Result<NetworkStats, Error> {
+		let chain_handler = ChainHandler {
+			chain: self.chain.clone(),
+		};
+		chain_handler.get_network_stats(window)
+	}
+
+	/// The hard fork schedule of the chain: the heights at which the header
+	/// version, the allowed feijoada policies and the difficulty adjustment
+	/// change, with the rules in force at a height.
+	///
+	/// # Arguments
+	/// * `height` - height to give the rules for, the next block height if None.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`ForkScheduleInfo`](types/struct.ForkScheduleInfo.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_fork_schedule(&self, height: Option<u64>) -> Result<ForkScheduleInfo, Error> {
+		let chain_handler = ChainHandler {
+			chain: self.chain.clone(),
+		};
+		chain_handler.get_fork_schedule(height)
+	}
+
+	/// Audits the foundation levy: checks every foundation height block of
+	/// the range holds the foundation output and kernel of the foundation
+	/// file, and sums the levy paid against the levy schedule.
+	///
+	/// # Arguments
+	/// * `start_height` - first height to audit, 1 if None.
+	/// * `end_height` - last height to audit, the chain head if None.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`FoundationLevyAudit`](types/struct.FoundationLevyAudit.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn audit_foundation_levy(
+		&self,
+		start_height: Option<u64>,
+		end_height: Option<u64>,
+	) -> Result<FoundationLevyAudit, Error> {
+		let foundation_levy_handler = FoundationLevyHandler {
+			chain: self.chain.clone(),
+		};
+		foundation_levy_handler.audit_foundation_levy(start_height, end_height)
+	}
+
+	/// The coins emitted up to a height, split between the miners and the
+	/// foundation, with the current block reward and the next halving. The
+	/// supply is cross-checked against the unspent outputs less the kernel
+	/// sums at the head, and against the block sums when not pruned.
+	///
+	/// # Arguments
+	/// * `height` - height to give the supply at, the chain head if None.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`Supply`](types/struct.Supply.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_supply(&self, height: Option<u64>) -> Result<Supply, Error> {
+		let chain_handler = ChainHandler {
+			chain: self.chain.clone(),
+		};
+		chain_handler.get_supply(height)
+	}
+
 	/// Returns a [`LocatedTxKernel`](types/struct.LocatedTxKernel.html) based on the kernel excess.
 	/// The `min_height` and `max_height` parameters are both optional.
 	/// If not supplied, `min_height` will be set to 0 and `max_height` will be set to the head of the chain.
@@ -387,6 +522,32 @@ where
 		pool_handler.get_unconfirmed_transactions()
 	}
 
+	/// Returns where the transaction with the provided kernel excess is:
+	/// in the stempool, in the txpool, mined or unknown to this node.
+	///
+	/// # Arguments
+	/// * `kernel_excess` - kernel excess of the transaction, in hex.
+	/// * `wait_secs` - optional time (capped at 30 seconds) to wait for the
+	/// status to change before returning, for long polling. Can't be called
+	/// in a JSON-RPC batch.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`TxStatus`](types/enum.TxStatus.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+	pub fn get_tx_status(
+		&self,
+		kernel_excess: String,
+		wait_secs: Option<u64>,
+	) -> Result<TxStatus, Error> {
+		let tx_status_handler = TxStatusHandler {
+			chain: self.chain.clone(),
+			tx_pool: self.tx_pool.clone(),
+		};
+		tx_status_handler.get_tx_status(kernel_excess, wait_secs)
+	}
+
 	/// Push new transaction to our local transaction pool.
 	///
 	/// # Arguments
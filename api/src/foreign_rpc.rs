@@ -23,8 +23,9 @@ use crate::handlers::mining_api::{BlockTemplate, CoinbaseData, FinalizedBlockTem
 use crate::pool::{BlockChain, PoolAdapter, PoolEntry};
 use crate::rest::Error;
 use crate::types::{
-	BlockHeaderPrintable, BlockPrintable, LocatedTxKernel, OutputListing, OutputPrintable, Tip,
-	Version,
+	BlockHeaderPrintable, BlockListing, BlockPrintable, BlockStats, ForkScheduleInfo,
+	FoundationLevyAudit, LocatedTxKernel, NetworkStats, OutputListing, OutputPrintable, Supply,
+	Tip, TxStatus, Version,
 };
 use crate::util;
 use epic_core::core::TxKernel;
@@ -269,6 +270,138 @@ pub trait ForeignRpc: Sync + Send {
 		commit: Option<String>,
 	) -> Result<Vec<BlockPrintable>, Error>;
 
+	/**
+	Networked version of [Foreign::get_block_summaries](struct.Node.html#method.get_block_summaries).
+
+	# Json rpc example
+
+	```
+	# epic_api::doctest_helper_json_rpc_foreign_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_block_summaries",
+		"params": [0, 0, 1],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+			"last_retrieved_height": 0,
+			"next_height": null,
+			"blocks": [
+				{
+				"header": {
+					"edge_bits": 29,
+					"hash": "454018a56d86e37611bdcabc7de670305c3f3dc9675e314b437f1adc29430851",
+					"height": 0,
+					"kernel_root": "4d9ddf437dfbb86f8563ac4e96a0d86842eda609a5125244f43261d4188292e4",
+					"nonce": 41,
+					"output_root": "b10fe806a4373d9b8d8edde98a4ec39d726b542036971c2f14c0738b0605c9cd",
+					"prev_root": "00000000000000000004de683e7aa4d35c51f46ec76c6852b0f3161bd1e2e00e",
+					"previous": "0000000000000000000000000000000000000000000000000000000000000000",
+					"proof": "Cuckoo",
+					"range_proof_root": "e05333e51d9294f08cd6d2d7cea19de2843f92c285a61fd5d61d771c3ac74222",
+					"secondary_scaling": 1856,
+					"solution": {
+						"Cuckoo": [
+						4391451, 36730677, 38198400, 38797304, 60700446, 72910191, 73050441,
+						110099816, 140885802, 145512513, 149311222, 149994636, 157557529,
+						160778700, 162870981, 179649435, 194194460, 227378628, 230933064,
+						252046196, 272053956, 277878683, 288331253, 290266880, 293973036,
+						305315023, 321927758, 353841539, 356489212, 373843111, 381697287,
+						389274717, 403108317, 409994705, 411629694, 431823422, 441976653,
+						521469643, 521868369, 523044572, 524964447, 530250249
+						]
+					},
+					"timestamp": "2019-08-09T17:04:38+00:00",
+					"total_difficulty": {
+						"cuckaroo": 4,
+						"cuckatoo": 16384,
+						"progpow": 1073741824,
+						"randomx": 4194304
+					},
+					"total_kernel_offset": "0000000000000000000000000000000000000000000000000000000000000000",
+					"version": 6
+				},
+				"pow_algorithm": "cuckaroo",
+				"difficulty": {
+					"cuckaroo": 4,
+					"cuckatoo": 16384,
+					"progpow": 1073741824,
+					"randomx": 4194304
+				},
+				"num_inputs": 0,
+				"num_outputs": 0,
+				"num_kernels": 0,
+				"num_txs": 0,
+				"fees": 0,
+				"total_reward": 1600000000,
+				"has_foundation_output": false
+				}
+			]
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn get_block_summaries(
+		&self,
+		start_height: u64,
+		end_height: u64,
+		max: u64,
+	) -> Result<BlockListing, Error>;
+
+	/**
+	Networked version of [Foreign::get_block_stats](struct.Node.html#method.get_block_stats).
+
+	# Json rpc example
+
+	```
+	# epic_api::doctest_helper_json_rpc_foreign_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_block_stats",
+		"params": [0],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+			"height": 0,
+			"hash": "454018a56d86e37611bdcabc7de670305c3f3dc9675e314b437f1adc29430851",
+			"weight": 0,
+			"fullness": 0.0,
+			"num_inputs": 0,
+			"num_outputs": 0,
+			"num_kernels": 0,
+			"spent_ratio": 0.0,
+			"fees": 0,
+			"fee_rate": 0.0,
+			"min_fee": 0,
+			"max_fee": 0
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn get_block_stats(&self, height: u64) -> Result<BlockStats, Error>;
+
 	/**
 	Networked version of [Foreign::get_version](struct.Node.html#method.get_version).
 
@@ -337,6 +470,271 @@ pub trait ForeignRpc: Sync + Send {
 	 */
 	fn get_tip(&self) -> Result<Tip, Error>;
 
+	/**
+	Networked version of [Foreign::get_network_stats](struct.Node.html#method.get_network_stats).
+
+	# Json rpc example
+
+	```
+	# epic_api::doctest_helper_json_rpc_foreign_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_network_stats",
+		"params": [1440],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+			"height": 1500000,
+			"window": 1440,
+			"average_block_time": 60.4,
+			"policy": 5,
+			"next_algorithm": "cuckatoo",
+			"algorithms": [
+				{
+				"algorithm": "cuckatoo",
+				"blocks": 1224,
+				"share": 85.0,
+				"policy_share": 85.0,
+				"average_solve_time": 60.1,
+				"difficulty": 2112,
+				"average_difficulty": 2098,
+				"difficulty_trend": 1.3,
+				"hashrate": 34.9
+				},
+				{
+				"algorithm": "randomx",
+				"blocks": 216,
+				"share": 15.0,
+				"policy_share": 15.0,
+				"average_solve_time": 62.2,
+				"difficulty": 81253,
+				"average_difficulty": 80911,
+				"difficulty_trend": -0.4,
+				"hashrate": 1300.8
+				}
+			]
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn get_network_stats(&self, window: u64) -> Result<NetworkStats, Error>;
+
+	/**
+	Networked version of [Foreign::get_fork_schedule](struct.Node.html#method.get_fork_schedule).
+
+	# Json rpc example
+
+	```
+	# epic_api::doctest_helper_json_rpc_foreign_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_fork_schedule",
+		"params": [100],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+			"chain_type": "AutomatedTesting",
+			"version": 1,
+			"height": 100,
+			"policy": 0,
+			"active": {
+				"name": "difficulty era 1",
+				"height": 50,
+				"header_version": 7,
+				"allowed_policies": 1,
+				"difficulty_era": "Era1"
+			},
+			"next": {
+				"name": "policy era 2",
+				"height": 480961,
+				"header_version": 7,
+				"allowed_policies": 2,
+				"difficulty_era": "Era1"
+			},
+			"forks": [
+				{
+				"name": "genesis",
+				"height": 0,
+				"header_version": 6,
+				"allowed_policies": 1,
+				"difficulty_era": "Initial"
+				},
+				{
+				"name": "header version 7",
+				"height": 6,
+				"header_version": 7,
+				"allowed_policies": 1,
+				"difficulty_era": "Initial"
+				},
+				{
+				"name": "difficulty era 1",
+				"height": 50,
+				"header_version": 7,
+				"allowed_policies": 1,
+				"difficulty_era": "Era1"
+				},
+				{
+				"name": "policy era 2",
+				"height": 480961,
+				"header_version": 7,
+				"allowed_policies": 2,
+				"difficulty_era": "Era1"
+				},
+				{
+				"name": "policy era 3",
+				"height": 1157761,
+				"header_version": 7,
+				"allowed_policies": 4,
+				"difficulty_era": "Era1"
+				},
+				{
+				"name": "policy era 4",
+				"height": 2023201,
+				"header_version": 7,
+				"allowed_policies": 8,
+				"difficulty_era": "Era1"
+				},
+				{
+				"name": "policy era 5",
+				"height": 3175201,
+				"header_version": 7,
+				"allowed_policies": 16,
+				"difficulty_era": "Era1"
+				},
+				{
+				"name": "policy era 6",
+				"height": 4642561,
+				"header_version": 7,
+				"allowed_policies": 32,
+				"difficulty_era": "Era1"
+				}
+			]
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn get_fork_schedule(&self, height: Option<u64>) -> Result<ForkScheduleInfo, Error>;
+
+	/**
+	Networked version of [Foreign::audit_foundation_levy](struct.Node.html#method.audit_foundation_levy).
+
+	# Json rpc example
+
+	```
+	# epic_api::doctest_helper_json_rpc_foreign_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "audit_foundation_levy",
+		"params": [1, 12],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+			"start_height": 1,
+			"end_height": 12,
+			"expected_levy": 1420800000,
+			"paid_levy": 1420800000,
+			"discrepancies": 0,
+			"blocks": [
+				{
+				"height": 5,
+				"hash": "0c2b1d1f0b7e3b4a5a3e6d7f43e7a6c4b0a5d15c7f3a9c9f7b5c12ad0a9a8e41",
+				"levy": 710400000,
+				"status": "Paid"
+				},
+				{
+				"height": 10,
+				"hash": "a3f6e0e18a7cbb1fa4d4f2b4a7a63d8a9c2c3e4b1d52a2ef34b1f07f8c2d6b90",
+				"levy": 710400000,
+				"status": "Paid"
+				}
+			]
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn audit_foundation_levy(
+		&self,
+		start_height: Option<u64>,
+		end_height: Option<u64>,
+	) -> Result<FoundationLevyAudit, Error>;
+
+	/**
+	Networked version of [Foreign::get_supply](struct.Node.html#method.get_supply).
+
+	# Json rpc example
+
+	```
+	# epic_api::doctest_helper_json_rpc_foreign_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_supply",
+		"params": [10],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+			"height": 10,
+			"hash": "00000100c54dcb7a9cbb03aaf55da511aca2c98b801ffd45046b3991e4f697f9",
+			"circulating_supply": 16000000000,
+			"miner_emission": 14579200000,
+			"foundation_emission": 1420800000,
+			"foundation_pending": 0,
+			"block_reward": 1600000000,
+			"foundation_levy": 142080000,
+			"next_halving_height": 480961,
+			"blocks_to_next_halving": 480951,
+			"next_halving_eta": "2020-11-30T00:01:00+00:00",
+			"utxo_sums_balance": null,
+			"sums_balance": true
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn get_supply(&self, height: Option<u64>) -> Result<Supply, Error>;
+
 	/**
 	Networked version of [Foreign::get_kernel](struct.Node.html#method.get_kernel).
 
@@ -798,6 +1196,46 @@ pub trait ForeignRpc: Sync + Send {
 	 */
 	fn push_transaction(&self, tx: Transaction, fluff: Option<bool>) -> Result<(), Error>;
 
+	/**
+	Networked version of [Foreign::get_tx_status](struct.Node.html#method.get_tx_status).
+
+	# Json rpc example
+
+	```
+	# epic_api::doctest_helper_json_rpc_foreign_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_tx_status",
+		"params": ["09c868a2fed619580f296e91d2819b6b3ae61ab734bf3d9c3eafa6d9700f00361b", 30],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+			"Mined": {
+				"height": 374557,
+				"header_hash": "00000100c54dcb7a9cbb03aaf55da511aca2c98b801ffd45046b3991e4f697f9",
+				"confirmations": 12
+			}
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn get_tx_status(
+		&self,
+		kernel_excess: String,
+		wait_secs: Option<u64>,
+	) -> Result<TxStatus, Error>;
+
 	fn get_block_template(&self) -> Result<BlockTemplate, Error>;
 	fn submit_block(&self, block: Block) -> Result<(), Error>;
 	fn finalize_block_template(
@@ -878,6 +1316,19 @@ where
 		));
 	}
 
+	fn get_block_summaries(
+		&self,
+		start_height: u64,
+		end_height: u64,
+		max: u64,
+	) -> Result<BlockListing, Error> {
+		Foreign::get_block_summaries(self, start_height, end_height, max)
+	}
+
+	fn get_block_stats(&self, height: u64) -> Result<BlockStats, Error> {
+		Foreign::get_block_stats(self, height)
+	}
+
 	fn get_block_template(&self) -> Result<BlockTemplate, Error> {
 		Foreign::get_block_template(self)
 	}
@@ -908,6 +1359,26 @@ where
 		Foreign::get_tip(self)
 	}
 
+	fn get_network_stats(&self, window: u64) -> Result<NetworkStats, Error> {
+		Foreign::get_network_stats(self, window)
+	}
+
+	fn get_fork_schedule(&self, height: Option<u64>) -> Result<ForkScheduleInfo, Error> {
+		Foreign::get_fork_schedule(self, height)
+	}
+
+	fn audit_foundation_levy(
+		&self,
+		start_height: Option<u64>,
+		end_height: Option<u64>,
+	) -> Result<FoundationLevyAudit, Error> {
+		Foreign::audit_foundation_levy(self, start_height, end_height)
+	}
+
+	fn get_supply(&self, height: Option<u64>) -> Result<Supply, Error> {
+		Foreign::get_supply(self, height)
+	}
+
 	fn get_kernel(
 		&self,
 		excess: String,
@@ -967,6 +1438,14 @@ where
 	fn push_transaction(&self, tx: Transaction, fluff: Option<bool>) -> Result<(), Error> {
 		Foreign::push_transaction(self, tx, fluff)
 	}
+
+	fn get_tx_status(
+		&self,
+		kernel_excess: String,
+		wait_secs: Option<u64>,
+	) -> Result<TxStatus, Error> {
+		Foreign::get_tx_status(self, kernel_excess, wait_secs)
+	}
 }
 
 #[doc(hidden)]
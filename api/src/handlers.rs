@@ -26,13 +26,16 @@ use self::blocks_api::BlockHandler;
 use self::blocks_api::HeaderHandler;
 use self::chain_api::ChainCompactHandler;
 use self::chain_api::ChainHandler;
+use self::chain_api::ChainKernelIndexHandler;
 use self::chain_api::ChainValidationHandler;
+use self::chain_api::FoundationLevyHandler;
 use self::chain_api::KernelHandler;
 use self::chain_api::OutputHandler;
 use self::mining_api::MiningHandler;
 use self::peers_api::PeerHandler;
 use self::peers_api::PeersAllHandler;
 use self::peers_api::PeersConnectedHandler;
+use self::peers_api::PeersLocalAddrsHandler;
 use self::peers_api::PeersOnionAddressesHandler;
 use self::pool_api::PoolInfoHandler;
 use self::pool_api::PoolPushHandler;
@@ -42,7 +45,7 @@ use self::server_api::StatusHandler;
 use self::transactions_api::TxHashSetHandler;
 use self::version_api::VersionHandler;
 use crate::auth::{
-	BasicAuthURIMiddleware, EPIC_BASIC_REALM, EPIC_FOREIGN_BASIC_REALM,
+	BasicAuthURIMiddleware, TokenAuthMiddleware, EPIC_BASIC_REALM, EPIC_FOREIGN_BASIC_REALM,
 };
 use crate::chain;
 use crate::chain::{Chain, SyncState};
@@ -53,7 +56,9 @@ use crate::owner_rpc::OwnerRpc;
 use crate::p2p;
 use crate::pool;
 use crate::pool::{BlockChain, PoolAdapter};
+use crate::rate_limit::{RateLimit, RateLimitConfig, RateLimitMiddleware};
 use crate::rest::{ApiServer, Error, TLSConfig};
+use crate::tokens::{ApiTokens, AuthContext, Scope};
 use crate::tor::Tor;
 use crate::tor_rpc::TorRpc;
 
@@ -63,6 +68,7 @@ use crate::util::RwLock;
 use crate::util::StopState;
 use crate::web::*;
 use easy_jsonrpc_mw::{Handler, MaybeReply};
+use serde_json::Value;
 
 use bytes::Bytes;
 use hyper::{Request, Response, StatusCode};
@@ -75,6 +81,9 @@ use crate::web::boxed_body;
 use crate::web::BoxBodyType;
 use std::thread;
 
+/// Default maximum number of requests in a JSON-RPC batch on the V2 APIs.
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 100;
+
 /// Listener version, providing same API but listening for requests on a
 /// port and wrapping the calls
 pub fn node_apis<B, P>(
@@ -86,6 +95,9 @@ pub fn node_apis<B, P>(
     api_secret: Option<String>,
     foreign_api_secret: Option<String>,
     tls_config: Option<TLSConfig>,
+    api_tokens: Option<ApiTokens>,
+    max_batch_size: usize,
+    rate_limit: Option<RateLimitConfig>,
     api_chan: &'static mut (
         tokio::sync::oneshot::Sender<()>,
         tokio::sync::oneshot::Receiver<()>,
@@ -104,10 +116,22 @@ where
 	)
 	.expect("unable to build API router");
 
+	// Scoped API tokens, the basic auth secrets keep working through them
+	let use_tokens = api_tokens.is_some();
+	if let Some(api_tokens) = api_tokens {
+		let token_auth =
+			TokenAuthMiddleware::new(api_tokens, api_secret.clone(), foreign_api_secret.clone());
+		router.add_middleware(Arc::new(token_auth));
+	}
 
+	// Rate limiting, after the token middleware to charge the token callers
+	// to their token
+	if let Some(rate_limit) = rate_limit {
+		router.add_middleware(Arc::new(RateLimitMiddleware::new(rate_limit)));
+	}
 
 	// Add basic auth to v2 owner API
-	if let Some(api_secret) = api_secret {
+	if let Some(api_secret) = api_secret.filter(|_| !use_tokens) {
 		let api_basic_auth =
 			"Basic ".to_string() + &to_base64(&("epic:".to_string() + &api_secret));
 		
@@ -131,12 +155,13 @@ where
 		Arc::downgrade(&chain),
 		Arc::downgrade(&peers),
 		Arc::downgrade(&sync_state),
+		max_batch_size,
 	);
 	router.add_route("/v2/owner", Arc::new(owner_api_handler))?;
 
 
 	// Add basic auth to v2 foreign API
-	if let Some(api_secret) = foreign_api_secret {
+	if let Some(api_secret) = foreign_api_secret.filter(|_| !use_tokens) {
 		let api_basic_auth =
 			"Basic ".to_string() + &to_base64(&("epic:".to_string() + &api_secret));
 		let basic_auth_middleware = Arc::new(BasicAuthURIMiddleware::new(
@@ -151,6 +176,7 @@ where
 		Arc::downgrade(&chain),
 		Arc::downgrade(&tx_pool),
 		Arc::downgrade(&sync_state),
+		max_batch_size,
 	);
 	router.add_route("/v2/foreign", Arc::new(foreign_api_handler))?;
 	
@@ -195,15 +221,22 @@ pub struct OwnerAPIHandlerV2 {
     pub chain: Weak<Chain>,
     pub peers: Weak<p2p::Peers>,
     pub sync_state: Weak<SyncState>,
+    pub max_batch_size: usize,
 }
 
 impl OwnerAPIHandlerV2 {
     /// Create a new owner API handler for GET methods
-    pub fn new(chain: Weak<Chain>, peers: Weak<p2p::Peers>, sync_state: Weak<SyncState>) -> Self {
+    pub fn new(
+        chain: Weak<Chain>,
+        peers: Weak<p2p::Peers>,
+        sync_state: Weak<SyncState>,
+        max_batch_size: usize,
+    ) -> Self {
         OwnerAPIHandlerV2 {
             chain,
             peers,
             sync_state,
+            max_batch_size,
         }
     }
 }
@@ -215,19 +248,18 @@ impl crate::router::Handler<Full<Bytes>> for OwnerAPIHandlerV2 {
             self.peers.clone(),
             self.sync_state.clone(),
         );
+        let max_batch_size = self.max_batch_size;
+        let auth = req.extensions().get::<AuthContext>().cloned();
+        let rate_limit = req.extensions().get::<RateLimit>().cloned();
 
 		Box::pin(async move {
 			match parse_body(req).await {
 				Ok(val) => {
+					if let Some(Err(throttled)) = rate_limit.map(|r| r.charge_rpc(&val)) {
+						return Ok(throttled.response());
+					}
 					let owner_api = &api as &dyn OwnerRpc;
-					let res = match owner_api.handle_request(val) {
-						MaybeReply::Reply(r) => r,
-						MaybeReply::DontReply => {
-							// Since it's http, we need to return something. We return [] because jsonrpc
-							// clients will parse it as an empty batch response.
-							serde_json::json!([])
-						}
-					};
+					let res = handle_rpc_request(owner_api, val, max_batch_size, auth.as_ref());
 					Ok(json_response_pretty(&res))
 				}
 				Err(e) => {
@@ -312,6 +344,7 @@ where
     pub chain: Weak<Chain>,
     pub tx_pool: Weak<RwLock<pool::TransactionPool<B, P>>>,
     pub sync_state: Weak<SyncState>,
+    pub max_batch_size: usize,
 }
 
 impl<B, P> ForeignAPIHandlerV2<B, P>
@@ -324,11 +357,13 @@ where
         chain: Weak<Chain>,
         tx_pool: Weak<RwLock<pool::TransactionPool<B, P>>>,
         sync_state: Weak<SyncState>,
+        max_batch_size: usize,
     ) -> Self {
         ForeignAPIHandlerV2 {
             chain,
             tx_pool,
             sync_state,
+            max_batch_size,
         }
     }
 }
@@ -344,19 +379,28 @@ where
             self.tx_pool.clone(),
             self.sync_state.clone(),
         );
+        let max_batch_size = self.max_batch_size;
+        let auth = req.extensions().get::<AuthContext>().cloned();
+        let rate_limit = req.extensions().get::<RateLimit>().cloned();
 
         Box::pin(async move {
             match parse_body(req).await {
                 Ok(val) => {
-                    let foreign_api = &api as &dyn ForeignRpc;
-                    let res = match foreign_api.handle_request(val) {
-                        MaybeReply::Reply(r) => r,
-                        MaybeReply::DontReply => {
-                            // Since it's http, we need to return something. We return [] because jsonrpc
-                            // clients will parse it as an empty batch response.
-                            serde_json::json!([])
+                    if let Some(rate_limit) = &rate_limit {
+                        if let Err(throttled) = rate_limit.charge_rpc(&val) {
+                            return Ok(throttled.response());
                         }
-                    };
+                        if is_long_poll(&val) {
+                            if let Err(throttled) = rate_limit.enter_long_poll() {
+                                return Ok(throttled.response());
+                            }
+                        }
+                    }
+                    let foreign_api = &api as &dyn ForeignRpc;
+                    // get_tx_status may long poll, don't hold up the other requests
+                    let res = tokio::task::block_in_place(|| {
+                        handle_rpc_request(foreign_api, val, max_batch_size, auth.as_ref())
+                    });
                     Ok(json_response_pretty(&res))
                 }
                 Err(e) => {
@@ -372,6 +416,144 @@ where
     }
 }
 
+/// Methods that may wait for a change before replying.
+const LONG_POLL_METHODS: &[&str] = &["get_tx_status"];
+
+/// Whether the JSON-RPC request may long poll. Batches never do, long poll
+/// methods are refused in them.
+fn is_long_poll(request: &Value) -> bool {
+	request
+		.get("method")
+		.and_then(|m| m.as_str())
+		.map_or(false, |m| LONG_POLL_METHODS.contains(&m))
+}
+
+/// Answers a single JSON-RPC request or a JSON-RPC 2.0 batch of them, the
+/// batch replies being returned in the order of the requests. Notifications
+/// get no reply. With API tokens, each request is checked against the
+/// scopes of the caller. Long poll methods must be called on their own, a
+/// batch of them could hold the connection for the sum of their waits.
+fn handle_rpc_request<H>(
+	api: &H,
+	val: Value,
+	max_batch_size: usize,
+	auth: Option<&AuthContext>,
+) -> Value
+where
+	H: Handler + ?Sized,
+{
+	let batch = match val {
+		Value::Array(batch) => batch,
+		request => {
+			if let Err(e) = authorize(auth, &request) {
+				return e;
+			}
+			return reply_or_empty(api.handle_request(request));
+		}
+	};
+	if batch.is_empty() {
+		return rpc_error(Value::Null, INVALID_REQUEST, "Invalid Request: empty batch");
+	}
+	if batch.len() > max_batch_size {
+		return rpc_error(
+			Value::Null,
+			INVALID_REQUEST,
+			&format!(
+				"Invalid Request: batch of {} requests, at most {} allowed",
+				batch.len(),
+				max_batch_size
+			),
+		);
+	}
+	let replies = batch
+		.into_iter()
+		.filter_map(|request| {
+			// a batch item is a single request, nested batches are invalid
+			if !request.is_object() {
+				return Some(rpc_error(Value::Null, INVALID_REQUEST, "Invalid Request"));
+			}
+			if is_long_poll(&request) {
+				return Some(rpc_error(
+					request.get("id").cloned().unwrap_or(Value::Null),
+					INVALID_REQUEST,
+					"Invalid Request: long poll methods can't be batched",
+				));
+			}
+			if let Err(e) = authorize(auth, &request) {
+				return Some(e);
+			}
+			match api.handle_request(request) {
+				MaybeReply::Reply(r) => Some(r),
+				MaybeReply::DontReply => None,
+			}
+		})
+		.collect();
+	Value::Array(replies)
+}
+
+/// Checks the caller is allowed to call the requested method, logging the
+/// admin calls.
+fn authorize(auth: Option<&AuthContext>, request: &Value) -> Result<(), Value> {
+	let auth = match auth {
+		Some(auth) => auth,
+		None => return Ok(()),
+	};
+	let method = request
+		.get("method")
+		.and_then(|m| m.as_str())
+		.unwrap_or_default();
+	let id = || request.get("id").cloned().unwrap_or(Value::Null);
+	let scope = match Scope::for_rpc_method(method) {
+		Some(scope) => scope,
+		None => {
+			return Err(rpc_error(
+				id(),
+				FORBIDDEN,
+				&format!("Forbidden: unknown method {}", method),
+			))
+		}
+	};
+	if !auth.allows(scope) {
+		return Err(rpc_error(
+			id(),
+			FORBIDDEN,
+			&format!("Forbidden: {} requires the {} scope", method, scope),
+		));
+	}
+	if scope.is_admin() {
+		info!("API audit: {} called {}", auth.name, method);
+	}
+	Ok(())
+}
+
+fn reply_or_empty(reply: MaybeReply) -> Value {
+	match reply {
+		MaybeReply::Reply(r) => r,
+		MaybeReply::DontReply => {
+			// Since it's http, we need to return something. We return [] because jsonrpc
+			// clients will parse it as an empty batch response.
+			serde_json::json!([])
+		}
+	}
+}
+
+/// JSON-RPC 2.0 "Invalid Request" error code.
+const INVALID_REQUEST: i64 = -32600;
+
+/// Server error code for a method the caller isn't allowed to call.
+const FORBIDDEN: i64 = -32001;
+
+fn rpc_error(id: Value, code: i64, message: &str) -> Value {
+	serde_json::json!({
+		"jsonrpc": "2.0",
+		"error": {
+			"code": code,
+			"message": message,
+		},
+		"id": id,
+	})
+}
+
 // pretty-printed version of above
 fn json_response_pretty(to_string: &serde_json::Value) -> Response<BoxBodyType> {
     let json = serde_json::to_string_pretty(to_string);
@@ -450,6 +632,8 @@ where
 		"get:  /v1/chain".to_string(),
 		"post: /v1/chain/compact".to_string(),
 		"get:  /v1/chain/validate".to_string(),
+		"post: /v1/chain/rebuild_kernel_index".to_string(),
+		"get:  /v1/chain/foundation_levy?start_height=1&end_height=1000".to_string(),
 		"get:  /v1/chain/kernels/xxx?min_height=yyy&max_height=zzz".to_string(),
 		"get:  /v1/chain/outputs/byids?id=xxx,yyy,zzz".to_string(),
 		"get:  /v1/chain/outputs/byheight?start_height=101&end_height=200".to_string(),
@@ -468,6 +652,7 @@ where
 		"get:  /v1/peers/connected".to_string(),
 		"get:  /v1/peers/a.b.c.d".to_string(),
 		"get:  /v1/peers/onion_addresses".to_string(),
+		"get:  /v1/peers/local_addrs".to_string(),
 		"get:  /v1/version".to_string(),
 		"get:  /v1/mining/block_template".to_string(),
 	];
@@ -494,6 +679,12 @@ where
 	let chain_validation_handler = ChainValidationHandler {
 		chain: Arc::downgrade(&chain),
 	};
+	let chain_kernel_index_handler = ChainKernelIndexHandler {
+		chain: Arc::downgrade(&chain),
+	};
+	let foundation_levy_handler = FoundationLevyHandler {
+		chain: Arc::downgrade(&chain),
+	};
 	let status_handler = StatusHandler {
 		chain: Arc::downgrade(&chain),
 		peers: Arc::downgrade(&peers),
@@ -520,6 +711,9 @@ where
 	let peers_onion_addresses_handler = PeersOnionAddressesHandler {
 		peers: Arc::downgrade(&peers),
 	};
+	let peers_local_addrs_handler = PeersLocalAddrsHandler {
+		peers: Arc::downgrade(&peers),
+	};
 	let peer_handler = PeerHandler {
 		peers: Arc::downgrade(&peers),
 	};
@@ -541,6 +735,8 @@ where
 	router.add_route("/v1/chain/kernels/*", Arc::new(kernel_handler))?;
 	router.add_route("/v1/chain/compact", Arc::new(chain_compact_handler))?;
 	router.add_route("/v1/chain/validate", Arc::new(chain_validation_handler))?;
+	router.add_route("/v1/chain/rebuild_kernel_index", Arc::new(chain_kernel_index_handler))?;
+	router.add_route("/v1/chain/foundation_levy", Arc::new(foundation_levy_handler))?;
 	router.add_route("/v1/txhashset/*", Arc::new(txhashset_handler))?;
 	router.add_route("/v1/status", Arc::new(status_handler))?;
 	router.add_route("/v1/kerneldownload", Arc::new(kernel_download_handler))?;
@@ -552,9 +748,106 @@ where
 		"/v1/peers/onion_addresses",
 		Arc::new(peers_onion_addresses_handler),
 	)?;
+	router.add_route("/v1/peers/local_addrs", Arc::new(peers_local_addrs_handler))?;
 	router.add_route("/v1/peers/**", Arc::new(peer_handler))?;
 	router.add_route("/v1/version", Arc::new(version_handler))?;
 	router.add_route("/v1/mining/block_template", Arc::new(mining_handler))?;
 
     Ok(router)
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+
+	#[easy_jsonrpc_mw::rpc]
+	pub trait Echo {
+		fn echo(&self, v: u64) -> u64;
+		fn get_tip(&self) -> u64;
+	}
+
+	struct EchoApi;
+
+	impl Echo for EchoApi {
+		fn echo(&self, v: u64) -> u64 {
+			v
+		}
+
+		fn get_tip(&self) -> u64 {
+			42
+		}
+	}
+
+	fn echo_request(v: u64) -> Value {
+		json!({"jsonrpc": "2.0", "method": "echo", "params": [v], "id": v})
+	}
+
+	#[test]
+	fn test_batch_requests() {
+		let api = &EchoApi as &dyn Echo;
+
+		let reply = handle_rpc_request(api, echo_request(1), 2, None);
+		assert_eq!(reply["result"], 1);
+
+		let batch = json!([
+			echo_request(1),
+			5,
+			{"jsonrpc": "2.0", "method": "echo", "params": [3]},
+			{"jsonrpc": "2.0", "method": "unknown", "params": [], "id": 4},
+			echo_request(2),
+		]);
+		let reply = handle_rpc_request(api, batch, 5, None);
+		let replies = reply.as_array().unwrap();
+		assert_eq!(replies.len(), 4);
+		assert_eq!(replies[0]["result"], 1);
+		assert_eq!(replies[1]["error"]["code"], INVALID_REQUEST);
+		assert_eq!(replies[2]["id"], 4);
+		assert!(replies[2]["error"].is_object());
+		assert_eq!(replies[3]["result"], 2);
+
+		let batch = json!([echo_request(1), echo_request(2), echo_request(3)]);
+		let reply = handle_rpc_request(api, batch, 2, None);
+		assert_eq!(reply["error"]["code"], INVALID_REQUEST);
+		let reply = handle_rpc_request(api, json!([]), 2, None);
+		assert_eq!(reply["error"]["code"], INVALID_REQUEST);
+	}
+
+	#[test]
+	fn test_scoped_requests() {
+		let api = &EchoApi as &dyn Echo;
+		let auth = AuthContext {
+			name: "indexer".to_owned(),
+			scopes: vec![Scope::ReadChain],
+		};
+		let batch = json!([
+			{"jsonrpc": "2.0", "method": "get_tip", "params": [], "id": 1},
+			{"jsonrpc": "2.0", "method": "compact_chain", "params": [], "id": 2},
+			echo_request(3),
+		]);
+		let reply = handle_rpc_request(api, batch, 3, Some(&auth));
+		let replies = reply.as_array().unwrap();
+		assert_eq!(replies[0]["result"], 42);
+		assert_eq!(replies[1]["id"], 2);
+		assert_eq!(replies[1]["error"]["code"], FORBIDDEN);
+		// methods without a scope are denied
+		assert_eq!(replies[2]["id"], 3);
+		assert_eq!(replies[2]["error"]["code"], FORBIDDEN);
+	}
+
+	#[test]
+	fn test_long_polls() {
+		let tx_status = json!({"jsonrpc": "2.0", "method": "get_tx_status", "params": [], "id": 1});
+		assert!(is_long_poll(&tx_status));
+		assert!(!is_long_poll(&echo_request(1)));
+		assert!(!is_long_poll(&json!([echo_request(1), tx_status.clone()])));
+
+		// long polls are refused in batches, the other requests still answered
+		let api = &EchoApi as &dyn Echo;
+		let reply = handle_rpc_request(api, json!([echo_request(2), tx_status]), 2, None);
+		let replies = reply.as_array().unwrap();
+		assert_eq!(replies[0]["result"], 2);
+		assert_eq!(replies[1]["id"], 1);
+		assert_eq!(replies[1]["error"]["code"], INVALID_REQUEST);
+	}
+}
@@ -15,6 +15,7 @@ use super::utils::{get_output, get_output_v2, w};
 use crate::chain;
 use crate::core::core::hash::Hash;
 use crate::core::core::hash::Hashed;
+use crate::core::core::OutputIdentifier;
 use crate::rest::*;
 use crate::router::{Handler, ResponseFuture};
 use crate::types::*;
@@ -29,6 +30,9 @@ use hyper::{Request, StatusCode};
 use bytes::Bytes;
 use http_body_util::Full;
 
+/// Maximum number of blocks returned by a single `get_block_summaries` call.
+pub const BLOCK_LISTING_MAX: u64 = 1000;
+
 pub struct HeaderHandler {
 	pub chain: Weak<chain::Chain>,
 }
@@ -144,6 +148,79 @@ impl BlockHandler {
 			.map_err(|_| Error::Internal("chain error".to_owned()))
 	}
 
+	/// Summaries of the blocks between the provided heights (inclusive), at
+	/// most `max` of them. Blocks we don't have the body of (pruned) are skipped.
+	pub fn get_block_summaries(
+		&self,
+		start_height: u64,
+		end_height: u64,
+		max: u64,
+	) -> Result<BlockListing, Error> {
+		if start_height > end_height {
+			return Err(Error::Argument(
+				"start_height must be lower or equal to end_height".to_owned(),
+			));
+		}
+		let chain = w(&self.chain)?;
+		let last_height = end_height.min(chain.head()?.height);
+		let max = max.clamp(1, BLOCK_LISTING_MAX);
+		// pruned blocks are skipped, the span walked is capped as well
+		let end_height = last_height.min(start_height.saturating_add(BLOCK_LISTING_MAX - 1));
+
+		let mut prev = match start_height {
+			0 => None,
+			h => chain.get_header_by_height(h - 1).ok(),
+		};
+		let mut blocks = vec![];
+		let mut last_retrieved_height = start_height.saturating_sub(1);
+		for height in start_height..=end_height {
+			if blocks.len() as u64 >= max {
+				break;
+			}
+			let header = chain
+				.get_header_by_height(height)
+				.map_err(|_| Error::NotFound)?;
+			if let Ok(block) = chain.get_block(&header.hash()) {
+				blocks.push(BlockSummaryPrintable::from_block(&block, prev.as_ref()));
+			}
+			last_retrieved_height = height;
+			prev = Some(header);
+		}
+		let next_height = if last_retrieved_height < last_height {
+			Some(last_retrieved_height + 1)
+		} else {
+			None
+		};
+		Ok(BlockListing {
+			last_retrieved_height,
+			next_height,
+			blocks,
+		})
+	}
+
+	/// Weight, spent outputs and fee statistics of the block at the provided height.
+	pub fn get_block_stats(&self, height: u64) -> Result<BlockStats, Error> {
+		let chain = w(&self.chain)?;
+		let header = chain
+			.get_header_by_height(height)
+			.map_err(|_| Error::NotFound)?;
+		let block = chain
+			.get_block(&header.hash())
+			.map_err(|_| Error::NotFound)?;
+		let mut spent_outputs = 0;
+		for out in block.outputs() {
+			match chain.is_unspent(&OutputIdentifier::from_output(out)) {
+				Ok(_) => (),
+				// the commitment may have been reused by a later output
+				Err(chain::Error::OutputNotFound) | Err(chain::Error::TxHashSetErr(_)) => {
+					spent_outputs += 1
+				}
+				Err(e) => return Err(Error::Internal(format!("{}", e))),
+			}
+		}
+		Ok(BlockStats::from_block(&block, spent_outputs))
+	}
+
 	// Try to decode the string as a height or a hash.
 	fn parse_input(&self, input: String) -> Result<Hash, Error> {
 		if let Ok(height) = input.parse() {
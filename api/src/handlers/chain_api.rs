@@ -14,21 +14,30 @@
 
 use super::utils::{get_output, get_output_v2, w};
 use crate::chain;
-use crate::core::core::hash::Hashed;
+use crate::core::consensus;
+use crate::core::core::committed;
+use crate::core::core::foundation::{load_foundation_outputs, CbData};
+use crate::core::core::hash::{Hash, Hashed};
+use crate::core::global;
+use crate::core::pow::PoWType;
+use crate::p2p::{self, ReasonForBan};
 use crate::rest::*;
 use crate::router::{Handler, ResponseFuture};
 use crate::types::*;
 use crate::util;
 use crate::util::secp::pedersen::Commitment;
 use crate::web::*;
-use epic_core::core::TxKernel;
+use epic_core::core::{BlockHeader, TxKernel};
 
 use hyper::{Request, StatusCode};
-use std::sync::Weak;
+use std::sync::{Arc, Weak};
 
 use bytes::Bytes;
 use http_body_util::Full;
 
+/// Largest window `get_network_stats` walks, a week of blocks.
+pub const NETWORK_STATS_WINDOW_MAX: u64 = consensus::WEEK_HEIGHT;
+
 /// Chain handler. Get the head details.
 /// GET /v1/chain
 pub struct ChainHandler {
@@ -38,10 +47,127 @@ pub struct ChainHandler {
 impl ChainHandler {
 	pub fn get_tip(&self) -> Result<Tip, Error> {
 		let head = w(&self.chain)?
-			.head()
+			.tip()
 			.map_err(|e| Error::Internal(format!("can't get head: {}", e)))?;
 		Ok(Tip::from_tip(head))
 	}
+
+	/// Per algorithm mining statistics over the last `window` blocks.
+	pub fn get_network_stats(&self, window: u64) -> Result<NetworkStats, Error> {
+		let chain = w(&self.chain)?;
+		let head = chain
+			.head_header()
+			.map_err(|e| Error::Internal(format!("can't get head: {}", e)))?;
+		let window = window.clamp(1, NETWORK_STATS_WINDOW_MAX) as usize;
+
+		let mut blocks = Vec::with_capacity(window);
+		let infos = chain
+			.difficulty_iter_all()
+			.map_err(|e| Error::Internal(format!("can't iterate the chain: {}", e)))?;
+		for info in infos.take(window) {
+			let header = chain
+				.get_block_header(&info.block_hash)
+				.map_err(|e| Error::Internal(format!("can't get header: {}", e)))?;
+			// the genesis has no parent to take a solve time against
+			if header.height == 0 {
+				break;
+			}
+			let algo: PoWType = (&header.pow.proof).into();
+			blocks.push((algo, info.difficulty.to_num(algo), info.prev_timespan));
+		}
+
+		let policy = global::get_emitted_policy(head.height + 1);
+		let weights = global::get_policies(policy)
+			.ok_or_else(|| Error::Internal(format!("no policy {} configured", policy)))?;
+		let bottles = chain
+			.bottles_iter(policy)
+			.map_err(|e| Error::Internal(format!("can't iterate the chain: {}", e)))?;
+		let (next_algorithm, _) = consensus::next_policy(policy, bottles);
+
+		Ok(NetworkStats::from_blocks(
+			head.height,
+			policy,
+			&weights,
+			next_algorithm,
+			&blocks,
+		))
+	}
+
+	/// Fork schedule of the chain and the rules in force at `height`, the
+	/// next block height when None.
+	pub fn get_fork_schedule(&self, height: Option<u64>) -> Result<ForkScheduleInfo, Error> {
+		let height = match height {
+			Some(height) => height,
+			None => {
+				let chain = w(&self.chain)?;
+				let head = chain
+					.head()
+					.map_err(|e| Error::Internal(format!("can't get head: {}", e)))?;
+				head.height + 1
+			}
+		};
+		Ok(ForkScheduleInfo::from_schedule(
+			global::get_chain_type(),
+			global::fork_schedule(),
+			height,
+		))
+	}
+
+	/// Coins emitted up to `height`, the head when None. At the head the
+	/// circulating supply is checked against the unspent outputs less the
+	/// kernel sums, at any height against the block sums when we still have
+	/// them (pruned nodes drop those of old blocks).
+	pub fn get_supply(&self, height: Option<u64>) -> Result<Supply, Error> {
+		let chain = w(&self.chain)?;
+		let head = chain
+			.head()
+			.map_err(|e| Error::Internal(format!("can't get head: {}", e)))?;
+		let height = height.unwrap_or(head.height);
+		if height > head.height {
+			return Err(Error::Argument(format!(
+				"height {} is above the head {}",
+				height, head.height
+			)));
+		}
+		let header = chain.get_header_by_height(height)?;
+		let genesis = chain.get_header_by_height(0)?;
+		let genesis_had_reward = genesis.kernel_mmr_size > 0;
+		let genesis_reward = if genesis_had_reward {
+			consensus::reward_at_height(0)
+		} else {
+			0
+		};
+
+		let emission = consensus::emission_at_height(height);
+		let circulating = genesis_reward + emission.total();
+		let utxo_sums_balance = if height == head.height {
+			let overage = -(circulating as i64);
+			Some(
+				chain
+					.verify_utxo_sums(&header, overage)
+					.map_err(|e| Error::Internal(format!("can't verify the utxo sums: {}", e)))?,
+			)
+		} else {
+			None
+		};
+		let sums_balance = match chain.get_block_sums(&header.hash()) {
+			Ok(sums) => match sums.verify(&header.total_kernel_offset()) {
+				Ok(()) => Some(true),
+				Err(committed::Error::KernelSumMismatch) => Some(false),
+				Err(e) => return Err(Error::Internal(format!("can't verify block sums: {}", e))),
+			},
+			Err(chain::Error::ChainStoreErr(epic_store::Error::NotFoundErr(_), _)) => None,
+			Err(e) => return Err(Error::Internal(format!("can't get block sums: {}", e))),
+		};
+
+		Ok(Supply::from_emission(
+			&header,
+			genesis_reward,
+			emission,
+			utxo_sums_balance,
+			sums_balance,
+		))
+	}
 }
 
 impl Handler<Full<Bytes>> for ChainHandler {
@@ -103,6 +229,136 @@ impl Handler<Full<Bytes>> for ChainCompactHandler {
 	}
 }
 
+/// Builds (or rebuilds) the kernel excess index.
+/// POST /v1/chain/rebuild_kernel_index
+pub struct ChainKernelIndexHandler {
+	pub chain: Weak<chain::Chain>,
+}
+
+impl ChainKernelIndexHandler {
+	pub fn rebuild_kernel_index(&self) -> Result<(), Error> {
+		let chain = w(&self.chain)?;
+		chain.rebuild_kernel_index()?;
+		Ok(())
+	}
+}
+
+impl Handler<Full<Bytes>> for ChainKernelIndexHandler {
+	fn post(&self, _req: Request<hyper::body::Incoming>) -> ResponseFuture {
+		match w_fut!(&self.chain).rebuild_kernel_index() {
+			Ok(_) => response(StatusCode::OK, "{}"),
+			Err(e) => response(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				format!("kernel index rebuild failed: {}", e),
+			),
+		}
+	}
+}
+
+/// Foundation levy audit handler. Checks the foundation height blocks of a
+/// height range hold the coinbase of the foundation file.
+/// GET /v1/chain/foundation_levy?start_height=1&end_height=1000
+/// Both parameters are optional, defaulting to the whole chain.
+pub struct FoundationLevyHandler {
+	pub chain: Weak<chain::Chain>,
+}
+
+impl FoundationLevyHandler {
+	pub fn audit_foundation_levy(
+		&self,
+		start_height: Option<u64>,
+		end_height: Option<u64>,
+	) -> Result<FoundationLevyAudit, Error> {
+		let chain = w(&self.chain)?;
+		let head = chain.head()?;
+		let start_height = start_height.unwrap_or(1).max(1);
+		let end_height = end_height.map_or(head.height, |h| h.min(head.height));
+		if start_height > end_height {
+			return Err(Error::Argument(format!(
+				"invalid height range {}-{}",
+				start_height, end_height
+			)));
+		}
+
+		let step = consensus::foundation_height();
+		let heights: Vec<u64> = (start_height.div_ceil(step) * step..=end_height)
+			.step_by(step as usize)
+			.filter(|height| consensus::is_foundation_height(*height))
+			.collect();
+		let coinbases = load_foundation_outputs(&heights)
+			.map_err(|e| Error::Internal(format!("loading the foundation file: {}", e)))?;
+
+		let mut blocks = Vec::with_capacity(heights.len());
+		for (height, coinbase) in heights.into_iter().zip(coinbases) {
+			let header = chain.get_header_by_height(height)?;
+			let status = match coinbase {
+				Some(coinbase) => check_foundation_levy(&chain, &header, &coinbase)?,
+				None => FoundationLevyStatus::NotInFoundationFile,
+			};
+			blocks.push(FoundationLevyBlock {
+				height,
+				hash: header.hash().to_hex(),
+				levy: consensus::cumulative_reward_foundation(height),
+				status,
+			});
+		}
+		Ok(FoundationLevyAudit::from_blocks(
+			start_height,
+			end_height,
+			blocks,
+		))
+	}
+}
+
+/// Looks for the foundation output and kernel in the block coinbase, or for
+/// the kernel alone in the block kernel range when the body was pruned.
+fn check_foundation_levy(
+	chain: &chain::Chain,
+	header: &BlockHeader,
+	coinbase: &CbData,
+) -> Result<FoundationLevyStatus, Error> {
+	if !chain.block_exists(header.hash())? {
+		let kernel = chain.get_kernel_height(
+			&coinbase.kernel.excess,
+			Some(header.height),
+			Some(header.height),
+		)?;
+		return Ok(match kernel {
+			Some(_) => FoundationLevyStatus::KernelOnly,
+			None => FoundationLevyStatus::MissingKernel,
+		});
+	}
+	let block = chain.get_block(&header.hash())?;
+	let output = block
+		.outputs()
+		.iter()
+		.any(|out| out.is_coinbase() && out.commitment() == coinbase.output.commitment());
+	let kernel = block
+		.kernels()
+		.iter()
+		.any(|kern| kern.is_coinbase() && kern.excess == coinbase.kernel.excess);
+	Ok(if !output {
+		FoundationLevyStatus::MissingOutput
+	} else if !kernel {
+		FoundationLevyStatus::MissingKernel
+	} else {
+		FoundationLevyStatus::Paid
+	})
+}
+
+impl Handler<Full<Bytes>> for FoundationLevyHandler {
+	fn get(&self, req: Request<hyper::body::Incoming>) -> ResponseFuture {
+		let params = QueryParams::from(req.uri().query());
+		let height = |name| params.get(name).map(|h| h.parse::<u64>()).transpose();
+		match (height("start_height"), height("end_height")) {
+			(Ok(start_height), Ok(end_height)) => {
+				result_to_response(self.audit_foundation_levy(start_height, end_height))
+			}
+			_ => response(StatusCode::BAD_REQUEST, "invalid height"),
+		}
+	}
+}
+
 // Supports retrieval of multiple outputs in a single request -
 // GET /v1/chain/outputs/byids?id=xxx,yyy,zzz
 // GET /v1/chain/outputs/byids?id=xxx&id=yyy&id=zzz
@@ -487,3 +743,133 @@ impl Handler<Full<Bytes>> for KernelHandler {
 		result_to_response(self.get_kernel(req))
 	}
 }
+
+/// Peers asked for a proof, one after the other until one proves it.
+pub const PROOF_PEERS: usize = 3;
+
+/// Peers which must all say they don't have an output or kernel before we
+/// report it as not found.
+pub const PROOF_NOT_FOUND_PEERS: usize = 2;
+
+/// Checks outputs and kernels with merkle proofs from peers, for light nodes
+/// which don't keep the chain state themselves.
+pub struct ProofHandler {
+	pub chain: Weak<chain::Chain>,
+	pub peers: Weak<p2p::Peers>,
+}
+
+impl ProofHandler {
+	pub fn verify_output(&self, commit: String) -> Result<ProvenInclusion, Error> {
+		let commit = parse_commitment(commit)?;
+		let (peer, header, proof) = self.prove(
+			|peers, peer| peers.request_output_proof(peer, commit),
+			|chain, hash, proof| chain.verify_output_proof(hash, &commit, proof),
+		)?;
+		let output_type = if proof.output.features.is_coinbase() {
+			OutputType::Coinbase
+		} else {
+			OutputType::Transaction
+		};
+		Ok(ProvenInclusion {
+			commit: util::to_hex(commit.0.to_vec()),
+			output_type: Some(output_type),
+			mmr_index: proof.pos,
+			header_height: header.height,
+			header_hash: header.hash().to_hex(),
+			peer: peer.info.addr.to_string(),
+		})
+	}
+
+	pub fn verify_kernel(&self, excess: String) -> Result<ProvenInclusion, Error> {
+		let excess = parse_commitment(excess)?;
+		let (peer, header, proof) = self.prove(
+			|peers, peer| peers.request_kernel_proof(peer, excess),
+			|chain, hash, proof| chain.verify_kernel_proof(hash, &excess, proof),
+		)?;
+		Ok(ProvenInclusion {
+			commit: util::to_hex(excess.0.to_vec()),
+			output_type: None,
+			mmr_index: proof.pos,
+			header_height: header.height,
+			header_hash: header.hash().to_hex(),
+			peer: peer.info.addr.to_string(),
+		})
+	}
+
+	/// Asks random peers for a proof until one of them proves it. A single
+	/// peer saying it doesn't have the data isn't enough to report it as not
+	/// found, it could be lying or lagging.
+	fn prove<T, R, V>(
+		&self,
+		request: R,
+		verify: V,
+	) -> Result<(Arc<p2p::Peer>, BlockHeader, T), Error>
+	where
+		R: Fn(&p2p::Peers, &p2p::Peer) -> Result<p2p::PeerProof<T>, p2p::Error>,
+		V: Fn(&chain::Chain, &Hash, &T) -> Result<BlockHeader, chain::Error>,
+	{
+		let peers = w(&self.peers)?;
+		let proof_peers = peers.random_proof_peers(PROOF_PEERS);
+		if proof_peers.is_empty() {
+			return Err(Error::Internal(
+				"no connected peer can serve proofs".to_owned(),
+			));
+		}
+
+		let mut not_found = 0;
+		let mut last_err = None;
+		for peer in proof_peers {
+			match request(&peers, &peer) {
+				Ok(Some((hash, proof))) => {
+					match self.check_proof(&peer, |chain| verify(chain, &hash, &proof)) {
+						Ok(header) => return Ok((peer, header, proof)),
+						Err(e) => last_err = Some(e),
+					}
+				}
+				Ok(None) => not_found += 1,
+				Err(e) => last_err = Some(proof_request_error(&peer, e)),
+			}
+		}
+		if not_found >= PROOF_NOT_FOUND_PEERS {
+			return Err(Error::NotFound);
+		}
+		Err(last_err.unwrap_or_else(|| {
+			Error::Internal(format!(
+				"not found by {} peer(s), {} needed to agree",
+				not_found, PROOF_NOT_FOUND_PEERS
+			))
+		}))
+	}
+
+	/// A proof that doesn't match the header it names is made up, the peer
+	/// sending it gets banned. A header we don't know of yet is no fault.
+	fn check_proof<F>(&self, peer: &p2p::Peer, verify: F) -> Result<BlockHeader, Error>
+	where
+		F: FnOnce(&chain::Chain) -> Result<BlockHeader, chain::Error>,
+	{
+		verify(&w(&self.chain)?).map_err(|e| {
+			if e.is_bad_data() {
+				if let Ok(peers) = w(&self.peers) {
+					let _ = peers.ban_peer(peer.info.addr, ReasonForBan::BadBlock);
+				}
+			}
+			Error::Internal(format!("proof from {} rejected: {}", peer.info.addr, e))
+		})
+	}
+}
+
+fn parse_commitment(commit: String) -> Result<Commitment, Error> {
+	let commit =
+		util::from_hex(commit).map_err(|_| Error::Argument("invalid commitment hex".into()))?;
+	if commit.len() != 33 {
+		return Err(Error::Argument("invalid commitment length".into()));
+	}
+	Ok(Commitment::from_vec(commit))
+}
+
+fn proof_request_error(peer: &p2p::Peer, e: p2p::Error) -> Error {
+	Error::Internal(format!(
+		"requesting proof from {} failed: {:?}",
+		peer.info.addr, e
+	))
+}
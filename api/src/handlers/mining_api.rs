@@ -82,6 +82,7 @@ where
 	/// Step 1: Returns a block template for mining, without coinbase output/kernel.
 	pub fn get_block_template(&self) -> Result<BlockTemplate, Error> {
 		let chain = w(&self.chain)?;
+		let _params = chain.params().enter();
 		let head = chain.head_header()?;
 		let height = head.height + 1;
 		//let proof_size = global::proofsize();
@@ -17,6 +17,7 @@ use crate::p2p::types::{PeerAddr, PeerInfoDisplay, ReasonForBan};
 use crate::p2p::{self, PeerData};
 use crate::rest::*;
 use crate::router::{Handler, ResponseFuture};
+use crate::types::LocalAddrs;
 use crate::web::*;
 
 use hyper::{Request, StatusCode};
@@ -86,6 +87,30 @@ impl Handler<Full<Bytes>> for PeersOnionAddressesHandler {
 	}
 }
 
+pub struct PeersLocalAddrsHandler {
+	pub peers: Weak<p2p::Peers>,
+}
+
+impl PeersLocalAddrsHandler {
+	pub fn get_local_addrs(&self) -> Result<LocalAddrs, Error> {
+		let peers = w(&self.peers)?;
+		Ok(LocalAddrs {
+			listen_addrs: peers.listen_addrs(),
+			external_addrs: peers.external_addrs(),
+		})
+	}
+}
+
+impl Handler<Full<Bytes>> for PeersLocalAddrsHandler {
+	fn get(&self, _req: Request<hyper::body::Incoming>) -> ResponseFuture {
+		let peers = w_fut!(&self.peers);
+		json_response(&LocalAddrs {
+			listen_addrs: peers.listen_addrs(),
+			external_addrs: peers.external_addrs(),
+		})
+	}
+}
+
 /// Peer operations
 /// GET /v1/peers/10.12.12.13
 /// POST /v1/peers/10.12.12.13/ban
@@ -97,7 +122,7 @@ pub struct PeerHandler {
 impl PeerHandler {
 	pub fn get_peers(&self, addr: Option<SocketAddr>) -> Result<Vec<PeerData>, Error> {
 		if let Some(addr) = addr {
-			let peer_addr = PeerAddr(addr);
+			let peer_addr = PeerAddr::Ip(addr);
 			let peer_data: PeerData = w(&self.peers)?.get_peer(peer_addr).map_err(|e| {
 				let e: Error = Error::Internal(format!("get peer error: {:?}", e));
 				e
@@ -109,14 +134,14 @@ impl PeerHandler {
 	}
 
 	pub fn ban_peer(&self, addr: SocketAddr) -> Result<(), Error> {
-		let peer_addr = PeerAddr(addr);
+		let peer_addr = PeerAddr::Ip(addr);
 		w(&self.peers)?
 			.ban_peer(peer_addr, ReasonForBan::ManualBan)
 			.map_err(|e| Error::Internal(format!("ban peer error: {:?}", e)))
 	}
 
 	pub fn unban_peer(&self, addr: SocketAddr) -> Result<(), Error> {
-		let peer_addr = PeerAddr(addr);
+		let peer_addr = PeerAddr::Ip(addr);
 		w(&self.peers)?
 			.unban_peer(peer_addr)
 			.map_err(|e| Error::Internal(format!("unban peer error: {:?}", e)))
@@ -127,14 +152,14 @@ impl Handler<Full<Bytes>> for PeerHandler {
 	fn get(&self, req: Request<hyper::body::Incoming>) -> ResponseFuture {
 		let command = right_path_element!(req);
 
-		// We support both "ip" and "ip:port" here for peer_addr.
+		// We support "ip", "ip:port" and "<onion address>:port" here for peer_addr.
 		// "ip:port" is only really useful for local usernet testing on loopback address.
 		// Normally we map peers to ip and only allow a single peer per ip address.
 		let peer_addr;
 		if let Ok(ip_addr) = command.parse() {
 			peer_addr = PeerAddr::from_ip(ip_addr);
 		} else if let Ok(addr) = command.parse() {
-			peer_addr = PeerAddr(addr);
+			peer_addr = addr;
 		} else {
 			return response(
 				StatusCode::BAD_REQUEST,
@@ -160,7 +185,7 @@ impl Handler<Full<Bytes>> for PeerHandler {
 				if let Ok(ip_addr) = a.parse() {
 					PeerAddr::from_ip(ip_addr)
 				} else if let Ok(addr) = a.parse() {
-					PeerAddr(addr)
+					addr
 				} else {
 					return response(
 						StatusCode::BAD_REQUEST,
@@ -13,18 +13,22 @@
 // limitations under the License.
 
 use super::utils::w;
-use crate::core::core::hash::Hashed;
+use crate::chain::Chain;
+use crate::core::core::hash::{Hash, Hashed};
 use crate::core::core::Transaction;
 
 use crate::pool::{self, BlockChain, PoolAdapter, PoolEntry};
 use crate::rest::*;
 use crate::router::{Handler, ResponseFuture};
 use crate::types::*;
-use crate::util::RwLock;
+use crate::util::secp::pedersen::Commitment;
+use crate::util::{self, RwLock};
 use crate::web::*;
 
 use hyper::{Request, StatusCode};
 use std::sync::Weak;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use bytes::Bytes;
 use http_body_util::Full;
@@ -109,13 +113,113 @@ where
 		Ok(res)
 	}
 }
+
+/// Longest a get_tx_status call may wait for a status change.
+pub const TX_STATUS_MAX_WAIT_SECS: u64 = 30;
+
+/// How often a waiting get_tx_status call checks for a status change.
+const TX_STATUS_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Tracks a transaction through the stempool, the txpool and the chain.
+pub struct TxStatusHandler<B, P>
+where
+	B: BlockChain,
+	P: PoolAdapter,
+{
+	pub chain: Weak<Chain>,
+	pub tx_pool: Weak<RwLock<pool::TransactionPool<B, P>>>,
+}
+
+impl<B, P> TxStatusHandler<B, P>
+where
+	B: BlockChain,
+	P: PoolAdapter,
+{
+	/// Status of the transaction with the provided kernel excess. With
+	/// `wait_secs`, waits up to that long for the status to change first.
+	pub fn get_tx_status(
+		&self,
+		kernel_excess: String,
+		wait_secs: Option<u64>,
+	) -> Result<TxStatus, Error> {
+		let excess = util::from_hex(kernel_excess)
+			.map_err(|_| Error::Argument("invalid excess hex".into()))?;
+		if excess.len() != 33 {
+			return Err(Error::Argument("invalid excess length".into()));
+		}
+		let excess = Commitment::from_vec(excess);
+
+		let status = self.tx_status(&excess)?;
+		let wait = Duration::from_secs(wait_secs.unwrap_or(0).min(TX_STATUS_MAX_WAIT_SECS));
+		let deadline = Instant::now() + wait;
+		let mut head = self.head_hash()?;
+		while Instant::now() < deadline {
+			thread::sleep(TX_STATUS_POLL_INTERVAL);
+			// The kernel lookup only needs redoing when the chain moved, in
+			// between only the pools can change.
+			let current_head = self.head_hash()?;
+			let current = if current_head != head {
+				head = current_head;
+				self.tx_status(&excess)?
+			} else if let TxStatus::Mined { .. } = status {
+				continue;
+			} else {
+				self.pool_status(&excess)?
+			};
+			if current.has_changed(&status) {
+				return Ok(current);
+			}
+		}
+		Ok(status)
+	}
+
+	fn head_hash(&self) -> Result<Hash, Error> {
+		let head = w(&self.chain)?
+			.head()
+			.map_err(|e| Error::Internal(format!("{}", e)))?;
+		Ok(head.last_block_h)
+	}
+
+	fn tx_status(&self, excess: &Commitment) -> Result<TxStatus, Error> {
+		let chain = w(&self.chain)?;
+		if let Some((_, height, _)) = chain
+			.get_kernel_height(excess, None, None)
+			.map_err(|e| Error::Internal(format!("{}", e)))?
+		{
+			let header = chain
+				.get_header_by_height(height)
+				.map_err(|e| Error::Internal(format!("{}", e)))?;
+			let head = chain
+				.head()
+				.map_err(|e| Error::Internal(format!("{}", e)))?;
+			return Ok(TxStatus::Mined {
+				height,
+				header_hash: header.hash().to_hex(),
+				confirmations: head.height.saturating_sub(height) + 1,
+			});
+		}
+		self.pool_status(excess)
+	}
+
+	fn pool_status(&self, excess: &Commitment) -> Result<TxStatus, Error> {
+		let pool_arc = w(&self.tx_pool)?;
+		let pool = pool_arc.read();
+		if pool.txpool.retrieve_tx_by_kernel_excess(excess).is_some() {
+			Ok(TxStatus::Txpool)
+		} else if pool.stempool.retrieve_tx_by_kernel_excess(excess).is_some() {
+			Ok(TxStatus::Stempool)
+		} else {
+			Ok(TxStatus::NotFound)
+		}
+	}
+}
+
 /// Dummy wrapper for the hex-encoded serialized transaction.
 #[derive(Serialize, Deserialize)]
 struct TxWrapper {
 	tx_hex: String,
 }
 
-
 /// Push new transaction to our local transaction pool.
 /// POST /v1/pool/push_tx
 pub struct PoolPushHandler<B, P>
@@ -37,20 +37,23 @@ mod foreign_rpc;
 mod handlers;
 mod owner;
 mod owner_rpc;
+pub mod rate_limit;
 pub mod rest;
 mod router;
+pub mod tokens;
 mod types;
 mod tor;
 mod tor_rpc;
 
 pub use crate::auth::{
-	BasicAuthURIMiddleware, EPIC_BASIC_REALM, EPIC_FOREIGN_BASIC_REALM,
+	BasicAuthURIMiddleware, TokenAuthMiddleware, EPIC_BASIC_REALM, EPIC_FOREIGN_BASIC_REALM,
 };
 pub use crate::foreign::Foreign;
 pub use crate::foreign_rpc::ForeignRpc;
-pub use crate::handlers::node_apis;
+pub use crate::handlers::{node_apis, DEFAULT_MAX_BATCH_SIZE};
 pub use crate::owner::Owner;
 pub use crate::owner_rpc::OwnerRpc;
+pub use crate::rate_limit::{RateLimitConfig, RateLimitMiddleware};
 pub use crate::rest::*;
 pub use crate::router::*;
 pub use crate::types::*;
@@ -15,13 +15,17 @@
 //! Owner API External Definition
 
 use crate::chain::{Chain, SyncState};
-use crate::handlers::chain_api::{ChainCompactHandler, ChainValidationHandler};
-use crate::handlers::peers_api::{PeerHandler, PeersConnectedHandler, PeersOnionAddressesHandler};
+use crate::handlers::chain_api::{
+	ChainCompactHandler, ChainKernelIndexHandler, ChainValidationHandler, ProofHandler,
+};
+use crate::handlers::peers_api::{
+	PeerHandler, PeersConnectedHandler, PeersLocalAddrsHandler, PeersOnionAddressesHandler,
+};
 use crate::handlers::server_api::StatusHandler;
 use crate::p2p::types::PeerInfoDisplay;
 use crate::p2p::{self, PeerData};
 use crate::rest::*;
-use crate::types::Status;
+use crate::types::{LocalAddrs, ProvenInclusion, Status};
 use std::net::SocketAddr;
 use std::sync::Weak;
 
@@ -107,6 +111,23 @@ impl Owner {
 		chain_compact_handler.compact_chain()
 	}
 
+	/// Builds (or rebuilds) the kernel excess index, after which kernels are
+	/// looked up by excess in a single db read instead of a kernel MMR scan.
+	/// The index is kept up to date as blocks are processed from then on.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * `Ok(())` if the index was rebuilt successfully
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn rebuild_kernel_index(&self) -> Result<(), Error> {
+		let chain_kernel_index_handler = ChainKernelIndexHandler {
+			chain: self.chain.clone(),
+		};
+		chain_kernel_index_handler.rebuild_kernel_index()
+	}
+
 	/// Retrieves information about stored peers.
 	/// If `None` is provided, will list all stored peers.
 	///
@@ -184,4 +205,64 @@ impl Owner {
 		};
 		peers_onion_addresses_handler.get_onion_addresses()
 	}
+
+	/// Retrieves the addresses our p2p server listens on and the externally
+	/// reachable addresses we advertise to our peers.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`LocalAddrs`](types/struct.LocalAddrs.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_local_addrs(&self) -> Result<LocalAddrs, Error> {
+		let peers_local_addrs_handler = PeersLocalAddrsHandler {
+			peers: self.peers.clone(),
+		};
+		peers_local_addrs_handler.get_local_addrs()
+	}
+
+	/// Asks connected peers keeping the chain state for the merkle proof of an
+	/// output and checks it against our header chain. This is how a node
+	/// running in light mode learns about outputs. The proof shows the output
+	/// was added to the chain, not that it's still unspent.
+	///
+	/// # Arguments
+	/// * `commit` - the hex encoded commitment of the output.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`ProvenInclusion`](types/struct.ProvenInclusion.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered, `NotFound`
+	/// when several peers agree they don't have the output.
+	///
+
+	pub fn verify_output(&self, commit: String) -> Result<ProvenInclusion, Error> {
+		let proof_handler = ProofHandler {
+			chain: self.chain.clone(),
+			peers: self.peers.clone(),
+		};
+		proof_handler.verify_output(commit)
+	}
+
+	/// Asks connected peers keeping the chain state for the merkle proof of a
+	/// kernel and checks it against our header chain.
+	///
+	/// # Arguments
+	/// * `excess` - the hex encoded excess commitment of the kernel.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`ProvenInclusion`](types/struct.ProvenInclusion.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered, `NotFound`
+	/// when several peers agree they don't have the kernel.
+	///
+
+	pub fn verify_kernel(&self, excess: String) -> Result<ProvenInclusion, Error> {
+		let proof_handler = ProofHandler {
+			chain: self.chain.clone(),
+			peers: self.peers.clone(),
+		};
+		proof_handler.verify_kernel(excess)
+	}
 }
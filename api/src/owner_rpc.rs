@@ -18,7 +18,7 @@ use crate::owner::Owner;
 use crate::p2p::types::PeerInfoDisplay;
 use crate::p2p::PeerData;
 use crate::rest::Error;
-use crate::types::Status;
+use crate::types::{LocalAddrs, ProvenInclusion, Status};
 use std::net::SocketAddr;
 
 /// Public definition used to generate Node jsonrpc api.
@@ -132,6 +132,36 @@ pub trait OwnerRpc: Sync + Send {
 	 */
 	fn compact_chain(&self) -> Result<(), Error>;
 
+	/**
+	Networked version of [Owner::rebuild_kernel_index](struct.Node.html#method.rebuild_kernel_index).
+
+	# Json rpc example
+
+	```
+	# epic_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "rebuild_kernel_index",
+		"params": [],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": null
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn rebuild_kernel_index(&self) -> Result<(), Error>;
+
 	/**
 	Networked version of [Owner::get_peers](struct.Node.html#method.get_peers).
 
@@ -385,6 +415,119 @@ pub trait OwnerRpc: Sync + Send {
 	/// # );
 	/// ```
 	fn get_onion_addresses(&self) -> Result<Vec<String>, Error>;
+
+	/**
+	Networked version of [Owner::get_local_addrs](struct.Node.html#method.get_local_addrs).
+
+	# Json rpc example
+
+	```
+	# epic_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_local_addrs",
+		"params": [],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+				"listen_addrs": [
+					"0.0.0.0:3414",
+					"[::]:3414"
+				],
+				"external_addrs": [
+					"70.50.33.130:3414",
+					"[2001:db8::1]:3414"
+				]
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn get_local_addrs(&self) -> Result<LocalAddrs, Error>;
+
+	/**
+	Networked version of [Owner::verify_output](struct.Node.html#method.verify_output).
+
+	# Json rpc example
+
+	```
+	# epic_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "verify_output",
+		"params": ["08e1da9e6dc4d6e808a718b2f110a991dd775d65ce5ae408a4e1f002a4961aa9e7"],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+				"commit": "08e1da9e6dc4d6e808a718b2f110a991dd775d65ce5ae408a4e1f002a4961aa9e7",
+				"output_type": "Coinbase",
+				"mmr_index": 2011,
+				"header_height": 1002,
+				"header_hash": "0f2a2e0ba1a1b4e9e7bc0d2b07d8e57c9a1e81fdde0d67b5c8c0a5b5d0f1a2c3",
+				"peer": "70.50.33.130:3414"
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn verify_output(&self, commit: String) -> Result<ProvenInclusion, Error>;
+
+	/**
+	Networked version of [Owner::verify_kernel](struct.Node.html#method.verify_kernel).
+
+	# Json rpc example
+
+	```
+	# epic_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "verify_kernel",
+		"params": ["08a1a9a14fbd4ac29c8f1ba5ff0ed4fc5cba76d2dbec7a8e4b4e52a1b0b4cd7a6d"],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+				"commit": "08a1a9a14fbd4ac29c8f1ba5ff0ed4fc5cba76d2dbec7a8e4b4e52a1b0b4cd7a6d",
+				"output_type": null,
+				"mmr_index": 1004,
+				"header_height": 1002,
+				"header_hash": "0f2a2e0ba1a1b4e9e7bc0d2b07d8e57c9a1e81fdde0d67b5c8c0a5b5d0f1a2c3",
+				"peer": "70.50.33.130:3414"
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn verify_kernel(&self, excess: String) -> Result<ProvenInclusion, Error>;
 }
 
 impl OwnerRpc for Owner {
@@ -400,6 +543,10 @@ impl OwnerRpc for Owner {
 		Owner::compact_chain(self)
 	}
 
+	fn rebuild_kernel_index(&self) -> Result<(), Error> {
+		Owner::rebuild_kernel_index(self)
+	}
+
 	fn get_peers(&self, addr: Option<SocketAddr>) -> Result<Vec<PeerData>, Error> {
 		Owner::get_peers(self, addr)
 	}
@@ -419,6 +566,18 @@ impl OwnerRpc for Owner {
 	fn get_onion_addresses(&self) -> Result<Vec<String>, Error> {
 		Owner::get_onion_addresses(self)
 	}
+
+	fn get_local_addrs(&self) -> Result<LocalAddrs, Error> {
+		Owner::get_local_addrs(self)
+	}
+
+	fn verify_output(&self, commit: String) -> Result<ProvenInclusion, Error> {
+		Owner::verify_output(self, commit)
+	}
+
+	fn verify_kernel(&self, excess: String) -> Result<ProvenInclusion, Error> {
+		Owner::verify_kernel(self, excess)
+	}
 }
 
 #[doc(hidden)]
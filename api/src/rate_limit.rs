@@ -0,0 +1,529 @@
+// Copyright 2020 The Epic Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Rate limiting of the API clients. Each client (its IP, or its API token
+//! when it has one) gets a token bucket, each call costing the weight of its
+//! method, and the number of requests served at once is capped, long polls
+//! having their own cap.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::future::ok;
+use http_body_util::Full;
+use hyper::header::RETRY_AFTER;
+use hyper::{Request, Response, StatusCode};
+use lru_cache::LruCache;
+use serde_json::Value;
+
+use crate::rest::ClientAddr;
+use crate::router::{Handler, HandlerObj, ResponseFuture};
+use crate::tokens::AuthContext;
+use crate::util::Mutex;
+use crate::web::{boxed_body, response, BoxBodyType};
+
+/// Above this many tracked clients the least recently seen ones are dropped.
+const MAX_BUCKETS: usize = 10_000;
+
+/// Lowest refill rate, a bucket that never refills would lock its client
+/// out for good.
+const MIN_RATE: f64 = 0.01;
+
+/// Rate limits of the API, per client.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct RateLimitConfig {
+	/// Cost refilled per second for each client IP
+	pub ip_requests_per_second: f64,
+	/// Cost a client IP can spend at once
+	pub ip_burst: u32,
+	/// Cost refilled per second for each API token
+	pub token_requests_per_second: f64,
+	/// Cost an API token can spend at once
+	pub token_burst: u32,
+	/// Requests served at once over all clients, 0 for no limit
+	pub max_concurrent_requests: usize,
+	/// Long polls served at once over all clients, each holding a blocking
+	/// worker, 0 for no limit
+	pub max_concurrent_long_polls: usize,
+	/// Cost of the methods (JSON-RPC method names, the v1 routes using
+	/// the name of the matching method), overriding the built-in ones
+	pub method_costs: HashMap<String, u32>,
+}
+
+impl Default for RateLimitConfig {
+	fn default() -> RateLimitConfig {
+		RateLimitConfig {
+			ip_requests_per_second: 20.0,
+			ip_burst: 100,
+			token_requests_per_second: 100.0,
+			token_burst: 500,
+			max_concurrent_requests: 64,
+			max_concurrent_long_polls: 8,
+			method_costs: HashMap::new(),
+		}
+	}
+}
+
+/// Built-in cost of a method, the ones walking the chain or returning large
+/// ranges costing more.
+fn default_method_cost(method: &str) -> u32 {
+	match method {
+		"validate_chain" | "compact_chain" | "rebuild_kernel_index" | "audit_foundation_levy" => 50,
+		"get_outputs"
+		| "get_unspent_outputs"
+		| "get_blocks"
+		| "get_block_summaries"
+		| "get_network_stats"
+		| "get_supply"
+		| "verify_output"
+		| "verify_kernel" => 10,
+		"get_kernel" | "get_pmmr_indices" | "get_block_stats" => 5,
+		_ => 1,
+	}
+}
+
+/// Method matching a v1 route, for its cost.
+fn route_method(path: &str) -> Option<&'static str> {
+	let under = |prefix: &str| path == prefix || path.starts_with(&format!("{}/", prefix));
+	let method = if under("/v1/chain/validate") {
+		"validate_chain"
+	} else if under("/v1/chain/compact") {
+		"compact_chain"
+	} else if under("/v1/chain/rebuild_kernel_index") {
+		"rebuild_kernel_index"
+	} else if under("/v1/chain/foundation_levy") {
+		"audit_foundation_levy"
+	} else if under("/v1/chain/outputs") {
+		"get_outputs"
+	} else if under("/v1/txhashset") {
+		"get_unspent_outputs"
+	} else if under("/v1/chain/kernels") {
+		"get_kernel"
+	} else {
+		return None;
+	};
+	Some(method)
+}
+
+/// Whose bucket a request is charged to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ClientKey {
+	Ip(IpAddr),
+	Token(String),
+	/// Requests we don't know the origin of, all sharing a bucket
+	Unknown,
+}
+
+struct Bucket {
+	tokens: f64,
+	last: Instant,
+}
+
+/// Why a client can't be served.
+#[derive(Debug, PartialEq)]
+pub enum Throttled {
+	/// It can afford the request after waiting that long
+	RetryAfter(Duration),
+	/// The request costs more than the client can ever spend at once
+	OverBurst,
+}
+
+impl Throttled {
+	/// HTTP 429 response for the client.
+	pub fn response(self) -> Response<BoxBodyType> {
+		match self {
+			Throttled::RetryAfter(retry_after) => too_many_requests(retry_after),
+			Throttled::OverBurst => error_response(
+				StatusCode::TOO_MANY_REQUESTS,
+				"Request costs more than the rate limit burst",
+			),
+		}
+	}
+}
+
+/// Token buckets of the API clients and the count of requests and long
+/// polls in flight.
+pub struct RateLimiter {
+	config: RateLimitConfig,
+	buckets: Mutex<LruCache<ClientKey, Bucket>>,
+	in_flight: AtomicUsize,
+	long_polls: AtomicUsize,
+}
+
+impl RateLimiter {
+	pub fn new(config: RateLimitConfig) -> RateLimiter {
+		RateLimiter {
+			config,
+			buckets: Mutex::new(LruCache::new(MAX_BUCKETS)),
+			in_flight: AtomicUsize::new(0),
+			long_polls: AtomicUsize::new(0),
+		}
+	}
+
+	/// Cost of a call to the provided method.
+	pub fn method_cost(&self, method: &str) -> u32 {
+		self.config
+			.method_costs
+			.get(method)
+			.copied()
+			.unwrap_or_else(|| default_method_cost(method))
+	}
+
+	/// Cost of a request to the provided route, the v2 APIs being charged
+	/// per JSON-RPC call instead.
+	fn route_cost(&self, path: &str) -> u32 {
+		route_method(path).map_or(1, |m| self.method_cost(m))
+	}
+
+	/// Cost of a JSON-RPC request or batch.
+	pub fn rpc_cost(&self, request: &Value) -> u32 {
+		let cost = |request: &Value| {
+			let method = request.get("method").and_then(|m| m.as_str());
+			method.map_or(1, |m| self.method_cost(m))
+		};
+		match request {
+			Value::Array(batch) => batch.iter().map(cost).fold(0, u32::saturating_add),
+			request => cost(request),
+		}
+	}
+
+	fn limits(&self, key: &ClientKey) -> (f64, f64) {
+		let (rate, burst) = match key {
+			ClientKey::Token(_) => (
+				self.config.token_requests_per_second,
+				self.config.token_burst,
+			),
+			_ => (self.config.ip_requests_per_second, self.config.ip_burst),
+		};
+		(rate.max(MIN_RATE), burst.max(1) as f64)
+	}
+
+	/// Takes the cost from the client bucket, or tells why the client can't
+	/// afford it.
+	fn take(&self, key: &ClientKey, cost: u32, now: Instant) -> Result<(), Throttled> {
+		let (rate, burst) = self.limits(key);
+		let cost = cost as f64;
+		if cost > burst {
+			return Err(Throttled::OverBurst);
+		}
+		let mut buckets = self.buckets.lock();
+		let mut bucket = buckets.remove(key).unwrap_or(Bucket {
+			tokens: burst,
+			last: now,
+		});
+		let elapsed = now.saturating_duration_since(bucket.last).as_secs_f64();
+		bucket.tokens = (bucket.tokens + elapsed * rate).min(burst);
+		bucket.last = now;
+		let res = if bucket.tokens >= cost {
+			bucket.tokens -= cost;
+			Ok(())
+		} else {
+			Err(Throttled::RetryAfter(Duration::from_secs_f64(
+				(cost - bucket.tokens) / rate,
+			)))
+		};
+		// evicts the least recently seen client when full
+		buckets.insert(key.clone(), bucket);
+		res
+	}
+
+	fn counter(&self, long_poll: bool) -> &AtomicUsize {
+		if long_poll {
+			&self.long_polls
+		} else {
+			&self.in_flight
+		}
+	}
+
+	/// Counts a request in flight until the guard is dropped, None if too
+	/// many requests are being served already.
+	fn enter(self: &Arc<Self>) -> Option<InFlightGuard> {
+		self.enter_counter(false, self.config.max_concurrent_requests)
+	}
+
+	/// Counts a long poll in flight until the guard is dropped, None if too
+	/// many long polls are being served already.
+	fn enter_long_poll(self: &Arc<Self>) -> Option<InFlightGuard> {
+		self.enter_counter(true, self.config.max_concurrent_long_polls)
+	}
+
+	fn enter_counter(self: &Arc<Self>, long_poll: bool, max: usize) -> Option<InFlightGuard> {
+		let counter = self.counter(long_poll);
+		let prev = counter.fetch_add(1, Ordering::SeqCst);
+		if max > 0 && prev >= max {
+			counter.fetch_sub(1, Ordering::SeqCst);
+			return None;
+		}
+		Some(InFlightGuard {
+			limiter: self.clone(),
+			long_poll,
+		})
+	}
+}
+
+struct InFlightGuard {
+	limiter: Arc<RateLimiter>,
+	long_poll: bool,
+}
+
+impl Drop for InFlightGuard {
+	fn drop(&mut self) {
+		let counter = self.limiter.counter(self.long_poll);
+		counter.fetch_sub(1, Ordering::SeqCst);
+	}
+}
+
+/// The bucket a request is charged to, attached to the request by the rate
+/// limiting middleware for the v2 handlers to charge each JSON-RPC call.
+#[derive(Clone)]
+pub struct RateLimit {
+	limiter: Arc<RateLimiter>,
+	key: ClientKey,
+	slot: Arc<Mutex<Option<InFlightGuard>>>,
+}
+
+impl RateLimit {
+	/// Charges the cost of a JSON-RPC request or batch, telling why the
+	/// client can't be served if it can't afford it.
+	pub fn charge_rpc(&self, request: &Value) -> Result<(), Throttled> {
+		let cost = self.limiter.rpc_cost(request);
+		self.limiter.take(&self.key, cost, Instant::now())
+	}
+
+	/// Counts the request against max_concurrent_long_polls instead of
+	/// max_concurrent_requests, for the long polls which would otherwise hold
+	/// the slots of the quick calls. Fails when too many long polls are
+	/// being served already.
+	pub fn enter_long_poll(&self) -> Result<(), Throttled> {
+		let guard = self
+			.limiter
+			.enter_long_poll()
+			.ok_or(Throttled::RetryAfter(Duration::from_secs(1)))?;
+		// drops the slot the request held so far
+		*self.slot.lock() = Some(guard);
+		Ok(())
+	}
+}
+
+// Rate Limiting Middleware
+//
+// Caps the requests served at once and charges the v1 routes to the bucket
+// of the client, a bearer of an API token (attached by the token middleware
+// running before) being charged to its token, anybody else to its IP.
+pub struct RateLimitMiddleware {
+	limiter: Arc<RateLimiter>,
+}
+
+impl RateLimitMiddleware {
+	pub fn new(config: RateLimitConfig) -> RateLimitMiddleware {
+		RateLimitMiddleware {
+			limiter: Arc::new(RateLimiter::new(config)),
+		}
+	}
+}
+
+fn client_key(req: &Request<hyper::body::Incoming>) -> ClientKey {
+	if let Some(auth) = req.extensions().get::<AuthContext>() {
+		if auth.name != "anonymous" {
+			return ClientKey::Token(auth.name.clone());
+		}
+	}
+	match req.extensions().get::<ClientAddr>() {
+		Some(addr) => ClientKey::Ip(addr.0.ip()),
+		None => ClientKey::Unknown,
+	}
+}
+
+impl Handler<Full<Bytes>> for RateLimitMiddleware {
+	fn call(
+		&self,
+		mut req: Request<hyper::body::Incoming>,
+		mut handlers: Box<dyn Iterator<Item = HandlerObj>>,
+	) -> ResponseFuture {
+		let next_handler = match handlers.next() {
+			Some(h) => h,
+			None => return response(StatusCode::INTERNAL_SERVER_ERROR, "no handler found"),
+		};
+		if req.method().as_str() == "OPTIONS" {
+			return next_handler.call(req, handlers);
+		}
+		let guard = match self.limiter.enter() {
+			Some(guard) => guard,
+			None => return Box::pin(ok(too_many_requests(Duration::from_secs(1)))),
+		};
+		let key = client_key(&req);
+		let path = req.uri().path();
+		if path != "/v2/owner" && path != "/v2/foreign" {
+			let cost = self.limiter.route_cost(path);
+			if let Err(throttled) = self.limiter.take(&key, cost, Instant::now()) {
+				return Box::pin(ok(throttled.response()));
+			}
+		}
+		let slot = Arc::new(Mutex::new(Some(guard)));
+		req.extensions_mut().insert(RateLimit {
+			limiter: self.limiter.clone(),
+			key,
+			slot: slot.clone(),
+		});
+		let res = next_handler.call(req, handlers);
+		Box::pin(async move {
+			let _slot = slot;
+			res.await
+		})
+	}
+}
+
+/// HTTP 429 response telling the client when to come back.
+pub fn too_many_requests(retry_after: Duration) -> Response<BoxBodyType> {
+	let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+	let mut res = error_response(StatusCode::TOO_MANY_REQUESTS, "Too Many Requests");
+	res.headers_mut().insert(RETRY_AFTER, secs.max(1).into());
+	res
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response<BoxBodyType> {
+	let body = serde_json::json!({
+		"jsonrpc": "2.0",
+		"error": {
+			"code": -32600,
+			"message": message,
+		},
+		"id": null,
+	});
+	Response::builder()
+		.status(status)
+		.header("content-type", "application/json")
+		.body(boxed_body(body.to_string()))
+		.unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+
+	#[test]
+	fn test_token_bucket() {
+		let limiter = RateLimiter::new(RateLimitConfig {
+			ip_requests_per_second: 2.0,
+			ip_burst: 4,
+			..RateLimitConfig::default()
+		});
+		let ip = ClientKey::Ip("10.0.0.1".parse().unwrap());
+		let other = ClientKey::Ip("10.0.0.2".parse().unwrap());
+		let now = Instant::now();
+
+		assert!(limiter.take(&ip, 3, now).is_ok());
+		assert!(limiter.take(&ip, 1, now).is_ok());
+		assert_eq!(
+			limiter.take(&ip, 1, now),
+			Err(Throttled::RetryAfter(Duration::from_millis(500)))
+		);
+		// other clients have their own bucket
+		assert!(limiter.take(&other, 4, now).is_ok());
+
+		// refilled at the configured rate, up to the burst
+		let later = now + Duration::from_secs(1);
+		assert!(limiter.take(&ip, 2, later).is_ok());
+		assert!(limiter.take(&ip, 1, later).is_err());
+		let much_later = now + Duration::from_secs(60);
+		assert!(limiter.take(&ip, 4, much_later).is_ok());
+		assert!(limiter.take(&ip, 1, much_later).is_err());
+
+		// a call costing more than the burst is never served
+		let full = much_later + Duration::from_secs(2);
+		assert_eq!(limiter.take(&ip, 100, full), Err(Throttled::OverBurst));
+		assert!(limiter.take(&ip, 4, full).is_ok());
+	}
+
+	#[test]
+	fn test_buckets_bounded() {
+		let limiter = RateLimiter::new(RateLimitConfig {
+			ip_burst: 4,
+			..RateLimitConfig::default()
+		});
+		let now = Instant::now();
+		let first = ClientKey::Ip("10.0.0.1".parse().unwrap());
+		assert!(limiter.take(&first, 4, now).is_ok());
+		for i in 0..MAX_BUCKETS as u32 {
+			let key = ClientKey::Ip(std::net::Ipv4Addr::from(i).into());
+			assert!(limiter.take(&key, 1, now).is_ok());
+		}
+		assert_eq!(limiter.buckets.lock().len(), MAX_BUCKETS);
+		// the least recently seen client got evicted
+		assert!(!limiter.buckets.lock().contains_key(&first));
+	}
+
+	#[test]
+	fn test_costs_and_concurrency() {
+		let mut method_costs = HashMap::new();
+		method_costs.insert("get_tip".to_owned(), 3);
+		let limiter = Arc::new(RateLimiter::new(RateLimitConfig {
+			max_concurrent_requests: 2,
+			method_costs,
+			..RateLimitConfig::default()
+		}));
+
+		assert_eq!(limiter.method_cost("get_header"), 1);
+		assert_eq!(limiter.method_cost("get_tip"), 3);
+		assert_eq!(limiter.method_cost("validate_chain"), 50);
+		assert_eq!(limiter.route_cost("/v1/chain/outputs/byids"), 10);
+		assert_eq!(limiter.route_cost("/v1/status"), 1);
+
+		let request = |method: &str| json!({"jsonrpc": "2.0", "method": method, "id": 1});
+		assert_eq!(limiter.rpc_cost(&request("get_blocks")), 10);
+		assert_eq!(limiter.rpc_cost(&request("get_block_summaries")), 10);
+		let batch = json!([request("get_tip"), request("get_kernel"), 5]);
+		assert_eq!(limiter.rpc_cost(&batch), 9);
+
+		let first = limiter.enter().unwrap();
+		let _second = limiter.enter().unwrap();
+		assert!(limiter.enter().is_none());
+		drop(first);
+		let third = limiter.enter().unwrap();
+		assert!(limiter.enter().is_none());
+
+		// a long poll gives its slot back for one of the long poll slots
+		let long_poll = |guard| RateLimit {
+			limiter: limiter.clone(),
+			key: ClientKey::Unknown,
+			slot: Arc::new(Mutex::new(Some(guard))),
+		};
+		let first_poll = long_poll(third);
+		assert!(first_poll.enter_long_poll().is_ok());
+		let fourth = limiter.enter().unwrap();
+		assert!(limiter.enter().is_none());
+		drop(fourth);
+
+		// which are capped too
+		let mut polls = vec![first_poll];
+		for _ in 1..RateLimitConfig::default().max_concurrent_long_polls {
+			let poll = long_poll(limiter.enter().unwrap());
+			assert!(poll.enter_long_poll().is_ok());
+			polls.push(poll);
+		}
+		let extra_poll = long_poll(limiter.enter().unwrap());
+		assert_eq!(
+			extra_poll.enter_long_poll(),
+			Err(Throttled::RetryAfter(Duration::from_secs(1)))
+		);
+		drop(polls);
+		assert!(extra_poll.enter_long_poll().is_ok());
+	}
+}
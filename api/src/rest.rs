@@ -57,6 +57,10 @@ pub enum Error {
 	},
 }
 
+/// Address of the client a request comes from, attached to every request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClientAddr(pub SocketAddr);
+
 impl From<hyper::http::Error> for Error {
 	fn from(error: hyper::http::Error) -> Error {
 		Error::RequestError(error.to_string())
@@ -194,15 +198,16 @@ impl ApiServer {
 							}
 							conn = listener.accept() => {
 								match conn {
-									Ok((stream, _)) => {
+									Ok((stream, client_addr)) => {
 										let router = router.clone();
 										let io = hyper_util::rt::TokioIo::new(stream);
 
 										tokio::task::spawn(async move {
 
 
-											let service = service_fn(move |req: Request<hyper::body::Incoming>| {
+											let service = service_fn(move |mut req: Request<hyper::body::Incoming>| {
 												let mut router = router.clone();
+												req.extensions_mut().insert(ClientAddr(client_addr));
 												async move {
 													router.call(req).await
 												}
@@ -278,7 +283,7 @@ impl ApiServer {
 							}
 							conn = listener.accept() => {
 								match conn {
-									Ok((stream, _)) => {
+									Ok((stream, client_addr)) => {
 										let router = router.clone();
 										let tls_acceptor = tls_acceptor.clone();
 										// Do not wrap stream with TokioIo yet
@@ -288,8 +293,9 @@ impl ApiServer {
 												Ok(tls_stream) => {
 													let io = hyper_util::rt::TokioIo::new(tls_stream);
 
-													let service = service_fn(move |req: Request<hyper::body::Incoming>| {
+													let service = service_fn(move |mut req: Request<hyper::body::Incoming>| {
 														let mut router = router.clone();
+														req.extensions_mut().insert(ClientAddr(client_addr));
 														async move {
 															router.call(req).await
 														}
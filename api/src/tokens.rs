@@ -0,0 +1,339 @@
+// Copyright 2020 The Epic Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Named API tokens and the scopes they grant. Only the SHA-256 of each
+//! token is stored on disk, the token itself is shown once on creation.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use rand::{rng, Rng};
+use ring::digest;
+use subtle::ConstantTimeEq;
+
+use crate::rest::Error;
+use crate::util::to_hex;
+
+/// Length of a generated token, in bytes.
+const TOKEN_LEN: usize = 32;
+
+/// What an API token is allowed to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+	/// Read the chain, the pool and the node status
+	ReadChain,
+	/// Push transactions to the pool
+	PushTx,
+	/// Get block templates and submit blocks
+	Mining,
+	/// Read, ban or unban peers and ask them for proofs
+	PeerAdmin,
+	/// Validate, compact and reindex the chain
+	ChainAdmin,
+}
+
+impl Scope {
+	/// All scopes.
+	pub fn all() -> Vec<Scope> {
+		vec![
+			Scope::ReadChain,
+			Scope::PushTx,
+			Scope::Mining,
+			Scope::PeerAdmin,
+			Scope::ChainAdmin,
+		]
+	}
+
+	/// The scopes of the foreign API.
+	pub fn foreign() -> Vec<Scope> {
+		vec![Scope::ReadChain, Scope::PushTx, Scope::Mining]
+	}
+
+	/// Admin calls get an audit log line.
+	pub fn is_admin(&self) -> bool {
+		matches!(self, Scope::PeerAdmin | Scope::ChainAdmin)
+	}
+
+	/// Scope required by a method of the v2 owner or foreign API, None for
+	/// the unknown methods, which nobody may call.
+	pub fn for_rpc_method(method: &str) -> Option<Scope> {
+		let scope = match method {
+			"get_version"
+			| "get_tip"
+			| "get_header"
+			| "get_block"
+			| "get_blocks"
+			| "get_block_summaries"
+			| "get_block_stats"
+			| "get_kernel"
+			| "get_last_n_kernels"
+			| "get_outputs"
+			| "get_unspent_outputs"
+			| "get_pmmr_indices"
+			| "get_pool_size"
+			| "get_stempool_size"
+			| "get_unconfirmed_transactions"
+			| "get_tx_status"
+			| "get_network_stats"
+			| "get_fork_schedule"
+			| "get_supply"
+			| "audit_foundation_levy"
+			| "get_status" => Scope::ReadChain,
+			"push_transaction" => Scope::PushTx,
+			"get_block_template" | "finalize_block_template" | "submit_block" => Scope::Mining,
+			// the proof queries ban the peers answering with bad proofs
+			"get_peers"
+			| "get_connected_peers"
+			| "get_local_addrs"
+			| "get_onion_addresses"
+			| "ban_peer"
+			| "unban_peer"
+			| "verify_output"
+			| "verify_kernel" => Scope::PeerAdmin,
+			"validate_chain" | "compact_chain" | "rebuild_kernel_index" => Scope::ChainAdmin,
+			_ => return None,
+		};
+		Some(scope)
+	}
+
+	/// Scope required by a v1 route, None for the routes not gated by route
+	/// (the v2 APIs are gated per method, the tor API isn't gated).
+	pub fn for_route(path: &str) -> Option<Scope> {
+		let under = |prefix: &str| path == prefix || path.starts_with(&format!("{}/", prefix));
+		if !under("/v1") {
+			return None;
+		}
+		let scope = if under("/v1/peers") {
+			Scope::PeerAdmin
+		} else if under("/v1/chain/compact")
+			|| under("/v1/chain/validate")
+			|| under("/v1/chain/rebuild_kernel_index")
+		{
+			Scope::ChainAdmin
+		} else if under("/v1/pool/push_tx") {
+			Scope::PushTx
+		} else if under("/v1/mining") {
+			Scope::Mining
+		} else {
+			Scope::ReadChain
+		};
+		Some(scope)
+	}
+}
+
+impl fmt::Display for Scope {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let s = match self {
+			Scope::ReadChain => "read_chain",
+			Scope::PushTx => "push_tx",
+			Scope::Mining => "mining",
+			Scope::PeerAdmin => "peer_admin",
+			Scope::ChainAdmin => "chain_admin",
+		};
+		write!(f, "{}", s)
+	}
+}
+
+impl FromStr for Scope {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Scope, Error> {
+		Scope::all()
+			.into_iter()
+			.find(|scope| scope.to_string() == s)
+			.ok_or_else(|| Error::Argument(format!("unknown scope {}", s)))
+	}
+}
+
+/// A named API token, as stored on disk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+	/// Name of the token, shows in the audit log
+	pub name: String,
+	/// Hex SHA-256 of the token
+	pub hash: String,
+	/// What the token is allowed to do
+	pub scopes: Vec<Scope>,
+}
+
+/// Who is calling the API and what they're allowed to do, attached to the
+/// request by the token middleware.
+#[derive(Debug, Clone)]
+pub struct AuthContext {
+	/// Token name, or how the caller authenticated otherwise
+	pub name: String,
+	/// Granted scopes
+	pub scopes: Vec<Scope>,
+}
+
+impl AuthContext {
+	/// Whether the caller has the provided scope.
+	pub fn allows(&self, scope: Scope) -> bool {
+		self.scopes.contains(&scope)
+	}
+}
+
+/// The configured API tokens.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ApiTokens {
+	tokens: Vec<ApiToken>,
+}
+
+impl ApiTokens {
+	/// Loads the tokens from the provided file, no tokens if it doesn't exist.
+	pub fn load(path: &str) -> Result<ApiTokens, Error> {
+		if !Path::new(path).exists() {
+			return Ok(ApiTokens::default());
+		}
+		let data = fs::read_to_string(path)
+			.map_err(|e| Error::Internal(format!("can't read {}: {}", path, e)))?;
+		serde_json::from_str(&data)
+			.map_err(|e| Error::Internal(format!("invalid api tokens file {}: {}", path, e)))
+	}
+
+	/// Stores the tokens in the provided file.
+	pub fn save(&self, path: &str) -> Result<(), Error> {
+		let data = serde_json::to_string_pretty(self)
+			.map_err(|e| Error::Internal(format!("can't serialize api tokens: {}", e)))?;
+		fs::write(path, data)
+			.map_err(|e| Error::Internal(format!("can't write {}: {}", path, e)))?;
+		set_permissions(path)
+	}
+
+	/// The configured tokens.
+	pub fn tokens(&self) -> &[ApiToken] {
+		&self.tokens
+	}
+
+	/// Adds a new token with the provided name and scopes, returning the
+	/// token itself, which isn't stored.
+	pub fn add(&mut self, name: &str, scopes: Vec<Scope>) -> Result<String, Error> {
+		if self.tokens.iter().any(|t| t.name == name) {
+			return Err(Error::Argument(format!("token {} already exists", name)));
+		}
+		if scopes.is_empty() {
+			return Err(Error::Argument("a token needs at least a scope".to_owned()));
+		}
+		let token = to_hex(rng().random::<[u8; TOKEN_LEN]>().to_vec());
+		self.tokens.push(ApiToken {
+			name: name.to_owned(),
+			hash: hash_token(&token),
+			scopes,
+		});
+		Ok(token)
+	}
+
+	/// Removes the named token, returning whether it existed.
+	pub fn remove(&mut self, name: &str) -> bool {
+		let len = self.tokens.len();
+		self.tokens.retain(|t| t.name != name);
+		self.tokens.len() != len
+	}
+
+	/// The token matching the provided secret, if any.
+	pub fn authenticate(&self, token: &str) -> Option<&ApiToken> {
+		let hash = hash_token(token);
+		self.tokens
+			.iter()
+			.find(|t| t.hash.as_bytes().ct_eq(hash.as_bytes()).unwrap_u8() == 1)
+	}
+}
+
+fn hash_token(token: &str) -> String {
+	to_hex(
+		digest::digest(&digest::SHA256, token.as_bytes())
+			.as_ref()
+			.to_vec(),
+	)
+}
+
+#[cfg(unix)]
+fn set_permissions(path: &str) -> Result<(), Error> {
+	use std::os::unix::prelude::*;
+	fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+		.map_err(|e| Error::Internal(format!("can't set permissions of {}: {}", path, e)))
+}
+
+#[cfg(windows)]
+fn set_permissions(_path: &str) -> Result<(), Error> {
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_tokens() {
+		let mut tokens = ApiTokens::default();
+		let indexer = tokens.add("indexer", vec![Scope::ReadChain]).unwrap();
+		let admin = tokens.add("admin", Scope::all()).unwrap();
+		assert!(tokens.add("admin", vec![Scope::Mining]).is_err());
+		assert!(tokens.add("none", vec![]).is_err());
+
+		// only the hashes are kept
+		let json = serde_json::to_string(&tokens).unwrap();
+		assert!(!json.contains(&indexer) && !json.contains(&admin));
+		let tokens: ApiTokens = serde_json::from_str(&json).unwrap();
+
+		assert_eq!(tokens.authenticate(&indexer).unwrap().name, "indexer");
+		assert_eq!(tokens.authenticate(&admin).unwrap().scopes, Scope::all());
+		assert!(tokens.authenticate("wrong").is_none());
+
+		let mut tokens = tokens;
+		assert!(tokens.remove("indexer"));
+		assert!(!tokens.remove("indexer"));
+		assert!(tokens.authenticate(&indexer).is_none());
+	}
+
+	#[test]
+	fn test_scopes() {
+		assert_eq!("peer_admin".parse::<Scope>().unwrap(), Scope::PeerAdmin);
+		assert!("root".parse::<Scope>().is_err());
+
+		assert_eq!(Scope::for_rpc_method("get_tip"), Some(Scope::ReadChain));
+		assert_eq!(
+			Scope::for_rpc_method("push_transaction"),
+			Some(Scope::PushTx)
+		);
+		assert_eq!(Scope::for_rpc_method("ban_peer"), Some(Scope::PeerAdmin));
+		assert_eq!(
+			Scope::for_rpc_method("verify_kernel"),
+			Some(Scope::PeerAdmin)
+		);
+		assert_eq!(
+			Scope::for_rpc_method("compact_chain"),
+			Some(Scope::ChainAdmin)
+		);
+		assert_eq!(Scope::for_rpc_method("shutdown"), None);
+
+		assert_eq!(
+			Scope::for_route("/v1/chain/kernels/xyz"),
+			Some(Scope::ReadChain)
+		);
+		assert_eq!(
+			Scope::for_route("/v1/chain/compact"),
+			Some(Scope::ChainAdmin)
+		);
+		assert_eq!(
+			Scope::for_route("/v1/peers/1.2.3.4:3414/ban"),
+			Some(Scope::PeerAdmin)
+		);
+		assert_eq!(Scope::for_route("/v1/pool/push_tx"), Some(Scope::PushTx));
+		assert_eq!(Scope::for_route("/v2/owner"), None);
+	}
+}
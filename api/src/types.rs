@@ -15,12 +15,14 @@
 use std::sync::Arc;
 
 use crate::chain;
-use crate::core::consensus::HeaderInfo;
+use crate::core::consensus::{self, HeaderInfo};
+use crate::core::core::block::feijoada::Policy;
 use crate::core::core::hash::Hashed;
 use crate::core::core::merkle_proof::MerkleProof;
 use crate::core::core::{BlockHeader, KernelFeatures, Transaction, TxKernel};
+use crate::core::forks::{Fork, ForkSchedule};
 use crate::core::pow::PoWType;
-use crate::core::{core, ser};
+use crate::core::{core, global, ser};
 use crate::p2p;
 use crate::util;
 use crate::util::secp::pedersen;
@@ -72,6 +74,15 @@ impl Tip {
 	}
 }
 
+/// Addresses our p2p server listens on and is reachable on
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LocalAddrs {
+	/// Socket addresses the p2p server is listening on
+	pub listen_addrs: Vec<std::net::SocketAddr>,
+	/// Externally reachable addresses, configured or learned from our peers
+	pub external_addrs: Vec<p2p::types::PeerAddr>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Status {
 	// The protocol version
@@ -714,6 +725,155 @@ impl BlockPrintable {
 	}
 }
 
+/// Summary of a block, as listed by block explorers.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BlockSummaryPrintable {
+	/// The block header
+	pub header: BlockHeaderPrintable,
+	/// Proof of work algorithm the block was mined with
+	pub pow_algorithm: PoWType,
+	/// Difficulty of this block alone, for each proof of work algorithm
+	pub difficulty: HashMap<PoWType, u64>,
+	/// Number of inputs
+	pub num_inputs: u64,
+	/// Number of outputs, coinbase and foundation outputs included
+	pub num_outputs: u64,
+	/// Number of kernels, coinbase kernels included
+	pub num_kernels: u64,
+	/// Number of transactions (non coinbase kernels)
+	pub num_txs: u64,
+	/// Sum of the transaction fees
+	pub fees: u64,
+	/// Total reward paid out by the block: mining reward, fees and foundation levy
+	pub total_reward: u64,
+	/// Whether the block pays the foundation levy out
+	pub has_foundation_output: bool,
+}
+
+impl BlockSummaryPrintable {
+	pub fn from_block(
+		block: &core::Block,
+		prev: Option<&core::BlockHeader>,
+	) -> BlockSummaryPrintable {
+		let total_difficulty = &block.header.pow.total_difficulty.num;
+		let difficulty = total_difficulty
+			.iter()
+			.map(|(algo, total)| {
+				let prev_total = prev.map_or(0, |p| p.pow.total_difficulty.to_num(*algo));
+				(*algo, total.saturating_sub(prev_total))
+			})
+			.collect();
+		let height = block.header.height;
+		let fees = block.total_fees();
+		BlockSummaryPrintable {
+			header: BlockHeaderPrintable::from_header(&block.header),
+			pow_algorithm: PoWType::from(&block.header.pow.proof),
+			difficulty,
+			num_inputs: block.inputs().len() as u64,
+			num_outputs: block.outputs().len() as u64,
+			num_kernels: block.kernels().len() as u64,
+			num_txs: block.kernels().iter().filter(|k| !k.is_coinbase()).count() as u64,
+			fees,
+			total_reward: consensus::reward_foundation(fees, height),
+			has_foundation_output: consensus::is_foundation_height(height),
+		}
+	}
+}
+
+/// A page of block summaries over a height range.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BlockListing {
+	/// Height of the last block retrieved, the next page starts right after it
+	pub last_retrieved_height: u64,
+	/// Height to resume the listing from, none when the requested range
+	/// (up to our head) was fully walked
+	pub next_height: Option<u64>,
+	/// The block summaries, by increasing height
+	pub blocks: Vec<BlockSummaryPrintable>,
+}
+
+/// Weight, spent outputs and fee statistics of a block.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BlockStats {
+	/// Height of the block
+	pub height: u64,
+	/// Hash of the block
+	pub hash: String,
+	/// Consensus weight of the block
+	pub weight: u64,
+	/// Share of the maximum block weight used
+	pub fullness: f64,
+	/// Number of inputs
+	pub num_inputs: u64,
+	/// Number of outputs
+	pub num_outputs: u64,
+	/// Number of kernels
+	pub num_kernels: u64,
+	/// Share of the block outputs spent since
+	pub spent_ratio: f64,
+	/// Sum of the transaction fees
+	pub fees: u64,
+	/// Fees per weight unit of the transactions (coinbase excluded)
+	pub fee_rate: f64,
+	/// Lowest transaction fee
+	pub min_fee: u64,
+	/// Highest transaction fee
+	pub max_fee: u64,
+}
+
+impl BlockStats {
+	/// Computes the statistics of a block, `spent_outputs` being the number
+	/// of its outputs spent since.
+	pub fn from_block(block: &core::Block, spent_outputs: u64) -> BlockStats {
+		let weight = core::TransactionBody::weight_as_block(
+			block.inputs().len(),
+			block.outputs().len(),
+			block.kernels().len(),
+		) as u64;
+		let coinbase_outputs = block.outputs().iter().filter(|o| o.is_coinbase()).count();
+		let coinbase_kernels = block.kernels().iter().filter(|k| k.is_coinbase()).count();
+		let tx_weight = core::TransactionBody::weight_as_block(
+			block.inputs().len(),
+			block.outputs().len() - coinbase_outputs,
+			block.kernels().len() - coinbase_kernels,
+		);
+		let tx_fees: Vec<u64> = block
+			.kernels()
+			.iter()
+			.filter_map(|k| match k.features {
+				KernelFeatures::Coinbase => None,
+				KernelFeatures::Plain { fee } | KernelFeatures::HeightLocked { fee, .. } => {
+					Some(fee)
+				}
+			})
+			.collect();
+		let fees = block.total_fees();
+		let num_outputs = block.outputs().len() as u64;
+		BlockStats {
+			height: block.header.height,
+			hash: util::to_hex(block.hash().to_vec()),
+			weight,
+			fullness: weight as f64 / global::max_block_weight() as f64,
+			num_inputs: block.inputs().len() as u64,
+			num_outputs,
+			num_kernels: block.kernels().len() as u64,
+			spent_ratio: if num_outputs == 0 {
+				0.0
+			} else {
+				spent_outputs as f64 / num_outputs as f64
+			},
+			fees,
+			fee_rate: if tx_weight == 0 {
+				0.0
+			} else {
+				fees as f64 / tx_weight as f64
+			},
+			min_fee: tx_fees.iter().min().cloned().unwrap_or(0),
+			max_fee: tx_fees.iter().max().cloned().unwrap_or(0),
+		}
+	}
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CompactBlockPrintable {
 	/// The block header
@@ -812,6 +972,363 @@ pub struct LocatedTxKernel {
 	pub mmr_index: u64,
 }
 
+/// An output or kernel one of our peers proved was added to its chain, the
+/// proof checked against a header of our own header chain. An output may have
+/// been spent since.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProvenInclusion {
+	/// Output commitment or kernel excess, hex encoded
+	pub commit: String,
+	/// Type of the output, None for a kernel
+	pub output_type: Option<OutputType>,
+	/// Position in the output or kernel MMR
+	pub mmr_index: u64,
+	/// Height of the header the proof was checked against
+	pub header_height: u64,
+	/// Hash of the header the proof was checked against
+	pub header_hash: String,
+	/// Address of the peer that sent the proof
+	pub peer: String,
+}
+
+/// Where a transaction, looked up by one of its kernel excesses, currently is
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum TxStatus {
+	/// In our Dandelion stempool, not broadcast yet
+	Stempool,
+	/// In the transaction pool, waiting to be mined
+	Txpool,
+	/// Mined in the block at that height
+	Mined {
+		height: u64,
+		header_hash: String,
+		confirmations: u64,
+	},
+	/// Unknown to this node
+	NotFound,
+}
+
+impl TxStatus {
+	/// Whether the transaction moved, a mined transaction getting more
+	/// confirmations doesn't count.
+	pub fn has_changed(&self, other: &TxStatus) -> bool {
+		match (self, other) {
+			(TxStatus::Mined { header_hash: a, .. }, TxStatus::Mined { header_hash: b, .. }) => {
+				a != b
+			}
+			(a, b) => std::mem::discriminant(a) != std::mem::discriminant(b),
+		}
+	}
+}
+
+/// Mining statistics of a proof of work algorithm over a window of blocks.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AlgorithmStats {
+	/// The proof of work algorithm
+	pub algorithm: PoWType,
+	/// Number of blocks of the window mined with the algorithm
+	pub blocks: u64,
+	/// Share of the window blocks mined with the algorithm, in percent
+	pub share: f64,
+	/// Share the current feijoada policy allots to the algorithm, in percent
+	pub policy_share: f64,
+	/// Average time between a block of the algorithm and its parent, in seconds
+	pub average_solve_time: f64,
+	/// Difficulty of the latest block mined with the algorithm
+	pub difficulty: u64,
+	/// Average difficulty of the blocks mined with the algorithm
+	pub average_difficulty: u64,
+	/// Change of the average difficulty from the older to the newer half of
+	/// the algorithm blocks, in percent
+	pub difficulty_trend: f64,
+	/// Estimated hashrate, the difficulty solved per second of mining (hashes
+	/// per second for RandomX and ProgPow)
+	pub hashrate: f64,
+}
+
+/// Per algorithm block share, solve time, difficulty and hashrate over the
+/// last blocks of the chain.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct NetworkStats {
+	/// Height of the chain head
+	pub height: u64,
+	/// Number of blocks the statistics cover
+	pub window: u64,
+	/// Average time between two blocks, in seconds
+	pub average_block_time: f64,
+	/// Feijoada policy the next block is mined with
+	pub policy: u8,
+	/// Algorithm the feijoada picks for the next block
+	pub next_algorithm: PoWType,
+	/// Statistics of the algorithms mined in the window or part of the policy
+	pub algorithms: Vec<AlgorithmStats>,
+}
+
+impl NetworkStats {
+	/// Builds the statistics from the window blocks, newest first, given as
+	/// their algorithm, difficulty and time since their parent.
+	pub fn from_blocks(
+		height: u64,
+		policy: u8,
+		weights: &Policy,
+		next_algorithm: PoWType,
+		blocks: &[(PoWType, u64, u64)],
+	) -> NetworkStats {
+		let window = blocks.len() as u64;
+		let total_weight: u32 = weights.values().sum();
+		let mut algos: Vec<PoWType> = blocks
+			.iter()
+			.map(|(algo, _, _)| *algo)
+			.chain(
+				weights
+					.iter()
+					.filter(|(_, weight)| **weight > 0)
+					.map(|(algo, _)| *algo),
+			)
+			.collect();
+		algos.sort();
+		algos.dedup();
+
+		let algorithms = algos
+			.into_iter()
+			.map(|algorithm| {
+				// oldest first
+				let mined: Vec<(u64, u64)> = blocks
+					.iter()
+					.rev()
+					.filter(|(algo, _, _)| *algo == algorithm)
+					.map(|(_, difficulty, solve_time)| (*difficulty, *solve_time))
+					.collect();
+				let count = mined.len() as u64;
+				let difficulty_sum: u64 = mined.iter().map(|(d, _)| d).sum();
+				let time_sum: u64 = mined.iter().map(|(_, t)| t).sum();
+				let average = |part: &[(u64, u64)]| {
+					part.iter().map(|(d, _)| *d as f64).sum::<f64>() / part.len().max(1) as f64
+				};
+				let (older, newer) = mined.split_at(mined.len() / 2);
+				let difficulty_trend = if older.is_empty() || average(older) == 0.0 {
+					0.0
+				} else {
+					100.0 * (average(newer) - average(older)) / average(older)
+				};
+				AlgorithmStats {
+					algorithm,
+					blocks: count,
+					share: 100.0 * count as f64 / window.max(1) as f64,
+					policy_share: match total_weight {
+						0 => 0.0,
+						total => {
+							100.0 * *weights.get(&algorithm).unwrap_or(&0) as f64 / total as f64
+						}
+					},
+					average_solve_time: time_sum as f64 / count.max(1) as f64,
+					difficulty: mined.last().map_or(0, |(d, _)| *d),
+					average_difficulty: difficulty_sum / count.max(1),
+					difficulty_trend,
+					hashrate: if time_sum == 0 {
+						0.0
+					} else {
+						difficulty_sum as f64 / time_sum as f64
+					},
+				}
+			})
+			.collect();
+
+		NetworkStats {
+			height,
+			window,
+			average_block_time: blocks.iter().map(|(_, _, t)| *t as f64).sum::<f64>()
+				/ window.max(1) as f64,
+			policy,
+			next_algorithm,
+			algorithms,
+		}
+	}
+}
+
+/// Fork schedule of the chain, with the consensus rules in force at a height.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ForkScheduleInfo {
+	/// Chain type the schedule belongs to
+	pub chain_type: global::ChainTypes,
+	/// Version of the schedule format
+	pub version: u32,
+	/// Height the rules are given for
+	pub height: u64,
+	/// Feijoada policy blocks at that height are emitted with
+	pub policy: u8,
+	/// Rules in force at that height
+	pub active: Fork,
+	/// Next fork after that height, if any is scheduled
+	pub next: Option<Fork>,
+	/// Every fork of the chain, by height
+	pub forks: Vec<Fork>,
+}
+
+impl ForkScheduleInfo {
+	/// Describes the schedule and the rules it sets at the given height.
+	pub fn from_schedule(
+		chain_type: global::ChainTypes,
+		schedule: &ForkSchedule,
+		height: u64,
+	) -> ForkScheduleInfo {
+		let active = schedule.fork_at(height).clone();
+		ForkScheduleInfo {
+			chain_type,
+			version: schedule.version,
+			height,
+			policy: active.emitted_policy(),
+			active,
+			next: schedule.next_fork(height).cloned(),
+			forks: schedule.forks.clone(),
+		}
+	}
+}
+
+/// Outcome of the foundation levy check of a foundation height block.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum FoundationLevyStatus {
+	/// The block coinbase holds the foundation output and kernel
+	Paid,
+	/// The block body was pruned, its kernel range holds the foundation kernel
+	KernelOnly,
+	/// The foundation output is missing from the block coinbase
+	MissingOutput,
+	/// The foundation kernel is missing from the block
+	MissingKernel,
+	/// The foundation file has no coinbase for the height
+	NotInFoundationFile,
+}
+
+impl FoundationLevyStatus {
+	/// Whether the levy of the block counts as paid.
+	pub fn is_paid(&self) -> bool {
+		matches!(
+			self,
+			FoundationLevyStatus::Paid | FoundationLevyStatus::KernelOnly
+		)
+	}
+}
+
+/// Foundation levy check of a foundation height block.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FoundationLevyBlock {
+	/// Height of the block
+	pub height: u64,
+	/// Hash of the block
+	pub hash: String,
+	/// Levy the schedule sets for the block, the sum of the levies of the
+	/// blocks since the previous foundation height
+	pub levy: u64,
+	/// Outcome of the check
+	pub status: FoundationLevyStatus,
+}
+
+/// Foundation levy paid by the blocks of a height range against the levy
+/// schedule.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FoundationLevyAudit {
+	/// First height audited
+	pub start_height: u64,
+	/// Last height audited
+	pub end_height: u64,
+	/// Levy the schedule sets for the foundation blocks of the range
+	pub expected_levy: u64,
+	/// Levy of the foundation blocks found paid
+	pub paid_levy: u64,
+	/// Number of foundation blocks not paying their levy
+	pub discrepancies: u64,
+	/// Every foundation block of the range
+	pub blocks: Vec<FoundationLevyBlock>,
+}
+
+impl FoundationLevyAudit {
+	/// Sums the checks of the foundation blocks of the range.
+	pub fn from_blocks(
+		start_height: u64,
+		end_height: u64,
+		blocks: Vec<FoundationLevyBlock>,
+	) -> FoundationLevyAudit {
+		let paid = blocks.iter().filter(|b| b.status.is_paid());
+		FoundationLevyAudit {
+			start_height,
+			end_height,
+			expected_levy: blocks.iter().map(|b| b.levy).sum(),
+			paid_levy: paid.clone().map(|b| b.levy).sum(),
+			discrepancies: (blocks.len() - paid.count()) as u64,
+			blocks,
+		}
+	}
+}
+
+/// Coins emitted up to a height, split between the miners and the foundation,
+/// cross-checked against the chain sums.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Supply {
+	/// Height the supply is given at
+	pub height: u64,
+	/// Hash of the block at that height
+	pub hash: String,
+	/// Coins in circulation, the genesis reward included
+	pub circulating_supply: u64,
+	/// Block rewards paid to the miners, the genesis reward included
+	pub miner_emission: u64,
+	/// Foundation levy paid at the foundation heights
+	pub foundation_emission: u64,
+	/// Foundation levy accrued since the last foundation height
+	pub foundation_pending: u64,
+	/// Total reward of the next block
+	pub block_reward: u64,
+	/// Foundation levy of the next block
+	pub foundation_levy: u64,
+	/// First height with a halved block reward, if the reward halves again
+	pub next_halving_height: Option<u64>,
+	/// Blocks left until the next halving
+	pub blocks_to_next_halving: Option<u64>,
+	/// Estimated time of the next halving, at the target block time
+	pub next_halving_eta: Option<String>,
+	/// Whether the unspent outputs less the kernel sums commit to the
+	/// circulating supply, only checked at the head
+	pub utxo_sums_balance: Option<bool>,
+	/// Whether the block sums of the block balance with its kernel offset,
+	/// none when the block sums were pruned
+	pub sums_balance: Option<bool>,
+}
+
+impl Supply {
+	/// Supply at the given header, from the consensus emission schedule.
+	pub fn from_emission(
+		header: &BlockHeader,
+		genesis_reward: u64,
+		emission: consensus::Emission,
+		utxo_sums_balance: Option<bool>,
+		sums_balance: Option<bool>,
+	) -> Supply {
+		let next = header.height + 1;
+		let next_halving_height = consensus::next_halving_height(header.height);
+		let blocks = next_halving_height.map(|h| h - header.height);
+		Supply {
+			height: header.height,
+			hash: header.hash().to_hex(),
+			circulating_supply: genesis_reward + emission.total(),
+			miner_emission: genesis_reward + emission.miner,
+			foundation_emission: emission.foundation,
+			foundation_pending: emission.foundation_pending,
+			block_reward: consensus::block_total_reward_at_height(next),
+			foundation_levy: consensus::reward_foundation_at_height(next),
+			next_halving_height,
+			blocks_to_next_halving: blocks,
+			next_halving_eta: blocks.map(|blocks| {
+				let secs = blocks.saturating_mul(consensus::BLOCK_TIME_SEC);
+				let eta = header.timestamp + chrono::Duration::seconds(secs as i64);
+				eta.to_rfc3339()
+			}),
+			utxo_sums_balance,
+			sums_balance,
+		}
+	}
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct PoolInfo {
 	/// Size of the pool
@@ -842,6 +1359,105 @@ mod test {
 		assert_eq!(serialized, hex_output);
 	}
 
+	#[test]
+	fn tx_status_transitions() {
+		let mined = |height, hash: &str, confirmations| TxStatus::Mined {
+			height,
+			header_hash: hash.to_owned(),
+			confirmations,
+		};
+		// pushed, fluffed, mined, then reorged out and back into the txpool
+		let path = vec![
+			TxStatus::NotFound,
+			TxStatus::Stempool,
+			TxStatus::Txpool,
+			mined(10, "aa", 1),
+			TxStatus::Txpool,
+		];
+		for pair in path.windows(2) {
+			assert!(pair[1].has_changed(&pair[0]));
+			assert!(pair[0].has_changed(&pair[1]));
+			assert!(!pair[0].has_changed(&pair[0]));
+		}
+
+		// more confirmations isn't a change, a reorg into another block is
+		assert!(!mined(10, "aa", 5).has_changed(&mined(10, "aa", 1)));
+		assert!(mined(11, "bb", 1).has_changed(&mined(10, "aa", 2)));
+	}
+
+	#[test]
+	fn foundation_levy_audit_from_blocks() {
+		let block = |height, levy, status| FoundationLevyBlock {
+			height,
+			hash: String::new(),
+			levy,
+			status,
+		};
+		let audit = FoundationLevyAudit::from_blocks(
+			1,
+			20,
+			vec![
+				block(5, 100, FoundationLevyStatus::Paid),
+				block(10, 100, FoundationLevyStatus::KernelOnly),
+				block(15, 90, FoundationLevyStatus::MissingOutput),
+				block(20, 90, FoundationLevyStatus::NotInFoundationFile),
+			],
+		);
+		assert_eq!(audit.expected_levy, 380);
+		assert_eq!(audit.paid_levy, 200);
+		assert_eq!(audit.discrepancies, 2);
+		assert_eq!(audit.blocks.len(), 4);
+
+		let empty = FoundationLevyAudit::from_blocks(1, 4, vec![]);
+		assert_eq!(
+			(empty.expected_levy, empty.paid_levy, empty.discrepancies),
+			(0, 0, 0)
+		);
+	}
+
+	#[test]
+	fn network_stats_from_blocks() {
+		let mut weights = Policy::new();
+		weights.insert(PoWType::RandomX, 60);
+		weights.insert(PoWType::Cuckatoo, 40);
+		weights.insert(PoWType::ProgPow, 0);
+		// newest first
+		let blocks = vec![
+			(PoWType::RandomX, 300, 30),
+			(PoWType::Cuckatoo, 50, 90),
+			(PoWType::RandomX, 200, 60),
+			(PoWType::RandomX, 100, 90),
+		];
+		let stats = NetworkStats::from_blocks(100, 2, &weights, PoWType::Cuckatoo, &blocks);
+		assert_eq!(stats.window, 4);
+		assert_eq!(stats.average_block_time, 67.5);
+		assert_eq!(stats.next_algorithm, PoWType::Cuckatoo);
+		assert_eq!(stats.algorithms.len(), 2);
+
+		let cuckatoo = &stats.algorithms[0];
+		assert_eq!(cuckatoo.algorithm, PoWType::Cuckatoo);
+		assert_eq!(cuckatoo.blocks, 1);
+		assert_eq!(cuckatoo.share, 25.0);
+		assert_eq!(cuckatoo.policy_share, 40.0);
+		assert_eq!(cuckatoo.difficulty_trend, 0.0);
+
+		let randomx = &stats.algorithms[1];
+		assert_eq!(randomx.blocks, 3);
+		assert_eq!(randomx.share, 75.0);
+		assert_eq!(randomx.policy_share, 60.0);
+		assert_eq!(randomx.average_solve_time, 60.0);
+		assert_eq!(randomx.difficulty, 300);
+		assert_eq!(randomx.average_difficulty, 200);
+		// 100 then (200 + 300) / 2
+		assert_eq!(randomx.difficulty_trend, 150.0);
+		assert_eq!(randomx.hashrate, 600.0 / 180.0);
+
+		let empty = NetworkStats::from_blocks(0, 0, &Policy::new(), PoWType::RandomX, &[]);
+		assert_eq!(empty.window, 0);
+		assert_eq!(empty.average_block_time, 0.0);
+		assert!(empty.algorithms.is_empty());
+	}
+
 	#[test]
 	fn serialize_output() {
 		let hex_commit = "{\
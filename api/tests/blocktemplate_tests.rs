@@ -2,7 +2,7 @@ use self::chain::types::NoopAdapter;
 use self::chain::Chain;
 use self::core::genesis;
 use self::core::libtx::{self, reward};
-use self::core::{consensus, pow};
+use self::core::{consensus, global, pow};
 use epic_chain as chain;
 use epic_core as core;
 use epic_core::core::Block;
@@ -38,6 +38,7 @@ fn test_external_coinbase_and_set_txhashset_roots() {
 		config.pool_config.clone(),
 		pool_adapter.clone(),
 		pool_net_adapter.clone(),
+		chain.params(),
 	)));
 
 	// 1. Get block template from node (without coinbase)
@@ -116,6 +117,7 @@ pub fn init_chain(dir_name: &str, genesis: Block) -> Chain {
 		genesis,
 		pow::verify_size,
 		false,
+		Arc::new(global::ChainParams::current()),
 	)
 	.unwrap()
 }
@@ -15,12 +15,13 @@
 //! Facade and handler for the rest of the blockchain implementation
 //! and mostly the chain pipeline.
 
+use crate::core::core::committed;
 use crate::core::core::hash::{Hash, Hashed, ZERO_HASH};
 use crate::core::core::merkle_proof::MerkleProof;
 use crate::core::core::{
 	Block, BlockHeader, BlockSums, Committed, Output, OutputIdentifier, Transaction, TxKernel,
 };
-use crate::core::global;
+use crate::core::global::{self, ChainParams};
 use crate::core::pow;
 use crate::core::pow::PoWType;
 use crate::core::ser::{ProtocolVersion, Readable, StreamingReader};
@@ -30,7 +31,7 @@ use crate::store;
 use crate::txhashset;
 use crate::txhashset::{PMMRHandle, TxHashSet};
 use crate::types::{
-	BlockStatus, BlockchainCheckpoints, ChainAdapter, CommitPos, NoStatus, Options, Tip,
+	BlockStatus, ChainAdapter, CommitPos, KernelProof, NoStatus, Options, OutputProof, Tip,
 	TxHashsetWriteStatus,
 };
 use crate::util::secp::pedersen::{Commitment, RangeProof};
@@ -47,6 +48,10 @@ use std::time::{Duration, Instant};
 /// Orphan pool size is limited by MAX_ORPHAN_SIZE
 pub const MAX_ORPHAN_SIZE: usize = 60;
 
+/// Number of blocks a kernel index rebuild indexes per db batch, releasing
+/// the txhashset lock in between.
+const KERNEL_INDEX_BATCH_SIZE: u64 = 1_000;
+
 /// How many blocks from chaintip to start looping through orphans
 pub const ORPHAN_LOOP_THRESHOLD: u64 = 120;
 
@@ -164,20 +169,25 @@ pub struct Chain {
 	// POW verification function
 	pow_verifier: fn(&BlockHeader) -> Result<(), pow::Error>,
 	archive_mode: bool,
+	light_mode: bool,
 	genesis: BlockHeader,
+	params: Arc<ChainParams>,
 }
 
 impl Chain {
 	/// Initializes the blockchain and returns a new Chain instance. Does a
 	/// check on the current chain head to make sure it exists and creates one
-	/// based on the genesis block if necessary.
+	/// based on the genesis block if necessary. The chain follows the
+	/// provided parameters, whatever the process wide settings are.
 	pub fn init(
 		db_root: String,
 		adapter: Arc<dyn ChainAdapter + Send + Sync>,
 		genesis: Block,
 		pow_verifier: fn(&BlockHeader) -> Result<(), pow::Error>,
 		archive_mode: bool,
+		params: Arc<ChainParams>,
 	) -> Result<Chain, Error> {
+		let _params = params.enter();
 		let store = Arc::new(store::ChainStore::new(&db_root)?);
 
 		// open the txhashset, creating a new one if necessary
@@ -227,7 +237,9 @@ impl Chain {
 			sync_pmmr: Arc::new(RwLock::new(sync_pmmr)),
 			pow_verifier,
 			archive_mode,
+			light_mode: false,
 			genesis: genesis.header.clone(),
+			params: params.clone(),
 		};
 
 		// DB migrations to be run prior to the chain being used.
@@ -241,6 +253,19 @@ impl Chain {
 		Ok(chain)
 	}
 
+	/// Follows the header chain only, the blocks and txhashset never being
+	/// downloaded. Outputs and kernels are then checked with merkle proofs
+	/// against the headers.
+	pub fn with_light_mode(mut self, light_mode: bool) -> Chain {
+		self.light_mode = light_mode;
+		self
+	}
+
+	/// Whether the chain follows the headers only.
+	pub fn is_light_mode(&self) -> bool {
+		self.light_mode
+	}
+
 	/// Return our shared header MMR handle.
 	pub fn header_pmmr(&self) -> Arc<RwLock<PMMRHandle<BlockHeader>>> {
 		self.header_pmmr.clone()
@@ -256,6 +281,11 @@ impl Chain {
 		self.store.clone()
 	}
 
+	/// Parameters of this chain.
+	pub fn params(&self) -> Arc<ChainParams> {
+		self.params.clone()
+	}
+
 	fn log_heads(&self) -> Result<(), Error> {
 		let log_head = |name, head: Tip| {
 			debug!(
@@ -292,6 +322,7 @@ impl Chain {
 	/// to loop through orphan list continually, if we are near chaintip
 	/// or if our OrphanBlockPool is nearly full.
 	pub fn process_block(&self, b: Block, opts: Options) -> Result<Option<Tip>, Error> {
+		let _params = self.params.enter();
 		let block_height = b.header.height;
 		let orphan_height;
 		let loop_height = std::cmp::max(block_height, ORPHAN_LOOP_THRESHOLD);
@@ -463,12 +494,14 @@ impl Chain {
 	/// Note: This will update header MMR and corresponding header_head
 	/// if total work increases (on the header chain).
 	pub fn process_block_header(&self, bh: &BlockHeader, opts: Options) -> Result<(), Error> {
+		let _params = self.params.enter();
 		let mut header_pmmr = self.header_pmmr.write();
 		let mut txhashset = self.txhashset.write();
 		let batch = self.store.batch()?;
 		let mut ctx = self.new_ctx(opts, batch, &mut header_pmmr, &mut txhashset)?;
 		pipe::process_block_header(bh, &mut ctx)?;
 		ctx.batch.commit()?;
+		self.update_rx_seeds(&header_pmmr);
 		Ok(())
 	}
 
@@ -476,6 +509,7 @@ impl Chain {
 	/// This is only ever used during sync and is based on sync_head.
 	/// We update header_head here if our total work increases.
 	pub fn sync_block_headers(&self, headers: &[BlockHeader], opts: Options) -> Result<(), Error> {
+		let _params = self.params.enter();
 		let mut sync_pmmr = self.sync_pmmr.write();
 		let mut header_pmmr = self.header_pmmr.write();
 		let mut txhashset = self.txhashset.write();
@@ -495,10 +529,32 @@ impl Chain {
 			pipe::process_block_header(header, &mut ctx)?;
 			ctx.batch.commit()?;
 		}
+		self.update_rx_seeds(&header_pmmr);
 
 		Ok(())
 	}
 
+	/// Keeps the RandomX VMs of the seeds of the current and next epochs after
+	/// our header head, the ones the next headers are hashed with.
+	fn update_rx_seeds(&self, header_pmmr: &txhashset::PMMRHandle<BlockHeader>) {
+		let height = match self.store.header_head() {
+			Ok(head) => head.height + 1,
+			Err(_) => return,
+		};
+		let seed_heights = Some(pow::randomx::rx_current_seed_height(height))
+			.into_iter()
+			.chain(pow::randomx::rx_next_seed_height(height));
+		let seeds = seed_heights
+			.filter_map(|h| header_pmmr.get_header_hash_by_height(h).ok())
+			.map(|hash| {
+				let mut seed = [0u8; 32];
+				seed.copy_from_slice(hash.as_bytes());
+				seed
+			})
+			.collect();
+		pow::randomx::RX_VERIFIER.set_active_seeds(seeds);
+	}
+
 	fn new_ctx<'a>(
 		&self,
 		opts: Options,
@@ -652,6 +708,7 @@ impl Chain {
 
 	/// Validate the tx against the current UTXO set.
 	pub fn validate_tx(&self, tx: &Transaction) -> Result<(), Error> {
+		let _params = self.params.enter();
 		let header_pmmr = self.header_pmmr.read();
 		let txhashset = self.txhashset.read();
 		txhashset::utxo_view(&header_pmmr, &txhashset, |utxo, batch| {
@@ -668,6 +725,7 @@ impl Chain {
 	/// Verify we are not attempting to spend a coinbase output
 	/// that has not yet sufficiently matured.
 	pub fn verify_coinbase_maturity(&self, tx: &Transaction) -> Result<(), Error> {
+		let _params = self.params.enter();
 		let height = self.next_block_height()?;
 		let header_pmmr = self.header_pmmr.read();
 		let txhashset = self.txhashset.read();
@@ -680,6 +738,7 @@ impl Chain {
 	/// Verify that the tx has a lock_height that is less than or equal to
 	/// the height of the next block.
 	pub fn verify_tx_lock_height(&self, tx: &Transaction) -> Result<(), Error> {
+		let _params = self.params.enter();
 		let height = self.next_block_height()?;
 		if tx.lock_height() <= height {
 			Ok(())
@@ -690,6 +749,7 @@ impl Chain {
 
 	/// Validate the current chain state.
 	pub fn validate(&self, fast_validation: bool) -> Result<(), Error> {
+		let _params = self.params.enter();
 		let header = self.store.head_header()?;
 
 		// Lets just treat an "empty" node that just got started up as valid.
@@ -711,9 +771,30 @@ impl Chain {
 		})
 	}
 
+	/// Whether the unspent outputs of the txhashset at the provided header,
+	/// less its kernel excesses and offset, commit to the provided overage.
+	/// Expensive, all the UTXOs and kernels are read from the MMRs.
+	pub fn verify_utxo_sums(&self, header: &BlockHeader, overage: i64) -> Result<bool, Error> {
+		let _params = self.params.enter();
+		let mut header_pmmr = self.header_pmmr.write();
+		let mut txhashset = self.txhashset.write();
+		txhashset::extending_readonly(&mut header_pmmr, &mut txhashset, |ext, batch| {
+			pipe::rewind_and_apply_fork(header, ext, batch)?;
+			match ext
+				.extension
+				.verify_kernel_sums(overage, header.total_kernel_offset())
+			{
+				Ok(_) => Ok(true),
+				Err(committed::Error::KernelSumMismatch) => Ok(false),
+				Err(e) => Err(e.into()),
+			}
+		})
+	}
+
 	/// Sets the txhashset roots on a brand new block by applying the block on
 	/// the current txhashset state.
 	pub fn set_txhashset_roots(&self, b: &mut Block) -> Result<(), Error> {
+		let _params = self.params.enter();
 		let mut header_pmmr = self.header_pmmr.write();
 		let mut txhashset = self.txhashset.write();
 
@@ -779,6 +860,85 @@ impl Chain {
 		txhashset.merkle_proof(commit)
 	}
 
+	/// Proof the output with the given commitment is in the output MMR of our
+	/// head, along with the head header.
+	pub fn get_output_proof(
+		&self,
+		commit: Commitment,
+	) -> Result<(BlockHeader, OutputProof), Error> {
+		let txhashset = self.txhashset.read();
+		let header = self.head_header()?;
+		let proof = txhashset.output_proof(commit)?;
+		Ok((header, proof))
+	}
+
+	/// Proof of the kernel with the given excess against our head, along
+	/// with the head header. Kernels are only looked up in the kernel_pos
+	/// index, searching the kernel MMR is too costly to do for our peers.
+	pub fn get_kernel_proof(
+		&self,
+		excess: &Commitment,
+	) -> Result<(BlockHeader, KernelProof), Error> {
+		if !self.store.kernel_index_complete()? {
+			return Err(Error::TxKernelNotFound);
+		}
+		let (pos, _) = self
+			.store
+			.get_kernel_pos_height(excess)
+			.map_err(|_| Error::TxKernelNotFound)?;
+		let txhashset = self.txhashset.read();
+		let header = self.head_header()?;
+		let proof = txhashset.kernel_proof(pos)?;
+		if proof.kernel.excess != *excess {
+			return Err(Error::TxKernelNotFound);
+		}
+		Ok((header, proof))
+	}
+
+	/// Checks an output proof received from a peer for the output with the
+	/// given commitment against the header it was built on, which must be on
+	/// our header chain. Returns that header.
+	pub fn verify_output_proof(
+		&self,
+		hash: &Hash,
+		commit: &Commitment,
+		proof: &OutputProof,
+	) -> Result<BlockHeader, Error> {
+		// a valid proof of another output proves nothing about this one
+		if proof.output.commit != *commit {
+			return Err(Error::MerkleProof);
+		}
+		let header = self.get_header_on_header_chain(hash)?;
+		proof.verify(&header)?;
+		Ok(header)
+	}
+
+	/// Checks a kernel proof received from a peer for the kernel with the
+	/// given excess against the header it was built on, which must be on our
+	/// header chain. Returns that header.
+	pub fn verify_kernel_proof(
+		&self,
+		hash: &Hash,
+		excess: &Commitment,
+		proof: &KernelProof,
+	) -> Result<BlockHeader, Error> {
+		if proof.kernel.excess != *excess {
+			return Err(Error::MerkleProof);
+		}
+		let header = self.get_header_on_header_chain(hash)?;
+		proof.verify(&header)?;
+		Ok(header)
+	}
+
+	fn get_header_on_header_chain(&self, hash: &Hash) -> Result<BlockHeader, Error> {
+		let unknown = || Error::Unfit(format!("header {} not on our header chain", hash));
+		let header = self.get_block_header(hash).map_err(|_| unknown())?;
+		match self.get_header_hash_by_height(header.height) {
+			Ok(h) if h == *hash => Ok(header),
+			_ => Err(unknown()),
+		}
+	}
+
 	/// Provides a reading view into the current kernel state.
 	pub fn kernel_data_read(&self) -> Result<File, Error> {
 		let txhashset = self.txhashset.read();
@@ -831,6 +991,7 @@ impl Chain {
 	/// and no longer support requesting arbitrary txhashsets.
 	/// Here we return the header of the txhashset we are currently offering to peers.
 	pub fn txhashset_archive_header(&self) -> Result<BlockHeader, Error> {
+		let _params = self.params.enter();
 		let sync_threshold = global::state_sync_threshold() as u64;
 		let body_head = self.head()?;
 		let archive_interval = global::txhashset_archive_interval();
@@ -900,6 +1061,7 @@ impl Chain {
 		caller: String,
 		hashes: &mut Option<Vec<Hash>>,
 	) -> Result<bool, Error> {
+		let _params = self.params.enter();
 		let horizon = global::cut_through_horizon() as u64;
 		let body_head = self.head()?;
 		let header_head = self.header_head()?;
@@ -1036,6 +1198,7 @@ impl Chain {
 		txhashset_data: File,
 		status: &dyn TxHashsetWriteStatus,
 	) -> Result<bool, Error> {
+		let _params = self.params.enter();
 		status.on_setup();
 
 		// Initial check whether this txhashset is needed or not
@@ -1119,6 +1282,11 @@ impl Chain {
 		// Rebuild our output_pos index in the db based on fresh UTXO set.
 		txhashset.init_output_pos_index(&header_pmmr, &batch)?;
 
+		// Our kernel_pos index doesn't match the new kernel MMR, it gets
+		// rebuilt once the new txhashset is in place.
+		let kernel_index = batch.kernel_index_enabled()?;
+		batch.disable_kernel_index()?;
+
 		// Commit all the changes to the db.
 		batch.commit()?;
 
@@ -1148,6 +1316,13 @@ impl Chain {
 
 		info!("Replaced txhashset with the new one");
 
+		drop(header_pmmr);
+		if kernel_index {
+			if let Err(e) = self.rebuild_kernel_index() {
+				warn!("txhashset_write: failed to rebuild the kernel index: {}", e);
+			}
+		}
+
 		status.on_done();
 
 		Ok(false)
@@ -1213,6 +1388,7 @@ impl Chain {
 	/// * removes historical blocks and associated data from the db (unless archive mode)
 	///
 	pub fn compact(&self) -> Result<(), Error> {
+		let _params = self.params.enter();
 		// A node may be restarted multiple times in a short period of time.
 		// We compact at most once per 60 blocks in this situation by comparing
 		// current "head" and "tail" height to our cut-through horizon and
@@ -1367,6 +1543,16 @@ impl Chain {
 			.map_err(|e| Error::ChainStoreErr(e, "header head".to_owned()).into())
 	}
 
+	/// The tip this node follows: the header head in light mode, the head of
+	/// the full blocks otherwise.
+	pub fn tip(&self) -> Result<Tip, Error> {
+		if self.light_mode {
+			self.header_head()
+		} else {
+			self.head()
+		}
+	}
+
 	/// Block header for the chain head
 	pub fn head_header(&self) -> Result<BlockHeader, Error> {
 		self.store
@@ -1447,6 +1633,14 @@ impl Chain {
 		min_height: Option<u64>,
 		max_height: Option<u64>,
 	) -> Result<Option<(TxKernel, u64, u64)>, Error> {
+		// A complete index has every kernel, there is nothing to search for.
+		if let Some(res) = self.get_kernel_height_indexed(excess, min_height, max_height)? {
+			return Ok(Some(res));
+		}
+		if self.store.kernel_index_complete()? {
+			return Ok(None);
+		}
+
 		let min_index = match min_height {
 			Some(h) => Some(self.get_header_by_height(h - 1)?.kernel_mmr_size + 1),
 			None => None,
@@ -1470,6 +1664,114 @@ impl Chain {
 
 		Ok(Some((kernel, header.height, mmr_index)))
 	}
+
+	/// Looks the kernel up in the kernel_pos index, if we maintain one.
+	/// Returns None when not found, the caller falling back to searching the
+	/// kernel MMR unless the index is complete.
+	fn get_kernel_height_indexed(
+		&self,
+		excess: &Commitment,
+		min_height: Option<u64>,
+		max_height: Option<u64>,
+	) -> Result<Option<(TxKernel, u64, u64)>, Error> {
+		if !self.store.kernel_index_enabled()? {
+			return Ok(None);
+		}
+		let (mut pos, mut height) = match self.store.get_kernel_pos_height(excess) {
+			Ok(pos_height) => pos_height,
+			Err(_) => return Ok(None),
+		};
+		// the index points to the latest kernel with this excess, earlier ones
+		// are chained from it
+		while max_height.map_or(false, |h| height > h) {
+			match self.store.get_prev_kernel_pos_height(excess, pos)? {
+				Some(prev) => (pos, height) = prev,
+				None => return Ok(None),
+			}
+		}
+		if min_height.map_or(false, |h| height < h) {
+			return Ok(None);
+		}
+		match self.txhashset.read().get_kernel_by_pos(pos) {
+			Some(kernel) if &kernel.excess == excess => Ok(Some((kernel, height, pos))),
+			_ => Ok(None),
+		}
+	}
+
+	/// Builds (or rebuilds) the optional kernel_pos index, making kernel
+	/// lookups by excess a single db read. The index is maintained as blocks
+	/// are applied and rewound from then on. The rebuild commits every
+	/// KERNEL_INDEX_BATCH_SIZE blocks, blocks keep being processed meanwhile.
+	pub fn rebuild_kernel_index(&self) -> Result<(), Error> {
+		let _params = self.params.enter();
+		let now = Instant::now();
+
+		// Lookups search the kernel MMR again until the index is complete.
+		{
+			let batch = self.store.batch()?;
+			batch.disable_kernel_index()?;
+			batch.commit()?;
+		}
+
+		// Start from scratch, whatever is left from a previous index may be stale.
+		loop {
+			let batch = self.store.batch()?;
+			let keys: Vec<_> = batch
+				.kernel_pos_iter()?
+				.chain(batch.prev_kernel_pos_iter()?)
+				.map(|(key, _)| key)
+				.take(KERNEL_INDEX_BATCH_SIZE as usize)
+				.collect();
+			if keys.is_empty() {
+				break;
+			}
+			for key in keys {
+				batch.delete(&key)?;
+			}
+			batch.commit()?;
+		}
+
+		// No block is applied while we hold the txhashset lock, the blocks
+		// applied after this index their own kernels.
+		{
+			let _txhashset = self.txhashset.read();
+			let batch = self.store.batch()?;
+			batch.save_kernel_index_height(0)?;
+			batch.commit()?;
+		}
+
+		let mut total_kernels = 0;
+		let mut height = 0;
+		loop {
+			let header_pmmr = self.header_pmmr.read();
+			let txhashset = self.txhashset.read();
+			let batch = self.store.batch()?;
+			let head = batch.head()?;
+			// A rewind below the height we reached rewinds the index with the
+			// chain, the blocks after it got indexed as they were applied.
+			if height <= head.height {
+				let to_height = head.height.min(height + KERNEL_INDEX_BATCH_SIZE - 1);
+				total_kernels +=
+					txhashset.index_kernels(&header_pmmr, &batch, height, to_height)?;
+				batch.save_kernel_index_height(to_height)?;
+				height = to_height + 1;
+			}
+			if height > head.height {
+				batch.save_kernel_index_complete(head.height)?;
+				batch.commit()?;
+				break;
+			}
+			batch.commit()?;
+		}
+
+		info!(
+			"Rebuilt kernel PosIndex: added entries for {} kernels, took {}s",
+			total_kernels,
+			now.elapsed().as_secs(),
+		);
+		Ok(())
+	}
+
 	/// Gets the block header in which a given kernel mmr index appears in the txhashset.
 	pub fn get_header_for_kernel_index(
 		&self,
@@ -1565,12 +1867,12 @@ impl Chain {
 	/// Check block headers against checkpoints hash and height. Returns
 	/// boolean in Result with 'false' once we are out of checkpointed range.
 	pub fn check_header_against_checkpoints(&self, header: &BlockHeader) -> Result<bool, Error> {
-		let checkpoints = BlockchainCheckpoints::new().checkpoints;
+		let checkpoints = &self.params.checkpoints;
 		let mut within_checkpointed_range = true;
-		if header.height > checkpoints.last().unwrap().height {
+		if header.height > checkpoints.last().map_or(0, |c| c.height) {
 			within_checkpointed_range = false;
 		} else {
-			for c in &checkpoints {
+			for c in checkpoints {
 				if header.height == c.height {
 					if header.hash() == c.block_hash {
 						info!("Checkpoint successfully passed at height({})! Hashes: header({:?}), checkpoint({:?})",
@@ -176,6 +176,8 @@ impl Error {
 			| Error::TxHashSetErr(_)
 			| Error::GenesisBlockRequired
 			| Error::Other(_) => false,
+			// our own foundation file failing to load says nothing about the block
+			Error::InvalidBlockProof(block::Error::Foundation(_)) => false,
 			_ => true,
 		}
 	}
@@ -202,10 +202,42 @@ pub fn sync_block_headers(
         }
     }
 
+    // The checks cheaper than the proof of work go first (see validate_header),
+    // a chunk with a bad header gets rejected before any hashing.
+    for (i, header) in headers.iter().enumerate() {
+        validate_header_version_and_time(header)?;
+        let prev_height = match i.checked_sub(1).map(|j| &headers[j]) {
+            Some(prev) if prev.hash() == header.prev_hash => prev.height,
+            _ => prev_header_store(header, &mut ctx.batch)?.height,
+        };
+        if header.height != prev_height + 1 {
+            return Err(Error::InvalidBlockHeight.into());
+        }
+    }
+
+    // Verify the proof of work of the whole chunk up front, concurrently, as RandomX
+    // headers are expensive to hash one at a time.
+    let pow_checked = !ctx.opts.contains(Options::SKIP_POW);
+    if pow_checked {
+        if headers
+            .iter()
+            .any(|h| !h.pow.is_primary() && !h.pow.is_secondary())
+        {
+            return Err(Error::LowEdgebits.into());
+        }
+        let results = pow::verify_headers(headers, ctx.pow_verifier);
+        for (header, res) in headers.iter().zip(results) {
+            if res.is_err() {
+                log_invalid_pow(header);
+                return Err(Error::InvalidPow.into());
+            }
+        }
+    }
+
     // Validate each header in the chunk and add to our db.
     // Note: This batch may be rolled back later if the MMR does not validate successfully.
     for header in headers {
-        validate_header(header, ctx)?;
+        validate_header(header, ctx, pow_checked)?;
         add_block_header(header, &ctx.batch)?;
     }
 
@@ -267,7 +299,7 @@ pub fn process_block_header(header: &BlockHeader, ctx: &mut BlockContext<'_>) ->
         },
     )?;
 
-    validate_header(header, ctx)?;
+    validate_header(header, ctx, false)?;
     add_block_header(header, &ctx.batch)?;
 
     if has_more_work(header, &header_head) {
@@ -329,10 +361,28 @@ fn seed_header_store(seed: &[u8; 32], batch: &mut store::Batch<'_>) -> Result<Bl
     Ok(prev)
 }
 
-/// First level of block validation that only needs to act on the block header
-/// to make it as cheap as possible. The different validations are also
-/// arranged by order of cost to have as little DoS surface as possible.
-fn validate_header(header: &BlockHeader, ctx: &mut BlockContext<'_>) -> Result<(), Error> {
+fn log_invalid_pow(header: &BlockHeader) {
+    match header.pow.proof {
+        pow::Proof::RandomXProof { ref hash } => {
+            error!("pipe: error validating header with randomx hash {:?}", hash);
+        }
+        pow::Proof::ProgPowProof { ref mix } => {
+            error!(
+                "pipe: error validating header with progpow mix hash {:?}",
+                mix
+            );
+        }
+        _ => {
+            error!(
+                "pipe: error validating header with cuckoo edge_bits {}",
+                header.pow.edge_bits()
+            );
+        }
+    };
+}
+
+/// The checks of validate_header that need nothing but the header itself.
+fn validate_header_version_and_time(header: &BlockHeader) -> Result<(), Error> {
     // check version, enforces scheduled hard fork
     if !consensus::valid_header_version(header.height, header.version) {
         error!(
@@ -351,33 +401,28 @@ fn validate_header(header: &BlockHeader, ctx: &mut BlockContext<'_>) -> Result<(
         return Err(Error::InvalidBlockTime.into());
     }
 
-    check_bad_header(header)?;
+    check_bad_header(header)
+}
 
-    if !ctx.opts.contains(Options::SKIP_POW) {
+/// First level of block validation that only needs to act on the block header
+/// to make it as cheap as possible. The different validations are also
+/// arranged by order of cost to have as little DoS surface as possible.
+/// `pow_checked` is set when the proof of work was already verified (in a batch
+/// during header sync).
+fn validate_header(
+    header: &BlockHeader,
+    ctx: &mut BlockContext<'_>,
+    pow_checked: bool,
+) -> Result<(), Error> {
+    validate_header_version_and_time(header)?;
+
+    if !ctx.opts.contains(Options::SKIP_POW) && !pow_checked {
         if !header.pow.is_primary() && !header.pow.is_secondary() {
             return Err(Error::LowEdgebits.into());
         }
 
-        let edge_bits = header.pow.edge_bits();
-        if !(ctx.pow_verifier)(header).is_ok() {
-            match header.pow.proof {
-                pow::Proof::RandomXProof { ref hash } => {
-                    error!("pipe: error validating header with randomx hash {:?}", hash);
-                }
-                pow::Proof::ProgPowProof { ref mix } => {
-                    error!(
-                        "pipe: error validating header with progpow mix hash {:?}",
-                        mix
-                    );
-                }
-                _ => {
-                    error!(
-                        "pipe: error validating header with cuckoo edge_bits {}",
-                        edge_bits
-                    );
-                }
-            };
-
+        if (ctx.pow_verifier)(header).is_err() {
+            log_invalid_pow(header);
             return Err(Error::InvalidPow.into());
         }
     }
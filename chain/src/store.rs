@@ -24,7 +24,7 @@ use crate::types::{CommitPos, Tip};
 use crate::util::secp::pedersen::Commitment;
 use croaring::{Bitmap, Portable};
 use epic_store as store;
-use epic_store::{option_to_not_found, to_key, Error, SerIterator};
+use epic_store::{option_to_not_found, to_key, to_key_u64, Error, SerIterator};
 use std::convert::TryInto;
 use std::sync::Arc;
 
@@ -40,6 +40,10 @@ const OUTPUT_POS_PREFIX: u8 = b'p';
 const BLOCK_INPUT_BITMAP_PREFIX: u8 = b'B';
 const BLOCK_SUMS_PREFIX: u8 = b'M';
 const BLOCK_SPENT_PREFIX: u8 = b'S';
+const KERNEL_POS_PREFIX: u8 = b'k';
+const KERNEL_POS_PREV_PREFIX: u8 = b'j';
+const KERNEL_INDEX_PREFIX: u8 = b'K';
+const KERNEL_INDEX_COMPLETE_PREFIX: u8 = b'c';
 
 /// All chain-related database operations
 pub struct ChainStore {
@@ -144,6 +148,41 @@ impl ChainStore {
 		)
 	}
 
+	/// Get kernel MMR pos and block height for the given kernel excess.
+	pub fn get_kernel_pos_height(&self, excess: &Commitment) -> Result<(u64, u64), Error> {
+		option_to_not_found(
+			self.db
+				.get_ser(&to_key(KERNEL_POS_PREFIX, &mut excess.as_ref().to_vec())),
+			|| format!("Kernel position for: {:?}", excess),
+		)
+	}
+
+	/// Kernel MMR pos and block height of the kernel with the same excess
+	/// preceding the one at `pos`, if any.
+	pub fn get_prev_kernel_pos_height(
+		&self,
+		excess: &Commitment,
+		pos: u64,
+	) -> Result<Option<(u64, u64)>, Error> {
+		self.db.get_ser(&to_key_u64(
+			KERNEL_POS_PREV_PREFIX,
+			&mut excess.as_ref().to_vec(),
+			pos,
+		))
+	}
+
+	/// Whether the (optional) kernel_pos index is maintained as blocks are
+	/// applied and rewound, it may still be getting built.
+	pub fn kernel_index_enabled(&self) -> Result<bool, Error> {
+		self.db.exists(&[KERNEL_INDEX_PREFIX])
+	}
+
+	/// Whether the kernel_pos index is fully built, a kernel missing from it
+	/// isn't in the chain.
+	pub fn kernel_index_complete(&self) -> Result<bool, Error> {
+		self.db.exists(&[KERNEL_INDEX_COMPLETE_PREFIX])
+	}
+
 	/// Builds a new batch to be used with this store.
 	pub fn batch(&self) -> Result<Batch<'_>, Error> {
 		Ok(Batch {
@@ -339,6 +378,93 @@ impl<'a> Batch<'a> {
 		)
 	}
 
+	/// Save kernel pos and block height to the kernel_pos index. The entry
+	/// of an earlier kernel with the same excess is kept aside, to be
+	/// restored when this one is rewound.
+	pub fn save_kernel_pos_height(
+		&self,
+		excess: &Commitment,
+		pos: u64,
+		height: u64,
+	) -> Result<(), Error> {
+		if let Ok(prev) = self.get_kernel_pos_height(excess) {
+			if prev.0 < pos {
+				self.db.put_ser(
+					&to_key_u64(KERNEL_POS_PREV_PREFIX, &mut excess.as_ref().to_vec(), pos)[..],
+					&prev,
+				)?;
+			}
+		}
+		self.db.put_ser(
+			&to_key(KERNEL_POS_PREFIX, &mut excess.as_ref().to_vec())[..],
+			&(pos, height),
+		)
+	}
+
+	/// Delete the kernel_pos index entry for a rewound kernel at `pos`,
+	/// restoring the entry of an earlier kernel with the same excess if any.
+	pub fn delete_kernel_pos_height(&self, excess: &Commitment, pos: u64) -> Result<(), Error> {
+		let prev_key = to_key_u64(KERNEL_POS_PREV_PREFIX, &mut excess.as_ref().to_vec(), pos);
+		let key = to_key(KERNEL_POS_PREFIX, &mut excess.as_ref().to_vec());
+		match self.db.get_ser::<(u64, u64)>(&prev_key)? {
+			Some(prev) => {
+				self.db.delete(&prev_key)?;
+				self.db.put_ser(&key[..], &prev)
+			}
+			None => self.db.delete(&key),
+		}
+	}
+
+	/// Get kernel pos and block height from the kernel_pos index.
+	pub fn get_kernel_pos_height(&self, excess: &Commitment) -> Result<(u64, u64), Error> {
+		option_to_not_found(
+			self.db
+				.get_ser(&to_key(KERNEL_POS_PREFIX, &mut excess.as_ref().to_vec())),
+			|| format!("Kernel position for excess: {:?}", excess),
+		)
+	}
+
+	/// Iterator over the kernel_pos index.
+	pub fn kernel_pos_iter(&self) -> Result<SerIterator<(u64, u64)>, Error> {
+		let key = to_key(KERNEL_POS_PREFIX, &mut "".to_string().into_bytes());
+		self.db.iter(&key)
+	}
+
+	/// Iterator over the kernel_pos entries kept aside for earlier kernels
+	/// with the same excess.
+	pub fn prev_kernel_pos_iter(&self) -> Result<SerIterator<(u64, u64)>, Error> {
+		let key = to_key(KERNEL_POS_PREV_PREFIX, &mut "".to_string().into_bytes());
+		self.db.iter(&key)
+	}
+
+	/// Whether the (optional) kernel_pos index is maintained as blocks are
+	/// applied and rewound, it may still be getting built.
+	pub fn kernel_index_enabled(&self) -> Result<bool, Error> {
+		self.db.exists(&[KERNEL_INDEX_PREFIX])
+	}
+
+	/// Mark the kernel_pos index as built up to the provided height, it is
+	/// maintained on block apply and rewind from then on.
+	pub fn save_kernel_index_height(&self, height: u64) -> Result<(), Error> {
+		self.db.put_ser(&[KERNEL_INDEX_PREFIX], &height)
+	}
+
+	/// Mark the kernel_pos index as fully built, as of the provided height.
+	pub fn save_kernel_index_complete(&self, height: u64) -> Result<(), Error> {
+		self.db.put_ser(&[KERNEL_INDEX_COMPLETE_PREFIX], &height)
+	}
+
+	/// Stop maintaining the kernel_pos index, lookups search the kernel MMR
+	/// until it is rebuilt.
+	pub fn disable_kernel_index(&self) -> Result<(), Error> {
+		for key in &[KERNEL_INDEX_COMPLETE_PREFIX, KERNEL_INDEX_PREFIX] {
+			if self.db.exists(&[*key])? {
+				self.db.delete(&[*key])?;
+			}
+		}
+		Ok(())
+	}
+
 	/// Get the previous header.
 	pub fn get_previous_header(&self, header: &BlockHeader) -> Result<BlockHeader, Error> {
 		self.get_block_header(&header.prev_hash)
@@ -25,7 +25,9 @@ use crate::error::Error;
 use crate::store::{Batch, ChainStore};
 use crate::txhashset::bitmap_accumulator::BitmapAccumulator;
 use crate::txhashset::{RewindableKernelView, UTXOView};
-use crate::types::{CommitPos, OutputRoots, Tip, TxHashSetRoots, TxHashsetWriteStatus};
+use crate::types::{
+	CommitPos, KernelProof, OutputProof, OutputRoots, Tip, TxHashSetRoots, TxHashsetWriteStatus,
+};
 use crate::util::secp::pedersen::{Commitment, RangeProof};
 use crate::util::{file, secp_static, zip};
 use croaring::Bitmap;
@@ -306,6 +308,11 @@ impl TxHashSet {
 		None
 	}
 
+	/// Get the kernel at the provided kernel MMR pos.
+	pub fn get_kernel_by_pos(&self, pos: u64) -> Option<TxKernel> {
+		ReadonlyPMMR::at(&self.kernel_pmmr_h.backend, self.kernel_pmmr_h.last_pos).get_data(pos)
+	}
+
 	/// Get MMR roots.
 	pub fn roots(&self) -> TxHashSetRoots {
 		let output_pmmr =
@@ -338,6 +345,32 @@ impl TxHashSet {
 			.map_err(|_| Error::MerkleProof.into())
 	}
 
+	/// Proof the output with the given commitment is in the current output
+	/// MMR.
+	pub fn output_proof(&self, commit: Commitment) -> Result<OutputProof, Error> {
+		let pos = self.commit_index.get_output_pos(&commit)?;
+		let output_roots = self.roots().output_roots;
+		let pmmr = ReadonlyPMMR::at(&self.output_pmmr_h.backend, self.output_pmmr_h.last_pos);
+		let output = pmmr.get_data(pos).ok_or(Error::OutputNotFound)?;
+		let proof = pmmr.merkle_proof(pos).map_err(|_| Error::MerkleProof)?;
+		Ok(OutputProof {
+			output,
+			pos,
+			pmmr_root: output_roots.pmmr_root,
+			bitmap_root: output_roots.bitmap_root,
+			proof,
+		})
+	}
+
+	/// Proof of the kernel at the provided kernel MMR pos against the current
+	/// kernel MMR.
+	pub fn kernel_proof(&self, pos: u64) -> Result<KernelProof, Error> {
+		let pmmr = ReadonlyPMMR::at(&self.kernel_pmmr_h.backend, self.kernel_pmmr_h.last_pos);
+		let kernel = pmmr.get_data(pos).ok_or(Error::TxKernelNotFound)?;
+		let proof = pmmr.merkle_proof(pos).map_err(|_| Error::MerkleProof)?;
+		Ok(KernelProof { kernel, pos, proof })
+	}
+
 	/// Compact the MMR data files and flush the rm logs
 	pub fn compact(
 		&mut self,
@@ -443,6 +476,40 @@ impl TxHashSet {
 		);
 		Ok(())
 	}
+
+	/// Add the kernels of the blocks from `from_height` to `to_height` to the
+	/// kernel_pos index, returning how many were added. The kernel_pos index
+	/// is rebuilt this way a batch of blocks at a time.
+	pub fn index_kernels(
+		&self,
+		header_pmmr: &PMMRHandle<BlockHeader>,
+		batch: &Batch<'_>,
+		from_height: u64,
+		to_height: u64,
+	) -> Result<u64, Error> {
+		let kernel_pmmr =
+			ReadonlyPMMR::at(&self.kernel_pmmr_h.backend, self.kernel_pmmr_h.last_pos);
+
+		let mut pos = if from_height == 0 {
+			1
+		} else {
+			let hash = header_pmmr.get_header_hash_by_height(from_height - 1)?;
+			batch.get_block_header(&hash)?.kernel_mmr_size + 1
+		};
+		let mut total_kernels = 0;
+		for height in from_height..=to_height {
+			let hash = header_pmmr.get_header_hash_by_height(height)?;
+			let header = batch.get_block_header(&hash)?;
+			while pos <= header.kernel_mmr_size {
+				if let Some(kernel) = kernel_pmmr.get_data(pos) {
+					batch.save_kernel_pos_height(&kernel.excess, pos, height)?;
+					total_kernels += 1;
+				}
+				pos += 1;
+			}
+		}
+		Ok(total_kernels)
+	}
 }
 
 /// Starts a new unit of work to extend (or rewind) the chain with additional
@@ -923,8 +990,13 @@ impl<'a> Extension<'a> {
 			spent.push(spent_pos);
 		}
 
+		// Add the new kernels to the kernel_pos index if we maintain one.
+		let kernel_index = batch.kernel_index_enabled()?;
 		for kernel in b.kernels() {
-			self.apply_kernel(kernel)?;
+			let pos = self.apply_kernel(kernel)?;
+			if kernel_index {
+				batch.save_kernel_pos_height(&kernel.excess, pos, b.header.height)?;
+			}
 		}
 
 		// Update our BitmapAccumulator based on affected outputs (both spent and created).
@@ -1016,12 +1088,13 @@ impl<'a> Extension<'a> {
 		Ok(output_pos)
 	}
 
-	/// Push kernel onto MMR (hash and data files).
-	fn apply_kernel(&mut self, kernel: &TxKernel) -> Result<(), Error> {
-		self.kernel_pmmr
+	/// Push kernel onto MMR (hash and data files), returning its pos.
+	fn apply_kernel(&mut self, kernel: &TxKernel) -> Result<u64, Error> {
+		let pos = self
+			.kernel_pmmr
 			.push(kernel)
 			.map_err(&Error::TxHashSetErr)?;
-		Ok(())
+		Ok(pos)
 	}
 
 	/// Build a Merkle proof for the given output and the block
@@ -1151,6 +1224,19 @@ impl<'a> Extension<'a> {
 			);
 		}
 
+		// Remove any entries from the kernel_pos index created by the block being rewound.
+		// Only entries pointing into the rewound block are removed, the entry of
+		// an earlier kernel with the same excess they replaced is restored.
+		if batch.kernel_index_enabled()? {
+			for kernel in block.kernels() {
+				if let Ok((pos, _)) = batch.get_kernel_pos_height(&kernel.excess) {
+					if pos > self.kernel_pmmr.last_pos {
+						batch.delete_kernel_pos_height(&kernel.excess, pos)?;
+					}
+				}
+			}
+		}
+
 		// Update output_pos based on "unspending" all spent pos from this block.
 		// This is necessary to ensure the output_pos index correclty reflects a
 		// reused output commitment. For example an output at pos 1, spent, reused at pos 2.
@@ -19,7 +19,9 @@ use chrono::prelude::{DateTime, Utc};
 use std::sync::Arc;
 
 use crate::core::core::hash::{Hash, Hashed, ZERO_HASH};
-use crate::core::core::{Block, BlockHeader, HeaderVersion};
+use crate::core::core::merkle_proof::MerkleProof;
+use crate::core::core::{Block, BlockHeader, HeaderVersion, OutputIdentifier, TxKernel};
+use crate::core::global::{ChainParams, ChainTypes};
 use crate::core::pow::Difficulty;
 use crate::core::ser::{self, PMMRIndexHashable, Readable, Reader, Writeable, Writer};
 use crate::error::Error;
@@ -287,6 +289,107 @@ impl Writeable for CommitPos {
 	}
 }
 
+/// Proof an output is part of the output MMR committed to by a block header,
+/// so nodes holding headers only can check it. It doesn't prove the output
+/// is unspent: spent outputs stay in the MMR root and the headers before
+/// version 7 don't commit to the unspent ones.
+#[derive(Debug, Clone)]
+pub struct OutputProof {
+	/// The proven output
+	pub output: OutputIdentifier,
+	/// Position of the output in the output MMR
+	pub pos: u64,
+	/// Root of the output MMR
+	pub pmmr_root: Hash,
+	/// Root of the bitmap accumulator, merged with the MMR root in the
+	/// headers from version 7, not checked any further
+	pub bitmap_root: Hash,
+	/// Merkle proof of the output up to the MMR root
+	pub proof: MerkleProof,
+}
+
+impl OutputProof {
+	/// Checks the proof against the output root and MMR size of the header.
+	pub fn verify(&self, header: &BlockHeader) -> Result<(), Error> {
+		if self.proof.mmr_size != header.output_mmr_size || self.pos > header.output_mmr_size {
+			return Err(Error::InvalidMMRSize);
+		}
+		let roots = OutputRoots {
+			pmmr_root: self.pmmr_root,
+			bitmap_root: self.bitmap_root,
+		};
+		if roots.root(header) != header.output_root {
+			return Err(Error::InvalidRoot);
+		}
+		self.proof
+			.verify(self.pmmr_root, &self.output, self.pos)
+			.map_err(|_| Error::MerkleProof)
+	}
+}
+
+impl Readable for OutputProof {
+	fn read(reader: &mut dyn Reader) -> Result<OutputProof, ser::Error> {
+		Ok(OutputProof {
+			output: OutputIdentifier::read(reader)?,
+			pos: reader.read_u64()?,
+			pmmr_root: Hash::read(reader)?,
+			bitmap_root: Hash::read(reader)?,
+			proof: MerkleProof::read(reader)?,
+		})
+	}
+}
+
+impl Writeable for OutputProof {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		self.output.write(writer)?;
+		writer.write_u64(self.pos)?;
+		self.pmmr_root.write(writer)?;
+		self.bitmap_root.write(writer)?;
+		self.proof.write(writer)
+	}
+}
+
+/// Proof a kernel is part of the kernel MMR committed to by a block header.
+#[derive(Debug, Clone)]
+pub struct KernelProof {
+	/// The proven kernel
+	pub kernel: TxKernel,
+	/// Position of the kernel in the kernel MMR
+	pub pos: u64,
+	/// Merkle proof of the kernel up to the kernel root
+	pub proof: MerkleProof,
+}
+
+impl KernelProof {
+	/// Checks the proof against the kernel root and MMR size of the header.
+	pub fn verify(&self, header: &BlockHeader) -> Result<(), Error> {
+		if self.proof.mmr_size != header.kernel_mmr_size || self.pos > header.kernel_mmr_size {
+			return Err(Error::InvalidMMRSize);
+		}
+		self.proof
+			.verify(header.kernel_root, &self.kernel, self.pos)
+			.map_err(|_| Error::MerkleProof)
+	}
+}
+
+impl Readable for KernelProof {
+	fn read(reader: &mut dyn Reader) -> Result<KernelProof, ser::Error> {
+		Ok(KernelProof {
+			kernel: TxKernel::read(reader)?,
+			pos: reader.read_u64()?,
+			proof: MerkleProof::read(reader)?,
+		})
+	}
+}
+
+impl Writeable for KernelProof {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		self.kernel.write(writer)?;
+		writer.write_u64(self.pos)?;
+		self.proof.write(writer)
+	}
+}
+
 /// The tip of a fork. A handle to the fork ancestry from its leaf in the
 /// blockchain tree. References the max height and the latest and previous
 /// blocks
@@ -415,106 +518,21 @@ pub enum BlockStatus {
 	Reorg(u64),
 }
 
-// Elements in checkpoint data vector
-#[derive(Debug)]
-pub struct Checkpoint {
-	pub height: u64,
-	pub block_hash: Hash,
-}
+/// Elements in checkpoint data vector, now defined along the chain parameters.
+pub use crate::core::global::Checkpoint;
 
+/// Mainnet checkpoints, kept for the code built against them.
+#[deprecated(note = "use the checkpoints of global::ChainParams")]
 #[derive(Debug)]
 pub struct BlockchainCheckpoints {
 	pub checkpoints: Vec<Checkpoint>,
 }
 
+#[allow(deprecated, clippy::new_without_default)]
 impl BlockchainCheckpoints {
 	pub fn new() -> BlockchainCheckpoints {
-		let checkpoints = vec![
-			Checkpoint {
-				height: 100000,
-				block_hash: Hash::from_hex(
-					"e835eb9ebc9f2e13b11061691cb268f44b20001f081003169b634497eb730848",
-				)
-				.unwrap(),
-			},
-			Checkpoint {
-				height: 200000,
-				block_hash: Hash::from_hex(
-					"b2365a8c9719a709f11d450bbddfd012011e21c862239bdc8590aba00815e84c",
-				)
-				.unwrap(),
-			},
-			Checkpoint {
-				height: 400000,
-				block_hash: Hash::from_hex(
-					"6578f1cdf5504d29fc757424e75ac60494e0f6d24b7553d124c8bea6ef99b5d8",
-				)
-				.unwrap(),
-			},
-			Checkpoint {
-				height: 600000,
-				block_hash: Hash::from_hex(
-					"de483eafb2141d66bf541a94d8e41858f01ffc517b9fa61d8781483c34c2a6f7",
-				)
-				.unwrap(),
-			},
-			Checkpoint {
-				height: 800000,
-				block_hash: Hash::from_hex(
-					"1465e7c094376e781b1e80ebd6b7a0c6350ec4d6554f9acdd843802162831003",
-				)
-				.unwrap(),
-			},
-			Checkpoint {
-				height: 1000000,
-				block_hash: Hash::from_hex(
-					"00e4a404130ac192face23fd25f2c46a99a38a31d8cf2d3cc79ea7a518830686",
-				)
-				.unwrap(),
-			},
-			Checkpoint {
-				height: 1200000,
-				block_hash: Hash::from_hex(
-					"8d69282df5579d32346ad0f6d3f4e03a43b1e00e741b1f3ba71c2934d81e5e1a",
-				)
-				.unwrap(),
-			},
-			Checkpoint {
-				height: 1400000,
-				block_hash: Hash::from_hex(
-					"e7e34e50e8a5c9bcf3fe7b7ad99e62a848cda37171ce8d37f21bc334035df4d2",
-				)
-				.unwrap(),
-			},
-			Checkpoint {
-				height: 1600000,
-				block_hash: Hash::from_hex(
-					"ba44beaf37776c3e7da3f4a1b906ae238e1178794cbaa90685e3945d2662d7a2",
-				)
-				.unwrap(),
-			},
-			Checkpoint {
-				height: 1800000,
-				block_hash: Hash::from_hex(
-					"4f23aaf2e83e4041cac670226d3024f4468e3b9bb6ffa2548ebc59489bd09b63",
-				)
-				.unwrap(),
-			},
-			Checkpoint {
-				height: 2000000,
-				block_hash: Hash::from_hex(
-					"eaf5d7a4b6f07ccb8bdbe5db2f39e10eea3ee1c28f8333907d91c9ccc21ce99d",
-				)
-				.unwrap(),
-			},
-			Checkpoint {
-				height: 2200000,
-				block_hash: Hash::from_hex(
-					"1243520890d08026daba8207ed3d67186da64d2b71b5c1e2dd26d34092dee6ba",
-				)
-				.unwrap(),
-			},
-		];
-		return BlockchainCheckpoints { checkpoints };
+		BlockchainCheckpoints {
+			checkpoints: ChainParams::new(ChainTypes::Mainnet).checkpoints,
+		}
 	}
 }
@@ -90,6 +90,7 @@ pub fn init_chain(dir_name: &str, genesis: Block) -> Chain {
 		genesis.clone(),
 		pow::verify_size,
 		false,
+		Arc::new(global::ChainParams::current()),
 	)
 	.unwrap();
 	chain
@@ -121,7 +122,7 @@ where
 		.unwrap();
 
 	let mut b = if consensus::is_foundation_height(height) {
-		let foundation_reward = load_foundation_output(height);
+		let foundation_reward = load_foundation_output(height).unwrap();
 		prepare_block_with_coinbase(
 			prev,
 			diff,
@@ -288,6 +289,7 @@ pub fn setup_with_status_adapter(
 		genesis,
 		pow::verify_size,
 		false,
+		Arc::new(global::ChainParams::current()),
 	)
 	.unwrap();
 
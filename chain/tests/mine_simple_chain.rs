@@ -27,7 +27,7 @@ use self::util::RwLock;
 use epic_chain as chain;
 use epic_chain::BlockStatus;
 use epic_core as core;
-use epic_core::core::hash::Hashed;
+use epic_core::core::hash::{Hashed, ZERO_HASH};
 use epic_keychain as keychain;
 use epic_util as util;
 use std::sync::Arc;
@@ -793,3 +793,68 @@ fn test_overflow_cached_rangeproof() {
 	}
 	clean_output_dir(".epic_overflow");
 }
+
+/// Test a header only chain checking output and kernel proofs from a full chain
+#[test]
+fn light_chain_verifies_proofs() {
+	clean_output_dir(".epic_full_proofs");
+	clean_output_dir(".epic_light_proofs");
+	set_foundation_path_for_test("foundation_floonet.json");
+
+	{
+		let genesis = pow::mine_genesis_block().unwrap();
+		let full = init_chain(".epic_full_proofs", genesis.clone());
+		let light = init_chain(".epic_light_proofs", genesis).with_light_mode(true);
+		// kernels are only proven from the kernel index
+		full.rebuild_kernel_index().unwrap();
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+
+		let mut blocks = vec![];
+		for n in 1..6 {
+			let prev = full.head_header().unwrap();
+			let block = prepare_block(&kc, &prev, &full, n, vec![], 1);
+			process_block(&full, &block);
+			process_header(&light, &block.header);
+			blocks.push(block);
+		}
+		assert_eq!(light.tip().unwrap().height, 5);
+		assert_eq!(light.head().unwrap().height, 0);
+
+		let output = blocks[2].outputs()[0].commitment();
+		let (header, proof) = full.get_output_proof(output).unwrap();
+		let verified = light
+			.verify_output_proof(&header.hash(), &output, &proof)
+			.unwrap();
+		assert_eq!(verified.height, 5);
+
+		let excess = blocks[2].kernels()[0].excess;
+		let (header, proof) = full.get_kernel_proof(&excess).unwrap();
+		let verified = light
+			.verify_kernel_proof(&header.hash(), &excess, &proof)
+			.unwrap();
+		assert_eq!(verified.height, 5);
+
+		// a proof for another position doesn't hash to the header roots
+		let (header, mut proof) = full.get_output_proof(output).unwrap();
+		proof.pos += 1;
+		let res = light.verify_output_proof(&header.hash(), &output, &proof);
+		assert!(res.unwrap_err().is_bad_data());
+
+		// a valid proof of another output doesn't prove the requested one
+		let other = blocks[3].outputs()[0].commitment();
+		let (header, proof) = full.get_output_proof(other).unwrap();
+		let res = light.verify_output_proof(&header.hash(), &output, &proof);
+		assert!(res.unwrap_err().is_bad_data());
+		let other = blocks[3].kernels()[0].excess;
+		let (header, proof) = full.get_kernel_proof(&other).unwrap();
+		let res = light.verify_kernel_proof(&header.hash(), &excess, &proof);
+		assert!(res.unwrap_err().is_bad_data());
+
+		// a header we don't know of is no proof of anything
+		let (_, proof) = full.get_output_proof(output).unwrap();
+		let res = light.verify_output_proof(&ZERO_HASH, &output, &proof);
+		assert!(!res.unwrap_err().is_bad_data());
+	}
+	clean_output_dir(".epic_full_proofs");
+	clean_output_dir(".epic_light_proofs");
+}
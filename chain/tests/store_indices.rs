@@ -82,3 +82,100 @@ fn test_store_indices() {
 	// Cleanup chain directory
 	clean_output_dir(chain_dir);
 }
+
+#[test]
+fn test_kernel_index() {
+	util::init_test_logger();
+
+	let chain_dir = ".epic_idx_2";
+	clean_output_dir(chain_dir);
+
+	set_foundation_path_for_test("foundation_floonet.json");
+
+	let genesis = pow::mine_genesis_block().unwrap();
+	let chain = init_chain(chain_dir, genesis);
+
+	let kc = ExtKeychain::from_random_seed(false).unwrap();
+	let mut prev = chain.head_header().unwrap();
+	let mut blocks = vec![];
+
+	for n in 1..=3 {
+		let b = prepare_block(&kc, &prev, &chain, n + 1, vec![], 1);
+		prev = b.header.clone();
+		process_block(&chain, &b);
+		blocks.push(b);
+	}
+
+	// No index until we build it, lookups still work through the kernel MMR.
+	let excess = blocks[1].kernels()[0].excess;
+	assert!(!chain.store().kernel_index_enabled().unwrap());
+	assert!(chain.store().get_kernel_pos_height(&excess).is_err());
+	let (_, height, pos) = chain
+		.get_kernel_height(&excess, None, None)
+		.unwrap()
+		.unwrap();
+	assert_eq!(height, 2);
+
+	// A stale entry left from a previous index is dropped by the rebuild.
+	let stale = blocks[0].outputs()[0].commitment();
+	{
+		let batch = chain.store().batch().unwrap();
+		batch.save_kernel_pos_height(&stale, 1, 1).unwrap();
+		batch.commit().unwrap();
+	}
+
+	chain.rebuild_kernel_index().unwrap();
+	assert!(chain.store().kernel_index_enabled().unwrap());
+	assert!(chain.store().kernel_index_complete().unwrap());
+	assert_eq!(
+		chain.store().get_kernel_pos_height(&excess).unwrap(),
+		(pos, 2)
+	);
+	assert!(chain.store().get_kernel_pos_height(&stale).is_err());
+
+	// The complete index is authoritative.
+	assert!(chain
+		.get_kernel_height(&stale, None, None)
+		.unwrap()
+		.is_none());
+
+	// The index is maintained as new blocks are applied.
+	let b = prepare_block(&kc, &prev, &chain, 5, vec![], 1);
+	process_block(&chain, &b);
+	let excess = b.kernels()[0].excess;
+	let (pos, height) = chain.store().get_kernel_pos_height(&excess).unwrap();
+	assert_eq!(height, 4);
+	let (kernel, height, mmr_index) = chain
+		.get_kernel_height(&excess, None, None)
+		.unwrap()
+		.unwrap();
+	assert_eq!((kernel.excess, height, mmr_index), (excess, 4, pos));
+
+	// Height bounds are still honoured.
+	assert!(chain
+		.get_kernel_height(&excess, Some(1), Some(3))
+		.unwrap()
+		.is_none());
+
+	// A kernel repeating an earlier excess keeps the earlier entry aside, it
+	// is restored when the later kernel is rewound.
+	{
+		let batch = chain.store().batch().unwrap();
+		batch.save_kernel_pos_height(&stale, 10, 5).unwrap();
+		batch.save_kernel_pos_height(&stale, 20, 6).unwrap();
+		assert_eq!(batch.get_kernel_pos_height(&stale).unwrap(), (20, 6));
+		batch.delete_kernel_pos_height(&stale, 20).unwrap();
+		assert_eq!(batch.get_kernel_pos_height(&stale).unwrap(), (10, 5));
+		batch.delete_kernel_pos_height(&stale, 10).unwrap();
+		assert!(batch.get_kernel_pos_height(&stale).is_err());
+		batch.commit().unwrap();
+	}
+	assert!(chain
+		.store()
+		.get_prev_kernel_pos_height(&stale, 20)
+		.unwrap()
+		.is_none());
+
+	// Cleanup chain directory
+	clean_output_dir(chain_dir);
+}
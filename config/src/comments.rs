@@ -48,6 +48,17 @@ fn comments() -> HashMap<String, String> {
 #private key for the TLS certificate
 #tls_certificate_key = \"\"
 
+#maximum number of requests in a JSON-RPC batch on the v2 owner and foreign APIs,
+#long polls (get_tx_status) must be sent on their own
+#api_max_batch_size = 100
+
+#scoped API tokens (read_chain, push_tx, mining, peer_admin, chain_admin),
+#managed with `epic api_token`, sent as \"Authorization: Bearer <token>\".
+#only the token hashes are stored in this file. When set, the secrets below
+#keep working, the api secret granting all scopes and the foreign api secret
+#the foreign API ones.
+#api_tokens_path = \"api_tokens.json\"
+
 #the address on which services will listen, e.g. Transaction Pool
 "
 		.to_string(),
@@ -88,6 +99,11 @@ fn comments() -> HashMap<String, String> {
 #UserTesting - For regular user testing (cuckoo 16)
 #Floonet - For the long term floonet test network
 #Mainnet - For mainnet
+#
+#a UserTesting node joins a custom network, with its own genesis, magic bytes,
+#ports, policies and foundation file, when pointed at its definition created
+#with `epic network new`, the network ports replacing the configured ones
+#network_file = \"network.json\"
 "
 		.to_string(),
 	);
@@ -129,6 +145,19 @@ fn comments() -> HashMap<String, String> {
 		.to_string(),
 	);
 
+	retval.insert(
+		"light_mode".to_string(),
+		"
+#run the node in light mode: only the block headers are synced and served,
+#outputs and kernels being checked on demand with merkle proofs from full
+#peers (verify_output and verify_kernel owner API methods), for devices with
+#little disk space. The proofs show an output was added to the chain, not
+#that it's still unspent. Transactions aren't validated nor relayed in this
+#mode.
+"
+		.to_string(),
+	);
+
 	retval.insert(
 		"skip_sync_wait".to_string(),
 		"
@@ -271,6 +300,12 @@ fn comments() -> HashMap<String, String> {
 #be specified as follows:
 seeds = [\"95.217.197.180:3414\",\"5.161.127.56:3414\",\"5.75.242.4:3414\",\"5.78.71.29:3414\"]
 
+#If the seeding type is DNSSeed, the DNS seeds queried for each chain type
+#can be overridden, e.g. with an epic-seeder instance of your own
+#[server.p2p_config.dns_seeds]
+#mainnet = [\"node.epiccash.com\", \"seed.example.com:3414\"]
+#floonet = [\"floonet.epiccash.com\"]
+
 #hardcoded peer lists for allow/deny
 #will *only* connect to peers in allow list
 #peers_allow = [\"192.168.0.1:3414\", \"192.168.0.2:3414\"]
@@ -279,6 +314,12 @@ seeds = [\"95.217.197.180:3414\",\"5.161.127.56:3414\",\"5.75.242.4:3414\",\"5.7
 #a list of preferred peers to connect to
 #peers_preferred = [\"192.168.0.1:3414\",\"192.168.0.2:3414\"]
 
+#additional addresses to listen on, e.g. to accept IPv6 connections
+#listen_addrs = [\"[::]:3414\"]
+#publicly reachable addresses advertised to our peers, these are also
+#learned automatically from the peers we connect to
+#external_addrs = [\"1.2.3.4:3414\"]
+
 #how long a banned peer should stay banned
 #ban_window = 10800
 
@@ -292,9 +333,30 @@ seeds = [\"95.217.197.180:3414\",\"5.161.127.56:3414\",\"5.75.242.4:3414\",\"5.7
 #until we get to at least this number
 #peer_min_preferred_outbound_count = 8
 
+#maximum number of outbound peers within the same network group, /16 for
+#IPv4 and /32 for IPv6 (default = 4)
+#peer_max_outbound_per_group = 4
+
+#run p2p connections over tor, using the [server.tor] socks_proxy_addr and
+#tor process. Enabled dials outbound peers through the SOCKS proxy and
+#publishes our p2p port as an onion service, OnionOnly additionally refuses
+#clearnet peers (best used with a List of onion seeds, e.g.
+#\"<onion address>.onion:3414\"). Default is Disabled.
+#tor_mode = \"Enabled\"
+
 # 31 = Bit flags for FULL_NODE, with HEADER_FASTSYNC capability
+# 95 = FULL_NODE with ENCRYPTED capability, encrypts the traffic with peers
+#supporting it and falls back to plaintext for the others
 #This structure needs to be changed internally, to make it more configurable
 
+#peers that must authenticate with a known static key (as logged on startup
+#of the peer as \"P2P static key\"), connections to these are always encrypted
+#Our own static key is stored under db_root, encrypted with the password of
+#the EPIC_IDENTITY_PASSWORD environment variable when set
+#[[server.p2p_config.pinned_peers]]
+#addr = \"192.168.0.1:3414\"
+#static_key = \"3b6a27bcceb6a42d62a3a8d02a6f0d73653215771de243a63ac048a18b59da29\"
+
 # A preferred dandelion_peer, mainly used for testing dandelion
 # dandelion_peer = \"10.0.0.1:13144\"
 
@@ -344,6 +406,69 @@ seeds = [\"95.217.197.180:3414\",\"5.161.127.56:3414\",\"5.75.242.4:3414\",\"5.7
 		.to_string(),
 	);
 
+	retval.insert(
+		"[server.api_rate_limit]".to_string(),
+		"
+################################################
+### API RATE LIMITS                          ###
+################################################
+
+#each API client gets a bucket refilled at a steady rate, each call taking
+#the cost of its method from it. Callers with an API token are charged to
+#their token, anybody else to their IP. A client running out gets an HTTP
+#429 response with a Retry-After header. Remove this section to disable it.
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"ip_requests_per_second".to_string(),
+		"
+#cost refilled per second, and cost that can be spent at once, per client IP
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"token_requests_per_second".to_string(),
+		"
+#cost refilled per second, and cost that can be spent at once, per API token
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"max_concurrent_requests".to_string(),
+		"
+#requests served at once over all the clients, long polls (get_tx_status)
+#aside, 0 for no limit
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"max_concurrent_long_polls".to_string(),
+		"
+#long polls (get_tx_status) served at once over all the clients, each of
+#them holding a worker thread while it waits, 0 for no limit
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"[server.api_rate_limit.method_costs]".to_string(),
+		"
+#cost of the API methods, by JSON-RPC method name, the v1 routes counting as
+#their v2 method. Most methods cost 1, validate_chain, compact_chain,
+#rebuild_kernel_index and audit_foundation_levy 50, get_outputs,
+#get_unspent_outputs, get_blocks, get_block_summaries, get_network_stats,
+#get_supply, verify_output and verify_kernel 10, get_kernel,
+#get_pmmr_indices and get_block_stats 5, e.g.
+#get_outputs = 20
+"
+		.to_string(),
+	);
+
 	retval.insert(
 		"[server.stratum_mining_config]".to_string(),
 		"
@@ -403,6 +528,49 @@ seeds = [\"95.217.197.180:3414\",\"5.161.127.56:3414\",\"5.75.242.4:3414\",\"5.7
 		.to_string(),
 	);
 
+	retval.insert(
+		"[server.tor]".to_string(),
+		"
+#########################################
+### TOR CONFIGURATION                 ###
+#########################################
+
+#tor is driven through its control port, publishing our API (ONIONSTEM
+#capability) and p2p port (tor_mode) as an ephemeral onion service under our
+#node identity, stored in db_root and encrypted with the
+#EPIC_IDENTITY_PASSWORD environment variable (see `epic identity`)
+
+#address of the tor control port (default = 127.0.0.1:9051)
+#control_addr = \"127.0.0.1:9051\"
+
+#reuse an already running tor instead of spawning our own from the tor
+#directory next to the epic executable (default = false)
+#use_system_tor = true
+
+#control auth cookie of the system tor, only needed when tor doesn't tell
+#where it is (e.g. \"/run/tor/control.authcookie\")
+#cookie_file = \"/run/tor/control.authcookie\"
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"socks_proxy_addr".to_string(),
+		"
+#address of the tor SOCKS proxy, outbound onion connections go through it.
+#Required by tor_mode, left empty the ONIONSTEM relaying is off
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"send_config_dir".to_string(),
+		"
+#directory of the torrc and data of the tor process we spawn
+"
+		.to_string(),
+	);
+
 	retval.insert(
 		"[logging]".to_string(),
 		"
@@ -23,6 +23,7 @@ use crate::core::block::feijoada::{
 };
 use crate::core::block::HeaderVersion;
 use crate::core::hash::{Hash, ZERO_HASH};
+use crate::forks::DifficultyEra;
 use crate::global;
 use crate::pow::{Difficulty, PoWType};
 use std::cmp::{max, min};
@@ -93,8 +94,7 @@ pub fn floonet_block_total_reward_at_height(height: u64) -> u64 {
 
 /// Compute the total reward generated by each block in a given height.
 pub fn block_total_reward_at_height(height: u64) -> u64 {
-	let param_ref = global::CHAIN_TYPE.read();
-	match *param_ref {
+	match global::get_chain_type() {
 		global::ChainTypes::Floonet => floonet_block_total_reward_at_height(height),
 		_ => mainnet_block_total_reward_at_height(height),
 	}
@@ -179,6 +179,85 @@ pub fn blocks_to_next_halving(height: u64) -> u64 {
 	}
 }
 
+/// Coins emitted by the blocks 1 to a height, split between the miners and
+/// the foundation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Emission {
+	/// Block rewards without the foundation levy
+	pub miner: u64,
+	/// Foundation levy paid, at the foundation heights
+	pub foundation: u64,
+	/// Foundation levy accrued since the last foundation height, paid at the
+	/// next one
+	pub foundation_pending: u64,
+}
+
+impl Emission {
+	/// Coins in circulation, the pending levy not being paid yet.
+	pub fn total(&self) -> u64 {
+		self.miner + self.foundation
+	}
+}
+
+/// Emission of the blocks 1 to `height`, summed era by era rather than block
+/// by block. Its total matches `total_overage_at_height` without the genesis
+/// reward.
+pub fn emission_at_height(height: u64) -> Emission {
+	let (total, levy) = scheduled_emission(height);
+	let (_, paid) = scheduled_emission(height - height % foundation_height());
+	Emission {
+		miner: total - levy,
+		foundation: paid,
+		foundation_pending: levy - paid,
+	}
+}
+
+/// Total block reward and foundation levy of the blocks 1 to `height`.
+fn scheduled_emission(height: u64) -> (u64, u64) {
+	let (mut total, mut levy) = (0, 0);
+	let mut start = 1;
+	while start <= height {
+		let end = min(height, next_emission_change(start) - 1);
+		let blocks = end - start + 1;
+		total += blocks * block_total_reward_at_height(start);
+		levy += blocks * reward_foundation_at_height(start);
+		start = end + 1;
+	}
+	(total, levy)
+}
+
+/// First height after `height` where the block reward or the foundation
+/// levy may change.
+fn next_emission_change(height: u64) -> u64 {
+	let levy_change = if height <= FOUNDATION_LEVY_ERA_1 {
+		FOUNDATION_LEVY_ERA_1 + 1
+	} else {
+		let eras = (height - FOUNDATION_LEVY_ERA_1 - 1) / FOUNDATION_LEVY_ERA_2_ONWARDS + 1;
+		FOUNDATION_LEVY_ERA_1 + 1 + eras * FOUNDATION_LEVY_ERA_2_ONWARDS
+	};
+	min(next_halving_height(height).unwrap_or(u64::MAX), levy_change)
+}
+
+/// First height after `height` where the block reward halves, None when it
+/// never does again.
+pub fn next_halving_height(height: u64) -> Option<u64> {
+	match global::get_chain_type() {
+		global::ChainTypes::Floonet if height <= FLOONET_BLOCK_ERA_1 => {
+			Some(FLOONET_BLOCK_ERA_1 + 1)
+		}
+		global::ChainTypes::Floonet => None,
+		_ if height <= BLOCK_ERA_5 => {
+			let eras = [BLOCK_ERA_1, BLOCK_ERA_2, BLOCK_ERA_3, BLOCK_ERA_4];
+			let era = eras.iter().find(|era| height <= **era);
+			Some(era.map_or(BLOCK_ERA_5, |era| *era) + 1)
+		}
+		_ => {
+			let eras = (height - BLOCK_ERA_5 - 1) / BLOCK_ERA_6_ONWARDS + 1;
+			Some(BLOCK_ERA_5 + 1 + eras * BLOCK_ERA_6_ONWARDS)
+		}
+	}
+}
+
 /// Set the height (and its multiples) where the foundation coinbase will be added to the block.
 /// This variable will sparse the blocks that receive the foundation coinbase.
 pub const MAINNET_FOUNDATION_HEIGHT: u64 = DAY_HEIGHT;
@@ -197,8 +276,7 @@ pub const FLOONET_FOUNDATION_HEIGHT: u64 = DAY_HEIGHT;
 
 /// Get the height where the foundation coinbase will be added to the block.
 pub fn foundation_height() -> u64 {
-	let param_ref = global::CHAIN_TYPE.read();
-	match *param_ref {
+	match global::get_chain_type() {
 		global::ChainTypes::AutomatedTesting => AUTOMATEDTEST_FOUNDATION_HEIGHT,
 		global::ChainTypes::UserTesting => USERNET_FOUNDATION_HEIGHT,
 		global::ChainTypes::Floonet => FLOONET_FOUNDATION_HEIGHT,
@@ -404,24 +482,17 @@ pub const FLOONET_FIRST_HARD_FORK: u64 = 25800;
 /// AutomatedTesting and UserTesting first hard fork height.
 pub const TESTING_FIRST_HARD_FORK: u64 = 6;
 
-/// Get the height of the first epic hard fork
+/// Get the height of the first epic hard fork, the first header version change
+/// of the fork schedule
 pub fn first_fork_height() -> u64 {
-	match global::CHAIN_TYPE.read().clone() {
-		global::ChainTypes::Mainnet => MAINNET_FIRST_HARD_FORK,
-		global::ChainTypes::Floonet => FLOONET_FIRST_HARD_FORK,
-		global::ChainTypes::AutomatedTesting | global::ChainTypes::UserTesting => {
-			TESTING_FIRST_HARD_FORK
-		}
-	}
+	let schedule = global::fork_schedule();
+	let genesis_version = schedule.forks[0].header_version;
+	schedule.first_height(|fork| fork.header_version != genesis_version)
 }
 
 /// Compute possible block version at a given height
 pub fn header_version(height: u64) -> HeaderVersion {
-	if height < first_fork_height() {
-		HeaderVersion(6)
-	} else {
-		HeaderVersion(7)
-	}
+	HeaderVersion(global::fork_schedule().fork_at(height).header_version)
 }
 
 /// Check whether the block version is valid at a given height, implements
@@ -455,13 +526,7 @@ pub const AR_SCALE_DAMP_FACTOR: u64 = 13;
 
 /// Get the height where the difficulty patch will be added.
 pub fn difficultyfix_height() -> u64 {
-	let param_ref = global::CHAIN_TYPE.read();
-	match *param_ref {
-		global::ChainTypes::AutomatedTesting => TESTING_DIFFICULTY_ERA,
-		global::ChainTypes::UserTesting => TESTING_DIFFICULTY_ERA,
-		global::ChainTypes::Floonet => FLOONET_DIFFICULTY_ERA,
-		_ => MAINNET_DIFFICULTY_ERA,
-	}
+	global::fork_schedule().first_height(|fork| fork.difficulty_era == DifficultyEra::Era1)
 }
 
 /// Compute weight of a graph as number of siphash bits defining the graph
@@ -665,9 +730,13 @@ pub fn next_difficulty<T>(height: u64, prev_algo: PoWType, cursor: T) -> HeaderI
 where
 	T: IntoIterator<Item = HeaderInfo>,
 {
-	if crate::global::is_floonet() || crate::global::is_user_testing_mode() {
+	if crate::global::is_floonet() {
 		return HeaderInfo::from_diff_scaling(Difficulty::from_num(1), 1);
 	}
+	if crate::global::is_user_testing_mode() {
+		let difficulty = Difficulty::from_num(global::initial_block_difficulty());
+		return HeaderInfo::from_diff_scaling(difficulty, 1);
+	}
 
 	let diff_data = match prev_algo.clone() {
 		PoWType::Cuckatoo => global::difficulty_data_to_vector(cursor, DIFFICULTY_ADJUST_WINDOW),
@@ -865,8 +934,7 @@ fn next_randomx_difficulty_era1(pow: PoWType, diff_data: &Vec<HeaderInfo>) -> u6
 		RX_CLAMP_FACTOR,
 	);
 
-	let param_ref = global::CHAIN_TYPE.read();
-	match *param_ref {
+	match global::get_chain_type() {
 		global::ChainTypes::UserTesting => max(
 			MIN_DIFFICULTY_RANDOMX_TESTING,
 			diff_sum * BLOCK_TIME_SEC / adj_ts,
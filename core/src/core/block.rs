@@ -42,7 +42,7 @@ use crate::pow::{verify_size, Difficulty, Proof, ProofOfWork};
 use crate::ser::{self, PMMRable, Readable, Reader, Writeable, Writer};
 use crate::util::{secp, static_secp_instance};
 
-use crate::core::foundation::load_foundation_output;
+use crate::core::foundation::{self, load_foundation_output};
 use thiserror::Error; // <-- Add this
 
 /// Errors thrown by Block validation
@@ -61,6 +61,9 @@ pub enum Error {
 
 	#[error("Invalid foundation output")]
 	InvalidFoundationOutput,
+	/// The foundation coinbase to check the block against couldn't be loaded
+	#[error("Foundation error {0}")]
+	Foundation(#[from] foundation::Error),
 
 	/// Restrict block total weight.
 	#[error("Block is too heavy")]
@@ -887,7 +890,7 @@ impl Block {
 
 		{
 			if is_foundation_height(self.header.height) {
-				let cb_data = load_foundation_output(self.header.height);
+				let cb_data = load_foundation_output(self.header.height)?;
 				if cb_outs
 					.iter()
 					.filter(|x| x.commitment() == cb_data.output.commitment())
@@ -15,10 +15,11 @@
 //! BlockSums per-block running totals for utxo_sum and kernel_sum.
 //! Allows fast "full" verification of kernel sums at a given block height.
 
-use crate::core::committed::Committed;
+use crate::core::committed::{Committed, Error};
+use crate::keychain::BlindingFactor;
 use crate::ser::{self, Readable, Reader, Writeable, Writer};
 use crate::util::secp::pedersen::Commitment;
-use crate::util::secp_static;
+use crate::util::{secp_static, static_secp_instance};
 
 /// The output_sum and kernel_sum for a given block.
 /// This is used to validate the next block being processed by applying
@@ -32,6 +33,24 @@ pub struct BlockSums {
 	pub kernel_sum: Commitment,
 }
 
+impl BlockSums {
+	/// Verify the kernel sum, with the commitment to the total kernel offset of
+	/// the block added, equals the utxo sum.
+	pub fn verify(&self, total_kernel_offset: &BlindingFactor) -> Result<(), Error> {
+		let secp = static_secp_instance();
+		let secp = secp.lock();
+		let mut commits = vec![self.kernel_sum];
+		if *total_kernel_offset != BlindingFactor::zero() {
+			let key = total_kernel_offset.secret_key(&secp)?;
+			commits.push(secp.commit(0, key)?);
+		}
+		if secp.commit_sum(commits, vec![])? != self.utxo_sum {
+			return Err(Error::KernelSumMismatch);
+		}
+		Ok(())
+	}
+}
+
 impl Writeable for BlockSums {
 	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
 		writer.write_fixed_bytes(&self.utxo_sum)?;
@@ -24,6 +24,7 @@ use std::fs::{create_dir, File};
 // use std::path::Path; // Already imported elsewhere or not needed
 use std::io::{prelude::*, BufRead, BufReader};
 use std::path::Path;
+use thiserror::Error;
 
 /// Response to build a coinbase output.
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -69,52 +70,73 @@ pub fn save_in_disk(serialization: String, path: &Path) {
 		.expect("Couldn't save the serialization in the disk!")
 }
 
-/// Load the foundation coinbase relative to the height of the chain
-/// Loads the foundation output for the given height.
-/// If the foundation.json file exists, loads from file. Otherwise, uses the embedded mainnet content.
-use crate::global::{ChainTypes, CHAIN_TYPE};
+use crate::global::{get_chain_type, ChainTypes};
 
-/// Load the foundation coinbase relative to the height of the chain
-/// Loads the foundation output for the given height.
-/// If the foundation.json file exists, loads from file. Otherwise, uses the embedded content for the current chain type.
-pub fn load_foundation_output(height: u64) -> CbData {
-	let index_foundation = foundation_index(height);
-
-	let path_str = get_foundation_path()
-		.unwrap_or_else(|| panic!("No path to the foundation.json was provided!"));
+/// Errors loading the foundation coinbases
+#[derive(Debug, Clone, Eq, PartialEq, Error)]
+pub enum Error {
+	/// The foundation file couldn't be read
+	#[error("Couldn't read the foundation file {0}: {1}")]
+	Unreadable(String, String),
+	/// The foundation file has no coinbase at the index
+	#[error("No foundation coinbase at index {0}")]
+	MissingCoinbase(u64),
+	/// The coinbase at the index couldn't be parsed
+	#[error("Invalid foundation coinbase at index {0}: {1}")]
+	InvalidCoinbase(u64, String),
+}
 
-	let path = Path::new(&path_str);
-	let lines: Vec<String> = if path.exists() {
-		// Load from file
-		let file = File::open(&path_str).unwrap_or_else(|why| {
-			panic!(
-				"Error trying to read the foundation coinbase. Couldn't open the file {}: {}",
-				path_str, why
-			)
-		});
-		let reader = BufReader::new(file);
-		reader.lines().map(|l| l.unwrap()).collect()
-	} else {
-		// Use embedded content based on chain type
-		let chain_type = CHAIN_TYPE.read().clone();
-		match chain_type {
-			ChainTypes::Mainnet => {
-				static EMBEDDED_FOUNDATION_JSON: &str = include_str!("../../../debian/foundation.json");
-				EMBEDDED_FOUNDATION_JSON.lines().map(|l| l.to_string()).collect()
-			},
-			ChainTypes::AutomatedTesting |
-			ChainTypes::UserTesting |
-			ChainTypes::Floonet => {
-				static EMBEDDED_FOUNDATION_FLOONET_JSON: &str = include_str!("../../../debian/foundation_floonet.json");
-				EMBEDDED_FOUNDATION_FLOONET_JSON.lines().map(|l| l.to_string()).collect()
-			}
-			
+/// Lines of the foundation file in use, one coinbase per line.
+/// Reads the foundation file of the chain parameters when they have one, uses the embedded
+/// content for the current chain type otherwise.
+fn foundation_lines() -> Result<Vec<String>, Error> {
+	let path_str = match get_foundation_path() {
+		Some(path_str) => path_str,
+		None => {
+			let embedded = match get_chain_type() {
+				ChainTypes::Mainnet => {
+					static EMBEDDED_FOUNDATION_JSON: &str =
+						include_str!("../../../debian/foundation.json");
+					EMBEDDED_FOUNDATION_JSON
+				}
+				ChainTypes::AutomatedTesting | ChainTypes::UserTesting | ChainTypes::Floonet => {
+					static EMBEDDED_FOUNDATION_FLOONET_JSON: &str =
+						include_str!("../../../debian/foundation_floonet.json");
+					EMBEDDED_FOUNDATION_FLOONET_JSON
+				}
+			};
+			return Ok(embedded.lines().map(|l| l.to_string()).collect());
 		}
 	};
 
+	let unreadable = |why: std::io::Error| Error::Unreadable(path_str.clone(), why.to_string());
+	let file = File::open(&path_str).map_err(unreadable)?;
+	BufReader::new(file)
+		.lines()
+		.collect::<Result<Vec<String>, _>>()
+		.map_err(unreadable)
+}
+
+/// Parse the foundation coinbase at an index of the foundation lines
+fn foundation_coinbase(lines: &[String], index: u64) -> Result<CbData, Error> {
 	let line = lines
-		.get(index_foundation as usize)
-		.unwrap_or_else(|| panic!("Index {} out of bounds in foundation.json!", index_foundation));
+		.get(index as usize)
+		.ok_or(Error::MissingCoinbase(index))?;
+	serde_json::from_str(line).map_err(|e| Error::InvalidCoinbase(index, e.to_string()))
+}
+
+/// Load the foundation coinbase relative to the height of the chain, from the foundation file
+/// of the chain parameters or else the embedded content for the current chain type.
+pub fn load_foundation_output(height: u64) -> Result<CbData, Error> {
+	foundation_coinbase(&foundation_lines()?, foundation_index(height))
+}
 
-	serde_json::from_str(line).unwrap()
+/// Load the foundation coinbases of several foundation heights, reading the foundation file
+/// once. None for the heights without a valid coinbase in the file (past its end).
+pub fn load_foundation_outputs(heights: &[u64]) -> Result<Vec<Option<CbData>>, Error> {
+	let lines = foundation_lines()?;
+	Ok(heights
+		.iter()
+		.map(|height| foundation_coinbase(&lines, foundation_index(*height)).ok())
+		.collect())
 }
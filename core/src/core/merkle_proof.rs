@@ -50,6 +50,10 @@ impl Readable for MerkleProof {
 	fn read(reader: &mut dyn Reader) -> Result<MerkleProof, ser::Error> {
 		let mmr_size = reader.read_u64()?;
 		let path_len = reader.read_u64()?;
+		// a path can't be longer than the height of a u64 sized MMR
+		if path_len > 64 {
+			return Err(ser::Error::TooLargeReadErr);
+		}
 		let mut path = Vec::with_capacity(path_len as usize);
 		for _ in 0..path_len {
 			let hash = Hash::read(reader)?;
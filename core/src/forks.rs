@@ -0,0 +1,330 @@
+// Copyright 2020 The Epic Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Hard fork schedule of each chain type. Every height at which a consensus
+//! rule changes (header version, allowed feijoada policies, difficulty
+//! adjustment algorithm) is declared here once, `consensus` and `global`
+//! looking the rules of a height up in the schedule.
+
+use crate::consensus;
+use crate::core::block::feijoada::{is_allowed_policy, AllowPolicy, AllowedPolicy, PolicyConfig};
+use crate::global::ChainTypes;
+use thiserror::Error;
+
+/// Version of the fork schedule format, bumped when the rules it describes
+/// change shape so third-party software can tell what it is reading.
+pub const FORK_SCHEDULE_VERSION: u32 = 1;
+
+/// Difficulty adjustment algorithm in force.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum DifficultyEra {
+	/// `consensus::next_difficulty`
+	Initial,
+	/// `consensus::next_difficulty_era1`
+	Era1,
+}
+
+/// Consensus rules in force from a height on, until the next fork.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Fork {
+	/// Rules changed at this height
+	pub name: String,
+	/// First height the rules apply to
+	pub height: u64,
+	/// Version the block headers must have
+	pub header_version: u16,
+	/// Bitmask of the feijoada policies blocks may use, bit n for policy n
+	pub allowed_policies: u64,
+	/// Difficulty adjustment algorithm
+	pub difficulty_era: DifficultyEra,
+}
+
+impl Fork {
+	/// Whether blocks may use the given feijoada policy.
+	pub fn allows_policy(&self, policy: u8) -> bool {
+		policy < 64 && self.allowed_policies & (1 << policy) != 0
+	}
+
+	/// Policy new blocks are emitted with, the newest one allowed.
+	pub fn emitted_policy(&self) -> u8 {
+		63u32.saturating_sub(self.allowed_policies.leading_zeros()) as u8
+	}
+}
+
+/// Fork schedule errors, found when validating it at startup.
+#[derive(Debug, Clone, Eq, PartialEq, Error)]
+pub enum Error {
+	/// The first fork has to hold the genesis rules
+	#[error("Fork schedule does not start at the genesis")]
+	NoGenesisFork,
+	/// Forks are not sorted by strictly increasing height
+	#[error("Fork at height {0} is not after the previous one")]
+	Unordered(u64),
+	/// Header versions only ever go up
+	#[error("Fork at height {0} lowers the header version")]
+	HeaderVersionDecrease(u64),
+	/// Difficulty eras only ever go forward
+	#[error("Fork at height {0} goes back to a previous difficulty era")]
+	DifficultyEraDecrease(u64),
+	/// Blocks could not use any policy
+	#[error("Fork at height {0} allows no policy")]
+	NoAllowedPolicy(u64),
+	/// An allowed policy is missing from the policy config
+	#[error("Fork at height {height} allows policy {policy} which is not configured")]
+	MissingPolicy {
+		/// Fork height
+		height: u64,
+		/// Policy index
+		policy: u8,
+	},
+	/// The algorithm proportions of a policy must sum to 100
+	#[error("Policy {policy} sums to {total} instead of 100")]
+	PolicyShares {
+		/// Policy index
+		policy: u8,
+		/// Sum of the proportions
+		total: u32,
+	},
+	/// The policy config allows other policies than the schedule
+	#[error("Policy config allowed policies do not follow the fork schedule")]
+	AllowedPoliciesMismatch,
+	/// The policy config doesn't allow the policy blocks are mined with
+	#[error("Policy config does not allow policy {policy} emitted from height {height}")]
+	EmittedPolicyNotAllowed {
+		/// Fork height
+		height: u64,
+		/// Policy index
+		policy: u8,
+	},
+}
+
+/// Consensus rule a fork changes.
+enum Rule {
+	HeaderVersion(u16),
+	AllowedPolicies(u64),
+	Difficulty(DifficultyEra),
+}
+
+/// Forks of a chain type, sorted by height, the first one at the genesis.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ForkSchedule {
+	/// Format version, `FORK_SCHEDULE_VERSION`
+	pub version: u32,
+	/// The forks
+	pub forks: Vec<Fork>,
+}
+
+lazy_static! {
+	static ref MAINNET_SCHEDULE: ForkSchedule = ForkSchedule::build(&ChainTypes::Mainnet);
+	static ref FLOONET_SCHEDULE: ForkSchedule = ForkSchedule::build(&ChainTypes::Floonet);
+	static ref TESTING_SCHEDULE: ForkSchedule = ForkSchedule::build(&ChainTypes::AutomatedTesting);
+}
+
+impl ForkSchedule {
+	/// Schedule of the given chain type.
+	pub fn get(chain_type: &ChainTypes) -> &'static ForkSchedule {
+		match chain_type {
+			ChainTypes::Mainnet => &MAINNET_SCHEDULE,
+			ChainTypes::Floonet => &FLOONET_SCHEDULE,
+			ChainTypes::AutomatedTesting | ChainTypes::UserTesting => &TESTING_SCHEDULE,
+		}
+	}
+
+	fn build(chain_type: &ChainTypes) -> ForkSchedule {
+		let (first_hard_fork, difficulty_era) = match chain_type {
+			ChainTypes::Mainnet => (
+				consensus::MAINNET_FIRST_HARD_FORK,
+				consensus::MAINNET_DIFFICULTY_ERA,
+			),
+			ChainTypes::Floonet => (
+				consensus::FLOONET_FIRST_HARD_FORK,
+				consensus::FLOONET_DIFFICULTY_ERA,
+			),
+			ChainTypes::AutomatedTesting | ChainTypes::UserTesting => (
+				consensus::TESTING_FIRST_HARD_FORK,
+				consensus::TESTING_DIFFICULTY_ERA,
+			),
+		};
+		// the policy eras are the same on every chain type
+		let mut changes = vec![
+			(
+				"policy era 2",
+				consensus::BLOCK_ERA_1 + 1,
+				Rule::AllowedPolicies(1 << 1),
+			),
+			(
+				"policy era 3",
+				consensus::BLOCK_ERA_2 + 1,
+				Rule::AllowedPolicies(1 << 2),
+			),
+			(
+				"policy era 4",
+				consensus::BLOCK_ERA_3 + 1,
+				Rule::AllowedPolicies(1 << 3),
+			),
+			(
+				"policy era 5",
+				consensus::BLOCK_ERA_4 + 1,
+				Rule::AllowedPolicies(1 << 4),
+			),
+			(
+				"policy era 6",
+				consensus::BLOCK_ERA_5 + 1,
+				Rule::AllowedPolicies(1 << 5),
+			),
+			(
+				"difficulty era 1",
+				difficulty_era,
+				Rule::Difficulty(DifficultyEra::Era1),
+			),
+			("header version 7", first_hard_fork, Rule::HeaderVersion(7)),
+		];
+		changes.sort_by_key(|(_, height, _)| *height);
+
+		let mut forks = vec![Fork {
+			name: "genesis".to_string(),
+			height: 0,
+			header_version: 6,
+			allowed_policies: 1 << 0,
+			difficulty_era: DifficultyEra::Initial,
+		}];
+		for (name, height, rule) in changes {
+			let last = forks.last_mut().expect("genesis fork");
+			if last.height == height {
+				last.name = format!("{} + {}", last.name, name);
+			} else {
+				let fork = Fork {
+					name: name.to_string(),
+					height,
+					..last.clone()
+				};
+				forks.push(fork);
+			}
+			let fork = forks.last_mut().expect("genesis fork");
+			match rule {
+				Rule::HeaderVersion(version) => fork.header_version = version,
+				Rule::AllowedPolicies(allowed) => fork.allowed_policies = allowed,
+				Rule::Difficulty(era) => fork.difficulty_era = era,
+			}
+		}
+		ForkSchedule {
+			version: FORK_SCHEDULE_VERSION,
+			forks,
+		}
+	}
+
+	/// Rules in force at the given height.
+	pub fn fork_at(&self, height: u64) -> &Fork {
+		self.forks
+			.iter()
+			.rev()
+			.find(|fork| fork.height <= height)
+			.unwrap_or(&self.forks[0])
+	}
+
+	/// First fork after the given height, if any is scheduled.
+	pub fn next_fork(&self, height: u64) -> Option<&Fork> {
+		self.forks.iter().find(|fork| fork.height > height)
+	}
+
+	/// Height of the first fork matching the predicate, `u64::MAX` (never)
+	/// when none does.
+	pub fn first_height<P>(&self, predicate: P) -> u64
+	where
+		P: Fn(&Fork) -> bool,
+	{
+		self.forks
+			.iter()
+			.find(|fork| predicate(fork))
+			.map_or(u64::MAX, |fork| fork.height)
+	}
+
+	/// Allowed policies of the schedule in the `PolicyConfig` format, one
+	/// entry each time the allowed policies change.
+	pub fn allowed_policies(&self) -> AllowedPolicy {
+		let mut allowed: AllowedPolicy = vec![];
+		for fork in &self.forks {
+			if allowed.last().map(|a| a.value) != Some(fork.allowed_policies) {
+				allowed.push(AllowPolicy {
+					height: fork.height,
+					value: fork.allowed_policies,
+				});
+			}
+		}
+		allowed
+	}
+
+	/// Checks the schedule of the given chain type is consistent and the
+	/// policy config provides, and allows, the policies it schedules. The
+	/// test chains may allow other policies as well, as long as the ones
+	/// blocks are mined with stay allowed.
+	pub fn validate(
+		&self,
+		chain_type: &ChainTypes,
+		policy_config: &PolicyConfig,
+	) -> Result<(), Error> {
+		let first = self.forks.first().ok_or(Error::NoGenesisFork)?;
+		if first.height != 0 {
+			return Err(Error::NoGenesisFork);
+		}
+		for pair in self.forks.windows(2) {
+			let (prev, fork) = (&pair[0], &pair[1]);
+			if fork.height <= prev.height {
+				return Err(Error::Unordered(fork.height));
+			}
+			if fork.header_version < prev.header_version {
+				return Err(Error::HeaderVersionDecrease(fork.height));
+			}
+			if fork.difficulty_era < prev.difficulty_era {
+				return Err(Error::DifficultyEraDecrease(fork.height));
+			}
+		}
+		for fork in &self.forks {
+			if fork.allowed_policies == 0 {
+				return Err(Error::NoAllowedPolicy(fork.height));
+			}
+			for policy in (0..64).filter(|p| fork.allows_policy(*p)) {
+				let missing = Error::MissingPolicy {
+					height: fork.height,
+					policy,
+				};
+				let weights = policy_config.policies.get(policy as usize).ok_or(missing)?;
+				let total: u32 = weights.values().sum();
+				if total != 100 {
+					return Err(Error::PolicyShares { policy, total });
+				}
+			}
+		}
+		match chain_type {
+			ChainTypes::Mainnet | ChainTypes::Floonet => {
+				if policy_config.allowed_policies != self.allowed_policies() {
+					return Err(Error::AllowedPoliciesMismatch);
+				}
+			}
+			ChainTypes::AutomatedTesting | ChainTypes::UserTesting => {
+				for fork in &self.forks {
+					let policy = fork.emitted_policy();
+					let allowed = policy_config.allowed_policies.clone();
+					if !is_allowed_policy(allowed, fork.height, policy) {
+						return Err(Error::EmittedPolicyNotAllowed {
+							height: fork.height,
+							policy,
+						});
+					}
+				}
+			}
+		}
+		Ok(())
+	}
+}
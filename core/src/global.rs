@@ -16,7 +16,6 @@
 //! having to pass them all over the place, but aren't consensus values.
 //! should be used sparingly.
 
-use crate::consensus;
 use crate::consensus::HeaderInfo;
 use crate::consensus::{
 	graph_weight, BASE_EDGE_BITS, BLOCK_TIME_SEC, COINBASE_MATURITY, CUT_THROUGH_HORIZON,
@@ -24,6 +23,9 @@ use crate::consensus::{
 	MAX_BLOCK_WEIGHT, PROOFSIZE, SECOND_POW_EDGE_BITS, STATE_SYNC_THRESHOLD,
 };
 use crate::core::block::feijoada::{AllowPolicy, Policy, PolicyConfig};
+use crate::core::hash::Hash;
+use crate::forks::ForkSchedule;
+use crate::network::NetworkDefinition;
 use crate::pow::{self, /*new_cuckaroo_ctx,*/ new_cuckatoo_ctx, EdgeType, PoWContext};
 /// An enum collecting sets of parameters used throughout the
 /// code wherever mining is needed. This should allow for
@@ -31,10 +33,12 @@ use crate::pow::{self, /*new_cuckaroo_ctx,*/ new_cuckatoo_ctx, EdgeType, PoWCont
 /// e.g. CI, User testing, production values
 use crate::util::RwLock;
 use sha2::{Digest, Sha256};
+use std::cell::RefCell;
 //use std::collections::HashMap;
 use std::env;
 use std::fs::File;
 use std::path::Path;
+use std::sync::Arc;
 /// Define these here, as they should be developer-set, not really tweakable
 /// by users
 
@@ -106,6 +110,10 @@ pub const TXHASHSET_ARCHIVE_INTERVAL: u64 = 12 * 60;
 
 pub const CURRENT_HEADER_VERSION: u16 = 6;
 
+/// Seconds without new headers after which header sync is considered done,
+/// unless configured otherwise.
+pub const DEFAULT_HEADER_SYNC_TIMEOUT: i64 = 10;
+
 pub const MAINNET_FOUNDATION_JSON_SHA256: &str =
 	"5a3a7584127dd31fba18eaeff1c551bfaa74b4e50e537a1e1904fe6730b17f5c";
 
@@ -178,29 +186,225 @@ lazy_static! {
 
 	/// Store the timeout for the header sync
 	pub static ref HEADER_SYNC_TIMEOUT : RwLock<i64> =
-			RwLock::new(10);
+			RwLock::new(DEFAULT_HEADER_SYNC_TIMEOUT);
+}
+
+thread_local! {
+	/// Chain parameters entered on this thread, taking precedence over the
+	/// process wide settings above.
+	static LOCAL_PARAMS: RefCell<Option<Arc<ChainParams>>> = RefCell::new(None);
+}
+
+/// Mainnet checkpoints, (height, block hash).
+const MAINNET_CHECKPOINTS: [(u64, &str); 12] = [
+	(
+		100000,
+		"e835eb9ebc9f2e13b11061691cb268f44b20001f081003169b634497eb730848",
+	),
+	(
+		200000,
+		"b2365a8c9719a709f11d450bbddfd012011e21c862239bdc8590aba00815e84c",
+	),
+	(
+		400000,
+		"6578f1cdf5504d29fc757424e75ac60494e0f6d24b7553d124c8bea6ef99b5d8",
+	),
+	(
+		600000,
+		"de483eafb2141d66bf541a94d8e41858f01ffc517b9fa61d8781483c34c2a6f7",
+	),
+	(
+		800000,
+		"1465e7c094376e781b1e80ebd6b7a0c6350ec4d6554f9acdd843802162831003",
+	),
+	(
+		1000000,
+		"00e4a404130ac192face23fd25f2c46a99a38a31d8cf2d3cc79ea7a518830686",
+	),
+	(
+		1200000,
+		"8d69282df5579d32346ad0f6d3f4e03a43b1e00e741b1f3ba71c2934d81e5e1a",
+	),
+	(
+		1400000,
+		"e7e34e50e8a5c9bcf3fe7b7ad99e62a848cda37171ce8d37f21bc334035df4d2",
+	),
+	(
+		1600000,
+		"ba44beaf37776c3e7da3f4a1b906ae238e1178794cbaa90685e3945d2662d7a2",
+	),
+	(
+		1800000,
+		"4f23aaf2e83e4041cac670226d3024f4468e3b9bb6ffa2548ebc59489bd09b63",
+	),
+	(
+		2000000,
+		"eaf5d7a4b6f07ccb8bdbe5db2f39e10eea3ee1c28f8333907d91c9ccc21ce99d",
+	),
+	(
+		2200000,
+		"1243520890d08026daba8207ed3d67186da64d2b71b5c1e2dd26d34092dee6ba",
+	),
+];
+
+/// A block the chain must go through.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+	/// Height of the block
+	pub height: u64,
+	/// Hash of the block
+	pub block_hash: Hash,
+}
+
+/// Everything a chain depends on that differs between chains: several
+/// chains (a mainnet and a floonet one, or test chains) can run in the same
+/// process, each with its own parameters.
+///
+/// The chain, the pool and the p2p server enter their parameters on the
+/// threads working for them, the free functions of this module (and the
+/// consensus ones built on them) then follow the parameters entered on the
+/// current thread, falling back to the process wide settings otherwise.
+#[derive(Debug, Clone)]
+pub struct ChainParams {
+	/// Chain type, picking the genesis, the reward schedule and the forks
+	pub chain_type: ChainTypes,
+	/// Proportions of the PoW algorithms, the process wide ones when None
+	pub policy_config: Option<PolicyConfig>,
+	/// Foundation file, loading the foundation coinbases fails without one
+	pub foundation_path: Option<String>,
+	/// Coinbase maturity for coinbases to be spent
+	pub coinbase_maturity: u64,
+	/// Maximum allowed block weight
+	pub max_block_weight: usize,
+	/// Horizon at which we can cut-through and do full local pruning
+	pub cut_through_horizon: u32,
+	/// Threshold at which we can request a txhashset
+	pub state_sync_threshold: u32,
+	/// Blocks the chain must go through
+	pub checkpoints: Vec<Checkpoint>,
+	/// Seconds without new headers after which header sync is considered done
+	pub header_sync_timeout: i64,
+	/// Version of the epic executable running the chain, if known
+	pub epic_version: Option<Version>,
+	/// Custom network run with the usertesting parameters, if any
+	pub network: Option<Arc<NetworkDefinition>>,
+}
+
+impl ChainParams {
+	/// Default parameters of the provided chain type.
+	pub fn new(chain_type: ChainTypes) -> ChainParams {
+		let checkpoints = match chain_type {
+			ChainTypes::Mainnet => MAINNET_CHECKPOINTS
+				.iter()
+				.map(|(height, hash)| Checkpoint {
+					height: *height,
+					block_hash: Hash::from_hex(hash).unwrap(),
+				})
+				.collect(),
+			_ => vec![],
+		};
+		ChainParams {
+			policy_config: None,
+			foundation_path: None,
+			coinbase_maturity: default_coinbase_maturity(&chain_type),
+			max_block_weight: default_max_block_weight(&chain_type),
+			cut_through_horizon: default_cut_through_horizon(&chain_type),
+			state_sync_threshold: default_state_sync_threshold(&chain_type),
+			checkpoints,
+			header_sync_timeout: DEFAULT_HEADER_SYNC_TIMEOUT,
+			epic_version: None,
+			network: None,
+			chain_type,
+		}
+	}
+
+	/// Parameters in effect on the current thread, the entered ones or else
+	/// the process wide settings.
+	pub fn current() -> ChainParams {
+		match ChainParams::entered() {
+			Some(params) => (*params).clone(),
+			None => ChainParams {
+				policy_config: Some(POLICY_CONFIG.read().clone()),
+				foundation_path: FOUNDATION_FILE.read().clone(),
+				header_sync_timeout: *HEADER_SYNC_TIMEOUT.read(),
+				epic_version: EPIC_VERSION.read().clone(),
+				..ChainParams::new(CHAIN_TYPE.read().clone())
+			},
+		}
+	}
+
+	/// Parameters entered on the current thread, if any.
+	pub fn entered() -> Option<Arc<ChainParams>> {
+		LOCAL_PARAMS.with(|p| p.borrow().clone())
+	}
+
+	/// Makes these parameters the ones in effect on the current thread
+	/// until the returned guard is dropped.
+	pub fn enter(self: &Arc<Self>) -> ParamsGuard {
+		let prev = LOCAL_PARAMS.with(|p| p.borrow_mut().replace(self.clone()));
+		ParamsGuard { prev }
+	}
 }
 
-pub fn foundation_json_sha256() -> &'static str {
-	let param_ref = CHAIN_TYPE.read();
-	match *param_ref {
-		ChainTypes::Mainnet => MAINNET_FOUNDATION_JSON_SHA256,
-		_ => FLOONET_FOUNDATION_JSON_SHA256,
+/// Restores the parameters previously in effect on the thread when dropped.
+pub struct ParamsGuard {
+	prev: Option<Arc<ChainParams>>,
+}
+
+impl Drop for ParamsGuard {
+	fn drop(&mut self) {
+		let prev = self.prev.take();
+		LOCAL_PARAMS.with(|p| *p.borrow_mut() = prev);
+	}
+}
+
+fn local_params<T>(f: impl FnOnce(&ChainParams) -> T) -> Option<T> {
+	LOCAL_PARAMS.with(|p| p.borrow().as_deref().map(f))
+}
+
+/// The chain type in effect on the current thread.
+pub fn get_chain_type() -> ChainTypes {
+	local_params(|p| p.chain_type.clone()).unwrap_or_else(|| CHAIN_TYPE.read().clone())
+}
+
+pub fn foundation_json_sha256() -> String {
+	match get_chain_type() {
+		ChainTypes::Mainnet => MAINNET_FOUNDATION_JSON_SHA256.to_string(),
+		ChainTypes::UserTesting => get_network().map_or_else(
+			|| FLOONET_FOUNDATION_JSON_SHA256.to_string(),
+			|n| n.foundation_sha256.clone(),
+		),
+		_ => FLOONET_FOUNDATION_JSON_SHA256.to_string(),
 	}
 }
 
+/// The custom network of the chain parameters entered on the current thread,
+/// if any.
+pub fn get_network() -> Option<Arc<NetworkDefinition>> {
+	local_params(|p| p.network.clone()).flatten()
+}
+
 /// Get the current Timeout without the verification of the existence of more headers to be synced,
 /// after all header were processed
 pub fn get_header_sync_timeout() -> i64 {
-	let header_sync_timeout = HEADER_SYNC_TIMEOUT.read();
-	header_sync_timeout.clone()
+	local_params(|p| p.header_sync_timeout).unwrap_or_else(|| *HEADER_SYNC_TIMEOUT.read())
 }
 
 /// Set the current Timeout without the verification of the existence of more headers to be synced,
 /// after all header were processed
 pub fn set_header_sync_timeout(timeout: i64) {
 	let mut header_sync_timeout = HEADER_SYNC_TIMEOUT.write();
-	*header_sync_timeout = if timeout <= 0 { 10 } else { timeout }
+	*header_sync_timeout = valid_header_sync_timeout(timeout);
+}
+
+/// The header sync timeout to use for the configured one, the default for
+/// a non positive one.
+pub fn valid_header_sync_timeout(timeout: i64) -> i64 {
+	if timeout <= 0 {
+		DEFAULT_HEADER_SYNC_TIMEOUT
+	} else {
+		timeout
+	}
 }
 
 /// Set the version of the current epic executable
@@ -213,8 +417,9 @@ pub fn set_epic_version(version_major: String, version_minor: String) {
 
 /// Get the version of the current epic executable
 pub fn get_epic_version() -> Option<Version> {
-	let epic_version = EPIC_VERSION.read();
-	epic_version.clone()
+	local_params(|p| p.epic_version.clone())
+		.flatten()
+		.or_else(|| EPIC_VERSION.read().clone())
 }
 
 /// Set the path to the foundation.json file (file with the foundation wallet outputs/kernels)
@@ -224,6 +429,12 @@ pub fn set_foundation_path(path: String) {
 	*foundation_path = Some(path_str);
 }
 
+/// Unset the path to the foundation.json file, the foundation coinbases then being loaded from
+/// the data embedded for the chain type
+pub fn clear_foundation_path() {
+	*FOUNDATION_FILE.write() = None;
+}
+
 ///	Check if the foundation.json exists in the directory appointed by the .toml file, if not,
 /// use the alternative path ../../debian/foundation.json relative to the folder where the executable is in.
 /// If we are running floonet, it will look for the file foundation_floonet.json .
@@ -251,7 +462,7 @@ pub fn use_alternative_path(path_str: String) -> String {
 		p.pop();
 	}
 	p.push("debian");
-	let foundation_name = match CHAIN_TYPE.read().clone() {
+	let foundation_name = match get_chain_type() {
 		ChainTypes::Mainnet => "foundation.json",
 		_ => "foundation_floonet.json",
 	};
@@ -268,10 +479,10 @@ pub fn use_alternative_path(path_str: String) -> String {
 	return p.to_str().expect("Failed to get the executable's directory and no path to the foundation.json was provided!").to_owned();
 }
 
-/// Get the current path to the foundation.json file (file with the foundation wallet outputs/kernels)
+/// Get the current path to the foundation.json file (file with the foundation wallet outputs/kernels),
+/// chain parameters entered on the thread never using the process wide one.
 pub fn get_foundation_path() -> Option<String> {
-	let foundation_path = FOUNDATION_FILE.read();
-	foundation_path.clone()
+	local_params(|p| p.foundation_path.clone()).unwrap_or_else(|| FOUNDATION_FILE.read().clone())
 }
 
 /// Check if the foundation file is correct
@@ -299,41 +510,29 @@ pub fn add_allowed_policy(height: u64, value: u64) {
 }
 
 pub fn get_allowed_policies() -> Vec<AllowPolicy> {
-	let policy_config = POLICY_CONFIG.read();
-	policy_config.allowed_policies.clone()
+	get_policy_config().allowed_policies
 }
 
+/// Policy new blocks at the given height are emitted with, following the
+/// fork schedule.
 pub fn get_emitted_policy(height: u64) -> u8 {
-	let _policy_config = POLICY_CONFIG.read();
-	if height <= consensus::BLOCK_ERA_1 {
-		0
-	} else if height <= consensus::BLOCK_ERA_2 {
-		1
-	} else if height <= consensus::BLOCK_ERA_3 {
-		2
-	} else if height <= consensus::BLOCK_ERA_4 {
-		3
-	} else if height <= consensus::BLOCK_ERA_5 {
-		4
-	} else {
-		5
-	}
+	fork_schedule().fork_at(height).emitted_policy()
 }
 
-pub fn get_policies(index: u8) -> Option<Policy> {
-	let policy_config = POLICY_CONFIG.read();
+/// Fork schedule of the chain type in use.
+pub fn fork_schedule() -> &'static ForkSchedule {
+	ForkSchedule::get(&get_chain_type())
+}
 
-	if (index as usize) < (*policy_config).policies.len() {
-		Some(policy_config.policies[index as usize].clone())
-	} else {
-		None
-	}
+pub fn get_policies(index: u8) -> Option<Policy> {
+	get_policy_config().policies.get(index as usize).cloned()
 }
 
 /// Get the policy configuration that is being used by the blockchain
 pub fn get_policy_config() -> PolicyConfig {
-	let policy_config = POLICY_CONFIG.read();
-	policy_config.clone()
+	local_params(|p| p.policy_config.clone())
+		.flatten()
+		.unwrap_or_else(|| POLICY_CONFIG.read().clone())
 }
 
 /// Set the mining mode
@@ -353,8 +552,7 @@ pub fn create_pow_context<T>(
 where
 	T: EdgeType + 'static,
 {
-	let chain_type = CHAIN_TYPE.read().clone();
-	match chain_type {
+	match get_chain_type() {
 		// Mainnet has Cuckaroo29 for AR and Cuckatoo30+ for AF
 		ChainTypes::Mainnet => new_cuckatoo_ctx(edge_bits, proof_size, max_sols),
 		//ChainTypes::Mainnet => new_cuckaroo_ctx(edge_bits, proof_size),
@@ -370,8 +568,7 @@ where
 
 /// The minimum acceptable edge_bits
 pub fn min_edge_bits() -> u8 {
-	let param_ref = CHAIN_TYPE.read();
-	match *param_ref {
+	match get_chain_type() {
 		ChainTypes::AutomatedTesting => AUTOMATED_TESTING_MIN_EDGE_BITS,
 		ChainTypes::UserTesting => USER_TESTING_MIN_EDGE_BITS,
 		_ => DEFAULT_MIN_EDGE_BITS,
@@ -382,8 +579,7 @@ pub fn min_edge_bits() -> u8 {
 /// while the min_edge_bits can be changed on a soft fork, changing
 /// base_edge_bits is a hard fork.
 pub fn base_edge_bits() -> u8 {
-	let param_ref = CHAIN_TYPE.read();
-	match *param_ref {
+	match get_chain_type() {
 		ChainTypes::AutomatedTesting => AUTOMATED_TESTING_MIN_EDGE_BITS,
 		ChainTypes::UserTesting => USER_TESTING_MIN_EDGE_BITS,
 		_ => BASE_EDGE_BITS,
@@ -392,8 +588,7 @@ pub fn base_edge_bits() -> u8 {
 
 /// The proofsize
 pub fn proofsize() -> usize {
-	let param_ref = CHAIN_TYPE.read();
-	match *param_ref {
+	match get_chain_type() {
 		ChainTypes::AutomatedTesting => AUTOMATED_TESTING_PROOF_SIZE,
 		ChainTypes::UserTesting => USER_TESTING_PROOF_SIZE,
 		_ => PROOFSIZE,
@@ -402,8 +597,12 @@ pub fn proofsize() -> usize {
 
 /// Coinbase maturity for coinbases to be spent
 pub fn coinbase_maturity() -> u64 {
-	let param_ref = CHAIN_TYPE.read();
-	match *param_ref {
+	local_params(|p| p.coinbase_maturity)
+		.unwrap_or_else(|| default_coinbase_maturity(&CHAIN_TYPE.read()))
+}
+
+fn default_coinbase_maturity(chain_type: &ChainTypes) -> u64 {
+	match chain_type {
 		ChainTypes::AutomatedTesting => AUTOMATED_TESTING_COINBASE_MATURITY,
 		ChainTypes::UserTesting => USER_TESTING_COINBASE_MATURITY,
 		ChainTypes::Floonet => FLOONET_COINBASE_MATURITY,
@@ -411,20 +610,20 @@ pub fn coinbase_maturity() -> u64 {
 	}
 }
 
-/// Initial mining difficulty
+/// Initial mining difficulty, the one of the custom network if any
 pub fn initial_block_difficulty() -> u64 {
-	let param_ref = CHAIN_TYPE.read();
-	match *param_ref {
+	match get_chain_type() {
 		ChainTypes::AutomatedTesting => TESTING_INITIAL_DIFFICULTY,
-		ChainTypes::UserTesting => TESTING_INITIAL_DIFFICULTY,
+		ChainTypes::UserTesting => {
+			get_network().map_or(TESTING_INITIAL_DIFFICULTY, |n| n.initial_difficulty)
+		}
 		ChainTypes::Floonet => INITIAL_DIFFICULTY,
 		ChainTypes::Mainnet => INITIAL_DIFFICULTY,
 	}
 }
 /// Initial mining secondary scale
 pub fn initial_graph_weight() -> u32 {
-	let param_ref = CHAIN_TYPE.read();
-	match *param_ref {
+	match get_chain_type() {
 		ChainTypes::AutomatedTesting => TESTING_INITIAL_GRAPH_WEIGHT,
 		ChainTypes::UserTesting => TESTING_INITIAL_GRAPH_WEIGHT,
 		ChainTypes::Floonet => graph_weight(0, SECOND_POW_EDGE_BITS) as u32,
@@ -434,8 +633,12 @@ pub fn initial_graph_weight() -> u32 {
 
 /// Maximum allowed block weight.
 pub fn max_block_weight() -> usize {
-	let param_ref = CHAIN_TYPE.read();
-	match *param_ref {
+	local_params(|p| p.max_block_weight)
+		.unwrap_or_else(|| default_max_block_weight(&CHAIN_TYPE.read()))
+}
+
+fn default_max_block_weight(chain_type: &ChainTypes) -> usize {
+	match chain_type {
 		ChainTypes::AutomatedTesting => TESTING_MAX_BLOCK_WEIGHT,
 		ChainTypes::UserTesting => TESTING_MAX_BLOCK_WEIGHT,
 		ChainTypes::Floonet => MAX_BLOCK_WEIGHT,
@@ -445,8 +648,12 @@ pub fn max_block_weight() -> usize {
 
 /// Horizon at which we can cut-through and do full local pruning
 pub fn cut_through_horizon() -> u32 {
-	let param_ref = CHAIN_TYPE.read();
-	match *param_ref {
+	local_params(|p| p.cut_through_horizon)
+		.unwrap_or_else(|| default_cut_through_horizon(&CHAIN_TYPE.read()))
+}
+
+fn default_cut_through_horizon(chain_type: &ChainTypes) -> u32 {
+	match chain_type {
 		ChainTypes::AutomatedTesting => TESTING_CUT_THROUGH_HORIZON,
 		ChainTypes::UserTesting => TESTING_CUT_THROUGH_HORIZON,
 		_ => CUT_THROUGH_HORIZON,
@@ -455,8 +662,12 @@ pub fn cut_through_horizon() -> u32 {
 
 /// Threshold at which we can request a txhashset (and full blocks from)
 pub fn state_sync_threshold() -> u32 {
-	let param_ref = CHAIN_TYPE.read();
-	match *param_ref {
+	local_params(|p| p.state_sync_threshold)
+		.unwrap_or_else(|| default_state_sync_threshold(&CHAIN_TYPE.read()))
+}
+
+fn default_state_sync_threshold(chain_type: &ChainTypes) -> u32 {
+	match chain_type {
 		ChainTypes::AutomatedTesting => TESTING_STATE_SYNC_THRESHOLD,
 		ChainTypes::UserTesting => TESTING_STATE_SYNC_THRESHOLD,
 		_ => STATE_SYNC_THRESHOLD,
@@ -465,20 +676,17 @@ pub fn state_sync_threshold() -> u32 {
 
 /// Are we in automated testing mode?
 pub fn is_automated_testing_mode() -> bool {
-	let param_ref = CHAIN_TYPE.read();
-	ChainTypes::AutomatedTesting == *param_ref
+	ChainTypes::AutomatedTesting == get_chain_type()
 }
 
 /// Are we in user testing mode?
 pub fn is_user_testing_mode() -> bool {
-	let param_ref = CHAIN_TYPE.read();
-	ChainTypes::UserTesting == *param_ref
+	ChainTypes::UserTesting == get_chain_type()
 }
 
 /// Number of blocks to reuse a txhashset zip for.
 pub fn txhashset_archive_interval() -> u64 {
-	let param_ref = CHAIN_TYPE.read();
-	match *param_ref {
+	match get_chain_type() {
 		ChainTypes::AutomatedTesting => TESTING_TXHASHSET_ARCHIVE_INTERVAL,
 		ChainTypes::UserTesting => TESTING_TXHASHSET_ARCHIVE_INTERVAL,
 		_ => TXHASHSET_ARCHIVE_INTERVAL,
@@ -488,8 +696,8 @@ pub fn txhashset_archive_interval() -> u64 {
 /// Are we in production mode?
 /// Production defined as a live public network, testnet[n] or mainnet.
 pub fn is_production_mode() -> bool {
-	let param_ref = CHAIN_TYPE.read();
-	ChainTypes::Floonet == *param_ref || ChainTypes::Mainnet == *param_ref
+	let chain_type = get_chain_type();
+	ChainTypes::Floonet == chain_type || ChainTypes::Mainnet == chain_type
 }
 
 /// Are we in floonet?
@@ -497,14 +705,12 @@ pub fn is_production_mode() -> bool {
 /// as possible to "mainnet" configuration as possible.
 /// We want to avoid missing any mainnet only code paths.
 pub fn is_floonet() -> bool {
-	let param_ref = CHAIN_TYPE.read();
-	ChainTypes::Floonet == *param_ref
+	ChainTypes::Floonet == get_chain_type()
 }
 
 /// Are we for real?
 pub fn is_mainnet() -> bool {
-	let param_ref = CHAIN_TYPE.read();
-	ChainTypes::Mainnet == *param_ref
+	ChainTypes::Mainnet == get_chain_type()
 }
 
 /// Helper function to get a nonce known to create a valid POW on
@@ -512,8 +718,7 @@ pub fn is_mainnet() -> bool {
 /// as the genesis block POW solution turns out to be the same for every new
 /// block chain at the moment
 pub fn get_genesis_nonce() -> u64 {
-	let param_ref = CHAIN_TYPE.read();
-	match *param_ref {
+	match get_chain_type() {
 		// won't make a difference
 		ChainTypes::AutomatedTesting => 0,
 		// Magic nonce for current genesis block at cuckatoo15
@@ -527,8 +732,7 @@ pub fn get_genesis_nonce() -> u64 {
 
 /// Short name representing the current chain type ("floo", "main", etc.)
 pub fn chain_shortname() -> String {
-	let param_ref = CHAIN_TYPE.read();
-	param_ref.shortname()
+	get_chain_type().shortname()
 }
 
 /// Converts an iterator of block difficulty data to more a more manageable
@@ -40,8 +40,10 @@ pub mod macros;
 
 pub mod consensus;
 pub mod core;
+pub mod forks;
 pub mod genesis;
 pub mod global;
 pub mod libtx;
+pub mod network;
 pub mod pow;
 pub mod ser;
@@ -0,0 +1,232 @@
+// Copyright 2020 The Epic Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Definitions of custom networks. A definition holds everything setting a
+//! long lived test network apart from the other usertesting chains: its
+//! genesis block, p2p magic bytes, ports, policies, foundation file and
+//! initial difficulty. `epic network new` writes it, usertesting nodes load
+//! it through their `network_file` setting.
+
+use crate::core::block::feijoada::PolicyConfig;
+use crate::core::hash::Hashed;
+use crate::core::Block;
+use crate::global::{self, ChainParams, ChainTypes};
+use crate::pow::{self, Difficulty, PoWType};
+use crate::ser::{self, ProtocolVersion};
+use crate::util::{from_hex, to_hex};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Version of the network definition format.
+pub const NETWORK_DEFINITION_VERSION: u32 = 1;
+
+/// Magic bytes of the mainnet p2p messages
+pub const MAINNET_MAGIC: [u8; 2] = [97, 61];
+/// Magic bytes of the floonet p2p messages
+pub const FLOONET_MAGIC: [u8; 2] = [83, 59];
+/// Magic bytes of the p2p messages of the test chains without a definition
+pub const OTHER_MAGIC: [u8; 2] = [73, 43];
+
+/// Network definition errors.
+#[derive(Debug, Clone, Eq, PartialEq, Error)]
+pub enum Error {
+	/// The definition file could not be read or written
+	#[error("Network definition IO error: {0}")]
+	Io(String),
+	/// The definition file is not a valid definition
+	#[error("Invalid network definition: {0}")]
+	Format(String),
+	/// The definition was written in an unknown format version
+	#[error("Unsupported network definition version {0}")]
+	Version(u32),
+	/// Peers of another network would talk to the nodes of this one
+	#[error("Magic bytes {0:?} are used by another network")]
+	ReservedMagic([u8; 2]),
+	/// The genesis block can't be read or its proof of work is invalid
+	#[error("Invalid genesis: {0}")]
+	Genesis(String),
+}
+
+/// A custom network, run with the usertesting chain parameters.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NetworkDefinition {
+	/// Format version, `NETWORK_DEFINITION_VERSION`
+	pub version: u32,
+	/// Name of the network
+	pub name: String,
+	/// Magic bytes starting every p2p message
+	pub magic: [u8; 2],
+	/// Port the nodes listen to peers on
+	pub p2p_port: u16,
+	/// Port of the node API
+	pub api_port: u16,
+	/// Difficulty of the genesis block, the blocks after it starting from it
+	#[serde(default = "default_initial_difficulty")]
+	pub initial_difficulty: u64,
+	/// Proportions of the PoW algorithms
+	pub policy_config: PolicyConfig,
+	/// Sha256 of the foundation file, as hex
+	pub foundation_sha256: String,
+	/// Hash of the genesis block
+	pub genesis_hash: String,
+	/// Genesis block, serialized as hex
+	pub genesis: String,
+}
+
+impl NetworkDefinition {
+	/// Definition of a network around a mined genesis block, with random
+	/// magic bytes and the usertesting defaults for everything else.
+	pub fn new(name: String, genesis: &Block) -> Result<NetworkDefinition, Error> {
+		let genesis_bytes = ser::ser_vec(genesis, ProtocolVersion::local())
+			.map_err(|e| Error::Genesis(e.to_string()))?;
+		let magic = loop {
+			let magic = rand::random::<[u8; 2]>();
+			if !is_reserved_magic(magic) {
+				break magic;
+			}
+		};
+		Ok(NetworkDefinition {
+			version: NETWORK_DEFINITION_VERSION,
+			name,
+			magic,
+			p2p_port: 23414,
+			api_port: 23413,
+			initial_difficulty: genesis.header.total_difficulty().to_num(PoWType::Cuckatoo),
+			policy_config: PolicyConfig::default(),
+			foundation_sha256: global::FLOONET_FOUNDATION_JSON_SHA256.to_string(),
+			genesis_hash: genesis.hash().to_hex(),
+			genesis: to_hex(genesis_bytes),
+		})
+	}
+
+	/// Reads and validates the definition stored at the given path.
+	pub fn load(path: &Path) -> Result<NetworkDefinition, Error> {
+		let data = fs::read_to_string(path).map_err(|e| Error::Io(e.to_string()))?;
+		let network: NetworkDefinition =
+			serde_json::from_str(&data).map_err(|e| Error::Format(e.to_string()))?;
+		network.validate()?;
+		Ok(network)
+	}
+
+	/// Writes the definition to the given path.
+	pub fn save(&self, path: &Path) -> Result<(), Error> {
+		let data = serde_json::to_string_pretty(self).map_err(|e| Error::Format(e.to_string()))?;
+		fs::write(path, data).map_err(|e| Error::Io(e.to_string()))
+	}
+
+	/// The genesis block of the network.
+	pub fn genesis(&self) -> Result<Block, Error> {
+		// proofs are read with the proof size of the usertesting chains
+		let params = Arc::new(ChainParams::new(ChainTypes::UserTesting));
+		let _params = params.enter();
+		let bytes = from_hex(self.genesis.clone()).map_err(|e| Error::Genesis(e.to_string()))?;
+		ser::deserialize(&mut &bytes[..], ProtocolVersion::local())
+			.map_err(|e| Error::Genesis(e.to_string()))
+	}
+
+	/// Checks the definition can be used: known version, magic bytes of its
+	/// own and a genesis block matching its hash with a valid proof of work.
+	pub fn validate(&self) -> Result<(), Error> {
+		if self.version != NETWORK_DEFINITION_VERSION {
+			return Err(Error::Version(self.version));
+		}
+		if is_reserved_magic(self.magic) {
+			return Err(Error::ReservedMagic(self.magic));
+		}
+		let genesis = self.genesis()?;
+		if genesis.header.height != 0 {
+			return Err(Error::Genesis(format!(
+				"height {} instead of 0",
+				genesis.header.height
+			)));
+		}
+		if genesis.header.total_difficulty() != Difficulty::from_num(self.initial_difficulty) {
+			return Err(Error::Genesis(format!(
+				"difficulty {} instead of {}",
+				genesis.header.total_difficulty(),
+				self.initial_difficulty
+			)));
+		}
+		if genesis.hash().to_hex() != self.genesis_hash {
+			return Err(Error::Genesis(format!(
+				"hash {} instead of {}",
+				genesis.hash(),
+				self.genesis_hash
+			)));
+		}
+		let params = Arc::new(ChainParams::new(ChainTypes::UserTesting));
+		let _params = params.enter();
+		pow::verify_size(&genesis.header).map_err(|e| Error::Genesis(e.to_string()))
+	}
+}
+
+fn default_initial_difficulty() -> u64 {
+	global::TESTING_INITIAL_DIFFICULTY
+}
+
+fn is_reserved_magic(magic: [u8; 2]) -> bool {
+	[MAINNET_MAGIC, FLOONET_MAGIC, OTHER_MAGIC].contains(&magic)
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn network_definition() {
+		let params = Arc::new(ChainParams::new(ChainTypes::UserTesting));
+		let genesis = {
+			let _params = params.enter();
+			pow::mine_genesis_block().unwrap()
+		};
+		let mut network = NetworkDefinition::new("test".to_string(), &genesis).unwrap();
+		network.policy_config = PolicyConfig::no_progpow();
+		assert!(!is_reserved_magic(network.magic));
+		assert_eq!(network.genesis().unwrap().hash(), genesis.hash());
+
+		let path = std::env::temp_dir().join(format!("epic_network_{}.json", network.genesis_hash));
+		network.save(&path).unwrap();
+		let loaded = NetworkDefinition::load(&path);
+		fs::remove_file(&path).unwrap();
+		assert_eq!(loaded.unwrap(), network);
+
+		let mut other = network.clone();
+		other.magic = FLOONET_MAGIC;
+		assert_eq!(other.validate(), Err(Error::ReservedMagic(FLOONET_MAGIC)));
+		let mut other = network.clone();
+		other.genesis_hash = "00".repeat(32);
+		assert!(matches!(other.validate(), Err(Error::Genesis(_))));
+		let mut other = network.clone();
+		other.initial_difficulty = 10;
+		assert!(matches!(other.validate(), Err(Error::Genesis(_))));
+		let mut other = network.clone();
+		other.version += 1;
+		assert_eq!(other.validate(), Err(Error::Version(2)));
+
+		// the network is only known to the chain parameters carrying it
+		assert!(global::get_network().is_none());
+		let network = Arc::new(network);
+		let params = Arc::new(ChainParams {
+			network: Some(network.clone()),
+			..ChainParams::new(ChainTypes::UserTesting)
+		});
+		{
+			let _params = params.enter();
+			assert_eq!(global::get_network(), Some(network));
+		}
+		assert!(global::get_network().is_none());
+	}
+}
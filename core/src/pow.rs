@@ -46,7 +46,9 @@ mod types;
 use crate::core::{Block, BlockHeader};
 use crate::genesis;
 use crate::global;
+use crate::ser::{self, ProtocolVersion};
 use chrono::{TimeZone, Utc};
+use std::sync::Arc;
 
 pub use self::common::EdgeType;
 pub use self::types::*;
@@ -84,9 +86,111 @@ pub fn verify_size(bh: &BlockHeader) -> Result<(), Error> {
 	ctx.verify(&bh.pow.proof)
 }
 
+/// Runs `verifier` over many headers concurrently, results are in header order.
+/// RandomX headers are spread over the VMs of the shared `RxVerifier` so a
+/// chunk of synced headers is not validated one hash at a time.
+pub fn verify_headers(
+	headers: &[BlockHeader],
+	verifier: fn(&BlockHeader) -> Result<(), Error>,
+) -> Vec<Result<(), Error>> {
+	let threads = std::thread::available_parallelism()
+		.map(|n| n.get())
+		.unwrap_or(1)
+		.min(headers.len());
+	if threads < 2 {
+		return headers.iter().map(verifier).collect();
+	}
+	let params = global::ChainParams::entered();
+	let chunk_size = (headers.len() + threads - 1) / threads;
+	std::thread::scope(|s| {
+		let handles: Vec<_> = headers
+			.chunks(chunk_size)
+			.map(|chunk| {
+				let params = params.clone();
+				s.spawn(move || {
+					let _params = params.as_ref().map(|p| p.enter());
+					chunk.iter().map(verifier).collect::<Vec<_>>()
+				})
+			})
+			.collect();
+		handles
+			.into_iter()
+			.flat_map(|h| h.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+			.collect()
+	})
+}
+
+/// Header whose proof of work `verify_header` checked.
+#[derive(Clone, Debug)]
+pub struct VerifiedHeader {
+	/// The deserialized header
+	pub header: BlockHeader,
+	/// Algorithm of the proof
+	pub pow_type: PoWType,
+	/// Difficulty the proof achieves
+	pub difficulty: u64,
+}
+
+/// Verifies the proof of work of a serialized header of the given chain type
+/// without any chain state, the chain parameters being only entered for the
+/// call. RandomX proofs are checked against the seed `seed_lookup` returns
+/// for the seed height of the header, the hash of the block at that height,
+/// and fail when it returns None or a seed other than the header one.
+pub fn verify_header<F>(
+	chain_type: global::ChainTypes,
+	header_bytes: &[u8],
+	seed_lookup: F,
+) -> Result<VerifiedHeader, Error>
+where
+	F: FnOnce(u64) -> Option<[u8; 32]>,
+{
+	let params = Arc::new(global::ChainParams::new(chain_type));
+	let _params = params.enter();
+
+	let header: BlockHeader = ser::deserialize(&mut &header_bytes[..], ProtocolVersion::local())
+		.map_err(|e| Error::Header(e.to_string()))?;
+	if let Proof::RandomXProof { .. } = header.pow.proof {
+		let seed_height = randomx::rx_current_seed_height(header.height);
+		match seed_lookup(seed_height) {
+			Some(seed) if seed == header.pow.seed => {}
+			Some(_) => {
+				return Err(Error::Seed(format!(
+					"header seed is not the hash of block {}",
+					seed_height
+				)))
+			}
+			None => {
+				return Err(Error::Seed(format!(
+					"no seed for height {}, the hash of block {}",
+					header.height, seed_height
+				)))
+			}
+		}
+	}
+	verify_size(&header)?;
+
+	let pow_type = PoWType::from(&header.pow.proof);
+	let difficulty = header
+		.pow
+		.to_difficulty(&header.pre_pow(), header.height, header.pow.nonce)
+		.to_num(pow_type);
+	Ok(VerifiedHeader {
+		header,
+		pow_type,
+		difficulty,
+	})
+}
+
 /// Mines a genesis block using the internal miner
 pub fn mine_genesis_block() -> Result<Block, Error> {
+	mine_genesis_block_at(Difficulty::from_num(global::TESTING_INITIAL_DIFFICULTY))
+}
+
+/// Mines a development genesis block at the provided difficulty, the one the
+/// blocks following it start from.
+pub fn mine_genesis_block_at(difficulty: Difficulty) -> Result<Block, Error> {
 	let mut gen = genesis::genesis_dev();
+	gen.header.pow.total_difficulty = difficulty;
 
 	if global::is_user_testing_mode() {
 		gen.header.timestamp = Utc::now();
@@ -188,4 +292,46 @@ mod test {
 		);
 		assert!(verify_size(&b.header).is_ok());
 	}
+
+	#[test]
+	fn verify_serialized_header() {
+		let params = Arc::new(global::ChainParams::new(ChainTypes::UserTesting));
+		let mut b = {
+			let _params = params.enter();
+			let mut b = genesis::genesis_dev();
+			pow_size(
+				&mut b.header,
+				Difficulty::min(),
+				global::proofsize(),
+				global::min_edge_bits(),
+			)
+			.unwrap();
+			b
+		};
+		let bytes = ser::ser_vec(&b.header, ProtocolVersion::local()).unwrap();
+
+		let verified = verify_header(ChainTypes::UserTesting, &bytes, |_| None).unwrap();
+		assert_eq!(verified.header, b.header);
+		assert!(verified.difficulty >= 1);
+		assert!(global::ChainParams::entered().is_none());
+
+		match verify_header(ChainTypes::UserTesting, &bytes[..20], |_| None) {
+			Err(Error::Header(_)) => {}
+			r => panic!("truncated header verified: {:?}", r),
+		}
+
+		// RandomX proofs are only checked with the seed of their seed height
+		b.header.pow.proof = Proof::RandomXProof { hash: [0; 32] };
+		b.header.pow.seed = [1; 32];
+		let bytes = ser::ser_vec(&b.header, ProtocolVersion::local()).unwrap();
+		for seed in [None, Some([2; 32])] {
+			match verify_header(ChainTypes::UserTesting, &bytes, |height| {
+				assert_eq!(height, 0);
+				seed
+			}) {
+				Err(Error::Seed(_)) => {}
+				r => panic!("unseeded randomx header verified: {:?}", r),
+			}
+		}
+	}
 }
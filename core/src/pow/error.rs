@@ -43,6 +43,15 @@ pub enum Error {
 	/// No Solution
 	#[error("No Solution")]
 	NoSolution,
+	/// Header could not be deserialized
+	#[error("Header Error: {0}")]
+	Header(String),
+	/// RandomX seed unknown or not the one of the seed height
+	#[error("Seed Error: {0}")]
+	Seed(String),
+	/// The RandomX cache or VM of a seed could not be created
+	#[error("RandomX Error: {0}")]
+	RandomX(String),
 
 	#[error("IO error: {0}")]
 	Io(#[from] io::Error),
@@ -1,15 +1,185 @@
-use num_bigint::BigUint;
+use std::ffi::c_void;
 use std::marker::PhantomData;
+use std::sync::Arc;
+use std::thread;
 
 use crate::pow::common::EdgeType;
 use crate::pow::error::Error;
 use crate::pow::{PoWContext, Proof};
-use crate::util::RwLock;
+use crate::util::{Condvar, Mutex};
 
-use randomx::{slow_hash, RxState};
+use randomx::{randomx_calculate_hash, RxState, RxVM};
+
+/// Maximum number of RandomX VMs kept per seed, they all share the cache of
+/// their seed.
+pub const MAX_RX_VMS_PER_SEED: usize = 4;
 
 lazy_static! {
-	pub static ref RX_STATE: RwLock<RxState> = RwLock::new(RxState::new());
+	/// Shared RandomX verifier used for mining and for validating headers.
+	pub static ref RX_VERIFIER: RxVerifier = RxVerifier::new(
+		thread::available_parallelism()
+			.map(|n| n.get())
+			.unwrap_or(1)
+			.min(MAX_RX_VMS_PER_SEED)
+	);
+}
+
+/// The cache of a seed and the VMs created from it.
+struct SeedVms {
+	/// Holds the cache of the seed, built by the first checkout
+	state: Option<RxState>,
+	idle: Vec<Arc<RxVM>>,
+	created: usize,
+}
+
+/// VMs initialized for a single seed.
+struct SeedPool {
+	seed: [u8; 32],
+	vms: Mutex<SeedVms>,
+	released: Condvar,
+}
+
+/// Pool of RandomX VMs keyed by seed. Each VM is only ever used by one thread
+/// at a time, so hashes for the same seed run concurrently on up to
+/// `vms_per_seed` VMs instead of queueing on a single global state, all of
+/// them sharing a single cache. Pools are kept for the active seeds, the ones
+/// of the current and next epochs of our chain, hashing for any other seed
+/// (while syncing old headers) keeping a single extra pool around.
+pub struct RxVerifier {
+	pools: Mutex<Vec<Arc<SeedPool>>>,
+	active: Mutex<Vec<[u8; 32]>>,
+	vms_per_seed: usize,
+}
+
+impl RxVerifier {
+	/// Creates a verifier keeping up to `vms_per_seed` VMs for each seed.
+	pub fn new(vms_per_seed: usize) -> RxVerifier {
+		RxVerifier {
+			pools: Mutex::new(vec![]),
+			active: Mutex::new(vec![]),
+			vms_per_seed: vms_per_seed.max(1),
+		}
+	}
+
+	/// Number of VMs kept for each seed.
+	pub fn vms_per_seed(&self) -> usize {
+		self.vms_per_seed
+	}
+
+	/// Seeds we currently hold VMs for.
+	pub fn seeds(&self) -> Vec<[u8; 32]> {
+		self.pools.lock().iter().map(|p| p.seed).collect()
+	}
+
+	/// Sets the seeds of the current and next epochs (see
+	/// `rx_current_seed_height` and `rx_next_seed_height`), dropping the pools
+	/// of the seeds no longer in use.
+	pub fn set_active_seeds(&self, seeds: Vec<[u8; 32]>) {
+		let mut pools = self.pools.lock();
+		pools.retain(|p| seeds.contains(&p.seed));
+		*self.active.lock() = seeds;
+	}
+
+	/// RandomX hash of `data` using a VM for `seed`, waiting for one of the VMs
+	/// of that seed to be released if they are all in use.
+	pub fn hash(&self, data: &[u8], seed: &[u8; 32]) -> Result<[u8; 32], Error> {
+		let vm = self.checkout(seed)?;
+		let mut hash = [0u8; 32];
+		unsafe {
+			randomx_calculate_hash(
+				vm.vm().vm,
+				data.as_ptr() as *const c_void,
+				data.len(),
+				hash.as_mut_ptr() as *mut c_void,
+			);
+		}
+		Ok(hash)
+	}
+
+	/// Checks `data` hashes to `expected` under `seed`.
+	pub fn verify(&self, data: &[u8], seed: &[u8; 32], expected: &[u8; 32]) -> Result<bool, Error> {
+		Ok(&self.hash(data, seed)? == expected)
+	}
+
+	/// Pool of the seed, created if needed. Besides the active seeds a single
+	/// pool is kept, the one of the last other seed used.
+	fn pool(&self, seed: &[u8; 32]) -> Arc<SeedPool> {
+		let mut pools = self.pools.lock();
+		if let Some(pool) = pools.iter().find(|p| &p.seed == seed) {
+			return pool.clone();
+		}
+		{
+			// VMs of a dropped pool in use are dropped when they are released.
+			let active = self.active.lock();
+			pools.retain(|p| active.contains(&p.seed));
+		}
+		let pool = Arc::new(SeedPool {
+			seed: *seed,
+			vms: Mutex::new(SeedVms {
+				state: None,
+				idle: vec![],
+				created: 0,
+			}),
+			released: Condvar::new(),
+		});
+		pools.push(pool.clone());
+		pool
+	}
+
+	fn checkout(&self, seed: &[u8; 32]) -> Result<CheckedOutVm, Error> {
+		let pool = self.pool(seed);
+		let mut vms = pool.vms.lock();
+		loop {
+			if let Some(vm) = vms.idle.pop() {
+				drop(vms);
+				return Ok(CheckedOutVm { pool, vm: Some(vm) });
+			}
+			if vms.created < self.vms_per_seed {
+				// The seed cache is only built once, for its first VM, a
+				// failure leaving it to the next checkout.
+				let mut state = match vms.state.take() {
+					Some(state) => state,
+					None => {
+						let mut state = RxState::new();
+						state
+							.init_cache(seed)
+							.map_err(|e| Error::RandomX(format!("cache: {:?}", e)))?;
+						state
+					}
+				};
+				let vm = state
+					.create_vm()
+					.map_err(|e| Error::RandomX(format!("vm: {:?}", e)));
+				vms.state = Some(state);
+				let vm = vm?;
+				vms.created += 1;
+				drop(vms);
+				return Ok(CheckedOutVm { pool, vm: Some(vm) });
+			}
+			pool.released.wait(&mut vms);
+		}
+	}
+}
+
+/// A VM checked out of its seed pool, given back on drop.
+struct CheckedOutVm {
+	pool: Arc<SeedPool>,
+	vm: Option<Arc<RxVM>>,
+}
+
+impl CheckedOutVm {
+	fn vm(&self) -> &RxVM {
+		self.vm.as_ref().expect("checked out vm")
+	}
+}
+
+impl Drop for CheckedOutVm {
+	fn drop(&mut self) {
+		if let Some(vm) = self.vm.take() {
+			self.pool.vms.lock().idle.push(vm);
+			self.pool.released.notify_one();
+		}
+	}
 }
 
 pub const SEEDHASH_EPOCH_BLOCKS: u64 = 1000;
@@ -96,25 +266,13 @@ where
 	}
 
 	fn pow_solve(&mut self) -> Result<Vec<Proof>, Error> {
-		let hash: num_bigint::BigUint = {
-			let mut state = RX_STATE.write();
-			slow_hash(&mut state, &self.header, &self.seed)
-		};
-
-		let hash_bytes: [u8; 32] = biguint_to_u8_32(hash);
+		let hash_bytes = RX_VERIFIER.hash(&self.header, &self.seed)?;
 		Ok(vec![Proof::RandomXProof { hash: hash_bytes }])
 	}
 
 	fn verify(&mut self, proof: &Proof) -> Result<(), Error> {
-		let hash = {
-			let mut state = RX_STATE.write();
-			slow_hash(&mut state, &self.header, &self.seed)
-		};
-
-		let hash_u8: [u8; 32] = biguint_to_u8_32(hash);
-
 		if let Proof::RandomXProof { hash: ref proof } = proof {
-			if &hash_u8 == proof {
+			if RX_VERIFIER.verify(&self.header, &self.seed, proof)? {
 				return Ok(());
 			}
 		}
@@ -123,13 +281,40 @@ where
 	}
 }
 
-fn biguint_to_u8_32(value: BigUint) -> [u8; 32] {
-	let mut bytes = [0u8; 32]; // Initialize a 32-byte array with zeros
-	let biguint_bytes = value.to_bytes_be(); // Get the big-endian byte representation of the BigUint
+#[cfg(test)]
+mod test {
+	use super::*;
+	use num_bigint::BigUint;
+	use randomx::slow_hash;
 
-	// Copy the bytes into the array, starting from the right (least significant bytes)
-	let start = 32usize.saturating_sub(biguint_bytes.len());
-	bytes[start..].copy_from_slice(&biguint_bytes[..std::cmp::min(32, biguint_bytes.len())]);
+	#[test]
+	fn shared_cache_vms_hash_like_slow_hash() {
+		let verifier = RxVerifier::new(2);
+		let seed = [7u8; 32];
+		let other_seed = [9u8; 32];
+		verifier.set_active_seeds(vec![seed]);
+		for data in [&b"epic"[..], &[0u8; 80][..]] {
+			let expected = biguint_to_u8_32(slow_hash(&mut RxState::new(), data, &seed));
+			assert_eq!(verifier.hash(data, &seed).unwrap(), expected);
+			assert!(verifier.verify(data, &seed, &expected).unwrap());
+		}
+
+		// a seed outside of the active ones gets a single extra pool
+		verifier.hash(b"epic", &other_seed).unwrap();
+		verifier.hash(b"epic", &[8u8; 32]).unwrap();
+		assert_eq!(verifier.seeds(), vec![seed, [8u8; 32]]);
+		verifier.set_active_seeds(vec![other_seed]);
+		assert!(verifier.seeds().is_empty());
+	}
 
-	bytes
+	fn biguint_to_u8_32(value: BigUint) -> [u8; 32] {
+		let mut bytes = [0u8; 32]; // Initialize a 32-byte array with zeros
+		let biguint_bytes = value.to_bytes_be(); // Get the big-endian byte representation of the BigUint
+
+		// Copy the bytes into the array, starting from the right (least significant bytes)
+		let start = 32usize.saturating_sub(biguint_bytes.len());
+		bytes[start..].copy_from_slice(&biguint_bytes[..std::cmp::min(32, biguint_bytes.len())]);
+
+		bytes
+	}
 }
@@ -26,7 +26,7 @@ use crate::core::core::{
 };
 use crate::core::libtx::build::{self, input, output};
 use crate::core::libtx::ProofBuilder;
-use crate::core::{global, ser};
+use crate::core::{global, pow, ser};
 use chrono::Duration;
 use epic_core as core;
 use epic_core::global::ChainTypes;
@@ -561,3 +561,27 @@ fn wrong_amount_range_proof() {
 		_ => panic!("Bad range proof should be invalid"),
 	}
 }
+
+fn verify_even_height(header: &BlockHeader) -> Result<(), pow::Error> {
+	if header.height % 2 == 0 {
+		Ok(())
+	} else {
+		Err(pow::Error::Verification("odd height".to_string()))
+	}
+}
+
+#[test]
+fn verify_headers_in_order() {
+	let headers: Vec<_> = (0..37)
+		.map(|height| BlockHeader {
+			height,
+			..BlockHeader::default()
+		})
+		.collect();
+	let results = pow::verify_headers(&headers, verify_even_height);
+	assert_eq!(results.len(), headers.len());
+	for (header, res) in headers.iter().zip(results) {
+		assert_eq!(res.is_ok(), header.height % 2 == 0);
+	}
+	assert!(pow::verify_headers(&[], verify_even_height).is_empty());
+}
@@ -15,12 +15,17 @@
 use epic_core as core;
 
 use self::core::consensus::*;
+use self::core::core::block::feijoada::{AllowPolicy, PolicyConfig};
 use self::core::core::block::HeaderVersion;
+use self::core::core::foundation::{self, load_foundation_output, load_foundation_outputs};
 use self::core::core::hash::ZERO_HASH;
+use self::core::forks::{self, DifficultyEra, ForkSchedule};
 use self::core::global;
 use self::core::pow::{Difficulty, PoWType};
 use chrono::prelude::Utc;
 use std::fmt::{self, Display};
+use std::sync::Arc;
+use std::thread;
 
 /// Last n blocks for difficulty calculation purposes
 /// (copied from stats in server crate)
@@ -701,6 +706,255 @@ fn hard_forks() {
 	assert!(valid_header_version(YEAR_HEIGHT / 2 - 1, HeaderVersion(7)));
 }
 
+#[test]
+fn fork_schedule() {
+	global::set_mining_mode(global::ChainTypes::AutomatedTesting);
+	assert_eq!(first_fork_height(), TESTING_FIRST_HARD_FORK);
+	assert_eq!(difficultyfix_height(), TESTING_DIFFICULTY_ERA);
+	assert_eq!(
+		header_version(TESTING_FIRST_HARD_FORK - 1),
+		HeaderVersion(6)
+	);
+	assert_eq!(header_version(TESTING_FIRST_HARD_FORK), HeaderVersion(7));
+
+	let mainnet = ForkSchedule::get(&global::ChainTypes::Mainnet);
+	assert_eq!(mainnet.fork_at(0).name, "genesis");
+	assert_eq!(
+		mainnet.fork_at(MAINNET_DIFFICULTY_ERA - 1).difficulty_era,
+		DifficultyEra::Initial
+	);
+	assert_eq!(
+		mainnet.fork_at(MAINNET_DIFFICULTY_ERA).difficulty_era,
+		DifficultyEra::Era1
+	);
+	assert_eq!(mainnet.fork_at(BLOCK_ERA_1).emitted_policy(), 0);
+	assert_eq!(mainnet.fork_at(BLOCK_ERA_1 + 1).emitted_policy(), 1);
+	assert_eq!(mainnet.fork_at(u64::MAX).emitted_policy(), 5);
+	assert_eq!(
+		mainnet.next_fork(BLOCK_ERA_5).map(|f| f.height),
+		Some(BLOCK_ERA_5 + 1)
+	);
+	assert_eq!(
+		mainnet.next_fork(MAINNET_FIRST_HARD_FORK).map(|f| f.height),
+		None
+	);
+
+	// one allowed policy entry per policy era, as the policy configs had
+	let eras = [
+		0,
+		BLOCK_ERA_1 + 1,
+		BLOCK_ERA_2 + 1,
+		BLOCK_ERA_3 + 1,
+		BLOCK_ERA_4 + 1,
+		BLOCK_ERA_5 + 1,
+	];
+	let allowed: Vec<AllowPolicy> = eras
+		.iter()
+		.enumerate()
+		.map(|(i, height)| AllowPolicy {
+			height: *height,
+			value: 1 << i,
+		})
+		.collect();
+	assert_eq!(mainnet.allowed_policies(), allowed);
+
+	for config in [
+		PolicyConfig::default(),
+		PolicyConfig::no_progpow(),
+		PolicyConfig::only_randomx(),
+	] {
+		assert_eq!(
+			mainnet.validate(&global::ChainTypes::Mainnet, &config),
+			Ok(())
+		);
+	}
+
+	let mut config = PolicyConfig::default();
+	config.policies.truncate(5);
+	assert_eq!(
+		mainnet.validate(&global::ChainTypes::Mainnet, &config),
+		Err(forks::Error::MissingPolicy {
+			height: BLOCK_ERA_5 + 1,
+			policy: 5
+		})
+	);
+	let mut config = PolicyConfig::default();
+	config.policies[2].insert(PoWType::RandomX, 0);
+	assert_eq!(
+		mainnet.validate(&global::ChainTypes::Mainnet, &config),
+		Err(forks::Error::PolicyShares {
+			policy: 2,
+			total: 52
+		})
+	);
+
+	let mut unordered = mainnet.clone();
+	unordered.forks.swap(1, 2);
+	assert_eq!(
+		unordered.validate(&global::ChainTypes::Mainnet, &PolicyConfig::default()),
+		Err(forks::Error::Unordered(unordered.forks[2].height))
+	);
+
+	// the test chains may allow more policies than scheduled, not fewer
+	let testing = ForkSchedule::get(&global::ChainTypes::UserTesting);
+	let mut config = PolicyConfig::default();
+	config.allowed_policies = vec![AllowPolicy {
+		height: 0,
+		value: 0b111111,
+	}];
+	assert_eq!(
+		testing.validate(&global::ChainTypes::UserTesting, &config),
+		Ok(())
+	);
+	assert_eq!(
+		mainnet.validate(&global::ChainTypes::Mainnet, &config),
+		Err(forks::Error::AllowedPoliciesMismatch)
+	);
+	config.allowed_policies = vec![AllowPolicy {
+		height: 0,
+		value: 1 << 1,
+	}];
+	assert_eq!(
+		testing.validate(&global::ChainTypes::UserTesting, &config),
+		Err(forks::Error::EmittedPolicyNotAllowed {
+			height: 0,
+			policy: 0
+		})
+	);
+}
+
+#[test]
+fn emission() {
+	global::set_mining_mode(global::ChainTypes::AutomatedTesting);
+	assert_eq!(emission_at_height(0), Emission::default());
+
+	let last = BLOCK_ERA_2 + 10;
+	let mut checkpoints = vec![1, 4, 5, 6, last];
+	for era in &[
+		BLOCK_ERA_1,
+		BLOCK_ERA_2,
+		FOUNDATION_LEVY_ERA_1,
+		FOUNDATION_LEVY_ERA_1 + FOUNDATION_LEVY_ERA_2_ONWARDS,
+	] {
+		checkpoints.extend_from_slice(&[era - 1, *era, era + 1, era + 4, era + 5]);
+	}
+
+	// brute force the block by block sum, as total_overage_at_height does
+	let (mut supply, mut levy) = (0, 0);
+	for height in 1..=last {
+		supply += reward_at_height(height) + add_reward_foundation(height);
+		levy += reward_foundation_at_height(height);
+		if checkpoints.contains(&height) {
+			let emission = emission_at_height(height);
+			assert_eq!(emission.total(), supply, "height {}", height);
+			assert_eq!(
+				emission.foundation + emission.foundation_pending,
+				levy,
+				"height {}",
+				height
+			);
+			if height % foundation_height() == 0 {
+				assert_eq!(emission.foundation_pending, 0);
+			}
+		}
+	}
+	assert_eq!(total_overage_at_height(last, false), supply as i64);
+
+	assert_eq!(next_halving_height(0), Some(BLOCK_ERA_1 + 1));
+	assert_eq!(next_halving_height(BLOCK_ERA_1), Some(BLOCK_ERA_1 + 1));
+	assert_eq!(next_halving_height(BLOCK_ERA_1 + 1), Some(BLOCK_ERA_2 + 1));
+	assert_eq!(
+		next_halving_height(BLOCK_ERA_5 + 1),
+		Some(BLOCK_ERA_5 + BLOCK_ERA_6_ONWARDS + 1)
+	);
+	let halving = next_halving_height(BLOCK_ERA_5 + BLOCK_ERA_6_ONWARDS).unwrap();
+	assert_eq!(
+		block_total_reward_at_height(halving),
+		block_total_reward_at_height(halving - 1) / 2
+	);
+}
+
+#[test]
+fn chain_params() {
+	let floonet = Arc::new(global::ChainParams::new(global::ChainTypes::Floonet));
+	let mainnet = Arc::new(global::ChainParams::new(global::ChainTypes::Mainnet));
+	assert!(!mainnet.checkpoints.is_empty() && floonet.checkpoints.is_empty());
+
+	// each thread follows the parameters it entered
+	let handles: Vec<_> = vec![floonet, mainnet]
+		.into_iter()
+		.map(|params| {
+			thread::spawn(move || {
+				let _params = params.enter();
+				for _ in 0..100 {
+					assert_eq!(global::get_chain_type(), params.chain_type);
+					assert_eq!(global::coinbase_maturity(), params.coinbase_maturity);
+					assert_eq!(
+						global::is_floonet(),
+						params.chain_type == global::ChainTypes::Floonet
+					);
+				}
+				first_fork_height()
+			})
+		})
+		.collect();
+	let heights: Vec<u64> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+	assert_eq!(
+		heights,
+		vec![FLOONET_FIRST_HARD_FORK, MAINNET_FIRST_HARD_FORK]
+	);
+
+	// nested parameters are restored, then the process wide ones
+	let testing = Arc::new(global::ChainParams {
+		coinbase_maturity: 7,
+		..global::ChainParams::new(global::ChainTypes::AutomatedTesting)
+	});
+	let user = Arc::new(global::ChainParams::new(global::ChainTypes::UserTesting));
+	{
+		let _testing = testing.enter();
+		assert_eq!(global::coinbase_maturity(), 7);
+		{
+			let _user = user.enter();
+			assert!(global::is_user_testing_mode());
+		}
+		assert_eq!(
+			global::get_chain_type(),
+			global::ChainTypes::AutomatedTesting
+		);
+		assert_eq!(global::ChainParams::current().coinbase_maturity, 7);
+	}
+	assert!(global::ChainParams::entered().is_none());
+}
+
+#[test]
+fn chain_params_foundation() {
+	// without a foundation file the data embedded for the chain type is used
+	let floonet = Arc::new(global::ChainParams {
+		header_sync_timeout: 25,
+		..global::ChainParams::new(global::ChainTypes::Floonet)
+	});
+	{
+		let _floonet = floonet.enter();
+		assert_eq!(global::get_header_sync_timeout(), 25);
+		assert!(load_foundation_output(foundation_height()).is_ok());
+	}
+
+	// a foundation file that can't be read is an error, whatever the process
+	// wide foundation file
+	let missing = Arc::new(global::ChainParams {
+		foundation_path: Some("/nonexistent/foundation.json".to_string()),
+		..global::ChainParams::new(global::ChainTypes::Floonet)
+	});
+	let _missing = missing.enter();
+	match load_foundation_output(foundation_height()) {
+		Err(foundation::Error::Unreadable(path, _)) => {
+			assert_eq!(path, "/nonexistent/foundation.json")
+		}
+		res => panic!("unexpected {:?}", res.map(|cb| cb.key_id)),
+	}
+	assert!(load_foundation_outputs(&[foundation_height()]).is_err());
+}
+
 // #[test]
 // fn hard_fork_2() {
 // 	assert!(valid_header_version(0, 1));
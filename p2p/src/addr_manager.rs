@@ -0,0 +1,257 @@
+// Copyright 2020 The Epic Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Selection of the addresses we open outbound connections to.
+//!
+//! Known addresses are split in a "tried" table (peers we successfully
+//! connected to before) and a "new" table (addresses we only heard about),
+//! each bucketed by network group. Selection first picks a table, then a
+//! bucket and finally an address, all at random, so the number of addresses
+//! an attacker controls in a single subnet doesn't increase its odds of being
+//! picked. On top of that the number of outbound connections per network
+//! group is capped.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv6Addr};
+
+use rand::Rng;
+
+use crate::store::PeerData;
+use crate::types::PeerAddr;
+
+/// Number of outbound connections kept as anchors across restarts.
+pub const MAX_ANCHORS: usize = 2;
+
+/// The network group an address belongs to. Peers in the same group are
+/// likely operated by the same entity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NetGroup {
+	/// Loopback, private and link local addresses.
+	Local,
+	/// The /16 of an IPv4 address.
+	Ipv4([u8; 2]),
+	/// The /32 of an IPv6 address.
+	Ipv6([u8; 4]),
+	/// Tor onion services, which all share a single group.
+	Onion,
+}
+
+impl NetGroup {
+	/// Network group of the provided address.
+	pub fn of(addr: PeerAddr) -> NetGroup {
+		if !addr.is_routable() {
+			return NetGroup::Local;
+		}
+		let ip = match addr {
+			PeerAddr::Ip(addr) => addr.ip(),
+			PeerAddr::Onion(..) => return NetGroup::Onion,
+		};
+		match ip {
+			IpAddr::V4(ip) => {
+				let octets = ip.octets();
+				NetGroup::Ipv4([octets[0], octets[1]])
+			}
+			IpAddr::V6(ip) => match ipv4_mapped(&ip) {
+				Some(octets) => NetGroup::Ipv4(octets),
+				None => {
+					let octets = ip.octets();
+					NetGroup::Ipv6([octets[0], octets[1], octets[2], octets[3]])
+				}
+			},
+		}
+	}
+
+	/// Whether outbound connections to this group are exempt from the per
+	/// group limit. Onion addresses tell nothing about who runs them and are
+	/// all we connect to in onion-only mode.
+	pub fn is_exempt(&self) -> bool {
+		*self == NetGroup::Local || *self == NetGroup::Onion
+	}
+}
+
+// The /16 of an IPv4-mapped IPv6 address, so the same host can't be counted
+// twice under different families.
+fn ipv4_mapped(ip: &Ipv6Addr) -> Option<[u8; 2]> {
+	ip.to_ipv4_mapped().map(|v4| {
+		let octets = v4.octets();
+		[octets[0], octets[1]]
+	})
+}
+
+/// A table of addresses bucketed by network group.
+struct Table {
+	buckets: Vec<(NetGroup, Vec<PeerAddr>)>,
+}
+
+impl Table {
+	fn new(peers: Vec<PeerData>) -> Table {
+		let mut groups: HashMap<NetGroup, Vec<PeerAddr>> = HashMap::new();
+		for p in peers {
+			groups.entry(NetGroup::of(p.addr)).or_default().push(p.addr);
+		}
+		Table {
+			buckets: groups.into_iter().collect(),
+		}
+	}
+
+	fn is_empty(&self) -> bool {
+		self.buckets.is_empty()
+	}
+
+	/// Removes and returns an address from a random bucket.
+	fn take<R: Rng>(&mut self, rng: &mut R) -> Option<(NetGroup, PeerAddr)> {
+		if self.buckets.is_empty() {
+			return None;
+		}
+		let i = rng.random_range(0..self.buckets.len());
+		let (group, addrs) = &mut self.buckets[i];
+		let group = *group;
+		let j = rng.random_range(0..addrs.len());
+		let addr = addrs.swap_remove(j);
+		if addrs.is_empty() {
+			self.buckets.swap_remove(i);
+		}
+		Some((group, addr))
+	}
+
+	fn remove_group(&mut self, group: NetGroup) {
+		self.buckets.retain(|(g, _)| *g != group);
+	}
+}
+
+/// Selects up to `count` addresses to connect to out of the `tried` and `new`
+/// tables, given the addresses of the outbound peers we are already connected
+/// to. No more than `max_per_group` outbound connections are allowed in any
+/// network group.
+pub fn select_outbound(
+	tried: Vec<PeerData>,
+	new: Vec<PeerData>,
+	outbound: &[PeerAddr],
+	max_per_group: usize,
+	count: usize,
+) -> Vec<PeerAddr> {
+	let mut per_group: HashMap<NetGroup, usize> = HashMap::new();
+	for addr in outbound {
+		*per_group.entry(NetGroup::of(*addr)).or_insert(0) += 1;
+	}
+
+	let mut rng = rand::rng();
+	let mut tried = Table::new(tried);
+	let mut new = Table::new(new);
+	let mut selected = vec![];
+
+	while selected.len() < count && !(tried.is_empty() && new.is_empty()) {
+		let table = if new.is_empty() || (!tried.is_empty() && rng.random_bool(0.5)) {
+			&mut tried
+		} else {
+			&mut new
+		};
+		let (group, addr) = match table.take(&mut rng) {
+			Some(x) => x,
+			None => break,
+		};
+		if outbound.contains(&addr) || selected.contains(&addr) {
+			continue;
+		}
+		let n = per_group.entry(group).or_insert(0);
+		if !group.is_exempt() && *n >= max_per_group {
+			table.remove_group(group);
+			continue;
+		}
+		*n += 1;
+		selected.push(addr);
+	}
+	selected
+}
+
+/// Whether connecting to `addr` would exceed the outbound limit of its network
+/// group, given the addresses of our current outbound peers.
+pub fn group_full(addr: PeerAddr, outbound: &[PeerAddr], max_per_group: usize) -> bool {
+	let group = NetGroup::of(addr);
+	!group.is_exempt()
+		&& outbound
+			.iter()
+			.filter(|a| **a != addr && NetGroup::of(**a) == group)
+			.count() >= max_per_group
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::store::State;
+	use crate::types::{Capabilities, ReasonForBan};
+
+	fn addr(s: &str) -> PeerAddr {
+		s.parse().unwrap()
+	}
+
+	fn peer(s: &str) -> PeerData {
+		PeerData {
+			addr: addr(s),
+			capabilities: Capabilities::UNKNOWN,
+			user_agent: "".to_string(),
+			flags: State::Unknown,
+			last_banned: 0,
+			ban_reason: ReasonForBan::None,
+			last_connected: 0,
+			local_timestamp: 0,
+		}
+	}
+
+	#[test]
+	fn test_net_groups() {
+		assert_eq!(
+			NetGroup::of(addr("8.8.8.8:3414")),
+			NetGroup::of(addr("8.8.4.4:3414"))
+		);
+		assert_ne!(
+			NetGroup::of(addr("8.8.8.8:3414")),
+			NetGroup::of(addr("8.9.8.8:3414"))
+		);
+		assert_eq!(
+			NetGroup::of(addr("[::ffff:8.8.8.8]:3414")),
+			NetGroup::Ipv4([8, 8])
+		);
+		assert_eq!(
+			NetGroup::of(addr("[2001:db8:1::1]:3414")),
+			NetGroup::of(addr("[2001:db8:2::1]:3414"))
+		);
+		assert!(NetGroup::of(addr("127.0.0.1:3414")).is_exempt());
+		assert!(NetGroup::of(addr("192.168.1.1:3414")).is_exempt());
+
+		let onion = addr("2a6at2obto3uvkpkitqp4wxcg6u36qf534eucbskqciturczzc5suyid.onion:3414");
+		assert_eq!(NetGroup::of(onion), NetGroup::Onion);
+		assert!(NetGroup::of(onion).is_exempt());
+	}
+
+	#[test]
+	fn test_select_outbound_limits_groups() {
+		// a single subnet flooding our new table
+		let mut new: Vec<_> = (0..200)
+			.map(|i| peer(&format!("1.2.{}.{}:3414", i / 250, i % 250)))
+			.collect();
+		new.push(peer("5.6.7.8:3414"));
+		let tried = vec![peer("9.9.9.9:3414")];
+
+		let outbound = vec![addr("1.2.200.1:3414")];
+		let selected = select_outbound(tried, new, &outbound, 2, 10);
+		assert_eq!(selected.len(), 3);
+		assert!(selected.contains(&addr("5.6.7.8:3414")));
+		assert!(selected.contains(&addr("9.9.9.9:3414")));
+
+		let all: Vec<_> = outbound.iter().chain(selected.iter()).cloned().collect();
+		assert!(group_full(addr("1.2.3.4:3414"), &all, 2));
+		assert!(!group_full(addr("127.0.0.1:3414"), &all, 0));
+	}
+}
@@ -20,12 +20,14 @@
 //! forces us to go through some additional gymnastic to loop over the async
 //! stream and make sure we get the right number of bytes out.
 
+use crate::core::global::ChainParams;
 use crate::core::ser;
 use crate::core::ser::{FixedLength, ProtocolVersion};
 use crate::msg::{
 	read_body, read_discard, read_header, read_item, write_message, Msg, MsgHeader,
 	MsgHeaderWrapper,
 };
+use crate::session::Session;
 use crate::types::Error;
 use crate::util::{RateCounter, RwLock};
 use std::io::{self, Read, Write};
@@ -241,7 +243,9 @@ impl Tracker {
 /// itself.
 pub fn listen<H>(
 	stream: TcpStream,
+	session: Option<Session>,
 	version: ProtocolVersion,
+	magic: [u8; 2],
 	tracker: Arc<Tracker>,
 	handler: H,
 ) -> io::Result<(ConnHandle, StopHandle)>
@@ -258,8 +262,10 @@ where
 
 	let (reader_thread, writer_thread) = poll(
 		stream,
+		session,
 		conn_handle.clone(),
 		version,
+		magic,
 		handler,
 		send_rx,
 		stopped.clone(),
@@ -278,8 +284,10 @@ where
 
 fn poll<H>(
 	conn: TcpStream,
+	session: Option<Session>,
 	conn_handle: ConnHandle,
 	version: ProtocolVersion,
+	magic: [u8; 2],
 	handler: H,
 	send_rx: mpsc::Receiver<Msg>,
 	stopped: Arc<AtomicBool>,
@@ -289,16 +297,30 @@ where
 	H: MessageHandler,
 {
 	// Split out tcp stream out into separate reader/writer halves.
-	let mut reader = conn.try_clone().expect("clone conn for reader failed");
-	let mut writer = conn.try_clone().expect("clone conn for writer failed");
+	let reader = conn.try_clone().expect("clone conn for reader failed");
+	let writer = conn.try_clone().expect("clone conn for writer failed");
+	// Messages go through these, the tcp streams above are still used to set
+	// timeouts and shut the connection down.
+	let (mut rx, mut tx): (Box<dyn Read + Send>, Box<dyn Write + Send>) = match session {
+		Some(session) => {
+			let (rx, tx) = session.split(reader.try_clone()?, writer.try_clone()?);
+			(Box::new(rx), Box::new(tx))
+		}
+		None => (Box::new(reader.try_clone()?), Box::new(writer.try_clone()?)),
+	};
 	let reader_stopped = stopped.clone();
 
 	let reader_tracker = tracker.clone();
 	let writer_tracker = tracker.clone();
 
+	// the connection threads follow the chain parameters of the server
+	let reader_params = ChainParams::entered();
+	let writer_params = reader_params.clone();
+
 	let reader_thread = thread::Builder::new()
 		.name("peer_read".to_string())
 		.spawn(move || {
+			let _params = reader_params.as_ref().map(|p| p.enter());
 			if let Err(e) = reader.set_read_timeout(Some(BODY_IO_TIMEOUT)) {
 				error!("Failed to set read timeout: {:?}", e);
 				return; // Beende den Thread sauber
@@ -306,9 +328,9 @@ where
 
 			loop {
 				// check the read end
-				match try_header!(read_header(&mut reader, version), &mut reader) {
+				match try_header!(read_header(&mut *rx, version, magic), &reader) {
 					Some(MsgHeaderWrapper::Known(header)) => {
-						let msg = Message::from_header(header, &mut reader, version);
+						let msg = Message::from_header(header, &mut *rx, version);
 
 						trace!(
 							"Received message header, type {:?}, len {}.",
@@ -336,7 +358,7 @@ where
 						// Increase received bytes counter
 						reader_tracker.inc_received(MsgHeader::LEN as u64 + msg_len);
 
-						try_break!(read_discard(msg_len, &mut reader));
+						try_break!(read_discard(msg_len, &mut *rx));
 					}
 					None => {}
 				}
@@ -360,6 +382,7 @@ where
 	let writer_thread = thread::Builder::new()
 		.name("peer_write".to_string())
 		.spawn(move || {
+			let _params = writer_params.as_ref().map(|p| p.enter());
 			if let Err(e) = writer.set_write_timeout(Some(BODY_IO_TIMEOUT)) {
 				error!("Failed to set write timeout: {:?}", e);
 				return; // Beende den Thread sauber
@@ -374,7 +397,7 @@ where
 				if let Ok(data) = maybe_data {
 					failcount = 0;
 					let written =
-						try_break!(write_message(&mut writer, &data, writer_tracker.clone()));
+						try_break!(write_message(&mut *tx, &data, writer_tracker.clone()));
 					if written.is_none() {
 						retry_send = Ok(data);
 					}
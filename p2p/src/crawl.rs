@@ -0,0 +1,126 @@
+// Copyright 2020 The Epic Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Short lived connections to network nodes, used to crawl the network
+//! without running a full p2p server: a handshake followed by a request for
+//! the addresses the node knows about.
+
+use std::net::{Shutdown, SocketAddr, TcpStream};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::conn::Tracker;
+use crate::core::pow::Difficulty;
+use crate::handshake::Handshake;
+use crate::msg::{
+	read_body, read_discard, read_header, write_message, GetPeerAddrs, Msg, MsgHeaderWrapper,
+	PeerAddrs, Type,
+};
+use crate::types::{Capabilities, Error, PeerAddr, PeerInfo};
+
+/// What we learned about a node by connecting to it.
+pub struct NodeProbe {
+	/// The node info, as provided during the handshake.
+	pub info: PeerInfo,
+	/// The addresses the node sent back, empty if it didn't answer in time.
+	pub peer_addrs: Vec<PeerAddr>,
+}
+
+/// Connects to the node at the provided address, handshakes and asks for the
+/// addresses of the peers it knows with the provided capabilities. Messages
+/// other than the expected peer addresses are discarded. Onion addresses
+/// can't be probed.
+pub fn probe(
+	hs: &Handshake,
+	addr: PeerAddr,
+	capab: Capabilities,
+	timeout: Duration,
+) -> Result<NodeProbe, Error> {
+	let socket_addr = match addr {
+		PeerAddr::Ip(socket_addr) => socket_addr,
+		PeerAddr::Onion(..) => return Err(Error::ConnectionClose),
+	};
+	let mut conn = TcpStream::connect_timeout(&socket_addr, timeout)?;
+	conn.set_read_timeout(Some(timeout))?;
+	conn.set_write_timeout(Some(timeout))?;
+
+	let res = request_peer_addrs(hs, &mut conn, addr, capab, timeout);
+	let _ = conn.shutdown(Shutdown::Both);
+	res
+}
+
+fn request_peer_addrs(
+	hs: &Handshake,
+	conn: &mut TcpStream,
+	addr: PeerAddr,
+	capab: Capabilities,
+	timeout: Duration,
+) -> Result<NodeProbe, Error> {
+	// We don't listen, so don't advertise an address or any capability.
+	let self_addr = PeerAddr::Ip(SocketAddr::new(
+		if addr.ip().map_or(true, |ip| ip.is_ipv4()) {
+			"0.0.0.0".parse().unwrap()
+		} else {
+			"::".parse().unwrap()
+		},
+		0,
+	));
+	let (info, session) = hs.initiate(
+		Capabilities::UNKNOWN,
+		Difficulty::min(),
+		self_addr,
+		addr,
+		conn,
+	)?;
+	if session.is_some() {
+		// Never negotiated as we don't advertise ENCRYPTED.
+		return Err(Error::ConnectionClose);
+	}
+
+	let version = info.version;
+	let msg = Msg::new(
+		Type::GetPeerAddrs,
+		GetPeerAddrs {
+			capabilities: capab,
+		},
+		version,
+		hs.magic(),
+	)?;
+	write_message(conn, &msg, Arc::new(Tracker::new()))?;
+
+	// The node may send us other messages (pings, headers...) first. A read
+	// timeout means it won't answer, we still got to know it though.
+	let deadline = Instant::now() + timeout;
+	let mut peer_addrs = vec![];
+	while Instant::now() < deadline {
+		let header = match read_header(conn, version, hs.magic()) {
+			Ok(header) => header,
+			Err(e) => {
+				debug!("probe: no peer addrs from {}: {:?}", addr, e);
+				break;
+			}
+		};
+		match header {
+			MsgHeaderWrapper::Known(header) if header.msg_type == Type::PeerAddrs => {
+				let addrs: PeerAddrs = read_body(&header, conn, version)?;
+				peer_addrs = addrs.peers;
+				break;
+			}
+			MsgHeaderWrapper::Known(header) => read_discard(header.msg_len, conn)?,
+			MsgHeaderWrapper::Unknown(msg_len, _) => read_discard(msg_len, conn)?,
+		}
+	}
+
+	Ok(NodeProbe { info, peer_addrs })
+}
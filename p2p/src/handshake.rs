@@ -16,10 +16,13 @@ use crate::conn::Tracker;
 use crate::core::core::hash::Hash;
 use crate::core::pow::Difficulty;
 use crate::core::ser::ProtocolVersion;
-use crate::msg::{read_message, write_message, Hand, Msg, Shake, Type, USER_AGENT};
+use crate::local_addrs::LocalAddrs;
+use crate::msg::{read_message, write_message, Hand, Msg, SessionKey, Shake, Type, USER_AGENT};
 use crate::peer::Peer;
+use crate::session::{self, KeyExchange, Session};
 use crate::types::{Capabilities, Direction, Error, P2PConfig, PeerAddr, PeerInfo, PeerLiveInfo};
 use crate::util::RwLock;
+use ed25519_dalek::SigningKey;
 use rand::{rng, Rng};
 use std::collections::VecDeque;
 use std::net::{SocketAddr, TcpStream};
@@ -49,21 +52,49 @@ pub struct Handshake {
 	config: P2PConfig,
 	protocol_version: ProtocolVersion,
 	tracker: Arc<Tracker>,
+	/// Our listening and external addresses, learned from the remote side of
+	/// each handshake.
+	pub local_addrs: Arc<LocalAddrs>,
+	/// Our static key, used to authenticate encrypted sessions.
+	static_key: SigningKey,
+	/// Magic numbers of the messages on our network.
+	magic: [u8; 2],
 }
 
 impl Handshake {
-	/// Creates a new handshake handler
-	pub fn new(genesis: Hash, config: P2PConfig) -> Handshake {
+	/// Creates a new handshake handler for the network of the provided
+	/// message magic numbers.
+	pub fn new(genesis: Hash, config: P2PConfig, magic: [u8; 2]) -> Handshake {
 		Handshake {
 			nonces: Arc::new(RwLock::new(VecDeque::with_capacity(NONCES_CAP))),
 			addrs: Arc::new(RwLock::new(VecDeque::with_capacity(ADDRS_CAP))),
 			genesis,
+			local_addrs: Arc::new(LocalAddrs::new(&config)),
 			config,
 			protocol_version: ProtocolVersion::local(),
 			tracker: Arc::new(Tracker::new()),
+			static_key: session::random_static_key(),
+			magic,
 		}
 	}
 
+	/// Use the provided static key to authenticate encrypted sessions
+	/// instead of a random one, so our peers can pin it.
+	pub fn with_static_key(mut self, static_key: SigningKey) -> Handshake {
+		self.static_key = static_key;
+		self
+	}
+
+	/// Magic numbers of the messages on our network.
+	pub fn magic(&self) -> [u8; 2] {
+		self.magic
+	}
+
+	/// Our static key, hex encoded, as our peers would pin it.
+	pub fn static_key(&self) -> String {
+		crate::util::to_hex(self.static_key.verifying_key().to_bytes().to_vec())
+	}
+
 	/// Select a protocol version here that we know is supported by both us and the remote peer.
 	///
 	/// Current strategy is to simply use `min(local, remote)`.
@@ -76,19 +107,18 @@ impl Handshake {
 		Ok(version)
 	}
 
+	/// Handshake with the peer at `peer_addr` over `conn`, which may go through
+	/// a proxy.
 	pub fn initiate(
 		&self,
 		capabilities: Capabilities,
 		total_difficulty: Difficulty,
 		self_addr: PeerAddr,
+		peer_addr: PeerAddr,
 		conn: &mut TcpStream,
-	) -> Result<PeerInfo, Error> {
+	) -> Result<(PeerInfo, Option<Session>), Error> {
 		// prepare the first part of the handshake
 		let nonce = self.next_nonce();
-		let peer_addr = match conn.peer_addr() {
-			Ok(pa) => PeerAddr(pa),
-			Err(e) => return Err(Error::Connection(e)),
-		};
 
 		let hand = Hand {
 			version: self.protocol_version,
@@ -102,10 +132,10 @@ impl Handshake {
 		};
 
 		// write and read the handshake response
-		let msg = Msg::new(Type::Hand, hand, self.protocol_version)?;
+		let msg = Msg::new(Type::Hand, &hand, self.protocol_version, self.magic)?;
 		write_message(conn, &msg, self.tracker.clone())?;
 
-		let shake: Shake = read_message(conn, self.protocol_version, Type::Shake)?;
+		let shake: Shake = read_message(conn, self.protocol_version, self.magic, Type::Shake)?;
 		if shake.genesis != self.genesis {
 			return Err(Error::GenesisMismatch {
 				us: self.genesis,
@@ -115,6 +145,27 @@ impl Handshake {
 
 		let negotiated_version = self.negotiate_protocol_version(shake.version)?;
 
+		// The peer tells us which address it sees us connecting from.
+		if let Some(observed) = shake.receiver_addr {
+			self.local_addrs.record(observed, peer_addr);
+		}
+
+		// If denied then we want to close the connection
+		// (without providing our peer with any details why).
+		if Peer::is_denied(&self.config, peer_addr) {
+			return Err(Error::ConnectionClose);
+		}
+
+		let session = self.negotiate_session(
+			conn,
+			peer_addr,
+			&hand,
+			&shake,
+			capabilities & shake.capabilities,
+			negotiated_version,
+			true,
+		)?;
+
 		let peer_info = PeerInfo {
 			capabilities: shake.capabilities,
 			user_agent: shake.user_agent,
@@ -124,14 +175,9 @@ impl Handshake {
 				shake.total_difficulty.clone(),
 			))),
 			direction: Direction::Outbound,
+			static_key: session.as_ref().map(|s| s.peer_key()),
 		};
 
-		// If denied then we want to close the connection
-		// (without providing our peer with any details why).
-		if Peer::is_denied(&self.config, peer_info.addr) {
-			return Err(Error::ConnectionClose);
-		}
-
 		debug!(
 			"Connected! Cumulative {} offered from {:?}, {:?}, {:?}, {:?}",
 			shake.total_difficulty.clone(),
@@ -141,7 +187,7 @@ impl Handshake {
 			peer_info.capabilities,
 		);
 		// when more than one protocol version is supported, choosing should go here
-		Ok(peer_info)
+		Ok((peer_info, session))
 	}
 
 	pub fn accept(
@@ -149,8 +195,8 @@ impl Handshake {
 		capab: Capabilities,
 		total_difficulty: Difficulty,
 		conn: &mut TcpStream,
-	) -> Result<PeerInfo, Error> {
-		let hand: Hand = read_message(conn, self.protocol_version, Type::Hand)?;
+	) -> Result<(PeerInfo, Option<Session>), Error> {
+		let hand: Hand = read_message(conn, self.protocol_version, self.magic, Type::Hand)?;
 
 		// all the reasons we could refuse this connection for
 		if hand.genesis != self.genesis {
@@ -175,21 +221,16 @@ impl Handshake {
 
 		let negotiated_version = self.negotiate_protocol_version(hand.version)?;
 
-		// all good, keep peer info
-		let peer_info = PeerInfo {
-			capabilities: hand.capabilities,
-			user_agent: hand.user_agent,
-			addr: resolve_peer_addr(hand.sender_addr, &conn),
-			version: negotiated_version,
-			live_info: Arc::new(RwLock::new(PeerLiveInfo::new(hand.total_difficulty))),
-			direction: Direction::Inbound,
-		};
+		// The peer dialed us on hand.receiver_addr so this is one of the
+		// addresses we are reachable on.
+		let addr = resolve_peer_addr(hand.sender_addr, &conn);
+		self.local_addrs.record(hand.receiver_addr, addr);
 
 		// At this point we know the published ip and port of the peer
 		// so check if we are configured to explicitly allow or deny it.
 		// If denied then we want to close the connection
 		// (without providing our peer with any details why).
-		if Peer::is_denied(&self.config, peer_info.addr) {
+		if Peer::is_denied(&self.config, addr) {
 			return Err(Error::ConnectionClose);
 		}
 
@@ -200,14 +241,94 @@ impl Handshake {
 			genesis: self.genesis,
 			total_difficulty,
 			user_agent: USER_AGENT.to_string(),
+			receiver_addr: conn.peer_addr().ok().map(PeerAddr::Ip),
 		};
 
-		let msg = Msg::new(Type::Shake, shake, negotiated_version)?;
+		let msg = Msg::new(Type::Shake, &shake, negotiated_version, self.magic)?;
 		write_message(conn, &msg, self.tracker.clone())?;
 
+		let session = self.negotiate_session(
+			conn,
+			addr,
+			&hand,
+			&shake,
+			capab & hand.capabilities,
+			negotiated_version,
+			false,
+		)?;
+
+		// all good, keep peer info
+		let peer_info = PeerInfo {
+			capabilities: hand.capabilities,
+			user_agent: hand.user_agent,
+			addr,
+			version: negotiated_version,
+			live_info: Arc::new(RwLock::new(PeerLiveInfo::new(hand.total_difficulty))),
+			direction: Direction::Inbound,
+			static_key: session.as_ref().map(|s| s.peer_key()),
+		};
+
 		trace!("Success handshake with {}.", peer_info.addr);
 
-		Ok(peer_info)
+		Ok((peer_info, session))
+	}
+
+	/// Exchange session keys if both sides support encrypted transport,
+	/// falling back to plaintext otherwise. Pinned peers must support it and
+	/// authenticate with their pinned static key.
+	/// The initiator sends its SessionKey first, both sign the Hand/Shake
+	/// they exchanged.
+	fn negotiate_session(
+		&self,
+		conn: &mut TcpStream,
+		addr: PeerAddr,
+		hand: &Hand,
+		shake: &Shake,
+		common: Capabilities,
+		version: ProtocolVersion,
+		initiator: bool,
+	) -> Result<Option<Session>, Error> {
+		let pinned = self.config.pinned_key(addr);
+		if !common.contains(Capabilities::ENCRYPTED) {
+			if pinned.is_some() {
+				debug!("Pinned peer {} does not support encryption.", addr);
+				return Err(Error::ConnectionClose);
+			}
+			return Ok(None);
+		}
+
+		let transcript = session::transcript(hand, shake, common)?;
+		let kx = KeyExchange::new();
+		let theirs: SessionKey = if initiator {
+			let ours = kx.message(&self.static_key, transcript, None);
+			let msg = Msg::new(Type::SessionKey, ours, version, self.magic)?;
+			write_message(conn, &msg, self.tracker.clone())?;
+			read_message(conn, version, self.magic, Type::SessionKey)?
+		} else {
+			let theirs: SessionKey = read_message(conn, version, self.magic, Type::SessionKey)?;
+			let ours = kx.message(&self.static_key, transcript, Some(&theirs.ephemeral));
+			let msg = Msg::new(Type::SessionKey, ours, version, self.magic)?;
+			write_message(conn, &msg, self.tracker.clone())?;
+			theirs
+		};
+		let session = kx.complete(&theirs, transcript, initiator)?;
+
+		if let Some(key) = pinned {
+			if !key.eq_ignore_ascii_case(&session.peer_key()) {
+				warn!(
+					"Pinned peer {} authenticated with unexpected key {}.",
+					addr,
+					session.peer_key()
+				);
+				return Err(Error::ConnectionClose);
+			}
+		}
+		debug!(
+			"Encrypted session with {}, key {}.",
+			addr,
+			session.peer_key()
+		);
+		Ok(Some(session))
 	}
 
 	/// Generate a new random nonce and store it in our ring buffer
@@ -224,10 +345,16 @@ impl Handshake {
 }
 
 /// Resolve the correct peer_addr based on the connection and the advertised port.
+/// Peers reaching us through our onion service connect from our own tor
+/// daemon, their advertised onion address is all we know about them then.
 fn resolve_peer_addr(advertised: PeerAddr, conn: &TcpStream) -> PeerAddr {
-	let port = advertised.0.port();
+	let port = advertised.port();
 	if let Ok(addr) = conn.peer_addr() {
-		PeerAddr(SocketAddr::new(addr.ip(), port))
+		if advertised.is_onion() && addr.ip().is_loopback() {
+			advertised
+		} else {
+			PeerAddr::Ip(SocketAddr::new(addr.ip(), port))
+		}
 	} else {
 		advertised
 	}
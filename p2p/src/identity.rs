@@ -0,0 +1,274 @@
+// Copyright 2020 The Epic Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Persistent identity of the node, the key our onion service key is
+//! derived from, so our onion address survives a wiped tor directory.
+//!
+//! The key is stored under db_root, encrypted with ChaCha20-Poly1305 under a
+//! key stretched from a password (PBKDF2-HMAC-SHA512). The password is taken
+//! from the `EPIC_IDENTITY_PASSWORD` environment variable, the key is still
+//! encrypted at rest without one but then only as protected as the file.
+//! Setting a password later encrypts the existing key again with it.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use ed25519_dalek::SigningKey as DalekSecretKey;
+use ed25519_dalek::VerifyingKey as DalekPublicKey;
+use hmac::Hmac;
+use pbkdf2::pbkdf2;
+use rand::{rng, Rng};
+use sha2::Sha512;
+
+use crate::tor::address;
+use crate::types::Error;
+use crate::util::secp::key::SecretKey;
+use crate::util::static_secp_instance;
+
+/// File (under db_root) holding our encrypted identity key.
+const IDENTITY_FILE: &str = "node_identity";
+
+/// Previous identity, kept around on rotation.
+const PREV_IDENTITY_FILE: &str = "node_identity.prev";
+
+/// Environment variable holding the identity password.
+pub const IDENTITY_PASSWORD_ENV: &str = "EPIC_IDENTITY_PASSWORD";
+
+const FORMAT_VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const KEY_LEN: usize = 32;
+const TAG_LEN: usize = 16;
+const KDF_ROUNDS: u32 = 100_000;
+
+/// The identity key of the node.
+pub struct NodeIdentity {
+	key: SecretKey,
+}
+
+impl NodeIdentity {
+	/// A new random identity.
+	pub fn generate() -> Result<NodeIdentity, Error> {
+		let secp = static_secp_instance();
+		let secp = secp.lock();
+		loop {
+			// out of range scalars are astronomically unlikely, just retry
+			if let Ok(key) = SecretKey::from_slice(&secp, &rng().random::<[u8; KEY_LEN]>()) {
+				return Ok(NodeIdentity { key });
+			}
+		}
+	}
+
+	/// Loads our identity from db_root, creating a new one on first start.
+	pub fn load_or_create(db_root: &str, password: &str) -> Result<NodeIdentity, Error> {
+		let path = identity_path(db_root);
+		if path.exists() {
+			return NodeIdentity::load(&path, password);
+		}
+		let identity = NodeIdentity::generate()?;
+		identity.save(db_root, password)?;
+		info!("Created a new node identity in {}", path.display());
+		Ok(identity)
+	}
+
+	/// Loads and decrypts the identity stored in the provided file. A file
+	/// written before the password was set is encrypted again with it.
+	pub fn load(path: &Path, password: &str) -> Result<NodeIdentity, Error> {
+		let data = fs::read(path)?;
+		let (seed, reencrypt) = decrypt_or_unprotected(&data, password)?;
+		if reencrypt {
+			fs::write(path, encrypt(&seed, password))?;
+			set_permissions(path)?;
+			info!("Encrypted {} with the identity password", path.display());
+		}
+		let secp = static_secp_instance();
+		let secp = secp.lock();
+		let key = SecretKey::from_slice(&secp, &seed)
+			.map_err(|e| Error::Encryption(format!("invalid identity key: {:?}", e)))?;
+		Ok(NodeIdentity { key })
+	}
+
+	/// Encrypts and stores the identity under db_root.
+	pub fn save(&self, db_root: &str, password: &str) -> Result<(), Error> {
+		fs::create_dir_all(db_root)?;
+		let path = identity_path(db_root);
+		fs::write(&path, encrypt(&self.key.0, password))?;
+		set_permissions(&path)?;
+		Ok(())
+	}
+
+	/// Replaces our identity with a new one, the previous one being kept
+	/// next to it. Our onion address changes accordingly.
+	pub fn rotate(db_root: &str, password: &str) -> Result<NodeIdentity, Error> {
+		let path = identity_path(db_root);
+		if path.exists() {
+			// make sure we can read the identity we're about to retire
+			NodeIdentity::load(&path, password)?;
+			let prev = Path::new(db_root).join(PREV_IDENTITY_FILE);
+			fs::copy(&path, &prev)?;
+			set_permissions(&prev)?;
+		}
+		let identity = NodeIdentity::generate()?;
+		identity.save(db_root, password)?;
+		Ok(identity)
+	}
+
+	/// Copies the encrypted identity to `dest`, which must not exist yet.
+	pub fn backup(db_root: &str, dest: &Path) -> Result<(), Error> {
+		if dest.exists() {
+			return Err(Error::Encryption(format!(
+				"{} already exists",
+				dest.display()
+			)));
+		}
+		fs::copy(identity_path(db_root), dest)?;
+		set_permissions(dest)?;
+		Ok(())
+	}
+
+	/// The ed25519 key our onion service is published under.
+	pub fn onion_keypair(&self) -> Result<(DalekSecretKey, DalekPublicKey), Error> {
+		address::ed25519_keypair(&self.key)
+	}
+
+	/// Our onion address, without ".onion".
+	pub fn onion_address(&self) -> Result<String, Error> {
+		let (_, pub_key) = self.onion_keypair()?;
+		address::onion_v3_from_pubkey(&pub_key)
+	}
+
+	/// The secret key itself, for the tor onion service file helpers.
+	pub fn secret_key(&self) -> &SecretKey {
+		&self.key
+	}
+}
+
+/// Path of the identity file under db_root.
+pub fn identity_path(db_root: &str) -> PathBuf {
+	Path::new(db_root).join(IDENTITY_FILE)
+}
+
+/// The identity password, empty when not set.
+pub fn password_from_env() -> String {
+	env::var(IDENTITY_PASSWORD_ENV).unwrap_or_default()
+}
+
+fn cipher(password: &str, salt: &[u8]) -> ChaCha20Poly1305 {
+	let mut key = [0u8; KEY_LEN];
+	let _ = pbkdf2::<Hmac<Sha512>>(password.as_bytes(), salt, KDF_ROUNDS, &mut key);
+	ChaCha20Poly1305::new(Key::from_slice(&key))
+}
+
+/// version | salt | nonce | encrypted key and tag
+/// Also used for the static key of our encrypted p2p sessions.
+pub(crate) fn encrypt(seed: &[u8], password: &str) -> Vec<u8> {
+	let salt = rng().random::<[u8; SALT_LEN]>();
+	let nonce = rng().random::<[u8; NONCE_LEN]>();
+	let ciphertext = cipher(password, &salt)
+		.encrypt(Nonce::from_slice(&nonce), seed)
+		.expect("encryption of a 32 bytes key can't fail");
+	let mut data = vec![FORMAT_VERSION];
+	data.extend_from_slice(&salt);
+	data.extend_from_slice(&nonce);
+	data.extend_from_slice(&ciphertext);
+	data
+}
+
+pub(crate) fn decrypt(data: &[u8], password: &str) -> Result<Vec<u8>, Error> {
+	if !is_encrypted(data) {
+		return Err(Error::Encryption("invalid encrypted key file".to_owned()));
+	}
+	let salt = &data[1..1 + SALT_LEN];
+	let nonce = &data[1 + SALT_LEN..1 + SALT_LEN + NONCE_LEN];
+	cipher(password, salt)
+		.decrypt(Nonce::from_slice(nonce), &data[1 + SALT_LEN + NONCE_LEN..])
+		.map_err(|_| Error::Encryption("wrong identity password".to_owned()))
+}
+
+/// Decrypts a key file, falling back to the empty password it was written
+/// with before a password was set. Tells whether it has to be encrypted again
+/// with the provided password.
+pub(crate) fn decrypt_or_unprotected(
+	data: &[u8],
+	password: &str,
+) -> Result<(Vec<u8>, bool), Error> {
+	match decrypt(data, password) {
+		Ok(seed) => Ok((seed, false)),
+		Err(e) if password.is_empty() => Err(e),
+		Err(e) => decrypt(data, "").map(|seed| (seed, true)).map_err(|_| e),
+	}
+}
+
+/// Whether data has the layout of an encrypted key file.
+pub(crate) fn is_encrypted(data: &[u8]) -> bool {
+	data.len() == 1 + SALT_LEN + NONCE_LEN + KEY_LEN + TAG_LEN && data[0] == FORMAT_VERSION
+}
+
+#[cfg(unix)]
+pub(crate) fn set_permissions(path: &Path) -> Result<(), Error> {
+	use std::os::unix::prelude::*;
+	fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+	Ok(())
+}
+
+#[cfg(windows)]
+pub(crate) fn set_permissions(_path: &Path) -> Result<(), Error> {
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_identity_persistence() {
+		let dir = tempfile::tempdir().unwrap();
+		let db_root = dir.path().to_str().unwrap();
+
+		let identity = NodeIdentity::load_or_create(db_root, "secret").unwrap();
+		let address = identity.onion_address().unwrap();
+		let data = fs::read(identity_path(db_root)).unwrap();
+		assert!(!data.windows(KEY_LEN).any(|w| w == &identity.key.0[..]));
+
+		let reloaded = NodeIdentity::load_or_create(db_root, "secret").unwrap();
+		assert_eq!(reloaded.onion_address().unwrap(), address);
+		match NodeIdentity::load(&identity_path(db_root), "wrong") {
+			Err(Error::Encryption(_)) => (),
+			_ => panic!("identity decrypted with the wrong password"),
+		}
+
+		let backup = dir.path().join("backup");
+		NodeIdentity::backup(db_root, &backup).unwrap();
+		assert!(NodeIdentity::backup(db_root, &backup).is_err());
+		let rotated = NodeIdentity::rotate(db_root, "secret").unwrap();
+		assert_ne!(rotated.onion_address().unwrap(), address);
+		let restored = NodeIdentity::load(&backup, "secret").unwrap();
+		assert_eq!(restored.onion_address().unwrap(), address);
+		let prev = NodeIdentity::load(&dir.path().join(PREV_IDENTITY_FILE), "secret").unwrap();
+		assert_eq!(prev.onion_address().unwrap(), address);
+
+		// an identity created without a password is encrypted with the one set later
+		let unprotected = NodeIdentity::generate().unwrap();
+		unprotected.save(db_root, "").unwrap();
+		let loaded = NodeIdentity::load_or_create(db_root, "secret").unwrap();
+		assert_eq!(
+			loaded.onion_address().unwrap(),
+			unprotected.onion_address().unwrap()
+		);
+		assert!(decrypt(&fs::read(identity_path(db_root)).unwrap(), "secret").is_ok());
+	}
+}
@@ -35,23 +35,32 @@ extern crate serde_derive;
 #[macro_use]
 extern crate log;
 
+mod addr_manager;
 mod conn;
+pub mod crawl;
 pub mod handshake;
+pub mod identity;
+mod local_addrs;
 pub mod msg;
 mod peer;
 mod peers;
 mod protocol;
 mod serv;
+mod session;
 mod store;
 pub mod tor;
 pub mod types;
+pub use crate::addr_manager::NetGroup;
 pub use crate::conn::SEND_CHANNEL_CAP;
+pub use crate::identity::NodeIdentity;
+pub use crate::local_addrs::LocalAddrs;
 pub use crate::peer::Peer;
-pub use crate::peers::Peers;
+pub use crate::peers::{PeerProof, Peers};
 pub use crate::serv::{DummyAdapter, Server};
+pub use crate::session::load_or_create_static_key;
 pub use crate::store::{PeerData, State};
 pub use crate::tor::process;
 pub use crate::types::{
-	Capabilities, ChainAdapter, Direction, Error, P2PConfig, PeerAddr, PeerInfo, ReasonForBan,
-	Seeding, TxHashSetRead, MAX_BLOCK_HEADERS, MAX_LOCATORS, MAX_PEER_ADDRS,
+	Capabilities, ChainAdapter, Direction, DnsSeeds, Error, P2PConfig, PeerAddr, PeerInfo,
+	ReasonForBan, Seeding, TorMode, TxHashSetRead, MAX_BLOCK_HEADERS, MAX_LOCATORS, MAX_PEER_ADDRS,
 };
@@ -0,0 +1,178 @@
+// Copyright 2020 The Epic Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tracks the addresses this node is reachable on, both configured ones and
+//! the ones our peers report seeing us on during the handshake.
+
+use crate::types::{P2PConfig, PeerAddr};
+use crate::util::RwLock;
+use lru_cache::LruCache;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+
+/// Number of distinct peers that must report the same address before we
+/// consider it our external address and start advertising it.
+const MIN_CONFIRMATIONS: usize = 2;
+
+/// Max number of peers whose report we keep track of, the ones that
+/// reported least recently being dropped first.
+const MAX_REPORTERS: usize = 256;
+
+/// Our listening and externally reachable addresses.
+pub struct LocalAddrs {
+	port: u16,
+	listen: Vec<SocketAddr>,
+	configured: Vec<PeerAddr>,
+	/// ip of the reporting peer -> the ip it sees us on
+	observed: RwLock<LruCache<IpAddr, IpAddr>>,
+}
+
+impl LocalAddrs {
+	pub fn new(config: &P2PConfig) -> LocalAddrs {
+		LocalAddrs {
+			port: config.port,
+			listen: config.listen_addrs(),
+			configured: config.external_addrs.clone().unwrap_or_default(),
+			observed: RwLock::new(LruCache::new(MAX_REPORTERS)),
+		}
+	}
+
+	/// The socket addresses we are listening on.
+	pub fn listen_addrs(&self) -> Vec<SocketAddr> {
+		self.listen.clone()
+	}
+
+	/// A peer told us it sees us on the provided address.
+	/// Only publicly routable addresses are taken into account and each peer
+	/// (by ip) only gets a single vote, its latest report replacing the
+	/// previous one.
+	pub fn record(&self, observed: PeerAddr, reporter: PeerAddr) {
+		if !observed.is_routable() {
+			return;
+		}
+		// we know our onion address, and peers reaching us over tor can't
+		// tell our clearnet one
+		let (ip, reporter_ip) = match (observed.ip(), reporter.ip()) {
+			(Some(ip), Some(reporter_ip)) => (ip, reporter_ip),
+			_ => return,
+		};
+		// evicts the peer that reported least recently when full
+		let prev = self.observed.write().insert(reporter_ip, ip);
+		if prev != Some(ip) {
+			debug!("local_addrs: {} reports our address as {}", reporter, ip);
+		}
+	}
+
+	/// All addresses we believe are externally reachable, configured ones
+	/// first followed by observed ones ordered by number of confirmations.
+	pub fn external_addrs(&self) -> Vec<PeerAddr> {
+		let mut res = self.configured.clone();
+
+		let mut confirmations = HashMap::new();
+		for (_, ip) in self.observed.read().iter() {
+			*confirmations.entry(*ip).or_insert(0) += 1;
+		}
+		let mut observed = confirmations
+			.into_iter()
+			.filter(|(_, count)| *count >= MIN_CONFIRMATIONS)
+			.map(|(ip, count)| (PeerAddr::Ip(SocketAddr::new(ip, self.port)), count))
+			.collect::<Vec<_>>();
+		observed.sort_by(|a, b| b.1.cmp(&a.1));
+
+		for (addr, _) in observed {
+			if !res.contains(&addr) {
+				res.push(addr);
+			}
+		}
+		res
+	}
+
+	/// Our best known external address of the same family (v4/v6/onion) as
+	/// the provided remote address, used when telling a peer who we are.
+	pub fn external_addr_for(&self, remote: PeerAddr) -> Option<PeerAddr> {
+		let family = |a: &PeerAddr| a.ip().map(|ip| ip.is_ipv4());
+		self.external_addrs()
+			.into_iter()
+			.find(|a| family(a) == family(&remote))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn addr(s: &str) -> PeerAddr {
+		PeerAddr::Ip(s.parse().unwrap())
+	}
+
+	#[test]
+	fn test_external_addr_confirmations() {
+		let config = P2PConfig {
+			port: 3414,
+			..P2PConfig::default()
+		};
+		let local = LocalAddrs::new(&config);
+
+		// private and loopback addresses are never recorded
+		local.record(addr("192.168.1.10:3414"), addr("8.8.8.8:3414"));
+		local.record(addr("127.0.0.1:3414"), addr("8.8.4.4:3414"));
+		assert!(local.external_addrs().is_empty());
+
+		// a single peer is not enough, even when reporting twice
+		local.record(addr("1.2.3.4:50000"), addr("8.8.8.8:3414"));
+		local.record(addr("1.2.3.4:50001"), addr("8.8.8.8:3414"));
+		assert!(local.external_addrs().is_empty());
+
+		local.record(addr("1.2.3.4:50002"), addr("8.8.4.4:3414"));
+		assert_eq!(local.external_addrs(), vec![addr("1.2.3.4:3414")]);
+
+		// v6 observations are tracked independently
+		local.record(addr("[2001:db8::1]:50000"), addr("[2001:db8::2]:3414"));
+		local.record(addr("[2001:db8::1]:50000"), addr("[2001:db8::3]:3414"));
+		assert_eq!(
+			local.external_addr_for(addr("[2001:db8::4]:3414")),
+			Some(addr("[2001:db8::1]:3414"))
+		);
+		assert_eq!(
+			local.external_addr_for(addr("5.6.7.8:3414")),
+			Some(addr("1.2.3.4:3414"))
+		);
+	}
+
+	#[test]
+	fn test_observed_addrs_bounded() {
+		let local = LocalAddrs::new(&P2PConfig::default());
+		local.record(addr("1.2.3.4:50000"), addr("8.8.8.8:3414"));
+		local.record(addr("1.2.3.4:50000"), addr("8.8.4.4:3414"));
+		assert_eq!(local.external_addrs().len(), 1);
+
+		// a peer changing its report takes its vote back
+		local.record(addr("5.6.7.8:50000"), addr("8.8.4.4:3414"));
+		assert!(local.external_addrs().is_empty());
+		local.record(addr("1.2.3.4:50000"), addr("8.8.4.4:3414"));
+
+		// peers flooding us with reports push the oldest ones out, new
+		// addresses still get a chance
+		for i in 0..MAX_REPORTERS as u32 {
+			let ip = std::net::Ipv4Addr::from(0x0909_0000 + i);
+			let reporter = PeerAddr::Ip(SocketAddr::new(ip.into(), 3414));
+			local.record(addr("6.6.6.6:50000"), reporter);
+		}
+		assert_eq!(local.observed.read().len(), MAX_REPORTERS);
+		assert_eq!(local.external_addrs(), vec![addr("6.6.6.6:3414")]);
+		local.record(addr("2.2.2.2:50000"), addr("8.8.8.8:3414"));
+		local.record(addr("2.2.2.2:50000"), addr("8.8.4.4:3414"));
+		assert_eq!(local.external_addrs()[1], addr("2.2.2.2:3414"));
+	}
+}
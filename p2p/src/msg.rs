@@ -14,9 +14,12 @@
 
 //! Message types that transit over the network and related serialization code.
 
+use crate::chain::{KernelProof, OutputProof};
 use crate::conn::Tracker;
 use crate::core::core::hash::Hash;
-use crate::core::core::BlockHeader;
+use crate::core::core::{BlockHeader, ShortId, Transaction};
+use crate::core::global::ChainParams;
+use crate::core::network::{FLOONET_MAGIC, MAINNET_MAGIC, OTHER_MAGIC};
 use crate::core::pow::Difficulty;
 use crate::core::ser::{
 	self, FixedLength, ProtocolVersion, Readable, Reader, StreamingReader, Writeable, Writer,
@@ -25,6 +28,7 @@ use crate::core::{consensus, global};
 use crate::types::{
 	Capabilities, Error, PeerAddr, ReasonForBan, MAX_BLOCK_HEADERS, MAX_LOCATORS, MAX_PEER_ADDRS,
 };
+use crate::util::secp::pedersen::Commitment;
 use num::FromPrimitive;
 use std::fs::File;
 use std::io::{Read, Write};
@@ -33,11 +37,6 @@ use std::sync::Arc;
 /// Epic's user agent with current version
 pub const USER_AGENT: &'static str = concat!("MW/Epic ", env!("CARGO_PKG_VERSION"));
 
-/// Magic numbers expected in the header of every message
-const OTHER_MAGIC: [u8; 2] = [73, 43];
-const FLOONET_MAGIC: [u8; 2] = [83, 59];
-const MAINNET_MAGIC: [u8; 2] = [97, 61];
-
 // Types of messages.
 // Note: Values here are *important* so we should only add new values at the
 // end.
@@ -71,6 +70,13 @@ enum_from_primitive! {
 		FastHeaders = 24,
 		OnionAddressRequest = 25,
 		OnionAddressResponse = 26,
+		SessionKey = 27,
+		GetBlockTxs = 28,
+		BlockTxs = 29,
+		OutputProofRequest = 30,
+		OutputProofResponse = 31,
+		KernelProofRequest = 32,
+		KernelProofResponse = 33,
 
 	}
 }
@@ -80,6 +86,11 @@ fn max_block_size() -> u64 {
 	(global::max_block_weight() / consensus::BLOCK_OUTPUT_WEIGHT * 708) as u64
 }
 
+/// Max theoretical number of kernels in a block.
+fn max_block_kernels() -> u64 {
+	(global::max_block_weight() / consensus::BLOCK_KERNEL_WEIGHT) as u64
+}
+
 // Max msg size when msg type is unknown.
 fn default_max_msg_size() -> u64 {
 	max_block_size()
@@ -90,7 +101,7 @@ fn max_msg_size(msg_type: Type) -> u64 {
 	match msg_type {
 		Type::Error => 0,
 		Type::Hand => 128,
-		Type::Shake => 88,
+		Type::Shake => 88 + 19,
 		Type::Ping => 16,
 		Type::Pong => 16,
 		Type::GetPeerAddrs => 4,
@@ -115,13 +126,26 @@ fn max_msg_size(msg_type: Type) -> u64 {
 		Type::KernelDataResponse => 8,
 		Type::OnionAddressRequest => 0,
 		Type::OnionAddressResponse => 256,
+		Type::SessionKey => 128,
+		Type::GetBlockTxs => 42 + 6 * max_block_kernels(),
+		Type::BlockTxs => max_block_size(),
+		Type::OutputProofRequest => 33,
+		Type::OutputProofResponse => 4096,
+		Type::KernelProofRequest => 33,
+		Type::KernelProofResponse => 4096,
 	}
 }
 
-fn magic() -> [u8; 2] {
-	match *global::CHAIN_TYPE.read() {
+/// Magic numbers expected in the header of every message on the network of
+/// the provided chain parameters.
+pub fn magic(params: &ChainParams) -> [u8; 2] {
+	match params.chain_type {
 		global::ChainTypes::Floonet => FLOONET_MAGIC,
 		global::ChainTypes::Mainnet => MAINNET_MAGIC,
+		global::ChainTypes::UserTesting => params
+			.network
+			.as_ref()
+			.map_or(OTHER_MAGIC, |network| network.magic),
 		_ => OTHER_MAGIC,
 	}
 }
@@ -138,10 +162,11 @@ impl Msg {
 		msg_type: Type,
 		msg: T,
 		version: ProtocolVersion,
+		magic: [u8; 2],
 	) -> Result<Msg, Error> {
 		let body = ser::ser_vec(&msg, version)?;
 		Ok(Msg {
-			header: MsgHeader::new(msg_type, body.len() as u64),
+			header: MsgHeader::new(msg_type, body.len() as u64, magic),
 			body,
 			attachment: None,
 			version,
@@ -162,9 +187,16 @@ impl Msg {
 pub fn read_header(
 	stream: &mut dyn Read,
 	version: ProtocolVersion,
+	magic: [u8; 2],
 ) -> Result<MsgHeaderWrapper, Error> {
 	let mut head = vec![0u8; MsgHeader::LEN];
 	stream.read_exact(&mut head)?;
+	if head[..2] != magic {
+		return Err(Error::Serialization(ser::Error::UnexpectedData {
+			expected: magic.to_vec(),
+			received: head[..2].to_vec(),
+		}));
+	}
 	let header = ser::deserialize::<MsgHeaderWrapper>(&mut &head[..], version)?;
 	Ok(header)
 }
@@ -204,9 +236,10 @@ pub fn read_discard(msg_len: u64, stream: &mut dyn Read) -> Result<(), Error> {
 pub fn read_message<T: Readable>(
 	stream: &mut dyn Read,
 	version: ProtocolVersion,
+	magic: [u8; 2],
 	msg_type: Type,
 ) -> Result<T, Error> {
-	match read_header(stream, version)? {
+	match read_header(stream, version, magic)? {
 		MsgHeaderWrapper::Known(header) => {
 			if header.msg_type == msg_type {
 				read_body(&header, stream, version)
@@ -272,9 +305,9 @@ pub struct MsgHeader {
 
 impl MsgHeader {
 	/// Creates a new message header.
-	pub fn new(msg_type: Type, len: u64) -> MsgHeader {
+	pub fn new(msg_type: Type, len: u64, magic: [u8; 2]) -> MsgHeader {
 		MsgHeader {
-			magic: magic(),
+			magic,
 			msg_type,
 			msg_len: len,
 		}
@@ -301,9 +334,8 @@ impl Writeable for MsgHeader {
 
 impl Readable for MsgHeaderWrapper {
 	fn read(reader: &mut dyn Reader) -> Result<MsgHeaderWrapper, ser::Error> {
-		let m = magic();
-		reader.expect_u8(m[0])?;
-		reader.expect_u8(m[1])?;
+		// checked against the magic of our network by read_header
+		let m = [reader.read_u8()?, reader.read_u8()?];
 
 		// Read the msg header.
 		// We do not yet know if the msg type is one we support locally.
@@ -423,6 +455,9 @@ pub struct Shake {
 	pub total_difficulty: Difficulty,
 	/// name of version of the software
 	pub user_agent: String,
+	/// network address the sender sees the receiver connecting from,
+	/// lets the receiver discover its own external address
+	pub receiver_addr: Option<PeerAddr>,
 }
 
 impl Writeable for Shake {
@@ -432,6 +467,10 @@ impl Writeable for Shake {
 		self.total_difficulty.write(writer)?;
 		writer.write_bytes(&self.user_agent)?;
 		self.genesis.write(writer)?;
+		// Trailing and optional, older peers simply ignore it.
+		if let Some(receiver_addr) = self.receiver_addr {
+			receiver_addr.write(writer)?;
+		}
 		Ok(())
 	}
 }
@@ -445,12 +484,16 @@ impl Readable for Shake {
 		let ua = reader.read_bytes_len_prefix()?;
 		let user_agent = String::from_utf8(ua).map_err(|_| ser::Error::CorruptedData)?;
 		let genesis = Hash::read(reader)?;
+		// this only works because the shake is read from its own msg body and
+		// this is the last data element, older peers do not send it
+		let receiver_addr = PeerAddr::read(reader).ok();
 		Ok(Shake {
 			version,
 			capabilities,
 			genesis,
 			total_difficulty,
 			user_agent,
+			receiver_addr,
 		})
 	}
 }
@@ -866,6 +909,190 @@ impl Readable for OnionAddressResponse {
 	}
 }
 
+/// Sent by both sides right after Hand/Shake when both peers advertise
+/// encrypted transport. Carries an ephemeral x25519 public key signed with
+/// the sender's static ed25519 key.
+#[derive(Debug, Clone)]
+pub struct SessionKey {
+	pub ephemeral: [u8; 32],
+	pub static_key: [u8; 32],
+	pub signature: [u8; 64],
+}
+
+impl Writeable for SessionKey {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		writer.write_fixed_bytes(&self.ephemeral)?;
+		writer.write_fixed_bytes(&self.static_key)?;
+		writer.write_fixed_bytes(&&self.signature[..])?;
+		Ok(())
+	}
+}
+
+impl Readable for SessionKey {
+	fn read(reader: &mut dyn Reader) -> Result<SessionKey, ser::Error> {
+		let mut ephemeral = [0u8; 32];
+		ephemeral.copy_from_slice(&reader.read_fixed_bytes(32)?);
+		let mut static_key = [0u8; 32];
+		static_key.copy_from_slice(&reader.read_fixed_bytes(32)?);
+		let mut signature = [0u8; 64];
+		signature.copy_from_slice(&reader.read_fixed_bytes(64)?);
+		Ok(SessionKey {
+			ephemeral,
+			static_key,
+			signature,
+		})
+	}
+}
+
+/// Request for the transactions of a compact block we could not find in
+/// our pool, identified by their kernel short ids.
+#[derive(Debug, Clone)]
+pub struct GetBlockTxs {
+	/// Hash of the compact block.
+	pub hash: Hash,
+	/// Nonce of the compact block, needed to compute the short ids.
+	pub nonce: u64,
+	/// Short ids of the kernels we are missing.
+	pub kern_ids: Vec<ShortId>,
+}
+
+impl Writeable for GetBlockTxs {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		self.hash.write(writer)?;
+		writer.write_u64(self.nonce)?;
+		writer.write_u16(self.kern_ids.len() as u16)?;
+		self.kern_ids.write(writer)
+	}
+}
+
+impl Readable for GetBlockTxs {
+	fn read(reader: &mut dyn Reader) -> Result<GetBlockTxs, ser::Error> {
+		let hash = Hash::read(reader)?;
+		let nonce = reader.read_u64()?;
+		let len = reader.read_u16()?;
+		let kern_ids = ser::read_multi(reader, len as u64)?;
+		Ok(GetBlockTxs {
+			hash,
+			nonce,
+			kern_ids,
+		})
+	}
+}
+
+/// Response to GetBlockTxs, with the transactions we found for the requested
+/// short ids (possibly not all of them).
+#[derive(Debug, Clone)]
+pub struct BlockTxs {
+	/// Hash of the compact block.
+	pub hash: Hash,
+	pub txs: Vec<Transaction>,
+}
+
+impl Writeable for BlockTxs {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		self.hash.write(writer)?;
+		writer.write_u16(self.txs.len() as u16)?;
+		self.txs.write(writer)
+	}
+}
+
+impl Readable for BlockTxs {
+	fn read(reader: &mut dyn Reader) -> Result<BlockTxs, ser::Error> {
+		let hash = Hash::read(reader)?;
+		let len = reader.read_u16()?;
+		let txs = ser::read_multi(reader, len as u64)?;
+		Ok(BlockTxs { hash, txs })
+	}
+}
+
+/// Request for a merkle proof of an output (by commitment) or of a
+/// kernel (by excess), against the head of the peer.
+#[derive(Debug, Clone)]
+pub struct ProofRequest {
+	pub commit: Commitment,
+}
+
+impl Writeable for ProofRequest {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		self.commit.write(writer)
+	}
+}
+
+impl Readable for ProofRequest {
+	fn read(reader: &mut dyn Reader) -> Result<ProofRequest, ser::Error> {
+		let commit = Commitment::read(reader)?;
+		Ok(ProofRequest { commit })
+	}
+}
+
+/// Response to OutputProofRequest, with the hash of the header the proof is
+/// built on, or nothing if the peer doesn't have the output.
+#[derive(Debug, Clone)]
+pub struct OutputProofResponse {
+	pub commit: Commitment,
+	pub proof: Option<(Hash, OutputProof)>,
+}
+
+impl Writeable for OutputProofResponse {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		self.commit.write(writer)?;
+		write_proof(writer, &self.proof)
+	}
+}
+
+impl Readable for OutputProofResponse {
+	fn read(reader: &mut dyn Reader) -> Result<OutputProofResponse, ser::Error> {
+		let commit = Commitment::read(reader)?;
+		let proof = read_proof(reader)?;
+		Ok(OutputProofResponse { commit, proof })
+	}
+}
+
+/// Response to KernelProofRequest, with the hash of the header the proof is
+/// built on, or nothing if the peer doesn't know the kernel.
+#[derive(Debug, Clone)]
+pub struct KernelProofResponse {
+	pub excess: Commitment,
+	pub proof: Option<(Hash, KernelProof)>,
+}
+
+impl Writeable for KernelProofResponse {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		self.excess.write(writer)?;
+		write_proof(writer, &self.proof)
+	}
+}
+
+impl Readable for KernelProofResponse {
+	fn read(reader: &mut dyn Reader) -> Result<KernelProofResponse, ser::Error> {
+		let excess = Commitment::read(reader)?;
+		let proof = read_proof(reader)?;
+		Ok(KernelProofResponse { excess, proof })
+	}
+}
+
+fn write_proof<W: Writer, T: Writeable>(
+	writer: &mut W,
+	proof: &Option<(Hash, T)>,
+) -> Result<(), ser::Error> {
+	match proof {
+		Some((hash, proof)) => {
+			writer.write_u8(1)?;
+			hash.write(writer)?;
+			proof.write(writer)
+		}
+		None => writer.write_u8(0),
+	}
+}
+
+fn read_proof<T: Readable>(reader: &mut dyn Reader) -> Result<Option<(Hash, T)>, ser::Error> {
+	match reader.read_u8()? {
+		0 => Ok(None),
+		1 => Ok(Some((Hash::read(reader)?, T::read(reader)?))),
+		_ => Err(ser::Error::CorruptedData),
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -916,4 +1143,49 @@ mod tests {
 			"Deserialization of invalid BanReason should fail"
 		);
 	}
+
+	#[test]
+	fn test_output_proof_response_serialization() {
+		use crate::core::core::merkle_proof::MerkleProof;
+		use crate::core::core::{OutputFeatures, OutputIdentifier};
+
+		let commit = Commitment::from_vec(vec![9; 33]);
+		let proof = OutputProof {
+			output: OutputIdentifier::new(OutputFeatures::Plain, &commit),
+			pos: 4,
+			pmmr_root: Hash::from_vec(&[1; 32]),
+			bitmap_root: Hash::from_vec(&[2; 32]),
+			proof: MerkleProof {
+				mmr_size: 4,
+				path: vec![Hash::from_vec(&[3; 32])],
+			},
+		};
+		for proof in [None, Some((Hash::from_vec(&[4; 32]), proof))] {
+			let response = OutputProofResponse { commit, proof };
+			let bytes = ser::ser_vec(&response, ProtocolVersion::local()).unwrap();
+			assert!(bytes.len() as u64 <= max_msg_size(Type::OutputProofResponse));
+			let read: OutputProofResponse =
+				ser::deserialize(&mut &bytes[..], ProtocolVersion::local()).unwrap();
+			assert_eq!(read.commit, commit);
+			match (read.proof, response.proof) {
+				(None, None) => {}
+				(Some((h1, p1)), Some((h2, p2))) => {
+					assert_eq!(h1, h2);
+					assert_eq!(p1.output, p2.output);
+					assert_eq!(p1.pos, p2.pos);
+					assert_eq!(p1.pmmr_root, p2.pmmr_root);
+					assert_eq!(p1.bitmap_root, p2.bitmap_root);
+					assert_eq!(p1.proof, p2.proof);
+				}
+				_ => panic!("proof lost in serialization"),
+			}
+		}
+
+		// unknown presence flag
+		let mut bytes = ser::ser_vec(&commit, ProtocolVersion::local()).unwrap();
+		bytes.push(2);
+		let read: Result<OutputProofResponse, _> =
+			ser::deserialize(&mut &bytes[..], ProtocolVersion::local());
+		assert!(read.is_err());
+	}
 }
@@ -30,14 +30,16 @@ use crate::core::ser::Writeable;
 use crate::core::{core, global};
 use crate::handshake::Handshake;
 use crate::msg::{
-	self, BanReason, GetPeerAddrs, KernelDataRequest, Locator, LocatorFastSync, Msg,
-	OnionAddressRequest, Ping, TxHashSetRequest, Type,
+	self, BanReason, GetBlockTxs, GetPeerAddrs, KernelDataRequest, Locator, LocatorFastSync, Msg,
+	OnionAddressRequest, Ping, ProofRequest, TxHashSetRequest, Type,
 };
 use crate::protocol::Protocol;
+use crate::session::Session;
 use crate::types::{
 	Capabilities, ChainAdapter, Error, NetAdapter, P2PConfig, PeerAddr, PeerInfo, ReasonForBan,
 	TxHashSetRead,
 };
+use crate::util::secp::pedersen::Commitment;
 use chrono::prelude::{DateTime, Utc};
 use epic_chain::types::SyncStatus;
 
@@ -66,6 +68,8 @@ pub struct Peer {
 	stop_handle: Mutex<conn::StopHandle>,
 	// Whether or not we requested a txhashset from this peer
 	state_sync_requested: Arc<AtomicBool>,
+	// magic numbers of the messages on our network
+	magic: [u8; 2],
 }
 
 impl fmt::Debug for Peer {
@@ -76,7 +80,13 @@ impl fmt::Debug for Peer {
 
 impl Peer {
 	// Only accept and connect can be externally used to build a peer
-	fn new(info: PeerInfo, conn: TcpStream, adapter: Arc<dyn NetAdapter>) -> std::io::Result<Peer> {
+	fn new(
+		info: PeerInfo,
+		conn: TcpStream,
+		session: Option<Session>,
+		adapter: Arc<dyn NetAdapter>,
+		magic: [u8; 2],
+	) -> std::io::Result<Peer> {
 		let state = Arc::new(RwLock::new(State::Connected));
 		let state_sync_requested = Arc::new(AtomicBool::new(false));
 		let tracking_adapter = TrackingAdapter::new(adapter);
@@ -84,9 +94,11 @@ impl Peer {
 			Arc::new(tracking_adapter.clone()),
 			info.clone(),
 			state_sync_requested.clone(),
+			magic,
 		);
 		let tracker = Arc::new(conn::Tracker::new());
-		let (sendh, stoph) = conn::listen(conn, info.version, tracker.clone(), handler)?;
+		let (sendh, stoph) =
+			conn::listen(conn, session, info.version, magic, tracker.clone(), handler)?;
 		let send_handle = Mutex::new(sendh);
 		let stop_handle = Mutex::new(stoph);
 
@@ -98,6 +110,7 @@ impl Peer {
 			send_handle,
 			stop_handle,
 			state_sync_requested,
+			magic,
 		})
 	}
 
@@ -109,10 +122,10 @@ impl Peer {
 		adapter: Arc<dyn NetAdapter>,
 	) -> Result<Peer, Error> {
 		debug!("accept: handshaking from {:?}", conn.peer_addr());
-		let info = hs.accept(capab, total_difficulty, &mut conn);
-		match info {
-			Ok(info) => {
-				let peer = Peer::new(info, conn, adapter)?;
+		let res = hs.accept(capab, total_difficulty, &mut conn);
+		match res {
+			Ok((info, session)) => {
+				let peer = Peer::new(info, conn, session, adapter, hs.magic())?;
 				// If the peer supports ONIONSTEM, request its onion address
 				if peer.info.capabilities.contains(Capabilities::ONIONSTEM) {
 					warn!(
@@ -142,14 +155,15 @@ impl Peer {
 		capab: Capabilities,
 		total_difficulty: Difficulty,
 		self_addr: PeerAddr,
+		peer_addr: PeerAddr,
 		hs: &Handshake,
 		adapter: Arc<dyn NetAdapter>,
 	) -> Result<Peer, Error> {
-		debug!("connect: handshaking with {:?}", conn.peer_addr());
-		let info = hs.initiate(capab, total_difficulty, self_addr, &mut conn);
-		match info {
-			Ok(info) => {
-				let peer = Peer::new(info, conn, adapter)?;
+		debug!("connect: handshaking with {}", peer_addr);
+		let res = hs.initiate(capab, total_difficulty, self_addr, peer_addr, &mut conn);
+		match res {
+			Ok((info, session)) => {
+				let peer = Peer::new(info, conn, session, adapter, hs.magic())?;
 				// Wenn der Peer ONIONSTEM unterstützt und wir eine Onion-Adresse haben, sende sie
 				if peer.info.capabilities.contains(Capabilities::ONIONSTEM) {
 					debug!(
@@ -162,9 +176,8 @@ impl Peer {
 			}
 			Err(e) => {
 				debug!(
-					"connect: handshaking with {:?} failed with error: {:?}",
-					conn.peer_addr(),
-					e
+					"connect: handshaking with {} failed with error: {:?}",
+					peer_addr, e
 				);
 				if let Err(e) = conn.shutdown(Shutdown::Both) {
 					debug!("Error shutting down conn: {:?}", e);
@@ -259,7 +272,7 @@ impl Peer {
 
 	/// Send a msg with given msg_type to our peer via the connection.
 	fn send<T: Writeable>(&self, msg: T, msg_type: Type) -> Result<(), Error> {
-		let msg = Msg::new(msg_type, msg, self.info.version)?;
+		let msg = Msg::new(msg_type, msg, self.info.version, self.magic)?;
 		self.send_handle.lock().send(msg)
 	}
 
@@ -426,6 +439,51 @@ impl Peer {
 		self.send(&h, msg::Type::GetCompactBlock)
 	}
 
+	/// Sends a request for the txs of a compact block we are missing,
+	/// identified by their kernel short ids.
+	pub fn send_block_txs_request(
+		&self,
+		h: Hash,
+		nonce: u64,
+		kern_ids: Vec<core::ShortId>,
+	) -> Result<(), Error> {
+		debug!(
+			"Requesting {} txs of compact block {} from {}",
+			kern_ids.len(),
+			h,
+			self.info.addr
+		);
+		self.send(
+			&GetBlockTxs {
+				hash: h,
+				nonce,
+				kern_ids,
+			},
+			msg::Type::GetBlockTxs,
+		)
+	}
+
+	/// Sends a request for the proof of an output.
+	pub fn send_output_proof_request(&self, commit: Commitment) -> Result<(), Error> {
+		debug!(
+			"Requesting proof of output {:?} from {}",
+			commit, self.info.addr
+		);
+		self.send(&ProofRequest { commit }, msg::Type::OutputProofRequest)
+	}
+
+	/// Sends a request for the proof of a kernel.
+	pub fn send_kernel_proof_request(&self, excess: Commitment) -> Result<(), Error> {
+		debug!(
+			"Requesting proof of kernel {:?} from {}",
+			excess, self.info.addr
+		);
+		self.send(
+			&ProofRequest { commit: excess },
+			msg::Type::KernelProofRequest,
+		)
+	}
+
 	pub fn send_peerlist_request(&self, capab: Capabilities) -> Result<(), Error> {
 		info!("Asking {} for more peers.", self.info.addr);
 		self.send(
@@ -531,6 +589,10 @@ impl ChainAdapter for TrackingAdapter {
 		self.adapter.sync_status()
 	}
 
+	fn is_light_mode(&self) -> bool {
+		self.adapter.is_light_mode()
+	}
+
 	fn tx_kernel_received(
 		&self,
 		kernel_hash: Hash,
@@ -582,6 +644,24 @@ impl ChainAdapter for TrackingAdapter {
 		self.adapter.compact_block_received(cb, peer_info)
 	}
 
+	fn get_block_txs(
+		&self,
+		hash: Hash,
+		nonce: u64,
+		kern_ids: &[core::ShortId],
+	) -> Vec<core::Transaction> {
+		self.adapter.get_block_txs(hash, nonce, kern_ids)
+	}
+
+	fn block_txs_received(
+		&self,
+		hash: Hash,
+		txs: Vec<core::Transaction>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		self.adapter.block_txs_received(hash, txs, peer_info)
+	}
+
 	fn header_received(
 		&self,
 		bh: core::BlockHeader,
@@ -611,6 +691,14 @@ impl ChainAdapter for TrackingAdapter {
 		self.adapter.get_block(h)
 	}
 
+	fn get_output_proof(&self, commit: Commitment) -> Option<(Hash, chain::OutputProof)> {
+		self.adapter.get_output_proof(commit)
+	}
+
+	fn get_kernel_proof(&self, excess: Commitment) -> Option<(Hash, chain::KernelProof)> {
+		self.adapter.get_kernel_proof(excess)
+	}
+
 	fn kernel_data_read(&self) -> Result<File, chain::Error> {
 		self.adapter.kernel_data_read()
 	}
@@ -682,4 +770,20 @@ impl NetAdapter for TrackingAdapter {
 	fn my_onion_addr(&self) -> Option<String> {
 		self.adapter.my_onion_addr()
 	}
+	fn output_proof_received(
+		&self,
+		commit: Commitment,
+		proof: Option<(Hash, chain::OutputProof)>,
+		peer_info: &PeerInfo,
+	) {
+		self.adapter.output_proof_received(commit, proof, peer_info)
+	}
+	fn kernel_proof_received(
+		&self,
+		excess: Commitment,
+		proof: Option<(Hash, chain::KernelProof)>,
+		peer_info: &PeerInfo,
+	) {
+		self.adapter.kernel_proof_received(excess, proof, peer_info)
+	}
 }
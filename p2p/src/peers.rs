@@ -12,26 +12,29 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::util::RwLock;
+use crate::util::secp::pedersen::Commitment;
+use crate::util::{Mutex, RwLock};
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{mpsc, Arc};
 
 use rand::rng;
 use rand::seq::SliceRandom;
 
+use crate::addr_manager::{self, MAX_ANCHORS};
 use crate::chain;
 use crate::core::core;
 use crate::core::core::hash::{Hash, Hashed};
 use crate::core::global;
 use crate::core::pow::Difficulty;
+use crate::local_addrs::LocalAddrs;
 use crate::peer::Peer;
 use crate::store::{PeerData, PeerStore, State};
 use crate::types::{
 	Capabilities, ChainAdapter, Error, NetAdapter, P2PConfig, PeerAddr, PeerInfo, ReasonForBan,
-	TxHashSetRead, MAX_PEER_ADDRS,
+	TorMode, TxHashSetRead, MAX_PEER_ADDRS,
 };
 use chrono::prelude::*;
 use chrono::Duration;
@@ -39,12 +42,78 @@ use epic_chain::types::SyncStatus;
 
 const LOCK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
 
+/// How long we wait for a peer to answer a proof request.
+const PROOF_REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// A proof from a peer, with the hash of the header it is built on.
+pub type PeerProof<T> = Option<(Hash, T)>;
+
+/// Proofs requested from peers, waiting for their answer.
+struct ProofRequests<T> {
+	pending: Mutex<HashMap<Commitment, Vec<(PeerAddr, mpsc::Sender<PeerProof<T>>)>>>,
+}
+
+impl<T: Clone> ProofRequests<T> {
+	fn new() -> ProofRequests<T> {
+		ProofRequests {
+			pending: Mutex::new(HashMap::new()),
+		}
+	}
+
+	/// Sends the request to the peer and waits for its answer.
+	fn request<F>(&self, peer: &Peer, commit: Commitment, send: F) -> Result<PeerProof<T>, Error>
+	where
+		F: FnOnce(&Peer) -> Result<(), Error>,
+	{
+		let addr = peer.info.addr;
+		let (tx, rx) = mpsc::channel();
+		self.pending
+			.lock()
+			.entry(commit)
+			.or_insert_with(Vec::new)
+			.push((addr, tx));
+		let res = send(peer).and_then(|_| {
+			rx.recv_timeout(PROOF_REQUEST_TIMEOUT)
+				.map_err(|_| Error::Timeout)
+		});
+		if res.is_err() {
+			self.remove(commit, addr);
+		}
+		res
+	}
+
+	/// Hands the answer of a peer to the requests waiting for it, answers
+	/// nobody asked for are dropped.
+	fn received(&self, commit: Commitment, addr: PeerAddr, proof: PeerProof<T>) {
+		for tx in self.remove(commit, addr) {
+			let _ = tx.send(proof.clone());
+		}
+	}
+
+	fn remove(&self, commit: Commitment, addr: PeerAddr) -> Vec<mpsc::Sender<PeerProof<T>>> {
+		let mut pending = self.pending.lock();
+		let requests = match pending.remove(&commit) {
+			Some(requests) => requests,
+			None => return vec![],
+		};
+		let (answered, waiting): (Vec<_>, Vec<_>) =
+			requests.into_iter().partition(|(a, _)| *a == addr);
+		if !waiting.is_empty() {
+			pending.insert(commit, waiting);
+		}
+		answered.into_iter().map(|(_, tx)| tx).collect()
+	}
+}
+
 pub struct Peers {
 	pub adapter: Arc<dyn ChainAdapter>,
 	store: PeerStore,
 	peers: RwLock<HashMap<PeerAddr, Arc<Peer>>>,
 	config: P2PConfig,
 	my_onion_addr: Arc<RwLock<Option<String>>>,
+	local_addrs: Arc<LocalAddrs>,
+	output_proofs: ProofRequests<chain::OutputProof>,
+	kernel_proofs: ProofRequests<chain::KernelProof>,
 }
 
 impl Peers {
@@ -53,6 +122,7 @@ impl Peers {
 		adapter: Arc<dyn ChainAdapter>,
 		config: P2PConfig,
 		my_onion_addr: Option<String>,
+		local_addrs: Arc<LocalAddrs>,
 	) -> Peers {
 		Peers {
 			adapter,
@@ -60,6 +130,9 @@ impl Peers {
 			config,
 			peers: RwLock::new(HashMap::new()),
 			my_onion_addr: Arc::new(RwLock::new(my_onion_addr)),
+			local_addrs,
+			output_proofs: ProofRequests::new(),
+			kernel_proofs: ProofRequests::new(),
 		}
 	}
 
@@ -375,6 +448,43 @@ impl Peers {
 		outbound_peers.pop()
 	}
 
+	/// Returns up to count connected peers able to prove outputs and kernels,
+	/// picked at random.
+	pub fn random_proof_peers(&self, count: usize) -> Vec<Arc<Peer>> {
+		let mut proof_peers = self
+			.connected_peers()
+			.into_iter()
+			.filter(|p| p.info.capabilities.contains(Capabilities::MERKLE_PROOFS))
+			.collect::<Vec<_>>();
+		proof_peers.shuffle(&mut rng());
+		proof_peers.truncate(count);
+		proof_peers
+	}
+
+	/// Asks the peer for the proof of the output with the given
+	/// commitment and waits for its answer. The proof is the peer's word
+	/// until checked against our headers.
+	pub fn request_output_proof(
+		&self,
+		peer: &Peer,
+		commit: Commitment,
+	) -> Result<PeerProof<chain::OutputProof>, Error> {
+		self.output_proofs
+			.request(peer, commit, |p| p.send_output_proof_request(commit))
+	}
+
+	/// Asks the peer for the proof of the kernel with the given excess and
+	/// waits for its answer. The proof is the peer's word until checked
+	/// against our headers.
+	pub fn request_kernel_proof(
+		&self,
+		peer: &Peer,
+		excess: Commitment,
+	) -> Result<PeerProof<chain::KernelProof>, Error> {
+		self.kernel_proofs
+			.request(peer, excess, |p| p.send_kernel_proof_request(excess))
+	}
+
 	/// Unban a peer, checks if it exists and banned then unban
 	pub fn unban_peer(&self, peer_addr: PeerAddr) -> Result<(), Error> {
 		info!("Unban peer {}", peer_addr);
@@ -635,6 +745,7 @@ impl Peers {
 	}
 
 	pub fn stop(&self) {
+		self.save_anchors();
 		let mut peers = self.peers.write();
 		for peer in peers.values() {
 			peer.stop();
@@ -644,6 +755,80 @@ impl Peers {
 		}
 	}
 
+	/// Remember a few of our outbound peers so we reconnect to them first on
+	/// restart, instead of relying only on addresses we heard from others.
+	fn save_anchors(&self) {
+		let anchors = self
+			.outgoing_connected_peers()
+			.into_iter()
+			.filter(|p| !p.is_banned())
+			.take(MAX_ANCHORS)
+			.map(|p| p.info.addr)
+			.collect::<Vec<_>>();
+		debug!("save_anchors: {:?}", anchors);
+		if let Err(e) = self.store.save_anchors(&anchors) {
+			error!("failed to save anchor peers: {:?}", e);
+		}
+	}
+
+	/// The anchor peers saved on last shutdown. They are removed from store
+	/// and saved again on the next shutdown if still connected.
+	pub fn take_anchors(&self) -> Vec<PeerAddr> {
+		match self.store.take_anchors() {
+			Ok(anchors) => anchors,
+			Err(e) => {
+				debug!("take_anchors failed: {:?}", e);
+				vec![]
+			}
+		}
+	}
+
+	/// Selects up to `count` addresses to open outbound connections to, at
+	/// random from the peers we connected to before and the ones we only
+	/// heard about, respecting the outbound limit per network group.
+	pub fn select_outbound_addrs(&self, count: usize) -> Vec<PeerAddr> {
+		let outbound = self
+			.outgoing_connected_peers()
+			.iter()
+			.map(|p| p.info.addr)
+			.collect::<Vec<_>>();
+		// addresses we would refuse to connect to anyway
+		let onion_only = self.config.tor_mode() == TorMode::OnionOnly;
+		let usable = |p: &PeerData| !onion_only || p.addr.is_onion();
+		let tried = self
+			.find_peers(State::Healthy, Capabilities::UNKNOWN, usize::MAX)
+			.into_iter()
+			.filter(|p| usable(p) && !self.is_connected(p.addr))
+			.collect();
+		let new = self
+			.find_peers(State::Unknown, Capabilities::UNKNOWN, usize::MAX)
+			.into_iter()
+			.filter(usable)
+			.collect();
+		addr_manager::select_outbound(
+			tried,
+			new,
+			&outbound,
+			self.config.peer_max_outbound_per_group() as usize,
+			count,
+		)
+	}
+
+	/// Whether we already have as many outbound connections as allowed to
+	/// the network group of the provided address.
+	pub fn outbound_group_full(&self, addr: PeerAddr) -> bool {
+		let outbound = self
+			.outgoing_connected_peers()
+			.iter()
+			.map(|p| p.info.addr)
+			.collect::<Vec<_>>();
+		addr_manager::group_full(
+			addr,
+			&outbound,
+			self.config.peer_max_outbound_per_group() as usize,
+		)
+	}
+
 	/// We have enough outbound connected peers
 	pub fn enough_outbound_peers(&self) -> bool {
 		self.peer_outbound_count() >= self.config.peer_min_preferred_outbound_count()
@@ -654,6 +839,16 @@ impl Peers {
 		&self.config
 	}
 
+	/// The socket addresses our p2p server listens on.
+	pub fn listen_addrs(&self) -> Vec<std::net::SocketAddr> {
+		self.local_addrs.listen_addrs()
+	}
+
+	/// Our externally reachable addresses, configured or learned from peers.
+	pub fn external_addrs(&self) -> Vec<PeerAddr> {
+		self.local_addrs.external_addrs()
+	}
+
 	/// Removes those peers that seem to have expired
 	pub fn remove_expired_defunc_peers(&self) {
 		let now = Utc::now();
@@ -715,6 +910,10 @@ impl ChainAdapter for Peers {
 		self.adapter.sync_status()
 	}
 
+	fn is_light_mode(&self) -> bool {
+		self.adapter.is_light_mode()
+	}
+
 	fn tx_kernel_received(
 		&self,
 		kernel_hash: Hash,
@@ -783,6 +982,40 @@ impl ChainAdapter for Peers {
 		}
 	}
 
+	fn get_block_txs(
+		&self,
+		hash: Hash,
+		nonce: u64,
+		kern_ids: &[core::ShortId],
+	) -> Vec<core::Transaction> {
+		self.adapter.get_block_txs(hash, nonce, kern_ids)
+	}
+
+	fn block_txs_received(
+		&self,
+		hash: Hash,
+		txs: Vec<core::Transaction>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		if !self.adapter.block_txs_received(hash, txs, peer_info)? {
+			// the txs completed the compact block the peer sent us
+			// but it does not hydrate into a valid block
+			warn!(
+				"Received bad txs for compact block {} from  {}, the peer will be banned",
+				hash, peer_info.addr
+			);
+			self.ban_peer(peer_info.addr, ReasonForBan::BadCompactBlock)
+				.map_err(|e| {
+					let err: chain::Error =
+						chain::Error::Other(format!("ban peer error :{:?}", e)).into();
+					err
+				})?;
+			Ok(false)
+		} else {
+			Ok(true)
+		}
+	}
+
 	fn header_received(
 		&self,
 		bh: core::BlockHeader,
@@ -804,6 +1037,11 @@ impl ChainAdapter for Peers {
 				return Ok(true);
 			}
 
+			// Light nodes, without the chain state, follow the headers only
+			if self.adapter.is_light_mode() {
+				return Ok(true);
+			}
+
 			// move from NetAdapter header_received here
 			// After we have received a block header in "header first" propagation
 			// we need to go request the block (compact representation) from the
@@ -847,6 +1085,14 @@ impl ChainAdapter for Peers {
 		self.adapter.get_block(h)
 	}
 
+	fn get_output_proof(&self, commit: Commitment) -> Option<(Hash, chain::OutputProof)> {
+		self.adapter.get_output_proof(commit)
+	}
+
+	fn get_kernel_proof(&self, excess: Commitment) -> Option<(Hash, chain::KernelProof)> {
+		self.adapter.get_kernel_proof(excess)
+	}
+
 	fn kernel_data_read(&self) -> Result<File, chain::Error> {
 		self.adapter.kernel_data_read()
 	}
@@ -914,11 +1160,25 @@ impl NetAdapter for Peers {
 	/// addresses.
 	/// exceptions are made for loopback, unspecified, private and unique local addresses.
 	fn find_peer_addrs(&self, capab: Capabilities) -> Vec<PeerAddr> {
+		// Advertise ourselves first so peers learn about all our reachable
+		// addresses (v4 and v6), then fill up with healthy peers.
+		let mut addrs = vec![];
+		if self.config.capabilities.contains(capab) {
+			addrs.extend(
+				self.external_addrs()
+					.into_iter()
+					.filter(|addr| addr.is_routable()),
+			);
+		}
+
 		let peers = self.find_peers(State::Healthy, capab, MAX_PEER_ADDRS as usize);
-		peers
+		let peer_addrs = peers
 			.into_iter()
 			.filter(|p| {
-				let ip = p.addr.0.ip();
+				let ip = match p.addr.ip() {
+					Some(ip) => ip,
+					None => return true,
+				};
 				!ip.is_loopback()
 					&& !ip.is_unspecified()
 					&& match ip {
@@ -931,19 +1191,30 @@ impl NetAdapter for Peers {
 						std::net::IpAddr::V6(ipv6) => !ipv6.is_unique_local(),
 					}
 			})
-			.map(|p| p.addr)
-			.collect()
+			.map(|p| p.addr);
+
+		for addr in peer_addrs {
+			if !addrs.contains(&addr) {
+				addrs.push(addr);
+			}
+		}
+		addrs.truncate(MAX_PEER_ADDRS as usize);
+		addrs
 	}
 
 	/// A list of peers has been received from one of our peers.
 	fn peer_addrs_received(&self, peer_addrs: Vec<PeerAddr>) {
 		trace!("Received {} peer addrs, saving.", peer_addrs.len());
+		let onion_only = self.config.tor_mode() == TorMode::OnionOnly;
 		for pa in peer_addrs {
-			let ip = pa.0.ip();
-			if global::is_mainnet() {
-				// Strict filtering on mainnet
-				// Ignore loopback, unspecified, private and unique local addresses
-				// We do not want to connect to these addresses
+			if onion_only && !pa.is_onion() {
+				trace!("Ignoring clearnet peer address in onion-only mode: {}", pa);
+				continue;
+			}
+			// Strict filtering on mainnet
+			// Ignore loopback, unspecified, private and unique local addresses
+			// We do not want to connect to these addresses
+			if let Some(ip) = pa.ip().filter(|_| global::is_mainnet()) {
 				if ip.is_loopback()
 					|| ip.is_unspecified()
 					|| match ip {
@@ -1014,4 +1285,22 @@ impl NetAdapter for Peers {
 	fn my_onion_addr(&self) -> Option<String> {
 		self.my_onion_addr.read().clone()
 	}
+
+	fn output_proof_received(
+		&self,
+		commit: Commitment,
+		proof: Option<(Hash, chain::OutputProof)>,
+		peer_info: &PeerInfo,
+	) {
+		self.output_proofs.received(commit, peer_info.addr, proof);
+	}
+
+	fn kernel_proof_received(
+		&self,
+		excess: Commitment,
+		proof: Option<(Hash, chain::KernelProof)>,
+		peer_info: &PeerInfo,
+	) {
+		self.kernel_proofs.received(excess, peer_info.addr, proof);
+	}
 }
@@ -16,12 +16,14 @@ use crate::chain;
 use crate::conn::{Message, MessageHandler, Tracker};
 use crate::core::core::{self, hash::Hash, hash::Hashed, CompactBlock};
 use crate::util::format::human_readable_size;
+use crate::util::{Mutex, RateCounter};
 
 use crate::msg::{
-	BanReason, FastHeaders, GetPeerAddrs, Headers, KernelDataResponse, Locator, LocatorFastSync,
-	Msg, OnionAddressResponse, PeerAddrs, Ping, Pong, TxHashSetArchive, TxHashSetRequest, Type,
+	BanReason, BlockTxs, FastHeaders, GetBlockTxs, GetPeerAddrs, Headers, KernelDataResponse,
+	KernelProofResponse, Locator, LocatorFastSync, Msg, OnionAddressResponse, OutputProofResponse,
+	PeerAddrs, Ping, Pong, ProofRequest, TxHashSetArchive, TxHashSetRequest, Type,
 };
-use crate::types::{Error, NetAdapter, PeerInfo};
+use crate::types::{Capabilities, Error, NetAdapter, PeerInfo};
 use chrono::prelude::Utc;
 use rand::{rng, Rng};
 use std::cmp;
@@ -32,10 +34,16 @@ use std::sync::Arc;
 use std::time::Instant;
 use tempfile::tempfile;
 
+/// Output and kernel proof requests we answer a peer per minute, the ones
+/// above are ignored.
+const MAX_PROOF_REQUESTS_PER_MIN: u64 = 60;
+
 pub struct Protocol {
 	adapter: Arc<dyn NetAdapter>,
 	peer_info: PeerInfo,
 	state_sync_requested: Arc<AtomicBool>,
+	proof_requests: Mutex<RateCounter>,
+	magic: [u8; 2],
 }
 
 impl Protocol {
@@ -43,12 +51,30 @@ impl Protocol {
 		adapter: Arc<dyn NetAdapter>,
 		peer_info: PeerInfo,
 		state_sync_requested: Arc<AtomicBool>,
+		magic: [u8; 2],
 	) -> Protocol {
 		Protocol {
 			adapter,
 			peer_info,
 			state_sync_requested,
+			magic,
+			proof_requests: Mutex::new(RateCounter::new()),
+		}
+	}
+
+	/// Whether we answer one more proof request from this peer, proofs are
+	/// built on demand so we can't let a peer ask for as many as it wants.
+	fn proof_request_allowed(&self) -> bool {
+		let mut proof_requests = self.proof_requests.lock();
+		proof_requests.inc(0);
+		let allowed = proof_requests.count_per_min() <= MAX_PROOF_REQUESTS_PER_MIN;
+		if !allowed {
+			debug!(
+				"ignoring proof request from {}, too many requests",
+				self.peer_info.addr
+			);
 		}
+		allowed
 	}
 }
 
@@ -90,6 +116,7 @@ impl MessageHandler for Protocol {
 						local_timestamp: Utc::now().timestamp(),
 					},
 					self.peer_info.version,
+					self.magic,
 				)?))
 			}
 
@@ -126,6 +153,7 @@ impl MessageHandler for Protocol {
 						Type::Transaction,
 						tx,
 						self.peer_info.version,
+						self.magic,
 					)?))
 				} else {
 					Ok(None)
@@ -152,7 +180,12 @@ impl MessageHandler for Protocol {
 
 				let bo = adapter.get_block(h);
 				if let Some(b) = bo {
-					return Ok(Some(Msg::new(Type::Block, b, self.peer_info.version)?));
+					return Ok(Some(Msg::new(
+						Type::Block,
+						b,
+						self.peer_info.version,
+						self.magic,
+					)?));
 				}
 				Ok(None)
 			}
@@ -177,6 +210,7 @@ impl MessageHandler for Protocol {
 						Type::CompactBlock,
 						cb,
 						self.peer_info.version,
+						self.magic,
 					)?))
 				} else {
 					Ok(None)
@@ -191,6 +225,79 @@ impl MessageHandler for Protocol {
 				Ok(None)
 			}
 
+			Type::GetBlockTxs => {
+				let req: GetBlockTxs = msg.body()?;
+				let txs = adapter.get_block_txs(req.hash, req.nonce, &req.kern_ids);
+				debug!(
+					"GetBlockTxs: {}, found {} txs for {} kern_ids",
+					req.hash,
+					txs.len(),
+					req.kern_ids.len()
+				);
+				Ok(Some(Msg::new(
+					Type::BlockTxs,
+					BlockTxs {
+						hash: req.hash,
+						txs,
+					},
+					self.peer_info.version,
+					self.magic,
+				)?))
+			}
+
+			Type::BlockTxs => {
+				debug!("Received block txs: msg_len: {}", msg.header.msg_len);
+				let resp: BlockTxs = msg.body()?;
+				adapter.block_txs_received(resp.hash, resp.txs, &self.peer_info)?;
+				Ok(None)
+			}
+
+			Type::OutputProofRequest => {
+				let req: ProofRequest = msg.body()?;
+				if !self.proof_request_allowed() {
+					return Ok(None);
+				}
+				let proof = adapter.get_output_proof(req.commit);
+				Ok(Some(Msg::new(
+					Type::OutputProofResponse,
+					OutputProofResponse {
+						commit: req.commit,
+						proof,
+					},
+					self.peer_info.version,
+					self.magic,
+				)?))
+			}
+
+			Type::OutputProofResponse => {
+				let resp: OutputProofResponse = msg.body()?;
+				adapter.output_proof_received(resp.commit, resp.proof, &self.peer_info);
+				Ok(None)
+			}
+
+			Type::KernelProofRequest => {
+				let req: ProofRequest = msg.body()?;
+				if !self.proof_request_allowed() {
+					return Ok(None);
+				}
+				let proof = adapter.get_kernel_proof(req.commit);
+				Ok(Some(Msg::new(
+					Type::KernelProofResponse,
+					KernelProofResponse {
+						excess: req.commit,
+						proof,
+					},
+					self.peer_info.version,
+					self.magic,
+				)?))
+			}
+
+			Type::KernelProofResponse => {
+				let resp: KernelProofResponse = msg.body()?;
+				adapter.kernel_proof_received(resp.excess, resp.proof, &self.peer_info);
+				Ok(None)
+			}
+
 			Type::GetHeaders => {
 				// load headers from the locator
 				let loc: Locator = msg.body()?;
@@ -205,6 +312,7 @@ impl MessageHandler for Protocol {
 						headers,
 					},
 					self.peer_info.version,
+					self.magic,
 				)?))
 			}
 
@@ -221,6 +329,7 @@ impl MessageHandler for Protocol {
 						headers,
 					},
 					self.peer_info.version,
+					self.magic,
 				)?))
 			}
 
@@ -272,11 +381,20 @@ impl MessageHandler for Protocol {
 
 			Type::GetPeerAddrs => {
 				let get_peers: GetPeerAddrs = msg.body()?;
-				let peers = adapter.find_peer_addrs(get_peers.capabilities);
+				let mut peers = adapter.find_peer_addrs(get_peers.capabilities);
+				// older peers can't read onion addresses
+				if !self
+					.peer_info
+					.capabilities
+					.contains(Capabilities::TOR_ADDRESS)
+				{
+					peers.retain(|addr| !addr.is_onion());
+				}
 				Ok(Some(Msg::new(
 					Type::PeerAddrs,
 					PeerAddrs { peers },
 					self.peer_info.version,
+					self.magic,
 				)?))
 			}
 
@@ -294,6 +412,7 @@ impl MessageHandler for Protocol {
 					Type::KernelDataResponse,
 					&kernel_data_response,
 					self.peer_info.version,
+					self.magic,
 				)?;
 				response.add_attachment(kernel_data);
 				Ok(Some(response))
@@ -358,6 +477,7 @@ impl MessageHandler for Protocol {
 							bytes: file_sz,
 						},
 						self.peer_info.version,
+						self.magic,
 					)?;
 					resp.add_attachment(txhashset.reader);
 					Ok(Some(resp))
@@ -508,7 +628,7 @@ impl MessageHandler for Protocol {
 
 				Ok(None)
 			}
-			Type::Error | Type::Hand | Type::Shake => {
+			Type::Error | Type::Hand | Type::Shake | Type::SessionKey => {
 				debug!("Received an unexpected msg: {:?}", msg.header.msg_type);
 				Ok(None)
 			}
@@ -521,6 +641,7 @@ impl MessageHandler for Protocol {
 						Type::OnionAddressResponse,
 						response,
 						self.peer_info.version,
+						self.magic,
 					)?))
 				} else {
 					warn!("No onion address set for this node, cannot respond to onion address request.");
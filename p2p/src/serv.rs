@@ -23,19 +23,28 @@ use std::time::Duration;
 use crate::chain;
 use crate::core::core;
 use crate::core::core::hash::Hash;
-use crate::core::global;
+use crate::core::global::{self, ChainParams};
 use crate::core::pow::Difficulty;
 use crate::handshake::Handshake;
+use crate::identity;
+use crate::msg;
 use crate::peer::Peer;
 use crate::peers::Peers;
+use crate::session;
 use crate::store::PeerStore;
+use crate::tor::socks;
 use crate::types::{
 	Capabilities, ChainAdapter, Error, NetAdapter, P2PConfig, PeerAddr, PeerInfo, ReasonForBan,
-	TxHashSetRead,
+	TorMode, TxHashSetRead,
 };
+use crate::util::secp::pedersen::Commitment;
 use crate::util::StopState;
 use chrono::prelude::{DateTime, Utc};
 use epic_chain::types::SyncStatus;
+use net2::TcpBuilder;
+
+/// Circuits to onion services take a while to build.
+const TOR_CONNECT_TIMEOUT_SECS: u64 = 30;
 
 /// P2P server implementation, handling bootstrapping to find and connect to
 /// peers, receiving connections from other peers and keep track of all of them.
@@ -45,11 +54,14 @@ pub struct Server {
 	handshake: Arc<Handshake>,
 	pub peers: Arc<Peers>,
 	stop_state: Arc<StopState>,
+	socks_proxy: Option<SocketAddr>,
+	params: Arc<ChainParams>,
 }
 
 // TODO TLS
 impl Server {
-	/// Creates a new idle p2p server with no peers
+	/// Creates a new idle p2p server with no peers, on the network of the
+	/// provided chain parameters.
 	pub fn new(
 		db_root: &str,
 		capab: Capabilities,
@@ -58,33 +70,68 @@ impl Server {
 		genesis: Hash,
 		stop_state: Arc<StopState>,
 		onion_addr: Option<String>,
+		params: Arc<ChainParams>,
 	) -> Result<Server, Error> {
+		let handshake =
+			Handshake::new(genesis, config.clone(), msg::magic(&params)).with_static_key(
+				session::load_or_create_static_key(db_root, &identity::password_from_env())?,
+			);
+		info!("P2P static key: {}", handshake.static_key());
+		let handshake = Arc::new(handshake);
+
+		// Pinned peers can only be reached over an encrypted transport.
+		let capab = if config.pinned_peers.is_some() {
+			capab | Capabilities::ENCRYPTED
+		} else {
+			capab
+		};
+		// Every node can serve the txs of the compact blocks it relays and
+		// understands onion peer addresses.
+		let capab = capab | Capabilities::COMPACT_BLOCKS | Capabilities::TOR_ADDRESS;
+		// Nodes keeping the chain state can prove its outputs and kernels.
+		let capab = if capab.contains(Capabilities::TXHASHSET_HIST) {
+			capab | Capabilities::MERKLE_PROOFS
+		} else {
+			capab
+		};
 		Ok(Server {
 			config: config.clone(),
 			capabilities: capab,
-			handshake: Arc::new(Handshake::new(genesis, config.clone())),
 			peers: Arc::new(Peers::new(
 				PeerStore::new(db_root)?,
 				adapter,
 				config,
 				onion_addr,
+				handshake.local_addrs.clone(),
 			)),
+			handshake,
 			stop_state,
+			socks_proxy: None,
+			params,
 		})
 	}
 
-	/// Starts a new TCP server and listen to incoming connections. This is a
-	/// blocking call until the TCP server stops.
-	pub fn listen(&self) -> Result<(), Error> {
-		// Start TCP listener and handle incoming connections
-		let addr = SocketAddr::new(self.config.host, self.config.port);
+	/// Dial outbound connections through the tor SOCKS proxy listening on
+	/// `socks_proxy` when the tor mode is enabled.
+	pub fn with_socks_proxy(mut self, socks_proxy: SocketAddr) -> Server {
+		self.socks_proxy = Some(socks_proxy);
+		self
+	}
 
-		let listener = match TcpListener::bind(addr) {
+	/// Binds a non-blocking TCP listener on the provided address.
+	/// IPv6 listeners are bound as v6 only so we can listen on both
+	/// "0.0.0.0" and "[::]" with the same port.
+	fn bind(addr: SocketAddr) -> Result<TcpListener, Error> {
+		let res = match addr {
+			SocketAddr::V4(_) => TcpListener::bind(addr),
+			SocketAddr::V6(_) => bind_v6_only(addr),
+		};
+		let listener = match res {
 			Ok(listener) => listener,
 			Err(ref e) if e.kind() == io::ErrorKind::AddrInUse => {
 				error!(
-					"Address {}:{} is already in use. Please check if another instance is running.",
-					self.config.host, self.config.port
+					"Address {} is already in use. Please check if another instance is running.",
+					addr
 				);
 				return Err(Error::Connection(io::Error::new(
 					io::ErrorKind::AddrInUse,
@@ -92,15 +139,34 @@ impl Server {
 				)));
 			}
 			Err(e) => {
-				error!(
-					"Failed to bind to address {}:{}: {:?}",
-					self.config.host, self.config.port, e
-				);
+				error!("Failed to bind to address {}: {:?}", addr, e);
 				return Err(Error::Connection(e));
 			}
 		};
-
 		listener.set_nonblocking(true)?;
+		Ok(listener)
+	}
+
+	/// Starts a new TCP server and listen to incoming connections on all
+	/// configured addresses. This is a blocking call until the TCP server stops.
+	pub fn listen(&self) -> Result<(), Error> {
+		let _params = self.params.enter();
+		// Start TCP listeners and handle incoming connections.
+		// We only fail if we are unable to listen on any of the addresses.
+		let mut listeners = vec![];
+		let mut last_err = None;
+		for addr in self.config.listen_addrs() {
+			match Server::bind(addr) {
+				Ok(listener) => {
+					info!("P2P server listening on {}", addr);
+					listeners.push(listener);
+				}
+				Err(e) => last_err = Some(e),
+			}
+		}
+		if listeners.is_empty() {
+			return Err(last_err.unwrap_or(Error::Internal));
+		}
 
 		let sleep_time = Duration::from_millis(100);
 		loop {
@@ -110,39 +176,8 @@ impl Server {
 				continue;
 			}
 
-			match listener.accept() {
-				Ok((stream, peer_addr)) => {
-					// We want out TCP stream to be in blocking mode.
-					// The TCP listener is in nonblocking mode so we *must* explicitly
-					// move the accepted TCP stream into blocking mode (or all kinds of
-					// bad things can and will happen).
-					// A nonblocking TCP listener will accept nonblocking TCP streams which
-					// we do not want.
-					stream.set_nonblocking(false)?;
-
-					let peer_addr = PeerAddr(peer_addr);
-
-					if self.check_undesirable(&stream) {
-						if let Err(e) = stream.shutdown(Shutdown::Both) {
-							debug!("Error shutting down conn: {:?}", e);
-						}
-						continue;
-					}
-					match self.handle_new_peer(stream) {
-						Err(Error::ConnectionClose) => debug!("shutting down, ignoring a new peer"),
-						Err(e) => {
-							debug!("Error accepting peer {}: {:?}", peer_addr.to_string(), e);
-							let _ = self.peers.add_banned(peer_addr, ReasonForBan::BadHandshake);
-						}
-						Ok(_) => {}
-					}
-				}
-				Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
-					// nothing to do, will retry in next iteration
-				}
-				Err(e) => {
-					debug!("Couldn't establish new client connection: {:?}", e);
-				}
+			for listener in &listeners {
+				self.accept_on(listener)?;
 			}
 			if self.stop_state.is_stopped() {
 				break;
@@ -152,9 +187,59 @@ impl Server {
 		Ok(())
 	}
 
+	/// Accept a pending connection (if any) on the provided listener.
+	fn accept_on(&self, listener: &TcpListener) -> Result<(), Error> {
+		match listener.accept() {
+			Ok((stream, peer_addr)) => {
+				// We want out TCP stream to be in blocking mode.
+				// The TCP listener is in nonblocking mode so we *must* explicitly
+				// move the accepted TCP stream into blocking mode (or all kinds of
+				// bad things can and will happen).
+				// A nonblocking TCP listener will accept nonblocking TCP streams which
+				// we do not want.
+				stream.set_nonblocking(false)?;
+
+				// Connections through our onion service come from our tor daemon.
+				if self.config.tor_mode() == TorMode::OnionOnly && !peer_addr.ip().is_loopback() {
+					debug!(
+						"Refusing clearnet connection from {} in onion-only mode.",
+						peer_addr
+					);
+					let _ = stream.shutdown(Shutdown::Both);
+					return Ok(());
+				}
+
+				let peer_addr = PeerAddr::Ip(peer_addr);
+
+				if self.check_undesirable(&stream) {
+					if let Err(e) = stream.shutdown(Shutdown::Both) {
+						debug!("Error shutting down conn: {:?}", e);
+					}
+					return Ok(());
+				}
+				match self.handle_new_peer(stream) {
+					Err(Error::ConnectionClose) => debug!("shutting down, ignoring a new peer"),
+					Err(e) => {
+						debug!("Error accepting peer {}: {:?}", peer_addr.to_string(), e);
+						let _ = self.peers.add_banned(peer_addr, ReasonForBan::BadHandshake);
+					}
+					Ok(_) => {}
+				}
+			}
+			Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+				// nothing to do, will retry in next iteration
+			}
+			Err(e) => {
+				debug!("Couldn't establish new client connection: {:?}", e);
+			}
+		}
+		Ok(())
+	}
+
 	/// Asks the server to connect to a new peer. Directly returns the peer if
 	/// we're already connected to the provided address.
 	pub fn connect(&self, addr: PeerAddr) -> Result<Arc<Peer>, Error> {
+		let _params = self.params.enter();
 		if self.stop_state.is_stopped() {
 			return Err(Error::ConnectionClose);
 		}
@@ -164,6 +249,14 @@ impl Server {
 			return Err(Error::ConnectionClose);
 		}
 
+		if self.config.tor_mode() == TorMode::OnionOnly && !addr.is_onion() {
+			debug!(
+				"connect_peer: peer {} is not an onion address, not connecting.",
+				addr
+			);
+			return Err(Error::ConnectionClose);
+		}
+
 		if global::is_production_mode() {
 			let hs = self.handshake.clone();
 			let addrs = hs.addrs.read();
@@ -185,16 +278,26 @@ impl Server {
 			self.config.port,
 			addr
 		);
-		match TcpStream::connect_timeout(&addr.0, Duration::from_secs(10)) {
+		match self.dial(addr) {
 			Ok(stream) => {
-				let addr = SocketAddr::new(self.config.host, self.config.port);
+				// Tell the peer our external address (of the same family) if we know it,
+				// the address we listen on otherwise.
+				let self_addr = self
+					.handshake
+					.local_addrs
+					.external_addr_for(addr)
+					.unwrap_or(PeerAddr::Ip(SocketAddr::new(
+						self.config.host,
+						self.config.port,
+					)));
 				let total_diff = self.peers.total_difficulty()?;
 
 				let peer = Peer::connect(
 					stream,
 					self.capabilities,
 					total_diff,
-					PeerAddr(addr),
+					self_addr,
+					addr,
 					&self.handshake,
 					self.peers.clone(),
 				)?;
@@ -215,6 +318,29 @@ impl Server {
 		}
 	}
 
+	/// Opens a TCP connection to the peer, through the tor SOCKS proxy when
+	/// tor is enabled. Onion addresses can only be reached this way.
+	fn dial(&self, addr: PeerAddr) -> io::Result<TcpStream> {
+		if let PeerAddr::Ip(socket_addr) = addr {
+			if !self.config.tor_mode().is_enabled() {
+				return TcpStream::connect_timeout(&socket_addr, Duration::from_secs(10));
+			}
+		}
+		let proxy = self.socks_proxy.ok_or_else(|| {
+			io::Error::new(io::ErrorKind::NotConnected, "no tor SOCKS proxy configured")
+		})?;
+		let host = match addr {
+			PeerAddr::Ip(socket_addr) => socket_addr.ip().to_string(),
+			PeerAddr::Onion(..) => addr.as_key(),
+		};
+		socks::connect(
+			proxy,
+			&host,
+			addr.port(),
+			Duration::from_secs(TOR_CONNECT_TIMEOUT_SECS),
+		)
+	}
+
 	fn handle_new_peer(&self, stream: TcpStream) -> Result<(), Error> {
 		if self.stop_state.is_stopped() {
 			return Err(Error::ConnectionClose);
@@ -254,7 +380,7 @@ impl Server {
 			return true;
 		}
 		if let Ok(peer_addr) = stream.peer_addr() {
-			let peer_addr = PeerAddr(peer_addr);
+			let peer_addr = PeerAddr::Ip(peer_addr);
 			if self.peers.is_banned(peer_addr) {
 				debug!("Peer {} banned, refusing connection.", peer_addr);
 				return true;
@@ -293,6 +419,17 @@ impl Server {
 	}
 }
 
+/// Bind an IPv6 only listener, leaving the port free for an IPv4 listener.
+fn bind_v6_only(addr: SocketAddr) -> io::Result<TcpListener> {
+	let builder = TcpBuilder::new_v6()?;
+	builder.only_v6(true)?;
+	// Mirror what std does for TcpListener::bind on unix.
+	#[cfg(unix)]
+	builder.reuse_address(true)?;
+	builder.bind(addr)?;
+	builder.listen(128)
+}
+
 /// A no-op network adapter used for testing.
 pub struct DummyAdapter {}
 
@@ -314,6 +451,10 @@ impl ChainAdapter for DummyAdapter {
 		chain::SyncStatus::NoSync
 	}
 
+	fn is_light_mode(&self) -> bool {
+		false
+	}
+
 	fn tx_kernel_received(&self, _h: Hash, _peer_info: &PeerInfo) -> Result<bool, chain::Error> {
 		Ok(true)
 	}
@@ -332,6 +473,22 @@ impl ChainAdapter for DummyAdapter {
 	) -> Result<bool, chain::Error> {
 		Ok(true)
 	}
+	fn get_block_txs(
+		&self,
+		_hash: Hash,
+		_nonce: u64,
+		_kern_ids: &[core::ShortId],
+	) -> Vec<core::Transaction> {
+		vec![]
+	}
+	fn block_txs_received(
+		&self,
+		_hash: Hash,
+		_txs: Vec<core::Transaction>,
+		_peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		Ok(true)
+	}
 	fn header_received(
 		&self,
 		_bh: core::BlockHeader,
@@ -363,6 +520,12 @@ impl ChainAdapter for DummyAdapter {
 	fn get_block(&self, _: Hash) -> Option<core::Block> {
 		None
 	}
+	fn get_output_proof(&self, _commit: Commitment) -> Option<(Hash, chain::OutputProof)> {
+		None
+	}
+	fn get_kernel_proof(&self, _excess: Commitment) -> Option<(Hash, chain::KernelProof)> {
+		None
+	}
 	fn kernel_data_read(&self) -> Result<File, chain::Error> {
 		unimplemented!()
 	}
@@ -421,4 +584,18 @@ impl NetAdapter for DummyAdapter {
 	fn my_onion_addr(&self) -> Option<String> {
 		None
 	}
+	fn output_proof_received(
+		&self,
+		_commit: Commitment,
+		_proof: Option<(Hash, chain::OutputProof)>,
+		_peer_info: &PeerInfo,
+	) {
+	}
+	fn kernel_proof_received(
+		&self,
+		_excess: Commitment,
+		_proof: Option<(Hash, chain::KernelProof)>,
+		_peer_info: &PeerInfo,
+	) {
+	}
 }
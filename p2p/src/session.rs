@@ -0,0 +1,520 @@
+// Copyright 2020 The Epic Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Optional encrypted transport for peer connections.
+//!
+//! When both sides advertise `Capabilities::ENCRYPTED` the Hand/Shake exchange
+//! is followed by a `SessionKey` message in each direction, carrying an
+//! ephemeral x25519 public key signed with the node's static ed25519 key,
+//! along with a hash of the Hand/Shake exchange.
+//! Both sides derive one ChaCha20-Poly1305 key per direction from the shared
+//! secret and all subsequent traffic is sent as length prefixed, encrypted
+//! frames.
+
+use crate::core::core::hash::Hash;
+use crate::core::ser::{self, ProtocolVersion};
+use crate::identity;
+use crate::msg::{Hand, SessionKey, Shake};
+use crate::types::{Capabilities, Error};
+use crate::util::{from_hex, to_hex};
+use blake2_rfc::blake2b::blake2b;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand::{rng, Rng};
+use std::cmp;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+/// File (under db_root) holding the seed of our static ed25519 key.
+const STATIC_KEY_FILE: &str = "p2p_static_key";
+
+/// Max plaintext bytes carried by a single encrypted frame.
+const MAX_FRAME_LEN: usize = 65_536;
+
+/// Size of the Poly1305 authentication tag appended to each frame.
+const TAG_LEN: usize = 16;
+
+/// Size of the length prefix of each frame.
+const LEN_PREFIX: usize = 4;
+
+const AUTH_DOMAIN: &[u8] = b"epic_p2p_session_auth";
+const KDF_DOMAIN: &[u8] = b"epic_p2p_session_keys";
+const TRANSCRIPT_DOMAIN: &[u8] = b"epic_p2p_session_transcript";
+
+/// Loads our static key from db_root, creating a new one on first start.
+/// This is the key peers can pin to authenticate us. It is encrypted with the
+/// identity password when one is set, like our node identity, and otherwise
+/// stored in plaintext only readable by us. The file is rewritten when the
+/// password is set or removed.
+pub fn load_or_create_static_key(db_root: &str, password: &str) -> Result<SigningKey, Error> {
+	let path = Path::new(db_root).join(STATIC_KEY_FILE);
+	if path.exists() {
+		let data = fs::read(&path)?;
+		let (key, outdated) = if identity::is_encrypted(&data) {
+			let (seed, reencrypt) = identity::decrypt_or_unprotected(&data, password)?;
+			(signing_key(&seed)?, reencrypt || password.is_empty())
+		} else {
+			let hex = String::from_utf8(data)
+				.map_err(|_| Error::Encryption("invalid static key file".to_owned()))?;
+			let bytes = from_hex(hex.trim().to_string())
+				.map_err(|_| Error::Encryption("invalid static key file".to_owned()))?;
+			(signing_key(&bytes)?, !password.is_empty())
+		};
+		if outdated {
+			save_static_key(&path, &key, password)?;
+			info!(
+				"Updated the protection of the p2p static key in {}",
+				path.display()
+			);
+		}
+		return Ok(key);
+	}
+
+	fs::create_dir_all(db_root)?;
+	let key = random_static_key();
+	save_static_key(&path, &key, password)?;
+	Ok(key)
+}
+
+fn save_static_key(path: &Path, key: &SigningKey, password: &str) -> Result<(), Error> {
+	if password.is_empty() {
+		fs::write(path, to_hex(key.to_bytes().to_vec()))?;
+	} else {
+		fs::write(path, identity::encrypt(&key.to_bytes(), password))?;
+	}
+	identity::set_permissions(path)
+}
+
+fn signing_key(bytes: &[u8]) -> Result<SigningKey, Error> {
+	if bytes.len() != 32 {
+		return Err(Error::Encryption("invalid static key file".to_owned()));
+	}
+	let mut seed = [0u8; 32];
+	seed.copy_from_slice(bytes);
+	Ok(SigningKey::from_bytes(&seed))
+}
+
+/// A new random static key, used when the key does not need to persist.
+pub fn random_static_key() -> SigningKey {
+	SigningKey::from_bytes(&rng().random::<[u8; 32]>())
+}
+
+/// Hash of the Hand/Shake exchange the session is negotiated after, both
+/// sides sign it so a tampered handshake (e.g. downgraded capabilities)
+/// fails authentication.
+/// Only fields read back exactly as they were sent are covered, the
+/// capabilities being the ones both sides have in common.
+pub fn transcript(hand: &Hand, shake: &Shake, common: Capabilities) -> Result<Hash, Error> {
+	let mut data = TRANSCRIPT_DOMAIN.to_vec();
+	data.extend_from_slice(&u32::from(hand.version).to_be_bytes());
+	data.extend_from_slice(&hand.nonce.to_be_bytes());
+	data.extend_from_slice(hand.genesis.as_bytes());
+	data.extend_from_slice(&ser::ser_vec(
+		&hand.total_difficulty,
+		ProtocolVersion::local(),
+	)?);
+	data.extend_from_slice(&(hand.user_agent.len() as u64).to_be_bytes());
+	data.extend_from_slice(hand.user_agent.as_bytes());
+	data.extend_from_slice(&u32::from(shake.version).to_be_bytes());
+	data.extend_from_slice(shake.genesis.as_bytes());
+	data.extend_from_slice(&ser::ser_vec(
+		&shake.total_difficulty,
+		ProtocolVersion::local(),
+	)?);
+	data.extend_from_slice(&(shake.user_agent.len() as u64).to_be_bytes());
+	data.extend_from_slice(shake.user_agent.as_bytes());
+	data.extend_from_slice(&common.bits().to_be_bytes());
+	Ok(Hash::from_vec(blake2b(32, &[], &data).as_bytes()))
+}
+
+/// What a peer signs with its static key to bind its ephemeral key to
+/// itself and to the handshake. The responder, which receives the
+/// initiator's key first, also signs that one so its answer can't be
+/// replayed in another session.
+fn auth_msg(
+	transcript: Hash,
+	initiator: bool,
+	ephemeral: &[u8; 32],
+	initiator_ephemeral: Option<&[u8; 32]>,
+) -> Vec<u8> {
+	let mut msg = AUTH_DOMAIN.to_vec();
+	msg.extend_from_slice(transcript.as_bytes());
+	msg.push(initiator as u8);
+	msg.extend_from_slice(ephemeral);
+	if let Some(initiator_ephemeral) = initiator_ephemeral {
+		msg.extend_from_slice(initiator_ephemeral);
+	}
+	msg
+}
+
+fn nonce(counter: u64) -> [u8; 12] {
+	let mut nonce = [0u8; 12];
+	nonce[4..].copy_from_slice(&counter.to_le_bytes());
+	nonce
+}
+
+/// Our half of a session key exchange.
+pub struct KeyExchange {
+	secret: StaticSecret,
+	public: PublicKey,
+}
+
+impl KeyExchange {
+	pub fn new() -> KeyExchange {
+		let secret = StaticSecret::from(rng().random::<[u8; 32]>());
+		let public = PublicKey::from(&secret);
+		KeyExchange { secret, public }
+	}
+
+	/// The SessionKey message we send to our peer, the responder passing the
+	/// ephemeral key the initiator sent it.
+	pub fn message(
+		&self,
+		static_key: &SigningKey,
+		transcript: Hash,
+		initiator_ephemeral: Option<&[u8; 32]>,
+	) -> SessionKey {
+		let ephemeral = self.public.to_bytes();
+		let signature = static_key.sign(&auth_msg(
+			transcript,
+			initiator_ephemeral.is_none(),
+			&ephemeral,
+			initiator_ephemeral,
+		));
+		SessionKey {
+			ephemeral,
+			static_key: static_key.verifying_key().to_bytes(),
+			signature: signature.to_bytes(),
+		}
+	}
+
+	/// Verify the SessionKey received from our peer and derive the session.
+	pub fn complete(
+		self,
+		theirs: &SessionKey,
+		transcript: Hash,
+		initiator: bool,
+	) -> Result<Session, Error> {
+		if theirs.ephemeral == self.public.to_bytes() {
+			return Err(Error::Encryption("reflected session key".to_owned()));
+		}
+		let peer_key = VerifyingKey::from_bytes(&theirs.static_key)
+			.map_err(|_| Error::Encryption("invalid static key".to_owned()))?;
+		let signature = Signature::from_bytes(&theirs.signature);
+		let ours = self.public.to_bytes();
+		let signed = if initiator {
+			auth_msg(transcript, false, &theirs.ephemeral, Some(&ours))
+		} else {
+			auth_msg(transcript, true, &theirs.ephemeral, None)
+		};
+		peer_key
+			.verify(&signed, &signature)
+			.map_err(|_| Error::Encryption("invalid session key signature".to_owned()))?;
+
+		let shared = self
+			.secret
+			.diffie_hellman(&PublicKey::from(theirs.ephemeral));
+		if !shared.was_contributory() {
+			return Err(Error::Encryption("non contributory session key".to_owned()));
+		}
+
+		let (initiator_key, responder_key) = if initiator {
+			(self.public.to_bytes(), theirs.ephemeral)
+		} else {
+			(theirs.ephemeral, self.public.to_bytes())
+		};
+		let mut data = KDF_DOMAIN.to_vec();
+		data.extend_from_slice(transcript.as_bytes());
+		data.extend_from_slice(&initiator_key);
+		data.extend_from_slice(&responder_key);
+		let okm = blake2b(64, shared.as_bytes(), &data);
+
+		let (first, second) = okm.as_bytes().split_at(32);
+		let (send_key, recv_key) = if initiator {
+			(first, second)
+		} else {
+			(second, first)
+		};
+		Ok(Session {
+			send: ChaCha20Poly1305::new(Key::from_slice(send_key)),
+			recv: ChaCha20Poly1305::new(Key::from_slice(recv_key)),
+			peer_key,
+		})
+	}
+}
+
+/// An established encrypted session with a peer.
+pub struct Session {
+	send: ChaCha20Poly1305,
+	recv: ChaCha20Poly1305,
+	peer_key: VerifyingKey,
+}
+
+impl Session {
+	/// The static key the peer authenticated with, hex encoded.
+	pub fn peer_key(&self) -> String {
+		to_hex(self.peer_key.to_bytes().to_vec())
+	}
+
+	/// Wrap the read and write halves of the connection.
+	pub fn split<R: Read, W: Write>(
+		self,
+		reader: R,
+		writer: W,
+	) -> (SecureReader<R>, SecureWriter<W>) {
+		(
+			SecureReader {
+				inner: reader,
+				cipher: self.recv,
+				counter: 0,
+				raw: Vec::with_capacity(LEN_PREFIX + TAG_LEN),
+				plain: vec![],
+				pos: 0,
+			},
+			SecureWriter {
+				inner: writer,
+				cipher: self.send,
+				counter: 0,
+			},
+		)
+	}
+}
+
+/// Decrypts frames read from the underlying stream.
+/// A partially received frame is kept across read timeouts so the stream
+/// can be polled without losing data.
+pub struct SecureReader<R> {
+	inner: R,
+	cipher: ChaCha20Poly1305,
+	counter: u64,
+	raw: Vec<u8>,
+	plain: Vec<u8>,
+	pos: usize,
+}
+
+impl<R: Read> SecureReader<R> {
+	fn read_frame(&mut self) -> io::Result<()> {
+		let mut buf = [0u8; 8192];
+		loop {
+			let needed = if self.raw.len() < LEN_PREFIX {
+				LEN_PREFIX
+			} else {
+				let mut len = [0u8; LEN_PREFIX];
+				len.copy_from_slice(&self.raw[..LEN_PREFIX]);
+				let len = u32::from_be_bytes(len) as usize;
+				if len < TAG_LEN || len > MAX_FRAME_LEN + TAG_LEN {
+					return Err(io::Error::new(
+						io::ErrorKind::InvalidData,
+						"invalid encrypted frame length",
+					));
+				}
+				LEN_PREFIX + len
+			};
+			if self.raw.len() > LEN_PREFIX && self.raw.len() == needed {
+				break;
+			}
+			let to_read = cmp::min(buf.len(), needed - self.raw.len());
+			let n = self.inner.read(&mut buf[..to_read])?;
+			if n == 0 {
+				return Err(io::ErrorKind::UnexpectedEof.into());
+			}
+			self.raw.extend_from_slice(&buf[..n]);
+		}
+
+		let plain = self
+			.cipher
+			.decrypt(
+				Nonce::from_slice(&nonce(self.counter)),
+				&self.raw[LEN_PREFIX..],
+			)
+			.map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "frame decryption failed"))?;
+		self.counter += 1;
+		self.raw.clear();
+		self.plain = plain;
+		self.pos = 0;
+		Ok(())
+	}
+}
+
+impl<R: Read> Read for SecureReader<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		if buf.is_empty() {
+			return Ok(0);
+		}
+		while self.pos == self.plain.len() {
+			self.read_frame()?;
+		}
+		let n = cmp::min(buf.len(), self.plain.len() - self.pos);
+		buf[..n].copy_from_slice(&self.plain[self.pos..self.pos + n]);
+		self.pos += n;
+		Ok(n)
+	}
+}
+
+/// Encrypts everything written into frames on the underlying stream.
+pub struct SecureWriter<W> {
+	inner: W,
+	cipher: ChaCha20Poly1305,
+	counter: u64,
+}
+
+impl<W: Write> Write for SecureWriter<W> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		if buf.is_empty() {
+			return Ok(0);
+		}
+		let len = cmp::min(buf.len(), MAX_FRAME_LEN);
+		let sealed = self
+			.cipher
+			.encrypt(Nonce::from_slice(&nonce(self.counter)), &buf[..len])
+			.map_err(|_| io::Error::new(io::ErrorKind::Other, "frame encryption failed"))?;
+		self.counter += 1;
+
+		let mut frame = Vec::with_capacity(LEN_PREFIX + sealed.len());
+		frame.extend_from_slice(&(sealed.len() as u32).to_be_bytes());
+		frame.extend_from_slice(&sealed);
+		self.inner.write_all(&frame)?;
+		Ok(len)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.inner.flush()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::core::pow::Difficulty;
+	use crate::types::PeerAddr;
+
+	fn transcript_hash(nonce: u64) -> Hash {
+		let hand = Hand {
+			version: ProtocolVersion::local(),
+			capabilities: Capabilities::ENCRYPTED,
+			nonce,
+			genesis: Hash::default(),
+			total_difficulty: Difficulty::min(),
+			sender_addr: PeerAddr::Ip("127.0.0.1:3414".parse().unwrap()),
+			receiver_addr: PeerAddr::Ip("127.0.0.1:3415".parse().unwrap()),
+			user_agent: "a".to_owned(),
+		};
+		let shake = Shake {
+			version: ProtocolVersion::local(),
+			capabilities: Capabilities::ENCRYPTED,
+			genesis: Hash::default(),
+			total_difficulty: Difficulty::min(),
+			user_agent: "b".to_owned(),
+			receiver_addr: None,
+		};
+		transcript(&hand, &shake, Capabilities::ENCRYPTED).unwrap()
+	}
+
+	fn sessions(transcript: Hash) -> (Session, Session) {
+		let (a_key, b_key) = (random_static_key(), random_static_key());
+		let (a, b) = (KeyExchange::new(), KeyExchange::new());
+		let a_msg = a.message(&a_key, transcript, None);
+		let b_msg = b.message(&b_key, transcript, Some(&a_msg.ephemeral));
+		let a_session = a.complete(&b_msg, transcript, true).unwrap();
+		let b_session = b.complete(&a_msg, transcript, false).unwrap();
+		assert_eq!(
+			a_session.peer_key(),
+			to_hex(b_key.verifying_key().to_bytes().to_vec())
+		);
+		(a_session, b_session)
+	}
+
+	#[test]
+	fn test_session_roundtrip() {
+		let (a, b) = sessions(transcript_hash(1));
+		let (_, mut a_writer) = a.split(io::empty(), vec![]);
+		let data = (0..200_000).map(|i| i as u8).collect::<Vec<u8>>();
+		a_writer.write_all(&data).unwrap();
+		a_writer.write_all(b"ping").unwrap();
+		assert!(a_writer.inner.len() > data.len());
+
+		let (mut b_reader, _) = b.split(&a_writer.inner[..], io::sink());
+		let mut received = vec![0u8; data.len()];
+		b_reader.read_exact(&mut received).unwrap();
+		assert_eq!(received, data);
+		let mut ping = [0u8; 4];
+		b_reader.read_exact(&mut ping).unwrap();
+		assert_eq!(&ping, b"ping");
+	}
+
+	#[test]
+	fn test_session_rejects_tampering() {
+		let transcript = transcript_hash(1);
+		let (a, b) = sessions(transcript);
+		let (_, mut a_writer) = a.split(io::empty(), vec![]);
+		a_writer.write_all(b"hello").unwrap();
+		let mut sealed = a_writer.inner.clone();
+		sealed[LEN_PREFIX] ^= 1;
+		let (mut b_reader, _) = b.split(&sealed[..], io::sink());
+		let mut buf = [0u8; 5];
+		assert!(b_reader.read_exact(&mut buf).is_err());
+
+		// a session key signed by someone else is rejected
+		let mut forged = KeyExchange::new().message(&random_static_key(), transcript, None);
+		forged.static_key = random_static_key().verifying_key().to_bytes();
+		assert!(KeyExchange::new()
+			.complete(&forged, transcript, false)
+			.is_err());
+
+		// as is one signed over another handshake
+		let key = random_static_key();
+		let initiator = KeyExchange::new().message(&key, transcript_hash(2), None);
+		assert!(KeyExchange::new()
+			.complete(&initiator, transcript, false)
+			.is_err());
+
+		// or a responder key replayed to another initiator
+		let (a, b) = (KeyExchange::new(), KeyExchange::new());
+		let a_msg = a.message(&key, transcript, None);
+		let b_msg = b.message(&key, transcript, Some(&a_msg.ephemeral));
+		assert!(KeyExchange::new()
+			.complete(&b_msg, transcript, true)
+			.is_err());
+	}
+
+	#[test]
+	fn test_static_key_protection() {
+		let dir = tempfile::tempdir().unwrap();
+		let db_root = dir.path().to_str().unwrap();
+		let path = dir.path().join(STATIC_KEY_FILE);
+
+		// without a password the key is kept in plaintext
+		let key = load_or_create_static_key(db_root, "").unwrap();
+		assert!(!identity::is_encrypted(&fs::read(&path).unwrap()));
+
+		// and encrypted once one is set
+		let reloaded = load_or_create_static_key(db_root, "secret").unwrap();
+		assert_eq!(reloaded.to_bytes(), key.to_bytes());
+		let data = fs::read(&path).unwrap();
+		assert!(identity::is_encrypted(&data));
+		assert!(!data.windows(32).any(|w| w == &key.to_bytes()[..]));
+		let reloaded = load_or_create_static_key(db_root, "secret").unwrap();
+		assert_eq!(reloaded.to_bytes(), key.to_bytes());
+		assert!(load_or_create_static_key(db_root, "wrong").is_err());
+
+		// a key encrypted without a password is encrypted again with it
+		fs::write(&path, identity::encrypt(&key.to_bytes(), "")).unwrap();
+		let reloaded = load_or_create_static_key(db_root, "secret").unwrap();
+		assert_eq!(reloaded.to_bytes(), key.to_bytes());
+		assert!(identity::decrypt(&fs::read(&path).unwrap(), "secret").is_ok());
+	}
+}
@@ -27,6 +27,7 @@ const DB_NAME: &'static str = "peer";
 const STORE_SUBPATH: &'static str = "peers";
 
 const PEER_PREFIX: u8 = 'P' as u8;
+const ANCHOR_PREFIX: u8 = 'A' as u8;
 
 //State of peer
 enum_from_primitive! {
@@ -269,6 +270,36 @@ impl PeerStore {
 
 		Ok(())
 	}
+
+	/// Replaces the saved anchors, the outbound peers we reconnect to first
+	/// on restart.
+	pub fn save_anchors(&self, addrs: &[PeerAddr]) -> Result<(), Error> {
+		let existing = self
+			.db
+			.iter::<PeerAddr>(&to_key(ANCHOR_PREFIX, &mut "".to_string().into_bytes()))?
+			.map(|(k, _)| k)
+			.collect::<Vec<_>>();
+		let batch = self.db.batch()?;
+		for key in existing {
+			batch.delete(&key[..])?;
+		}
+		for addr in addrs {
+			batch.put_ser(&anchor_key(*addr)[..], addr)?;
+		}
+		batch.commit()
+	}
+
+	/// Returns the saved anchors and removes them from the store, so a bad
+	/// anchor doesn't keep being reconnected to on every restart.
+	pub fn take_anchors(&self) -> Result<Vec<PeerAddr>, Error> {
+		let anchors = self
+			.db
+			.iter::<PeerAddr>(&to_key(ANCHOR_PREFIX, &mut "".to_string().into_bytes()))?
+			.map(|(_, v)| v)
+			.collect::<Vec<_>>();
+		self.save_anchors(&[])?;
+		Ok(anchors)
+	}
 }
 
 // Ignore the port unless ip is loopback address.
@@ -276,6 +307,10 @@ fn peer_key(peer_addr: PeerAddr) -> Vec<u8> {
 	to_key(PEER_PREFIX, &mut peer_addr.as_key().into_bytes())
 }
 
+fn anchor_key(peer_addr: PeerAddr) -> Vec<u8> {
+	to_key(ANCHOR_PREFIX, &mut peer_addr.as_key().into_bytes())
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -283,7 +318,7 @@ mod tests {
 
 	#[test]
 	fn test_update_ban_reason() {
-		let peer_addr = "127.0.0.1:13414".parse().map(PeerAddr).unwrap();
+		let peer_addr = "127.0.0.1:13414".parse().map(PeerAddr::Ip).unwrap();
 		let ban_reason = ReasonForBan::BadBlock;
 
 		// Erstelle einen PeerStore und füge einen Peer hinzu
@@ -19,6 +19,7 @@ use crate::Error;
 
 use ed25519_dalek::SigningKey as DalekSecretKey;
 use ed25519_dalek::VerifyingKey as DalekPublicKey;
+use sha2::{Digest, Sha512};
 
 use std::fs::{self, File};
 use std::io::Write;
@@ -30,6 +31,7 @@ const TORRC_FILE: &'static str = "torrc";
 const TOR_DATA_DIR: &'static str = "data";
 const AUTH_CLIENTS_DIR: &'static str = "authorized_clients";
 const HIDDEN_SERVICES_DIR: &'static str = "onion_service_addresses";
+const CONTROL_COOKIE_FILE: &'static str = "control_auth_cookie";
 
 #[cfg(unix)]
 fn set_permissions(file_path: &str) -> Result<(), Error> {
@@ -100,10 +102,21 @@ pub fn create_onion_service_sec_key_file(
 	let mut file = File::create(key_file_path)?;
 	// Tag is always 32 bytes, so pad with null zeroes
 	file.write("== ed25519v1-secret: type0 ==\0\0\0".as_bytes())?;
-	file.write_all(&sec_key.to_bytes())?;
+	file.write_all(&expanded_secret_key(sec_key))?;
 	Ok(())
 }
 
+/// Tor stores the expanded form of the secret key: the clamped scalar
+/// followed by the nonce prefix, both taken from the SHA-512 of the seed.
+pub(crate) fn expanded_secret_key(sec_key: &DalekSecretKey) -> [u8; 64] {
+	let mut expanded = [0u8; 64];
+	expanded.copy_from_slice(&Sha512::digest(sec_key.to_bytes()));
+	expanded[0] &= 248;
+	expanded[31] &= 127;
+	expanded[31] |= 64;
+	expanded
+}
+
 pub fn create_onion_service_pub_key_file(
 	os_directory: &str,
 	pub_key: &DalekPublicKey,
@@ -167,7 +180,24 @@ pub fn output_torrc(
 	service_dirs: &Vec<String>,
 ) -> Result<(), Error> {
 	let torrc_file_path = format!("{}/{}", tor_config_directory, TORRC_FILE);
+	let props = torrc_config(
+		tor_config_directory,
+		api_http_addr,
+		socks_port,
+		service_dirs,
+	);
+	props.write_to_file(&torrc_file_path)?;
+
+	Ok(())
+}
 
+/// torrc items for the socks port and the API hidden services
+fn torrc_config(
+	tor_config_directory: &str,
+	api_http_addr: &str,
+	socks_port: &str,
+	service_dirs: &Vec<String>,
+) -> TorRcConfig {
 	let tor_data_dir = format!("{}/{}", tor_config_directory, TOR_DATA_DIR);
 
 	let mut props = TorRcConfig::new();
@@ -190,9 +220,7 @@ pub fn output_torrc(
 		);
 	}
 
-	props.write_to_file(&torrc_file_path)?;
-
-	Ok(())
+	props
 }
 
 /// output entire tor config for a list of secret keys
@@ -220,6 +248,27 @@ pub fn output_tor_listener_config_auto(
 	Ok(())
 }
 
+/// output the torrc of a tor process we drive through its control port: no
+/// onion service is configured on disk, they are added with ADD_ONION once
+/// tor runs. Returns the path of the control auth cookie.
+pub fn output_tor_control_config(
+	tor_config_directory: &str,
+	socks_listener_addr: &str,
+	control_addr: &str,
+) -> Result<String, Error> {
+	let tor_data_dir = format!("{}{}{}", tor_config_directory, MAIN_SEPARATOR, TOR_DATA_DIR);
+	fs::create_dir_all(&tor_data_dir)?;
+	let cookie_file = format!("{}{}{}", tor_data_dir, MAIN_SEPARATOR, CONTROL_COOKIE_FILE);
+
+	let mut props = torrc_config(tor_config_directory, "", socks_listener_addr, &vec![]);
+	props.add_item("ControlPort", control_addr);
+	props.add_item("CookieAuthentication", "1");
+	props.add_item("CookieAuthFile", &cookie_file);
+	props.write_to_file(&format!("{}/{}", tor_config_directory, TORRC_FILE))?;
+
+	Ok(cookie_file)
+}
+
 /// output tor config for a send
 pub fn output_tor_sender_config(
 	tor_config_dir: &str,
@@ -343,6 +392,20 @@ mod tests {
 		Ok(())
 	}
 
+	#[test]
+	fn test_control_config() -> Result<(), Error> {
+		let test_dir = "target/test_output/tor_control";
+		setup(test_dir);
+		let cookie_file = output_tor_control_config(test_dir, "127.0.0.1:9050", "127.0.0.1:9051")?;
+		assert!(cookie_file.ends_with(CONTROL_COOKIE_FILE));
+		let torrc = fs::read_to_string(format!("{}/{}", test_dir, TORRC_FILE))?;
+		assert!(torrc.contains("ControlPort 127.0.0.1:9051"));
+		assert!(torrc.contains("CookieAuthentication 1"));
+		assert!(!torrc.contains("HiddenServiceDir"));
+		clean_output_dir(test_dir);
+		Ok(())
+	}
+
 	#[test]
 	fn test_output_tor_config() -> Result<(), Error> {
 		let test_dir = "./target/test_output/tor";
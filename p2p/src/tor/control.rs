@@ -0,0 +1,465 @@
+// Copyright 2020 The Epic Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Minimal client for the tor control protocol (control-spec.txt), enough to
+//! authenticate, publish ephemeral onion services and keep an eye on the
+//! bootstrap and circuits of a tor daemon, be it our own or the system one.
+
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use data_encoding::BASE64;
+use ed25519_dalek::SigningKey as DalekSecretKey;
+
+use crate::tor::config::expanded_secret_key;
+use crate::util::to_hex;
+
+/// Length of the tor control auth cookie.
+const COOKIE_LEN: usize = 32;
+
+#[derive(Debug)]
+pub enum Error {
+	IO(io::Error),
+	/// The control port replied something we don't understand.
+	Protocol(String),
+	/// Tor refused the command, with its status code and message.
+	Tor(u16, String),
+	/// None of the authentication methods offered by tor is usable.
+	Auth(String),
+	Timeout,
+}
+
+impl From<io::Error> for Error {
+	fn from(e: io::Error) -> Error {
+		Error::IO(e)
+	}
+}
+
+/// What PROTOCOLINFO tells us about the tor daemon.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProtocolInfo {
+	pub auth_methods: Vec<String>,
+	pub cookie_file: Option<PathBuf>,
+	pub tor_version: Option<String>,
+}
+
+/// Bootstrap progress, as reported by tor.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BootstrapStatus {
+	/// Progress in percent, 100 once bootstrapped
+	pub progress: u8,
+	/// Short tag of the current phase, e.g. "done"
+	pub tag: String,
+	/// Human readable summary of the current phase
+	pub summary: String,
+}
+
+impl BootstrapStatus {
+	pub fn is_done(&self) -> bool {
+		self.progress >= 100
+	}
+}
+
+/// Health of the circuits of the tor daemon.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CircuitHealth {
+	/// Circuits ready to carry traffic
+	pub built: usize,
+	/// Circuits being built
+	pub building: usize,
+	/// Whether tor believes the network is reachable
+	pub network_live: bool,
+}
+
+/// A single reply of the control port.
+#[derive(Debug)]
+struct Reply {
+	code: u16,
+	lines: Vec<String>,
+}
+
+/// Connection to the control port of a tor daemon.
+pub struct TorControl {
+	reader: BufReader<TcpStream>,
+	writer: TcpStream,
+}
+
+impl TorControl {
+	/// Connects to the control port listening on `addr`. Every reply is
+	/// expected within `timeout`.
+	pub fn connect(addr: SocketAddr, timeout: Duration) -> Result<TorControl, Error> {
+		let stream = TcpStream::connect_timeout(&addr, timeout)?;
+		stream.set_read_timeout(Some(timeout))?;
+		stream.set_write_timeout(Some(timeout))?;
+		Ok(TorControl {
+			reader: BufReader::new(stream.try_clone()?),
+			writer: stream,
+		})
+	}
+
+	/// Asks tor how we can authenticate, allowed before authenticating.
+	pub fn protocol_info(&mut self) -> Result<ProtocolInfo, Error> {
+		let reply = self.command("PROTOCOLINFO 1")?;
+		let mut info = ProtocolInfo {
+			auth_methods: vec![],
+			cookie_file: None,
+			tor_version: None,
+		};
+		for line in &reply.lines {
+			if let Some(auth) = line.strip_prefix("AUTH ") {
+				if let Some(methods) = value_of(auth, "METHODS") {
+					info.auth_methods = methods.split(',').map(|m| m.to_string()).collect();
+				}
+				info.cookie_file = value_of(auth, "COOKIEFILE").map(PathBuf::from);
+			} else if let Some(version) = line.strip_prefix("VERSION ") {
+				info.tor_version = value_of(version, "Tor");
+			}
+		}
+		Ok(info)
+	}
+
+	/// Authenticates with the auth cookie, read from `cookie_file` or from
+	/// the file tor tells us about. Tor instances without authentication are
+	/// fine too.
+	pub fn authenticate(&mut self, cookie_file: Option<&Path>) -> Result<(), Error> {
+		let info = self.protocol_info()?;
+		if info.auth_methods.iter().any(|m| m == "NULL") {
+			self.command("AUTHENTICATE")?;
+			return Ok(());
+		}
+		if !info.auth_methods.iter().any(|m| m == "COOKIE") {
+			return Err(Error::Auth(format!(
+				"cookie authentication not enabled, tor offers {:?}",
+				info.auth_methods
+			)));
+		}
+		let path = match cookie_file {
+			Some(path) => path.to_path_buf(),
+			None => info
+				.cookie_file
+				.ok_or_else(|| Error::Auth("tor did not tell us its cookie file".to_string()))?,
+		};
+		let cookie = fs::read(&path)?;
+		if cookie.len() != COOKIE_LEN {
+			return Err(Error::Auth(format!(
+				"invalid cookie file {}",
+				path.display()
+			)));
+		}
+		self.command(&format!("AUTHENTICATE {}", to_hex(cookie)))?;
+		Ok(())
+	}
+
+	/// Publishes an ephemeral onion service under the provided key, mapping
+	/// each virtual port to a local target. The service lives as long as
+	/// this connection. Returns the onion address, without ".onion".
+	pub fn add_onion(
+		&mut self,
+		key: &DalekSecretKey,
+		ports: &[(u16, SocketAddr)],
+	) -> Result<String, Error> {
+		let mut cmd = format!(
+			"ADD_ONION ED25519-V3:{}",
+			BASE64.encode(&expanded_secret_key(key))
+		);
+		for (port, target) in ports {
+			cmd.push_str(&format!(" Port={},{}", port, target));
+		}
+		let reply = self.command(&cmd)?;
+		reply
+			.lines
+			.iter()
+			.find_map(|l| l.strip_prefix("ServiceID="))
+			.map(|id| id.to_string())
+			.ok_or_else(|| Error::Protocol("no ServiceID in ADD_ONION reply".to_string()))
+	}
+
+	/// Takes down an onion service published with add_onion.
+	pub fn del_onion(&mut self, service_id: &str) -> Result<(), Error> {
+		self.command(&format!("DEL_ONION {}", service_id))?;
+		Ok(())
+	}
+
+	/// The value of a GETINFO key.
+	pub fn get_info(&mut self, key: &str) -> Result<String, Error> {
+		let reply = self.command(&format!("GETINFO {}", key))?;
+		let prefix = format!("{}=", key);
+		reply
+			.lines
+			.iter()
+			.find_map(|l| l.strip_prefix(&prefix))
+			.map(|v| v.trim_start_matches('\n').to_string())
+			.ok_or_else(|| Error::Protocol(format!("no {} in GETINFO reply", key)))
+	}
+
+	pub fn bootstrap_status(&mut self) -> Result<BootstrapStatus, Error> {
+		// e.g. "NOTICE BOOTSTRAP PROGRESS=100 TAG=done SUMMARY="Done""
+		let phase = self.get_info("status/bootstrap-phase")?;
+		let progress = value_of(&phase, "PROGRESS")
+			.and_then(|p| p.parse().ok())
+			.ok_or_else(|| Error::Protocol(format!("invalid bootstrap phase: {}", phase)))?;
+		Ok(BootstrapStatus {
+			progress,
+			tag: value_of(&phase, "TAG").unwrap_or_default(),
+			summary: value_of(&phase, "SUMMARY").unwrap_or_default(),
+		})
+	}
+
+	/// Polls the bootstrap status until tor is done bootstrapping.
+	pub fn wait_for_bootstrap(&mut self, timeout: Duration) -> Result<BootstrapStatus, Error> {
+		let deadline = Instant::now() + timeout;
+		let mut last_progress = None;
+		loop {
+			let status = self.bootstrap_status()?;
+			if last_progress != Some(status.progress) {
+				info!("Tor bootstrapped {}%: {}", status.progress, status.summary);
+				last_progress = Some(status.progress);
+			}
+			if status.is_done() {
+				return Ok(status);
+			}
+			if Instant::now() >= deadline {
+				return Err(Error::Timeout);
+			}
+			thread::sleep(Duration::from_millis(500));
+		}
+	}
+
+	pub fn circuit_health(&mut self) -> Result<CircuitHealth, Error> {
+		// one "<id> <status> <path> <flags>..." line per circuit
+		let circuits = self.get_info("circuit-status")?;
+		let mut health = CircuitHealth::default();
+		for status in circuits.lines().filter_map(|l| l.split(' ').nth(1)) {
+			match status {
+				"BUILT" => health.built += 1,
+				"LAUNCHED" | "EXTENDED" | "GUARD_WAIT" => health.building += 1,
+				_ => (),
+			}
+		}
+		health.network_live = self.get_info("network-liveness")? == "up";
+		Ok(health)
+	}
+
+	/// Sends a command and reads its reply, failing on anything but 250.
+	fn command(&mut self, cmd: &str) -> Result<Reply, Error> {
+		self.writer.write_all(format!("{}\r\n", cmd).as_bytes())?;
+		let reply = self.read_reply()?;
+		if reply.code != 250 {
+			return Err(Error::Tor(reply.code, reply.lines.join(" ")));
+		}
+		Ok(reply)
+	}
+
+	/// Reads a reply: "XYZ-" lines, "XYZ+" lines followed by a dot terminated
+	/// data block, and a final "XYZ " line.
+	fn read_reply(&mut self) -> Result<Reply, Error> {
+		let mut lines = vec![];
+		loop {
+			let line = self.read_line()?;
+			if line.len() < 4 || !line.is_char_boundary(3) {
+				return Err(Error::Protocol(format!("invalid reply line: {}", line)));
+			}
+			let code: u16 = line[..3]
+				.parse()
+				.map_err(|_| Error::Protocol(format!("invalid reply line: {}", line)))?;
+			let mut text = line[4..].to_string();
+			// asynchronous events, we never ask for them
+			if code == 650 {
+				continue;
+			}
+			match &line[3..4] {
+				"-" => lines.push(text),
+				"+" => {
+					loop {
+						let data = self.read_line()?;
+						if data == "." {
+							break;
+						}
+						text.push('\n');
+						text.push_str(data.strip_prefix('.').unwrap_or(&data));
+					}
+					lines.push(text);
+				}
+				" " => {
+					lines.push(text);
+					return Ok(Reply { code, lines });
+				}
+				_ => return Err(Error::Protocol(format!("invalid reply line: {}", line))),
+			}
+		}
+	}
+
+	fn read_line(&mut self) -> Result<String, Error> {
+		let mut line = String::new();
+		if self.reader.read_line(&mut line)? == 0 {
+			return Err(Error::IO(io::Error::new(
+				io::ErrorKind::UnexpectedEof,
+				"tor closed the control connection",
+			)));
+		}
+		Ok(line.trim_end_matches(&['\r', '\n'][..]).to_string())
+	}
+}
+
+/// The value of a KEY=VALUE pair in a space separated reply line, VALUE
+/// possibly being a quoted string.
+fn value_of(line: &str, key: &str) -> Option<String> {
+	let prefix = format!("{}=", key);
+	let start = line
+		.match_indices(&prefix)
+		.map(|(i, _)| i)
+		.find(|i| *i == 0 || line.as_bytes()[i - 1] == b' ')?
+		+ prefix.len();
+	let rest = &line[start..];
+	if let Some(quoted) = rest.strip_prefix('"') {
+		let mut value = String::new();
+		let mut chars = quoted.chars();
+		while let Some(c) = chars.next() {
+			match c {
+				'\\' => value.push(chars.next()?),
+				'"' => return Some(value),
+				c => value.push(c),
+			}
+		}
+		None
+	} else {
+		Some(rest.split(' ').next().unwrap_or("").to_string())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::net::TcpListener;
+
+	/// Stand-in control port, checks each command it receives and answers
+	/// with the scripted reply.
+	fn stand_in(script: Vec<(&'static str, String)>) -> (SocketAddr, thread::JoinHandle<()>) {
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let addr = listener.local_addr().unwrap();
+		let handle = thread::spawn(move || {
+			let (conn, _) = listener.accept().unwrap();
+			let mut reader = BufReader::new(conn.try_clone().unwrap());
+			let mut writer = conn;
+			for (expected, reply) in script {
+				let mut cmd = String::new();
+				reader.read_line(&mut cmd).unwrap();
+				assert!(
+					cmd.trim_end().starts_with(expected),
+					"unexpected command {}",
+					cmd
+				);
+				writer.write_all(reply.as_bytes()).unwrap();
+			}
+		});
+		(addr, handle)
+	}
+
+	#[test]
+	fn test_cookie_auth() {
+		let dir = tempfile::tempdir().unwrap();
+		let cookie_path = dir.path().join("control_auth_cookie");
+		fs::write(&cookie_path, [42u8; COOKIE_LEN]).unwrap();
+
+		let protocol_info = format!(
+			"250-PROTOCOLINFO 1\r\n\
+			 250-AUTH METHODS=COOKIE,SAFECOOKIE COOKIEFILE=\"{}\"\r\n\
+			 250-VERSION Tor=\"0.4.8.9\"\r\n\
+			 250 OK\r\n",
+			cookie_path.display()
+		);
+		let (addr, handle) = stand_in(vec![
+			("PROTOCOLINFO 1", protocol_info.clone()),
+			("PROTOCOLINFO 1", protocol_info),
+			(
+				"AUTHENTICATE 2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a",
+				"250 OK\r\n".to_string(),
+			),
+		]);
+
+		let mut control = TorControl::connect(addr, Duration::from_secs(5)).unwrap();
+		let info = control.protocol_info().unwrap();
+		assert_eq!(info.auth_methods, vec!["COOKIE", "SAFECOOKIE"]);
+		assert_eq!(info.cookie_file, Some(cookie_path));
+		assert_eq!(info.tor_version, Some("0.4.8.9".to_string()));
+		control.authenticate(None).unwrap();
+		handle.join().unwrap();
+	}
+
+	#[test]
+	fn test_onion_and_status() {
+		let key = DalekSecretKey::from_bytes(&[7u8; 32]);
+		let service_id = crate::tor::address::onion_v3_from_pubkey(&key.verifying_key()).unwrap();
+		let (addr, handle) = stand_in(vec![
+			(
+				"ADD_ONION ED25519-V3:",
+				format!("250-ServiceID={}\r\n250 OK\r\n", service_id),
+			),
+			(
+				"GETINFO status/bootstrap-phase",
+				"250-status/bootstrap-phase=NOTICE BOOTSTRAP PROGRESS=85 TAG=ap_conn \
+				 SUMMARY=\"Connecting to a relay to build circuits\"\r\n250 OK\r\n"
+					.to_string(),
+			),
+			(
+				"GETINFO circuit-status",
+				"250+circuit-status=\r\n\
+				 1 BUILT $A~a,$B~b,$C~c BUILD_FLAGS=NEED_CAPACITY PURPOSE=GENERAL\r\n\
+				 2 BUILT $A~a,$D~d PURPOSE=HS_SERVICE_INTRO\r\n\
+				 3 LAUNCHED PURPOSE=GENERAL\r\n\
+				 .\r\n\
+				 250 OK\r\n"
+					.to_string(),
+			),
+			(
+				"GETINFO network-liveness",
+				"250-network-liveness=up\r\n250 OK\r\n".to_string(),
+			),
+			("DEL_ONION", "552 Unknown Onion Service id\r\n".to_string()),
+		]);
+
+		let mut control = TorControl::connect(addr, Duration::from_secs(5)).unwrap();
+		let target: SocketAddr = "127.0.0.1:3414".parse().unwrap();
+		assert_eq!(
+			control.add_onion(&key, &[(3414, target)]).unwrap(),
+			service_id
+		);
+
+		let status = control.bootstrap_status().unwrap();
+		assert_eq!(status.progress, 85);
+		assert_eq!(status.tag, "ap_conn");
+		assert_eq!(status.summary, "Connecting to a relay to build circuits");
+		assert!(!status.is_done());
+
+		let health = control.circuit_health().unwrap();
+		assert_eq!(
+			health,
+			CircuitHealth {
+				built: 2,
+				building: 1,
+				network_live: true,
+			}
+		);
+
+		match control.del_onion("unknown") {
+			Err(Error::Tor(552, _)) => (),
+			res => panic!("unexpected result {:?}", res),
+		}
+		handle.join().unwrap();
+	}
+}
@@ -0,0 +1,148 @@
+// Copyright 2020 The Epic Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Minimal SOCKS5 client (RFC 1928), enough to open TCP connections through
+//! the tor SOCKS proxy. The target is always sent as a domain name so tor
+//! resolves it, which is required for onion addresses.
+
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+const SOCKS_VERSION: u8 = 0x05;
+const NO_AUTH: u8 = 0x00;
+const CMD_CONNECT: u8 = 0x01;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+/// Opens a TCP connection to `host:port` through the SOCKS5 proxy at
+/// `proxy`. The returned stream has its read and write timeouts set to
+/// `timeout`.
+pub fn connect(
+	proxy: SocketAddr,
+	host: &str,
+	port: u16,
+	timeout: Duration,
+) -> io::Result<TcpStream> {
+	if host.is_empty() || host.len() > 255 {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidInput,
+			"invalid SOCKS5 target host",
+		));
+	}
+
+	let mut stream = TcpStream::connect_timeout(&proxy, timeout)?;
+	stream.set_read_timeout(Some(timeout))?;
+	stream.set_write_timeout(Some(timeout))?;
+
+	// greeting, we only offer "no authentication"
+	stream.write_all(&[SOCKS_VERSION, 1, NO_AUTH])?;
+	let mut reply = [0u8; 2];
+	stream.read_exact(&mut reply)?;
+	if reply[0] != SOCKS_VERSION || reply[1] != NO_AUTH {
+		return Err(socks_error("proxy refused our authentication method"));
+	}
+
+	let mut req = vec![SOCKS_VERSION, CMD_CONNECT, 0x00, ATYP_DOMAIN];
+	req.push(host.len() as u8);
+	req.extend_from_slice(host.as_bytes());
+	req.extend_from_slice(&port.to_be_bytes());
+	stream.write_all(&req)?;
+
+	let mut head = [0u8; 4];
+	stream.read_exact(&mut head)?;
+	if head[0] != SOCKS_VERSION {
+		return Err(socks_error("invalid proxy reply"));
+	}
+	if head[1] != 0x00 {
+		return Err(socks_error(reply_message(head[1])));
+	}
+
+	// skip the bound address the proxy replies with
+	let addr_len = match head[3] {
+		ATYP_IPV4 => 4,
+		ATYP_IPV6 => 16,
+		ATYP_DOMAIN => {
+			let mut len = [0u8; 1];
+			stream.read_exact(&mut len)?;
+			len[0] as usize
+		}
+		_ => return Err(socks_error("invalid address type in proxy reply")),
+	};
+	let mut bound = vec![0u8; addr_len + 2];
+	stream.read_exact(&mut bound)?;
+
+	Ok(stream)
+}
+
+fn socks_error(msg: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::Other, format!("SOCKS5: {}", msg))
+}
+
+fn reply_message(code: u8) -> &'static str {
+	match code {
+		0x01 => "general failure",
+		0x02 => "connection not allowed by ruleset",
+		0x03 => "network unreachable",
+		0x04 => "host unreachable",
+		0x05 => "connection refused",
+		0x06 => "TTL expired",
+		0x07 => "command not supported",
+		0x08 => "address type not supported",
+		_ => "unknown error",
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::net::TcpListener;
+	use std::thread;
+
+	#[test]
+	fn test_socks5_connect() {
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let proxy = listener.local_addr().unwrap();
+
+		// stand-in proxy, checks the request and echoes a byte back
+		let handle = thread::spawn(move || {
+			let (mut conn, _) = listener.accept().unwrap();
+			let mut greeting = [0u8; 3];
+			conn.read_exact(&mut greeting).unwrap();
+			assert_eq!(greeting, [5, 1, 0]);
+			conn.write_all(&[5, 0]).unwrap();
+
+			let mut head = [0u8; 5];
+			conn.read_exact(&mut head).unwrap();
+			assert_eq!(&head[..4], &[5, 1, 0, 3]);
+			let mut host = vec![0u8; head[4] as usize + 2];
+			conn.read_exact(&mut host).unwrap();
+			assert_eq!(&host[..host.len() - 2], b"example.onion");
+			assert_eq!(&host[host.len() - 2..], &3414u16.to_be_bytes());
+			conn.write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0]).unwrap();
+
+			let mut ping = [0u8; 1];
+			conn.read_exact(&mut ping).unwrap();
+			conn.write_all(&ping).unwrap();
+		});
+
+		let mut stream = connect(proxy, "example.onion", 3414, Duration::from_secs(5)).unwrap();
+		stream.write_all(&[42]).unwrap();
+		let mut pong = [0u8; 1];
+		stream.read_exact(&mut pong).unwrap();
+		assert_eq!(pong, [42]);
+		handle.join().unwrap();
+	}
+}
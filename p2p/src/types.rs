@@ -19,6 +19,7 @@ use std::fs::File;
 use std::io::{self, Read};
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
 use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::Arc;
 
 use chrono::prelude::*;
@@ -29,10 +30,13 @@ use crate::core::core::hash::Hash;
 use crate::core::global;
 use crate::core::pow::Difficulty;
 use crate::core::ser::{self, ProtocolVersion, Readable, Reader, Writeable, Writer};
+use crate::tor::address;
+use crate::util::secp::pedersen::Commitment;
 use bitflags::bitflags;
+use ed25519_dalek::VerifyingKey as DalekPublicKey;
 use epic_chain::types::SyncStatus;
 use epic_store;
-use serde::{Deserialize, Serialize};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 /// Maximum number of block headers a peer should ever send
 pub const MAX_BLOCK_HEADERS: u32 = 512;
@@ -47,6 +51,12 @@ pub const MAX_PEER_ADDRS: u32 = 256;
 /// Maximum number of block header hashes to send as part of a locator
 pub const MAX_LOCATORS: u32 = 20;
 
+/// Default DNS seeds for mainnet
+const MAINNET_DNS_SEEDS: &'static [&'static str] = &["node.epiccash.com"];
+
+/// Default DNS seeds for floonet
+const FLOONET_DNS_SEEDS: &'static [&'static str] = &["floonet.epiccash.com"];
+
 /// How long a banned peer should be banned for
 const BAN_WINDOW: i64 = 10800;
 
@@ -59,6 +69,9 @@ const PEER_MAX_OUTBOUND_COUNT: u32 = 128;
 /// The min preferred outbound peer count
 const PEER_MIN_PREFERRED_OUTBOUND_COUNT: u32 = 4;
 
+/// The max outbound peer count within a single network group
+const PEER_MAX_OUTBOUND_PER_GROUP: u32 = 4;
+
 /// The peer listener buffer count. Allows temporarily accepting more connections
 /// than allowed by PEER_MAX_INBOUND_COUNT to encourage network bootstrapping.
 const PEER_LISTENER_BUFFER_COUNT: u32 = 8;
@@ -89,6 +102,7 @@ pub enum Error {
 	InvalidBanReason,
 	IO(io::Error), // Fehler vom Typ io::Error
 	NotOnion(String),
+	Encryption(String),
 
 	AddressDecoding(String),
 }
@@ -114,13 +128,18 @@ impl From<io::Error> for Error {
 	}
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-pub struct PeerAddr(pub SocketAddr);
+/// Address of a peer, either a socket address or a tor v3 onion service.
+#[derive(Debug, Clone, Copy)]
+pub enum PeerAddr {
+	Ip(SocketAddr),
+	/// The ed25519 public key of the onion service and its port.
+	Onion([u8; 32], u16),
+}
 
 impl Writeable for PeerAddr {
 	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
-		match self.0 {
-			SocketAddr::V4(sav4) => {
+		match self {
+			PeerAddr::Ip(SocketAddr::V4(sav4)) => {
 				ser_multiwrite!(
 					writer,
 					[write_u8, 0],
@@ -128,13 +147,18 @@ impl Writeable for PeerAddr {
 					[write_u16, sav4.port()]
 				);
 			}
-			SocketAddr::V6(sav6) => {
+			PeerAddr::Ip(SocketAddr::V6(sav6)) => {
 				writer.write_u8(1)?;
 				for seg in &sav6.ip().segments() {
 					writer.write_u16(*seg)?;
 				}
 				writer.write_u16(sav6.port())?;
 			}
+			PeerAddr::Onion(key, port) => {
+				writer.write_u8(2)?;
+				writer.write_fixed_bytes(&key.to_vec())?;
+				writer.write_u16(*port)?;
+			}
 		}
 		Ok(())
 	}
@@ -142,23 +166,34 @@ impl Writeable for PeerAddr {
 
 impl Readable for PeerAddr {
 	fn read(reader: &mut dyn Reader) -> Result<PeerAddr, ser::Error> {
-		let v4_or_v6 = reader.read_u8()?;
-		if v4_or_v6 == 0 {
-			let ip = reader.read_fixed_bytes(4)?;
-			let port = reader.read_u16()?;
-			Ok(PeerAddr(SocketAddr::V4(SocketAddrV4::new(
-				Ipv4Addr::new(ip[0], ip[1], ip[2], ip[3]),
-				port,
-			))))
-		} else {
-			let ip = try_iter_map_vec!(0..8, |_| reader.read_u16());
-			let port = reader.read_u16()?;
-			Ok(PeerAddr(SocketAddr::V6(SocketAddrV6::new(
-				Ipv6Addr::new(ip[0], ip[1], ip[2], ip[3], ip[4], ip[5], ip[6], ip[7]),
-				port,
-				0,
-				0,
-			))))
+		match reader.read_u8()? {
+			0 => {
+				let ip = reader.read_fixed_bytes(4)?;
+				let port = reader.read_u16()?;
+				Ok(PeerAddr::Ip(SocketAddr::V4(SocketAddrV4::new(
+					Ipv4Addr::new(ip[0], ip[1], ip[2], ip[3]),
+					port,
+				))))
+			}
+			1 => {
+				let ip = try_iter_map_vec!(0..8, |_| reader.read_u16());
+				let port = reader.read_u16()?;
+				Ok(PeerAddr::Ip(SocketAddr::V6(SocketAddrV6::new(
+					Ipv6Addr::new(ip[0], ip[1], ip[2], ip[3], ip[4], ip[5], ip[6], ip[7]),
+					port,
+					0,
+					0,
+				))))
+			}
+			2 => {
+				let mut key = [0u8; 32];
+				key.copy_from_slice(&reader.read_fixed_bytes(32)?);
+				let port = reader.read_u16()?;
+				// only accept keys we can turn back into an onion address
+				DalekPublicKey::from_bytes(&key).map_err(|_| ser::Error::CorruptedData)?;
+				Ok(PeerAddr::Onion(key, port))
+			}
+			_ => Err(ser::Error::CorruptedData),
 		}
 	}
 }
@@ -166,11 +201,12 @@ impl Readable for PeerAddr {
 impl std::hash::Hash for PeerAddr {
 	/// If loopback address then we care about ip and port.
 	/// If regular address then we only care about the ip and ignore the port.
+	/// Onion addresses are identified by their key only.
 	fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-		if self.0.ip().is_loopback() {
-			self.0.hash(state);
-		} else {
-			self.0.ip().hash(state);
+		match self {
+			PeerAddr::Ip(addr) if addr.ip().is_loopback() => addr.hash(state),
+			PeerAddr::Ip(addr) => addr.ip().hash(state),
+			PeerAddr::Onion(key, _) => key.hash(state),
 		}
 	}
 }
@@ -178,11 +214,13 @@ impl std::hash::Hash for PeerAddr {
 impl PartialEq for PeerAddr {
 	/// If loopback address then we care about ip and port.
 	/// If regular address then we only care about the ip and ignore the port.
+	/// Onion addresses are identified by their key only.
 	fn eq(&self, other: &PeerAddr) -> bool {
-		if self.0.ip().is_loopback() {
-			self.0 == other.0
-		} else {
-			self.0.ip() == other.0.ip()
+		match (self, other) {
+			(PeerAddr::Ip(a), PeerAddr::Ip(b)) if a.ip().is_loopback() => a == b,
+			(PeerAddr::Ip(a), PeerAddr::Ip(b)) => a.ip() == b.ip(),
+			(PeerAddr::Onion(a, _), PeerAddr::Onion(b, _)) => a == b,
+			_ => false,
 		}
 	}
 }
@@ -191,7 +229,46 @@ impl Eq for PeerAddr {}
 
 impl std::fmt::Display for PeerAddr {
 	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-		write!(f, "{}", self.0)
+		match self {
+			PeerAddr::Ip(addr) => write!(f, "{}", addr),
+			PeerAddr::Onion(_, port) => write!(f, "{}:{}", self.as_key(), port),
+		}
+	}
+}
+
+impl FromStr for PeerAddr {
+	type Err = Error;
+
+	/// Parses "ip:port" or "<v3 onion address>.onion:port".
+	fn from_str(s: &str) -> Result<PeerAddr, Error> {
+		if let Some((host, port)) = s.rsplit_once(':') {
+			if host.to_lowercase().ends_with(".onion") {
+				let key = address::pubkey_from_onion_v3(host)?;
+				let port = port
+					.parse()
+					.map_err(|_| Error::AddressDecoding(format!("invalid port in {}", s)))?;
+				return Ok(PeerAddr::Onion(key.to_bytes(), port));
+			}
+		}
+		s.parse()
+			.map(PeerAddr::Ip)
+			.map_err(|e| Error::AddressDecoding(format!("{}: {}", s, e)))
+	}
+}
+
+/// Peer addresses are (de)serialized as strings so they keep the same
+/// representation in the config file and the API as plain socket addresses.
+impl Serialize for PeerAddr {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_str(&self.to_string())
+	}
+}
+
+impl<'de> Deserialize<'de> for PeerAddr {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<PeerAddr, D::Error> {
+		let s = String::deserialize(deserializer)?;
+		s.parse()
+			.map_err(|e| de::Error::custom(format!("invalid peer address: {:?}", e)))
 	}
 }
 
@@ -200,16 +277,69 @@ impl PeerAddr {
 	/// defaults to port 3414 on mainnet and 13414 on floonet.
 	pub fn from_ip(addr: IpAddr) -> PeerAddr {
 		let port = if global::is_floonet() { 13414 } else { 3414 };
-		PeerAddr(SocketAddr::new(addr, port))
+		PeerAddr::Ip(SocketAddr::new(addr, port))
+	}
+
+	/// The onion address of the onion service with the provided public key.
+	pub fn from_onion_key(key: &DalekPublicKey, port: u16) -> PeerAddr {
+		PeerAddr::Onion(key.to_bytes(), port)
+	}
+
+	/// The ip of a clearnet address, none for onion addresses.
+	pub fn ip(&self) -> Option<IpAddr> {
+		match self {
+			PeerAddr::Ip(addr) => Some(addr.ip()),
+			PeerAddr::Onion(..) => None,
+		}
+	}
+
+	pub fn port(&self) -> u16 {
+		match self {
+			PeerAddr::Ip(addr) => addr.port(),
+			PeerAddr::Onion(_, port) => *port,
+		}
+	}
+
+	pub fn is_onion(&self) -> bool {
+		match self {
+			PeerAddr::Ip(_) => false,
+			PeerAddr::Onion(..) => true,
+		}
 	}
 
 	/// If the ip is loopback then our key is "ip:port" (mainly for local usernet testing).
 	/// Otherwise we only care about the ip (we disallow multiple peers on the same ip address).
+	/// Onion addresses are keyed by their "<address>.onion" host.
 	pub fn as_key(&self) -> String {
-		if self.0.ip().is_loopback() {
-			format!("{}:{}", self.0.ip(), self.0.port())
-		} else {
-			format!("{}", self.0.ip())
+		match self {
+			PeerAddr::Ip(addr) if addr.ip().is_loopback() => {
+				format!("{}:{}", addr.ip(), addr.port())
+			}
+			PeerAddr::Ip(addr) => format!("{}", addr.ip()),
+			PeerAddr::Onion(key, _) => {
+				// keys are checked to be valid points when built or read
+				let onion = DalekPublicKey::from_bytes(key)
+					.ok()
+					.and_then(|k| address::onion_v3_from_pubkey(&k).ok())
+					.unwrap_or_default();
+				format!("{}.onion", onion)
+			}
+		}
+	}
+
+	/// Whether this address is publicly routable, i.e. not loopback, unspecified,
+	/// private (rfc1918), link-local or unique local. Onion addresses always are.
+	pub fn is_routable(&self) -> bool {
+		let ip = match self {
+			PeerAddr::Ip(addr) => addr.ip(),
+			PeerAddr::Onion(..) => return true,
+		};
+		if ip.is_loopback() || ip.is_unspecified() || ip.is_multicast() {
+			return false;
+		}
+		match ip {
+			IpAddr::V4(ipv4) => !ipv4.is_private() && !ipv4.is_link_local(),
+			IpAddr::V6(ipv6) => !ipv6.is_unique_local() && !ipv6.is_unicast_link_local(),
 		}
 	}
 }
@@ -220,6 +350,14 @@ pub struct P2PConfig {
 	pub host: IpAddr,
 	pub port: u16,
 
+	/// Additional socket addresses to listen on, typically used to listen on
+	/// both IPv4 and IPv6 interfaces (e.g. "[::]:3414").
+	pub listen_addrs: Option<Vec<SocketAddr>>,
+
+	/// Externally reachable addresses of this node, if known upfront.
+	/// Addresses reported back to us by our peers are learned automatically.
+	pub external_addrs: Option<Vec<PeerAddr>>,
+
 	/// Method used to get the list of seed nodes for initial bootstrap.
 	#[serde(default)]
 	pub seeding_type: Seeding,
@@ -227,6 +365,9 @@ pub struct P2PConfig {
 	/// The list of seed nodes, if using Seeding as a seed type
 	pub seeds: Option<Vec<PeerAddr>>,
 
+	/// The DNS seed hostnames per chain type, if using DNSSeed as a seed type
+	pub dns_seeds: Option<DnsSeeds>,
+
 	/// Capabilities expose by this node, also conditions which other peers this
 	/// node will have an affinity toward when connection.
 	pub capabilities: Capabilities,
@@ -246,11 +387,39 @@ pub struct P2PConfig {
 
 	pub peer_min_preferred_outbound_count: Option<u32>,
 
+	/// Maximum number of outbound connections to peers of the same network
+	/// group (/16 for IPv4, /32 for IPv6).
+	pub peer_max_outbound_per_group: Option<u32>,
+
 	pub peer_listener_buffer_count: Option<u32>,
 
 	pub dandelion_peer: Option<PeerAddr>,
 
 	pub my_onion_addr: Option<String>,
+
+	/// Peers that must authenticate with a known static key, typically the
+	/// other nodes of our own cluster. Connections to these peers are
+	/// always encrypted.
+	pub pinned_peers: Option<Vec<PinnedPeer>>,
+
+	/// Whether p2p connections go through tor, see TorMode.
+	pub tor_mode: Option<TorMode>,
+}
+
+/// DNS seed hostnames for each chain type. A hostname can be followed by a
+/// port, the default p2p port of the chain is used otherwise.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DnsSeeds {
+	pub mainnet: Option<Vec<String>>,
+	pub floonet: Option<Vec<String>>,
+}
+
+/// A peer address along with the (hex encoded) static key it is expected to
+/// authenticate with.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PinnedPeer {
+	pub addr: PeerAddr,
+	pub static_key: String,
 }
 
 /// Default address for peer-to-peer connections.
@@ -260,9 +429,12 @@ impl Default for P2PConfig {
 		P2PConfig {
 			host: ipaddr,
 			port: 3414,
+			listen_addrs: None,
+			external_addrs: None,
 			capabilities: Capabilities::FULL_NODE,
 			seeding_type: Seeding::default(),
 			seeds: None,
+			dns_seeds: None,
 			peers_allow: None,
 			peers_deny: None,
 			peers_preferred: None,
@@ -270,9 +442,12 @@ impl Default for P2PConfig {
 			peer_max_inbound_count: None,
 			peer_max_outbound_count: None,
 			peer_min_preferred_outbound_count: None,
+			peer_max_outbound_per_group: None,
 			peer_listener_buffer_count: None,
 			dandelion_peer: None,
 			my_onion_addr: None,
+			pinned_peers: None,
+			tor_mode: None,
 		}
 	}
 }
@@ -280,6 +455,43 @@ impl Default for P2PConfig {
 /// Note certain fields are options just so they don't have to be
 /// included in epic-server.toml, but we don't want them to ever return none
 impl P2PConfig {
+	/// return all socket addresses we listen on, host:port first
+	pub fn listen_addrs(&self) -> Vec<SocketAddr> {
+		let mut addrs = vec![SocketAddr::new(self.host, self.port)];
+		if let Some(ref extra) = self.listen_addrs {
+			for addr in extra {
+				if !addrs.contains(addr) {
+					addrs.push(*addr);
+				}
+			}
+		}
+		addrs
+	}
+
+	/// return the static key the peer at the provided address is pinned to
+	pub fn pinned_key(&self, addr: PeerAddr) -> Option<&str> {
+		self.pinned_peers
+			.as_ref()
+			.and_then(|pinned| pinned.iter().find(|p| p.addr == addr))
+			.map(|p| p.static_key.as_str())
+	}
+
+	/// return the DNS seed hostnames of the chain we are running on
+	pub fn dns_seeds(&self) -> Vec<String> {
+		let (configured, default) = if global::is_floonet() {
+			(
+				self.dns_seeds.as_ref().and_then(|s| s.floonet.clone()),
+				FLOONET_DNS_SEEDS,
+			)
+		} else {
+			(
+				self.dns_seeds.as_ref().and_then(|s| s.mainnet.clone()),
+				MAINNET_DNS_SEEDS,
+			)
+		};
+		configured.unwrap_or_else(|| default.iter().map(|s| s.to_string()).collect())
+	}
+
 	/// return ban window
 	pub fn ban_window(&self) -> i64 {
 		match self.ban_window {
@@ -312,6 +524,14 @@ impl P2PConfig {
 		}
 	}
 
+	/// return maximum outbound peer connections count per network group
+	pub fn peer_max_outbound_per_group(&self) -> u32 {
+		match self.peer_max_outbound_per_group {
+			Some(n) => n,
+			None => PEER_MAX_OUTBOUND_PER_GROUP,
+		}
+	}
+
 	/// return peer buffer count for listener
 	pub fn peer_listener_buffer_count(&self) -> u32 {
 		match self.peer_listener_buffer_count {
@@ -319,6 +539,11 @@ impl P2PConfig {
 			None => PEER_LISTENER_BUFFER_COUNT,
 		}
 	}
+
+	/// return the tor mode of the p2p connections
+	pub fn tor_mode(&self) -> TorMode {
+		self.tor_mode.unwrap_or_default()
+	}
 }
 
 /// Type of seeding the server will use to find other peers on the network.
@@ -340,6 +565,32 @@ impl Default for Seeding {
 	}
 }
 
+/// How the p2p server uses tor.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum TorMode {
+	/// Plain TCP connections only
+	Disabled,
+	/// Outbound connections are dialled through the tor SOCKS proxy and the
+	/// p2p port is published as an onion service, clearnet peers are still
+	/// accepted
+	Enabled,
+	/// Like Enabled, but only onion peers are connected to and only
+	/// connections coming through our onion service are accepted
+	OnionOnly,
+}
+
+impl Default for TorMode {
+	fn default() -> TorMode {
+		TorMode::Disabled
+	}
+}
+
+impl TorMode {
+	pub fn is_enabled(&self) -> bool {
+		*self != TorMode::Disabled
+	}
+}
+
 // Section below will set [server.p2p_config.capabilities] bits value in epic-server.toml
 
 bitflags! {
@@ -362,6 +613,17 @@ bitflags! {
 		const HEADER_FASTSYNC = 0b00010000; // 31
 		/// Does support stem transactions via tor onion routing.
 		const ONIONSTEM = 0b00100000;
+		/// Does support encrypted transport negotiated during the handshake.
+		const ENCRYPTED = 0b01000000;
+		/// Can serve the transactions of a compact block by short id
+		/// (GetBlockTxs/BlockTxs).
+		const COMPACT_BLOCKS = 0b10000000;
+		/// Understands onion addresses in peer address lists.
+		const TOR_ADDRESS = 0b1_00000000;
+		/// Can prove outputs and kernels are in its chain to light nodes
+		/// (OutputProofRequest/OutputProofResponse and
+		/// KernelProofRequest/KernelProofResponse).
+		const MERKLE_PROOFS = 0b10_00000000;
 		/// All nodes right now are "full nodes".
 		/// Some nodes internally may maintain longer block histories (archival_mode)
 		/// but we do not advertise this to other nodes.
@@ -420,6 +682,8 @@ pub struct PeerInfo {
 	pub addr: PeerAddr,
 	pub direction: Direction,
 	pub live_info: Arc<RwLock<PeerLiveInfo>>,
+	/// Static key the peer authenticated with, if the connection is encrypted.
+	pub static_key: Option<String>,
 }
 
 impl PeerLiveInfo {
@@ -511,6 +775,8 @@ pub struct PeerInfoDisplay {
 	pub total_difficulty: Difficulty,
 	pub height: u64,
 	pub onion_addr: Option<String>,
+	#[serde(default)]
+	pub static_key: Option<String>,
 }
 
 impl From<PeerInfo> for PeerInfoDisplay {
@@ -524,6 +790,7 @@ impl From<PeerInfo> for PeerInfoDisplay {
 			total_difficulty: info.total_difficulty(),
 			height: info.height(),
 			onion_addr: info.live_info.read().onion_addr.clone(),
+			static_key: info.static_key.clone(),
 		}
 	}
 }
@@ -555,6 +822,9 @@ pub trait ChainAdapter: Sync + Send {
 	// In p2p::ChainAdapter trait (probably in p2p/src/types.rs or similar)
 	fn sync_status(&self) -> SyncStatus;
 
+	/// Whether the chain runs in light mode, following the headers only
+	fn is_light_mode(&self) -> bool;
+
 	/// A valid transaction has been received from one of our peers
 	fn transaction_received(
 		&self,
@@ -588,6 +858,24 @@ pub trait ChainAdapter: Sync + Send {
 		peer_info: &PeerInfo,
 	) -> Result<bool, chain::Error>;
 
+	/// Finds the transactions matching the provided kernel short ids of a
+	/// compact block, so a peer can hydrate it.
+	fn get_block_txs(
+		&self,
+		hash: Hash,
+		nonce: u64,
+		kern_ids: &[core::ShortId],
+	) -> Vec<core::Transaction>;
+
+	/// The transactions of a compact block we requested from a peer have
+	/// been received.
+	fn block_txs_received(
+		&self,
+		hash: Hash,
+		txs: Vec<core::Transaction>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error>;
+
 	fn header_received(
 		&self,
 		bh: core::BlockHeader,
@@ -615,6 +903,14 @@ pub trait ChainAdapter: Sync + Send {
 	/// Gets a full block by its hash.
 	fn get_block(&self, h: Hash) -> Option<core::Block>;
 
+	/// Proof the output with the given commitment is in the output MMR of our
+	/// head, with the hash of our head.
+	fn get_output_proof(&self, commit: Commitment) -> Option<(Hash, chain::OutputProof)>;
+
+	/// Proof of the kernel with the given excess against our head, with the
+	/// hash of our head.
+	fn get_kernel_proof(&self, excess: Commitment) -> Option<(Hash, chain::KernelProof)>;
+
 	fn kernel_data_read(&self) -> Result<File, chain::Error>;
 
 	fn kernel_data_write(&self, reader: &mut dyn Read) -> Result<bool, chain::Error>;
@@ -678,4 +974,20 @@ pub trait NetAdapter: ChainAdapter {
 
 	fn update_onion_addr(&self, addr: PeerAddr, onion_addr: String);
 	fn my_onion_addr(&self) -> Option<String>;
+
+	/// A peer answered our request for the proof of an output.
+	fn output_proof_received(
+		&self,
+		commit: Commitment,
+		proof: Option<(Hash, chain::OutputProof)>,
+		peer_info: &PeerInfo,
+	);
+
+	/// A peer answered our request for the proof of a kernel.
+	fn kernel_proof_received(
+		&self,
+		excess: Commitment,
+		proof: Option<(Hash, chain::KernelProof)>,
+		peer_info: &PeerInfo,
+	);
 }
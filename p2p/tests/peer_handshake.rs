@@ -23,6 +23,7 @@ use std::sync::Arc;
 use std::{thread, time};
 
 use crate::core::core::hash::Hash;
+use crate::core::global::ChainParams;
 use crate::core::pow::Difficulty;
 use crate::p2p::types::PeerAddr;
 use crate::p2p::Peer;
@@ -60,6 +61,7 @@ fn peer_handshake() {
 			Hash::from_vec(&vec![]),
 			Arc::new(StopState::new()),
 			None,
+			Arc::new(ChainParams::current()),
 		)
 		.unwrap(),
 	);
@@ -72,13 +74,18 @@ fn peer_handshake() {
 	let addr = SocketAddr::new(p2p_config.host, p2p_config.port);
 	let socket = TcpStream::connect_timeout(&addr, time::Duration::from_secs(10)).unwrap();
 
-	let my_addr = PeerAddr("127.0.0.1:5000".parse().unwrap());
+	let my_addr = PeerAddr::Ip("127.0.0.1:5000".parse().unwrap());
 	let peer = Peer::connect(
 		socket,
 		p2p::Capabilities::UNKNOWN,
 		Difficulty::min(),
 		my_addr,
-		&p2p::handshake::Handshake::new(Hash::from_vec(&vec![]), p2p_config.clone()),
+		PeerAddr::Ip(addr),
+		&p2p::handshake::Handshake::new(
+			Hash::from_vec(&vec![]),
+			p2p_config.clone(),
+			p2p::msg::magic(&ChainParams::current()),
+		),
 		net_adapter,
 	)
 	.unwrap();
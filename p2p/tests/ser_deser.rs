@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use epic_core::ser::{self, ProtocolVersion};
 use epic_p2p as p2p;
 
 use num::FromPrimitive;
@@ -71,3 +72,30 @@ fn test_capabilities() {
 			.contains(p2p::types::Capabilities::TX_KERNEL_HASH)
 	);
 }
+
+#[test]
+fn test_peer_addr() {
+	let onion_str = "2a6at2obto3uvkpkitqp4wxcg6u36qf534eucbskqciturczzc5suyid.onion:3414";
+	let onion: p2p::PeerAddr = onion_str.parse().unwrap();
+	assert!(onion.is_onion());
+	assert_eq!(onion.to_string(), onion_str);
+	assert!("abcdef.onion:3414".parse::<p2p::PeerAddr>().is_err());
+
+	let addrs: Vec<p2p::PeerAddr> = vec![
+		onion,
+		"1.2.3.4:3414".parse().unwrap(),
+		"[2001:db8::1]:13414".parse().unwrap(),
+	];
+	for addr in addrs {
+		let bytes = ser::ser_vec(&addr, ProtocolVersion::local()).unwrap();
+		let res: p2p::PeerAddr =
+			ser::deserialize(&mut &bytes[..], ProtocolVersion::local()).unwrap();
+		assert_eq!(res, addr);
+		assert_eq!(res.port(), addr.port());
+	}
+
+	// unknown address types are rejected
+	let res: Result<p2p::PeerAddr, ser::Error> =
+		ser::deserialize(&mut &[3u8, 0, 0][..], ProtocolVersion::local());
+	assert!(res.is_err());
+}
@@ -23,6 +23,7 @@ use self::core::core::{
 };
 use crate::types::{BlockChain, PoolEntry, PoolError};
 use epic_core as core;
+use epic_util::secp::pedersen::Commitment;
 use std::cmp::Reverse;
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
@@ -76,6 +77,17 @@ where
 		None
 	}
 
+	/// Query the tx pool for an individual tx with a kernel matching the
+	/// given excess, through its kernel hash.
+	pub fn retrieve_tx_by_kernel_excess(&self, excess: &Commitment) -> Option<Transaction> {
+		let kernel = self
+			.entries
+			.iter()
+			.flat_map(|x| x.tx.kernels())
+			.find(|k| k.excess == *excess)?;
+		self.retrieve_tx_by_kernel_hash(kernel.hash())
+	}
+
 	/// Query the tx pool for all known txs based on kernel short_ids
 	/// from the provided compact_block.
 	/// Note: does not validate that we return the full set of required txs.
@@ -20,6 +20,7 @@
 use self::core::core::hash::{Hash, Hashed};
 use self::core::core::id::ShortId;
 use self::core::core::{transaction, Block, BlockHeader, Transaction, Weighting};
+use self::core::global::ChainParams;
 use self::util::RwLock;
 use crate::pool::Pool;
 use crate::types::{BlockChain, PoolAdapter, PoolConfig, PoolEntry, PoolError, TxSource};
@@ -47,6 +48,8 @@ where
 	pub blockchain: Arc<B>,
 	/// The pool adapter
 	pub adapter: Arc<P>,
+	/// Parameters of the chain the pool is for
+	pub params: Arc<ChainParams>,
 }
 
 impl<B, P> TransactionPool<B, P>
@@ -54,8 +57,14 @@ where
 	B: BlockChain,
 	P: PoolAdapter,
 {
-	/// Create a new transaction pool
-	pub fn new(config: PoolConfig, chain: Arc<B>, adapter: Arc<P>) -> Self {
+	/// Create a new transaction pool, validating against the provided chain
+	/// parameters.
+	pub fn new(
+		config: PoolConfig,
+		chain: Arc<B>,
+		adapter: Arc<P>,
+		params: Arc<ChainParams>,
+	) -> Self {
 		TransactionPool {
 			config,
 			txpool: Pool::new(chain.clone(), "txpool".to_string()),
@@ -63,6 +72,7 @@ where
 			reorg_cache: Arc::new(RwLock::new(VecDeque::new())),
 			blockchain: chain,
 			adapter,
+			params,
 		}
 	}
 
@@ -127,6 +137,7 @@ where
 		stem: bool,
 		header: &BlockHeader,
 	) -> Result<(), PoolError> {
+		let _params = self.params.enter();
 		// Quick check to deal with common case of seeing the *same* tx
 		// broadcast from multiple peers simultaneously.
 		if !stem && self.txpool.contains_tx(tx.hash()) {
@@ -184,6 +195,7 @@ where
 	// Remove the last transaction from the flattened bucket transactions.
 	// No other tx depends on it, it has low fee_to_weight and is unlikely to participate in any cut-through.
 	pub fn evict_from_txpool(&mut self) {
+		let _params = self.params.enter();
 		// Get bucket transactions
 		let bucket_transactions = self.txpool.bucket_transactions(Weighting::NoLimit);
 
@@ -215,6 +227,7 @@ where
 	}
 
 	pub fn reconcile_reorg_cache(&mut self, header: &BlockHeader) -> Result<(), PoolError> {
+		let _params = self.params.enter();
 		let entries = self.reorg_cache.read().iter().cloned().collect::<Vec<_>>();
 		debug!(
 			"reconcile_reorg_cache: size: {}, block: {:?} ...",
@@ -234,6 +247,7 @@ where
 	/// Reconcile the transaction pool (both txpool and stempool) against the
 	/// provided block.
 	pub fn reconcile_block(&mut self, block: &Block) -> Result<(), PoolError> {
+		let _params = self.params.enter();
 		// First reconcile the txpool.
 		self.txpool.reconcile_block(block);
 		self.txpool.reconcile(None, &block.header)?;
@@ -265,6 +279,41 @@ where
 		self.txpool.retrieve_transactions(hash, nonce, kern_ids)
 	}
 
+	/// Retrieve the transactions matching the provided kernel short ids of
+	/// a compact block on behalf of a peer hydrating it.
+	/// Once the block is accepted its txs are no longer in the txpool so we
+	/// fall back to the reorg cache for the ones we cannot find there.
+	pub fn retrieve_block_txs(
+		&self,
+		hash: Hash,
+		nonce: u64,
+		kern_ids: &[ShortId],
+	) -> Vec<Transaction> {
+		let (mut txs, missing) = self.txpool.retrieve_transactions(hash, nonce, kern_ids);
+		if missing.is_empty() {
+			return txs;
+		}
+
+		let mut found = vec![];
+		for entry in self.reorg_cache.read().iter() {
+			let mut matched = false;
+			for k in entry.tx.kernels() {
+				let short_id = k.short_id(&hash, nonce);
+				if missing.contains(&short_id) && !found.contains(&short_id) {
+					found.push(short_id);
+					matched = true;
+				}
+			}
+			if matched && !txs.contains(&entry.tx) {
+				txs.push(entry.tx.clone());
+			}
+			if found.len() == missing.len() {
+				break;
+			}
+		}
+		txs
+	}
+
 	/// Whether the transaction is acceptable to the pool, given both how
 	/// full the pool is and the transaction weight.
 	fn is_acceptable(&self, tx: &Transaction, stem: bool) -> Result<(), PoolError> {
@@ -300,6 +349,7 @@ where
 	/// Returns a vector of transactions from the txpool so we can build a
 	/// block from them.
 	pub fn prepare_mineable_transactions(&self) -> Result<Vec<Transaction>, PoolError> {
+		let _params = self.params.enter();
 		self.txpool
 			.prepare_mineable_transactions(self.config.mineable_max_weight)
 	}
@@ -18,6 +18,7 @@ use self::chain::store::ChainStore;
 use self::chain::types::Tip;
 use self::core::core::hash::{Hash, Hashed};
 use self::core::core::{Block, BlockHeader, BlockSums, Committed, KernelFeatures, Transaction};
+use self::core::global::ChainParams;
 use self::core::libtx;
 use self::keychain::{ExtKeychain, Keychain};
 use self::pool::types::*;
@@ -157,6 +158,7 @@ pub fn test_setup<B: BlockChain>(chain: Arc<B>) -> TransactionPool<B, NoopAdapte
 		},
 		chain.clone(),
 		Arc::new(NoopAdapter {}),
+		Arc::new(ChainParams::current()),
 	)
 }
 
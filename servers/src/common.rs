@@ -16,5 +16,6 @@
 
 pub mod adapters;
 pub mod hooks;
+pub mod onion_relay;
 pub mod stats;
 pub mod types;
@@ -25,6 +25,8 @@ use std::time::Instant;
 
 use crate::chain::{self, BlockStatus, ChainAdapter, Options, SyncState, SyncStatus};
 use crate::common::hooks::{ChainEvents, NetEvents};
+use crate::common::onion_relay::OnionRelay;
+use crate::common::stats::{CompactBlockStats, StemRelayStats};
 use crate::common::types::{ChainValidationMode, DandelionEpoch, ServerConfig};
 use crate::core::core::hash::{Hash, Hashed};
 use crate::core::core::transaction::Transaction;
@@ -34,12 +36,13 @@ use crate::core::{core, global};
 use crate::p2p;
 use crate::p2p::types::PeerInfo;
 use crate::pool::{self, BlockChain, PoolAdapter};
+use crate::util::secp::pedersen::Commitment;
 use crate::util::OneTime;
 use chrono::prelude::*;
 use chrono::Duration;
 use rand::prelude::*;
 use std::collections::HashMap;
-use std::sync::Mutex;
+use std::sync::{mpsc, Mutex};
 
 /// Force full pow verification this many blocks from chaintip
 pub const POW_VERIFICATION_THRESHOLD: u64 = 1000;
@@ -52,6 +55,25 @@ pub const BLOCK_BROADCAST_IGNORE_THRESHOLD: u64 = 200;
 /// this many blocks from chaintip, while syncing
 pub const HEADER_BROADCAST_IGNORE_THRESHOLD: u64 = 200;
 
+/// How long we wait for the missing txs of a compact block before giving up
+/// on them and requesting the full block from the peer that sent it.
+const BLOCK_TXS_TIMEOUT_SECS: u64 = 10;
+
+/// Number of threads relaying stem txs over tor.
+const ONION_RELAY_WORKERS: usize = 2;
+
+/// Stem txs waiting for an onion relay thread, those beyond are relayed over
+/// p2p right away.
+const ONION_RELAY_QUEUE_SIZE: usize = 64;
+
+/// A compact block waiting for its missing txs to be sent by a peer.
+struct PendingCompactBlock {
+	cb: CompactBlock,
+	txs: Vec<Transaction>,
+	peer: p2p::PeerAddr,
+	requested_at: Instant,
+}
+
 /// Implementation of the NetAdapter for the . Gets notified when new
 /// blocks and transactions are received and forwards to the chain and pool
 /// implementations.
@@ -67,6 +89,8 @@ where
 	config: ServerConfig,
 	hooks: Vec<Box<dyn NetEvents + Send + Sync>>,
 	recently_seen_headers: Arc<Mutex<HashMap<Hash, (p2p::PeerAddr, Instant)>>>,
+	pending_compact_blocks: Arc<Mutex<HashMap<Hash, PendingCompactBlock>>>,
+	compact_block_stats: Arc<RwLock<CompactBlockStats>>,
 }
 
 impl<B, P> p2p::ChainAdapter for NetToChainAdapter<B, P>
@@ -78,6 +102,10 @@ where
 		self.sync_state.status()
 	}
 
+	fn is_light_mode(&self) -> bool {
+		self.chain().is_light_mode()
+	}
+
 	fn total_difficulty(&self) -> Result<Difficulty, chain::Error> {
 		Ok(self.chain().head()?.total_difficulty)
 	}
@@ -99,8 +127,9 @@ where
 		kernel_hash: Hash,
 		peer_info: &PeerInfo,
 	) -> Result<bool, chain::Error> {
-		// nothing much we can do with a new transaction while syncing
-		if self.sync_state.is_syncing() {
+		// nothing much we can do with a new transaction while syncing, nor
+		// without the UTXO set in light mode
+		if self.sync_state.is_syncing() || self.chain().is_light_mode() {
 			return Ok(true);
 		}
 
@@ -118,8 +147,9 @@ where
 		stem: bool,
 		peer_info: &PeerInfo,
 	) -> Result<bool, chain::Error> {
-		// nothing much we can do with a new transaction while syncing
-		if self.sync_state.is_syncing() {
+		// nothing much we can do with a new transaction while syncing, nor
+		// without the UTXO set in light mode
+		if self.sync_state.is_syncing() || self.chain().is_light_mode() {
 			return Ok(true);
 		}
 
@@ -149,6 +179,11 @@ where
 		peer_info: &PeerInfo,
 		opts: chain::Options,
 	) -> Result<bool, chain::Error> {
+		// light nodes only follow the headers
+		if self.chain().is_light_mode() {
+			return self.header_received(b.header, peer_info);
+		}
+
 		// TODO: guard against panic on unwrap, in case someone changes this constant
 		if self.sync_state.is_syncing() {
 			if b.header.height.clone()
@@ -184,6 +219,11 @@ where
 		cb: core::CompactBlock,
 		peer_info: &PeerInfo,
 	) -> Result<bool, chain::Error> {
+		// light nodes only follow the headers
+		if self.chain().is_light_mode() {
+			return self.header_received(cb.header, peer_info);
+		}
+
 		if self.sync_state.is_syncing() {
 			if cb.header.height.clone()
 				> (self.chain().head()?.height + BLOCK_BROADCAST_IGNORE_THRESHOLD)
@@ -224,11 +264,13 @@ where
 			cb.kern_full().len(),
 			cb.kern_ids().len(),
 		);
+		self.compact_block_stats.write().received += 1;
 
 		if cb.kern_ids().is_empty() {
 			// push the freshly hydrated block through the chain pipeline
 			match core::Block::hydrate_from(cb, vec![]) {
 				Ok(block) => {
+					self.compact_block_stats.write().hydrated_from_pool += 1;
 					if !self.sync_state.is_syncing() {
 						for hook in &self.hooks {
 							let _ = hook.on_block_received(&block, &peer_info.addr);
@@ -263,46 +305,94 @@ where
 				missing_short_ids.len(),
 			);
 
-			// If we have missing kernels then we know we cannot hydrate this compact block.
+			// If we have missing kernels we cannot hydrate this compact block yet,
+			// ask the peer for the missing txs if it supports it.
 			if missing_short_ids.len() > 0 {
-				self.request_block(&cb.header, peer_info, chain::Options::NONE);
+				if peer_info
+					.capabilities
+					.contains(p2p::Capabilities::COMPACT_BLOCKS)
+				{
+					self.request_block_txs(cb, txs, missing_short_ids, peer_info);
+				} else {
+					self.compact_block_stats.write().full_block_requests += 1;
+					self.request_block(&cb.header, peer_info, chain::Options::NONE);
+				}
 				return Ok(true);
 			}
 
-			let block = match core::Block::hydrate_from(cb.clone(), txs) {
-				Ok(block) => {
-					if !self.sync_state.is_syncing() {
-						for hook in &self.hooks {
-							let _ = hook.on_block_received(&block, &peer_info.addr);
-						}
-					}
-					block
-				}
-				Err(e) => {
-					debug!("Invalid hydrated block {}: {:?}", cb.hash(), e);
-					return Ok(false);
-				}
-			};
+			self.hydrate_compact_block(cb, txs, false, peer_info)
+		}
+	}
 
-			if let Ok(prev) = self.chain().get_previous_header(&cb.header) {
-				if block.validate(&prev.total_kernel_offset).is_ok() {
-					debug!("successfully hydrated block from tx pool!");
-					self.process_block(block, peer_info, chain::Options::NONE)
-				} else {
-					if self.sync_state.status() == SyncStatus::NoSync {
-						debug!("adapter: block invalid after hydration, requesting full block");
-						self.request_block(&cb.header, peer_info, chain::Options::NONE);
-						Ok(true)
-					} else {
-						debug!("block invalid after hydration, ignoring it, cause still syncing");
-						Ok(true)
-					}
-				}
-			} else {
-				debug!("failed to retrieve previous block header (still syncing?)");
-				Ok(true)
+	fn get_block_txs(
+		&self,
+		hash: Hash,
+		nonce: u64,
+		kern_ids: &[core::ShortId],
+	) -> Vec<core::Transaction> {
+		self.tx_pool
+			.read()
+			.retrieve_block_txs(hash, nonce, kern_ids)
+	}
+
+	fn block_txs_received(
+		&self,
+		hash: Hash,
+		txs: Vec<core::Transaction>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		let pending = {
+			let mut pending = self.pending_compact_blocks.lock().unwrap();
+			match pending.get(&hash) {
+				Some(p) if p.peer == peer_info.addr => pending.remove(&hash),
+				_ => None,
+			}
+		};
+		let mut pending = match pending {
+			Some(pending) => pending,
+			None => {
+				debug!(
+					"block_txs_received: unexpected txs for {} from {}",
+					hash, peer_info.addr
+				);
+				return Ok(true);
+			}
+		};
+
+		if self.chain().block_exists(hash)? {
+			return Ok(true);
+		}
+
+		// Check the peer gave us every tx we were missing.
+		let cb = pending.cb;
+		let nonce = cb.nonce;
+		let mut known = vec![];
+		for tx in pending.txs.iter().chain(txs.iter()) {
+			for k in tx.kernels() {
+				known.push(k.short_id(&hash, nonce));
 			}
 		}
+		let missing = cb
+			.kern_ids()
+			.iter()
+			.filter(|id| !known.contains(id))
+			.count();
+		debug!(
+			"block_txs_received: {} txs for {} from {}, still missing {}",
+			txs.len(),
+			hash,
+			peer_info.addr,
+			missing
+		);
+		if missing > 0 {
+			self.compact_block_stats.write().full_block_requests += 1;
+			self.request_block(&cb.header, peer_info, chain::Options::NONE);
+			return Ok(true);
+		}
+
+		pending.txs.extend(txs);
+		pending.txs.dedup();
+		self.hydrate_compact_block(cb, pending.txs, true, peer_info)
 	}
 
 	fn header_received(
@@ -493,6 +583,26 @@ where
 		}
 	}
 
+	fn get_output_proof(&self, commit: Commitment) -> Option<(Hash, chain::OutputProof)> {
+		match self.chain().get_output_proof(commit) {
+			Ok((header, proof)) => Some((header.hash(), proof)),
+			Err(e) => {
+				debug!("get_output_proof: {:?} not proven: {}", commit, e);
+				None
+			}
+		}
+	}
+
+	fn get_kernel_proof(&self, excess: Commitment) -> Option<(Hash, chain::KernelProof)> {
+		match self.chain().get_kernel_proof(&excess) {
+			Ok((header, proof)) => Some((header.hash(), proof)),
+			Err(e) => {
+				debug!("get_kernel_proof: {:?} not proven: {}", excess, e);
+				None
+			}
+		}
+	}
+
 	fn kernel_data_read(&self) -> Result<File, chain::Error> {
 		self.chain().kernel_data_read()
 	}
@@ -618,6 +728,7 @@ where
 		tx_pool: Arc<RwLock<pool::TransactionPool<B, P>>>,
 		config: ServerConfig,
 		hooks: Vec<Box<dyn NetEvents + Send + Sync>>,
+		compact_block_stats: Arc<RwLock<CompactBlockStats>>,
 	) -> Self {
 		NetToChainAdapter {
 			sync_state,
@@ -629,6 +740,8 @@ where
 			recently_seen_headers: Arc::new(Mutex::new(
 				HashMap::<Hash, (p2p::PeerAddr, Instant)>::with_capacity(10_000),
 			)),
+			pending_compact_blocks: Arc::new(Mutex::new(HashMap::new())),
+			compact_block_stats,
 		}
 	}
 
@@ -638,6 +751,40 @@ where
 		self.peers.init(Arc::downgrade(&peers));
 	}
 
+	/// Give up on the compact blocks whose missing txs didn't arrive in time,
+	/// requesting the full blocks from the peers that sent them instead.
+	pub fn expire_pending_compact_blocks(&self) {
+		let timeout = std::time::Duration::from_secs(BLOCK_TXS_TIMEOUT_SECS);
+		let expired: Vec<PendingCompactBlock> = {
+			let mut pending = self.pending_compact_blocks.lock().unwrap();
+			let hashes: Vec<Hash> = pending
+				.iter()
+				.filter(|(_, p)| p.requested_at.elapsed() >= timeout)
+				.map(|(h, _)| *h)
+				.collect();
+			hashes.iter().filter_map(|h| pending.remove(h)).collect()
+		};
+
+		for p in expired {
+			match self.peers().get_connected_peer(p.peer) {
+				Some(peer) => {
+					debug!(
+						"compact block {} txs not received from {} in time, requesting the full block",
+						p.cb.hash(),
+						p.peer
+					);
+					self.compact_block_stats.write().full_block_requests += 1;
+					self.request_block(&p.cb.header, &peer.info, chain::Options::NONE);
+				}
+				None => debug!(
+					"compact block {} txs not received, {} not connected anymore",
+					p.cb.hash(),
+					p.peer
+				),
+			}
+		}
+	}
+
 	fn peers(&self) -> Arc<p2p::Peers> {
 		self.peers
 			.borrow()
@@ -819,6 +966,88 @@ where
 		}
 	}
 
+	// Hydrates the compact block with the provided txs and pushes the result
+	// through the chain pipeline, falling back to requesting the full block
+	// if it does not validate.
+	fn hydrate_compact_block(
+		&self,
+		cb: CompactBlock,
+		txs: Vec<Transaction>,
+		with_block_txs: bool,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		let block = match core::Block::hydrate_from(cb.clone(), txs) {
+			Ok(block) => {
+				if !self.sync_state.is_syncing() {
+					for hook in &self.hooks {
+						let _ = hook.on_block_received(&block, &peer_info.addr);
+					}
+				}
+				block
+			}
+			Err(e) => {
+				debug!("Invalid hydrated block {}: {:?}", cb.hash(), e);
+				return Ok(false);
+			}
+		};
+
+		if let Ok(prev) = self.chain().get_previous_header(&cb.header) {
+			if block.validate(&prev.total_kernel_offset).is_ok() {
+				debug!("successfully hydrated block from tx pool!");
+				{
+					let mut stats = self.compact_block_stats.write();
+					if with_block_txs {
+						stats.hydrated_with_block_txs += 1;
+					} else {
+						stats.hydrated_from_pool += 1;
+					}
+				}
+				self.process_block(block, peer_info, chain::Options::NONE)
+			} else {
+				if self.sync_state.status() == SyncStatus::NoSync {
+					debug!("adapter: block invalid after hydration, requesting full block");
+					self.compact_block_stats.write().full_block_requests += 1;
+					self.request_block(&cb.header, peer_info, chain::Options::NONE);
+					Ok(true)
+				} else {
+					debug!("block invalid after hydration, ignoring it, cause still syncing");
+					Ok(true)
+				}
+			}
+		} else {
+			debug!("failed to retrieve previous block header (still syncing?)");
+			Ok(true)
+		}
+	}
+
+	// Keep the compact block around and ask the peer that sent it for the
+	// txs we could not find in our pool.
+	fn request_block_txs(
+		&self,
+		cb: CompactBlock,
+		txs: Vec<Transaction>,
+		missing: Vec<core::ShortId>,
+		peer_info: &PeerInfo,
+	) {
+		let hash = cb.hash();
+		let nonce = cb.nonce;
+		{
+			let mut pending = self.pending_compact_blocks.lock().unwrap();
+			pending.insert(
+				hash,
+				PendingCompactBlock {
+					cb,
+					txs,
+					peer: peer_info.addr,
+					requested_at: Instant::now(),
+				},
+			);
+		}
+		self.send_block_request_to_peer(hash, peer_info, |peer, h| {
+			peer.send_block_txs_request(h, nonce, missing.clone())
+		})
+	}
+
 	fn request_transaction(&self, h: Hash, peer_info: &PeerInfo) {
 		self.send_tx_request_to_peer(h, peer_info, |peer, h| peer.send_tx_request(h))
 	}
@@ -988,6 +1217,15 @@ where
 pub struct PoolToNetAdapter {
 	peers: OneTime<Weak<p2p::Peers>>,
 	dandelion_epoch: Arc<RwLock<DandelionEpoch>>,
+	onion_relay: Option<mpsc::SyncSender<OnionRelayJob>>,
+	stem_relay_stats: Arc<RwLock<StemRelayStats>>,
+}
+
+/// A stem tx to relay to the onion service of a relay peer.
+struct OnionRelayJob {
+	onion_addr: String,
+	peer: Arc<p2p::Peer>,
+	tx: Transaction,
 }
 
 /// Adapter between the Dandelion monitor and the current Dandelion "epoch".
@@ -1032,18 +1270,28 @@ impl pool::PoolAdapter for PoolToNetAdapter {
 		// If node is configured to always stem our (pushed via api) txs then do so.
 		if epoch.is_stem() || (entry.src.is_pushed() && epoch.always_stem_our_txs()) {
 			if let Some(peer) = epoch.relay_peer(&self.peers()) {
+				if let Some(onion_addr) = self.onion_relay_addr(&peer) {
+					info!("Stemming this epoch, relaying to next peer over tor.");
+					self.relay_over_tor(onion_addr, peer, entry.tx.clone());
+					return Ok(());
+				}
 				match peer.send_stem_transaction(&entry.tx) {
 					Ok(_) => {
 						info!("Stemming this epoch, relaying to next peer.");
+						self.stem_relay_stats.write().p2p_relayed += 1;
 						Ok(())
 					}
 					Err(e) => {
 						error!("Stemming tx failed. Fluffing. {:?}", e);
+						let mut stats = self.stem_relay_stats.write();
+						stats.p2p_failed += 1;
+						stats.fluffed += 1;
 						Err(pool::PoolError::DandelionError)
 					}
 				}
 			} else {
 				error!("No relay peer. Fluffing.");
+				self.stem_relay_stats.write().fluffed += 1;
 				Err(pool::PoolError::DandelionError)
 			}
 		} else {
@@ -1059,9 +1307,49 @@ impl PoolToNetAdapter {
 		PoolToNetAdapter {
 			peers: OneTime::new(),
 			dandelion_epoch: Arc::new(RwLock::new(DandelionEpoch::new(config))),
+			onion_relay: None,
+			stem_relay_stats: Arc::new(RwLock::new(StemRelayStats::default())),
 		}
 	}
 
+	/// Relay stem txs to the onion service of relay peers supporting
+	/// ONIONSTEM, rather than over the p2p connection. The relaying is done
+	/// by a few dedicated threads, tor being too slow to hold the pool lock
+	/// while waiting.
+	pub fn with_onion_relay(mut self, onion_relay: OnionRelay) -> PoolToNetAdapter {
+		let (sender, receiver) = mpsc::sync_channel(ONION_RELAY_QUEUE_SIZE);
+		let receiver = Arc::new(Mutex::new(receiver));
+		let mut workers = 0;
+		for i in 0..ONION_RELAY_WORKERS {
+			let onion_relay = onion_relay.clone();
+			let receiver = receiver.clone();
+			let stats = self.stem_relay_stats.clone();
+			let res = thread::Builder::new()
+				.name(format!("onion_relay_{}", i))
+				.spawn(move || loop {
+					// the lock is only held while waiting for the next job
+					let job = match receiver.lock().map(|r| r.recv()) {
+						Ok(Ok(job)) => job,
+						_ => break,
+					};
+					relay_job(&onion_relay, job, &stats);
+				});
+			match res {
+				Ok(_) => workers += 1,
+				Err(e) => error!("Failed to launch onion_relay thread: {:?}", e),
+			}
+		}
+		if workers > 0 {
+			self.onion_relay = Some(sender);
+		}
+		self
+	}
+
+	/// Statistics of the paths our stem txs were relayed through.
+	pub fn stem_relay_stats(&self) -> Arc<RwLock<StemRelayStats>> {
+		self.stem_relay_stats.clone()
+	}
+
 	/// Setup the p2p server on the adapter
 	pub fn init(&self, peers: Arc<p2p::Peers>) {
 		self.peers.init(Arc::downgrade(&peers));
@@ -1073,6 +1361,75 @@ impl PoolToNetAdapter {
 			.upgrade()
 			.expect("Failed to upgrade weak ref to our peers.")
 	}
+
+	/// The onion API address to relay stem txs to, if we relay over tor and
+	/// the peer supports it.
+	fn onion_relay_addr(&self, peer: &p2p::Peer) -> Option<String> {
+		let supported = peer
+			.info
+			.capabilities
+			.contains(p2p::Capabilities::ONIONSTEM);
+		if self.onion_relay.is_none() || !supported {
+			return None;
+		}
+		peer.info.live_info.read().onion_addr.clone()
+	}
+
+	/// Queues the stem tx for the onion relay threads, relaying it over p2p
+	/// right away when they are too far behind.
+	fn relay_over_tor(&self, onion_addr: String, peer: Arc<p2p::Peer>, tx: Transaction) {
+		let sender = match self.onion_relay.as_ref() {
+			Some(sender) => sender,
+			None => return,
+		};
+		let job = OnionRelayJob {
+			onion_addr,
+			peer,
+			tx,
+		};
+		if let Err(e) = sender.try_send(job) {
+			let job = match e {
+				mpsc::TrySendError::Full(job) | mpsc::TrySendError::Disconnected(job) => job,
+			};
+			warn!(
+				"Onion relay queue full, relaying stem tx {} to {} over p2p.",
+				job.tx.hash(),
+				job.peer.info.addr
+			);
+			relay_over_p2p(&job.peer, &job.tx, &self.stem_relay_stats);
+		}
+	}
+}
+
+/// Pushes the stem tx to the relay peer's onion service. Falls back to the
+/// p2p connection if that fails, then to the Dandelion monitor fluffing the
+/// tx once its embargo expires.
+fn relay_job(onion_relay: &OnionRelay, job: OnionRelayJob, stats: &RwLock<StemRelayStats>) {
+	let res = onion_relay.push_transaction(&job.onion_addr, &job.tx);
+	if res.is_ok() {
+		debug!("Relayed stem tx {} to {}", job.tx.hash(), job.onion_addr);
+		stats.write().onion_relayed += 1;
+		return;
+	}
+	warn!(
+		"Relaying stem tx {} to {} failed: {:?}, relaying to {} over p2p.",
+		job.tx.hash(),
+		job.onion_addr,
+		res,
+		job.peer.info.addr
+	);
+	stats.write().onion_failed += 1;
+	relay_over_p2p(&job.peer, &job.tx, stats);
+}
+
+fn relay_over_p2p(peer: &p2p::Peer, tx: &Transaction, stats: &RwLock<StemRelayStats>) {
+	match peer.send_stem_transaction(tx) {
+		Ok(_) => stats.write().p2p_relayed += 1,
+		Err(e) => {
+			error!("Stemming tx failed, will fluff after embargo. {:?}", e);
+			stats.write().p2p_failed += 1;
+		}
+	}
 }
 
 /// Implements the view of the  required by the TransactionPool to
@@ -0,0 +1,161 @@
+// Copyright 2020 The Epic Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Relays stem transactions to the onion `tor_rpc` endpoint of our Dandelion
+//! relay peer through the tor SOCKS proxy, instead of over the clear p2p
+//! connection.
+
+use std::io::{Read, Write};
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use crate::common::types::Error;
+use crate::core::core::transaction::Transaction;
+use crate::p2p::tor::socks;
+
+/// Path of the tor JSON-RPC endpoint on the peer's onion API.
+const TOR_RPC_PATH: &str = "/v2/tor";
+
+/// Tor circuits are slow to build, be patient.
+const RELAY_TIMEOUT_SECS: u64 = 60;
+
+/// The reply to a push is tiny, anything bigger than this is refused.
+const MAX_RESPONSE_SIZE: u64 = 16_384;
+
+/// Pushes stem transactions to peers' onion API through a SOCKS proxy.
+#[derive(Clone, Debug)]
+pub struct OnionRelay {
+	socks_proxy: SocketAddr,
+	timeout: Duration,
+}
+
+impl OnionRelay {
+	/// Relay through the tor SOCKS proxy listening on `socks_proxy`.
+	pub fn new(socks_proxy: SocketAddr) -> OnionRelay {
+		OnionRelay {
+			socks_proxy,
+			timeout: Duration::from_secs(RELAY_TIMEOUT_SECS),
+		}
+	}
+
+	/// Pushes the transaction, as a stem transaction, to the tor API of the
+	/// peer reachable at `onion_api_addr` (as sent in its
+	/// `OnionAddressResponse`, e.g. "http://xyz.onion:3413").
+	pub fn push_transaction(&self, onion_api_addr: &str, tx: &Transaction) -> Result<(), Error> {
+		let (host, port) = parse_onion_url(onion_api_addr)
+			.ok_or_else(|| Error::TorRelay(format!("invalid onion address {}", onion_api_addr)))?;
+
+		let body = json!({
+			"jsonrpc": "2.0",
+			"id": 1,
+			"method": "push_transaction",
+			"params": [tx, false],
+		})
+		.to_string();
+		let request = format!(
+			"POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+			 Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+			TOR_RPC_PATH,
+			host,
+			body.len(),
+			body
+		);
+
+		let mut stream = socks::connect(self.socks_proxy, &host, port, self.timeout)?;
+		stream.write_all(request.as_bytes())?;
+		let mut response = vec![];
+		stream
+			.take(MAX_RESPONSE_SIZE + 1)
+			.read_to_end(&mut response)?;
+		if response.len() as u64 > MAX_RESPONSE_SIZE {
+			return Err(Error::TorRelay(format!(
+				"response larger than {} bytes",
+				MAX_RESPONSE_SIZE
+			)));
+		}
+
+		parse_response(&String::from_utf8_lossy(&response))
+	}
+}
+
+/// Splits an onion API address into its host and port, defaulting to port 80.
+fn parse_onion_url(url: &str) -> Option<(String, u16)> {
+	let addr = url
+		.trim()
+		.trim_start_matches("http://")
+		.trim_end_matches('/');
+	let (host, port) = match addr.rsplit_once(':') {
+		Some((host, port)) => (host, port.parse().ok()?),
+		None => (addr, 80),
+	};
+	if !host.ends_with(".onion") {
+		return None;
+	}
+	Some((host.to_string(), port))
+}
+
+/// Checks the HTTP status and the JSON-RPC result of the response.
+fn parse_response(response: &str) -> Result<(), Error> {
+	let status = response.lines().next().unwrap_or("");
+	if status.split_whitespace().nth(1) != Some("200") {
+		return Err(Error::TorRelay(format!("unexpected response: {}", status)));
+	}
+
+	// the body may be chunked, the JSON object is all we need
+	let body = response
+		.find("\r\n\r\n")
+		.map(|i| &response[i + 4..])
+		.and_then(|b| Some(&b[b.find('{')?..=b.rfind('}')?]))
+		.ok_or_else(|| Error::TorRelay("empty response".to_string()))?;
+	let value: Value = serde_json::from_str(body)
+		.map_err(|e| Error::TorRelay(format!("invalid response: {}", e)))?;
+
+	match value.get("result") {
+		Some(result) if result.get("Ok").is_some() => Ok(()),
+		Some(result) => Err(Error::TorRelay(format!("rejected: {}", result))),
+		None => Err(Error::TorRelay(format!(
+			"rpc error: {}",
+			value.get("error").unwrap_or(&Value::Null)
+		))),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_onion_relay_parsing() {
+		let host = "vww6ybal4bd7szmgncyruucpgfkqahzddi37ktceo3ah7ngmcopnpyyd.onion";
+		assert_eq!(
+			parse_onion_url(&format!("http://{}:3413", host)),
+			Some((host.to_string(), 3413))
+		);
+		assert_eq!(
+			parse_onion_url(&format!("{}/", host)),
+			Some((host.to_string(), 80))
+		);
+		assert_eq!(parse_onion_url("http://1.2.3.4:3413"), None);
+
+		let ok = "HTTP/1.1 200 OK\r\ncontent-length: 44\r\n\r\n\
+		          {\"id\":1,\"jsonrpc\":\"2.0\",\"result\":{\"Ok\":null}}";
+		assert!(parse_response(ok).is_ok());
+		let rejected = "HTTP/1.1 200 OK\r\n\r\n\
+		                {\"id\":1,\"jsonrpc\":\"2.0\",\"result\":{\"Err\":{\"Internal\":\"bad tx\"}}}";
+		assert!(parse_response(rejected).is_err());
+		assert!(parse_response("HTTP/1.1 401 Unauthorized\r\n\r\n").is_err());
+	}
+}
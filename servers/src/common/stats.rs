@@ -30,6 +30,7 @@ use chrono::prelude::*;
 //use crate::chain;
 use crate::chain::SyncStatus;
 use crate::p2p;
+use crate::p2p::tor::control::{BootstrapStatus, CircuitHealth};
 
 /// Server state info collection struct, to be passed around into internals
 /// and populated when required
@@ -37,12 +38,18 @@ use crate::p2p;
 pub struct ServerStateInfo {
 	/// Stratum stats
 	pub stratum_stats: Arc<RwLock<StratumStats>>,
+	/// Compact block hydration stats
+	pub compact_block_stats: Arc<RwLock<CompactBlockStats>>,
+	/// Stem transaction relay stats
+	pub stem_relay_stats: Arc<RwLock<StemRelayStats>>,
 }
 
 impl Default for ServerStateInfo {
 	fn default() -> ServerStateInfo {
 		ServerStateInfo {
 			stratum_stats: Arc::new(RwLock::new(StratumStats::default())),
+			compact_block_stats: Arc::new(RwLock::new(CompactBlockStats::default())),
+			stem_relay_stats: Arc::new(RwLock::new(StemRelayStats::default())),
 		}
 	}
 }
@@ -66,6 +73,10 @@ pub struct ServerStats {
 	pub diff_stats: DiffStats,
 	/// Transaction pool statistics
 	pub tx_stats: Option<TxStats>,
+	/// Stem transaction relay statistics
+	pub stem_relay_stats: StemRelayStats,
+	/// Status of the tor daemon, when we run over tor
+	pub tor_stats: Option<TorStats>,
 	/// Disk usage in GB
 	pub disk_usage_gb: String,
 }
@@ -81,7 +92,59 @@ pub struct ChainStats {
 	pub total_difficulty: Difficulty,
 	/// Timestamp of highest block or header
 	pub latest_timestamp: DateTime<Utc>,
+	/// Compact block hydration statistics
+	pub compact_blocks: CompactBlockStats,
 }
+
+/// Compact block hydration statistics
+#[derive(Clone, Serialize, Debug, Default)]
+pub struct CompactBlockStats {
+	/// Number of compact blocks received
+	pub received: u64,
+	/// Compact blocks hydrated from our own tx pool
+	pub hydrated_from_pool: u64,
+	/// Compact blocks hydrated after requesting the missing txs from the peer
+	pub hydrated_with_block_txs: u64,
+	/// Compact blocks we gave up on and requested as full blocks instead
+	pub full_block_requests: u64,
+}
+
+impl CompactBlockStats {
+	/// Share of the received compact blocks we successfully hydrated
+	pub fn success_rate(&self) -> f64 {
+		if self.received == 0 {
+			return 0.0;
+		}
+		(self.hydrated_from_pool + self.hydrated_with_block_txs) as f64 / self.received as f64
+	}
+}
+
+/// Statistics of the paths our stem transactions were relayed through
+#[derive(Clone, Serialize, Debug, Default)]
+pub struct StemRelayStats {
+	/// Stem txs pushed to the relay peer's onion service
+	pub onion_relayed: u64,
+	/// Stem txs that failed to be pushed to the relay peer's onion service
+	pub onion_failed: u64,
+	/// Stem txs relayed over the p2p connection
+	pub p2p_relayed: u64,
+	/// Stem txs that failed to be relayed over the p2p connection
+	pub p2p_failed: u64,
+	/// Stem txs fluffed right away for lack of a working relay
+	pub fluffed: u64,
+}
+
+/// Status of the tor daemon, as reported by its control port
+#[derive(Clone, Serialize, Debug)]
+pub struct TorStats {
+	/// Our onion address, without ".onion"
+	pub onion_address: String,
+	/// Bootstrap progress
+	pub bootstrap: BootstrapStatus,
+	/// Circuit health
+	pub circuits: CircuitHealth,
+}
+
 /// Transaction Statistics
 #[derive(Clone, Serialize, Debug)]
 pub struct TxStats {
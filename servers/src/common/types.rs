@@ -64,6 +64,8 @@ pub enum Error {
 	General(String),
 	TorConfig(String),
 	TorProcess(String),
+	/// Relaying a transaction to a peer's onion service failed
+	TorRelay(String),
 }
 
 impl From<core::block::Error> for Error {
@@ -163,10 +165,22 @@ pub struct ServerConfig {
 	/// TLS certificate private key file
 	pub tls_certificate_key: Option<String>,
 
+	/// Maximum number of requests in a JSON-RPC batch on the V2 APIs
+	pub api_max_batch_size: Option<usize>,
+
+	/// Location of the scoped API tokens, replacing the secrets above with
+	/// per token scopes when set
+	pub api_tokens_path: Option<String>,
+
 	/// Setup the server for tests, testnet or mainnet
 	#[serde(default)]
 	pub chain_type: ChainTypes,
 
+	/// Definition of the custom network a usertesting node runs, created with
+	/// `epic network new`
+	#[serde(default)]
+	pub network_file: Option<String>,
+
 	/// Automatically run full chain validation during normal block processing?
 	#[serde(default)]
 	pub chain_validation_mode: ChainValidationMode,
@@ -174,6 +188,10 @@ pub struct ServerConfig {
 	/// Whether this node is a full archival node or a fast-sync, pruned node
 	pub archive_mode: Option<bool>,
 
+	/// Whether this node follows the headers only, checking outputs and
+	/// kernels with merkle proofs from full peers
+	pub light_mode: Option<bool>,
+
 	/// Disable pow validation in checkpointed range, fully validate all blocks
 	/// Outside of checkpointed range
 	pub skip_pow_validation: Option<bool>,
@@ -223,6 +241,10 @@ pub struct ServerConfig {
 	#[serde(default)]
 	pub stratum_mining_config: Option<StratumServerConfig>,
 
+	/// Rate limits of the API clients, no limits when not set
+	#[serde(default)]
+	pub api_rate_limit: Option<api::RateLimitConfig>,
+
 	/// Configuration for the webhooks that trigger on certain events
 	#[serde(default)]
 	pub webhook_config: WebHooksConfig,
@@ -244,11 +266,16 @@ impl Default for ServerConfig {
 			foundation_path: "foundation".to_string(),
 			tls_certificate_file: None,
 			tls_certificate_key: None,
+			api_max_batch_size: None,
+			api_tokens_path: None,
 			p2p_config: p2p::P2PConfig::default(),
 			dandelion_config: pool::DandelionConfig::default(),
 			stratum_mining_config: Some(StratumServerConfig::default()),
+			api_rate_limit: Some(api::RateLimitConfig::default()),
 			chain_type: ChainTypes::default(),
+			network_file: None,
 			archive_mode: Some(false),
+			light_mode: Some(false),
 			skip_pow_validation: Some(true),
 			disable_checkpoints: Some(true),
 			chain_validation_mode: ChainValidationMode::default(),
@@ -300,8 +327,7 @@ pub struct StratumServerConfig {
 
 impl Default for StratumServerConfig {
 	fn default() -> StratumServerConfig {
-		let param_ref = global::CHAIN_TYPE.read();
-		match *param_ref {
+		match global::get_chain_type() {
 			global::ChainTypes::UserTesting => StratumServerConfig {
 				wallet_listener_url: "http://127.0.0.1:3415".to_string(),
 				burn_reward: false,
@@ -466,6 +492,13 @@ pub struct TorConfig {
 	pub socks_proxy_addr: String,
 	/// Send configuration directory
 	pub send_config_dir: String,
+	/// Address of the tor control port
+	pub control_addr: Option<String>,
+	/// Control auth cookie of a system tor, when it doesn't tell us where
+	/// it is
+	pub cookie_file: Option<String>,
+	/// Reuse an already running tor instead of spawning our own
+	pub use_system_tor: Option<bool>,
 }
 
 impl Default for TorConfig {
@@ -473,6 +506,22 @@ impl Default for TorConfig {
 		TorConfig {
 			socks_proxy_addr: "127.0.0.1:9050".to_owned(),
 			send_config_dir: ".".into(),
+			control_addr: None,
+			cookie_file: None,
+			use_system_tor: None,
 		}
 	}
 }
+
+impl TorConfig {
+	/// Address of the tor control port, 127.0.0.1:9051 by default.
+	pub fn control_addr(&self) -> String {
+		self.control_addr
+			.clone()
+			.unwrap_or_else(|| "127.0.0.1:9051".to_owned())
+	}
+
+	pub fn use_system_tor(&self) -> bool {
+		self.use_system_tor.unwrap_or(false)
+	}
+}
@@ -18,4 +18,5 @@ pub mod dandelion_monitor;
 pub mod seed;
 pub mod server;
 pub mod sync;
+pub mod tor;
 pub mod version;
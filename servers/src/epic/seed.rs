@@ -34,9 +34,6 @@ use crate::p2p::types::PeerAddr;
 use crate::p2p::ChainAdapter;
 use crate::util::StopState;
 
-// DNS Seeds with contact email associated
-const MAINNET_DNS_SEEDS: &'static [&'static str] = &["node.epiccash.com"];
-const FLOONET_DNS_SEEDS: &'static [&'static str] = &["floonet.epiccash.com"]; //does not exist yet
 pub fn connect_and_monitor(
 	p2p_server: Arc<p2p::Server>,
 	capabilities: p2p::Capabilities,
@@ -231,17 +228,16 @@ fn monitor_peers(peers: Arc<p2p::Peers>, config: p2p::P2PConfig, tx: mpsc::Sende
 		}
 	}
 
+	// Pick random addresses out of both tried and new peers, bucketed by
+	// network group so a single subnet can't take over our outbound slots
 	let new_peers_limit = 10;
-	let mut new_peers =
-		peers.find_peers(p2p::State::Unknown, p2p::Capabilities::UNKNOWN, usize::MAX);
-	new_peers.shuffle(&mut rng());
-	let new_peers: Vec<_> = new_peers.into_iter().take(new_peers_limit).collect();
+	let new_peers = peers.select_outbound_addrs(new_peers_limit);
 
 	// Send all combined peers to the connection queue
-	for p in new_peers {
-		if let Ok(false) = peers.is_known(p.addr) {
-			trace!("try sending peer addr to connection queue: {}", p.addr);
-			let _ = tx.send(p.addr);
+	for addr in new_peers {
+		if let Ok(false) = peers.is_known(addr) {
+			trace!("try sending peer addr to connection queue: {}", addr);
+			let _ = tx.send(addr);
 		}
 	}
 }
@@ -263,16 +259,28 @@ fn promote_defunct_to_unknown(peers: &Arc<p2p::Peers>) {
 	}
 }
 
-/// Connect to all healthy peers from the peer store.
+/// Connect to the anchor peers saved on last shutdown first, then to a
+/// selection of the peers we know from the peer store.
 fn connect_to_healthy_peers(tx: mpsc::Sender<PeerAddr>, peers: Arc<p2p::Peers>) {
+	let anchors = peers.take_anchors();
+	for addr in &anchors {
+		info!("Connecting to anchor peer address: {}", addr);
+		if let Err(e) = tx.send(*addr) {
+			error!(
+				"Failed to send anchor peer addr {} to connection queue: {:?}",
+				addr, e
+			);
+		}
+	}
+
+	let max_outbound = peers.get_config().peer_max_outbound_count() as usize;
 	let healthy_peers: Vec<PeerAddr> = peers
-		.all_peers()
+		.select_outbound_addrs(max_outbound.saturating_sub(anchors.len()))
 		.into_iter()
-		.filter(|peer| peer.flags == p2p::State::Healthy)
-		.map(|peer| peer.addr)
+		.filter(|addr| !anchors.contains(addr))
 		.collect();
 
-	if healthy_peers.is_empty() {
+	if healthy_peers.is_empty() && anchors.is_empty() {
 		warn!("No healthy peers found in store.");
 	}
 
@@ -357,6 +365,19 @@ fn listen_for_addrs(
 			continue;
 		}
 
+		let preferred = p2p
+			.config
+			.peers_preferred
+			.as_ref()
+			.map_or(false, |preferred| preferred.contains(&addr));
+		if !preferred && peers.outbound_group_full(addr) {
+			debug!(
+				"peer_connect: too many outbound peers in the group of {}",
+				addr
+			);
+			continue;
+		}
+
 		// ignore the duplicate connecting to same peer within 30 seconds
 		let now = Utc::now();
 
@@ -416,23 +437,24 @@ fn listen_for_addrs(
 	}
 }
 
-pub fn dns_seeds() -> Box<dyn Fn() -> Vec<PeerAddr> + Send> {
-	Box::new(|| {
+/// Resolves the provided DNS seed hostnames into the addresses of the nodes
+/// to bootstrap from.
+pub fn dns_seeds(net_seeds: Vec<String>) -> Box<dyn Fn() -> Vec<PeerAddr> + Send> {
+	Box::new(move || {
 		let mut addresses: Vec<PeerAddr> = vec![];
-		let net_seeds = if global::is_floonet() {
-			FLOONET_DNS_SEEDS
-		} else {
-			MAINNET_DNS_SEEDS
-		};
-		for dns_seed in net_seeds {
+		for dns_seed in &net_seeds {
 			let temp_addresses = addresses.clone();
 			debug!("Retrieving seed nodes from dns {}", dns_seed);
-			let port = if global::is_floonet() { 13414 } else { 3414 };
-			let addr_str = format!("{}:{}", dns_seed, port);
+			let addr_str = if dns_seed.contains(':') {
+				dns_seed.clone()
+			} else {
+				let port = if global::is_floonet() { 13414 } else { 3414 };
+				format!("{}:{}", dns_seed, port)
+			};
 			match addr_str.to_socket_addrs() {
 				Ok(addrs) => addresses.append(
 					&mut (addrs
-						.map(|addr| PeerAddr(addr))
+						.map(PeerAddr::Ip)
 						.filter(|addr| !temp_addresses.contains(addr))
 						.collect()),
 				),
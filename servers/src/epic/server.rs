@@ -19,6 +19,7 @@
 use std::fs;
 use std::fs::File;
 use std::io::prelude::*;
+use std::net::SocketAddr;
 use std::path::Path;
 use std::sync::{mpsc, Arc};
 use std::{
@@ -33,22 +34,25 @@ use crate::common::adapters::{
 	ChainToPoolAndNetAdapter, NetToChainAdapter, PoolToChainAdapter, PoolToNetAdapter,
 };
 use crate::common::hooks::{init_chain_hooks, init_net_hooks};
+use crate::common::onion_relay::OnionRelay;
 use crate::common::stats::{
-	ChainStats, DiffBlock, DiffStats, PeerStats, ServerStateInfo, ServerStats, TxStats,
+	ChainStats, CompactBlockStats, DiffBlock, DiffStats, PeerStats, ServerStateInfo, ServerStats,
+	TxStats,
 };
 use crate::p2p::Capabilities;
 use crate::common::types::{Error, ServerConfig, StratumServerConfig};
 use crate::core::core::feijoada::PolicyConfig;
 use crate::core::core::hash::Hashed;
 use crate::core::core::hash::{Hash, ZERO_HASH};
+use crate::core::network::NetworkDefinition;
 use crate::core::pow::{PoWType, Proof};
 use crate::core::ser::ProtocolVersion;
 use crate::core::{consensus, genesis, global, pow};
+use crate::epic::tor::TorService;
 use crate::epic::{dandelion_monitor, seed, sync, version};
 use crate::mining::stratumserver;
 use crate::mining::test_miner::Miner;
 use crate::p2p;
-use crate::p2p::tor::process::TorProcess;
 use crate::p2p::types::PeerAddr;
 use crate::pool;
 use crate::util::file::get_first_line;
@@ -59,7 +63,7 @@ use fs2::FileExt;
 use walkdir::WalkDir;
 
 fn is_test_network() -> bool {
-	match *global::CHAIN_TYPE.read() {
+	match global::get_chain_type() {
 		global::ChainTypes::Mainnet => false,
 		_ => true,
 	}
@@ -89,7 +93,7 @@ pub struct Server {
 	connect_thread: Option<JoinHandle<()>>,
 	sync_thread: JoinHandle<()>,
 	dandelion_thread: JoinHandle<()>,
-	tor_process: Option<TorProcess>,
+	tor: Option<TorService>,
 }
 
 impl Server {
@@ -97,7 +101,7 @@ impl Server {
 	/// for the server to send an ARC copy of itself, to allow another process
 	/// to poll info about the server status
 	pub fn start<F>(
-		config: ServerConfig,
+		mut config: ServerConfig,
 		logs_rx: Option<mpsc::Receiver<LogEntry>>,
 		mut info_callback: F,
 		stop_state: Option<Arc<StopState>>,
@@ -142,8 +146,19 @@ impl Server {
 			}
 		}
 
+		let network = match config.network_file.clone() {
+			Some(network_file) => {
+				let network = Server::load_network(&mut config, &network_file)?;
+				Some(Arc::new(network))
+			}
+			None => None,
+		};
+
 		global::set_foundation_path(config.foundation_path.clone().to_owned());
 		let policy_config = global::get_policy_config();
+		global::fork_schedule()
+			.validate(&config.chain_type, &policy_config)
+			.map_err(|e| Error::Configuration(format!("Invalid fork schedule: {}", e)))?;
 
 		info!("Block policy:");
 		if let Some((_i, policy)) = policy_config.policies.iter().enumerate().last() {
@@ -154,10 +169,12 @@ impl Server {
 
 		let foundation_path = global::get_foundation_path().unwrap();
 		let foundation_file = std::path::Path::new(&foundation_path);
-		
+
 		if foundation_file.exists() {
-			
-			let hash_to_compare = global::foundation_json_sha256();
+			let hash_to_compare = match network {
+				Some(ref network) => network.foundation_sha256.clone(),
+				None => global::foundation_json_sha256(),
+			};
 			let hash = global::get_file_sha256(&foundation_path);
 			if hash.as_str() != hash_to_compare {
 				error!("Invalid {} file!\nThe sha256 of this file should be: {} - {}\nCheck if the file was not changed!", foundation_path, hash_to_compare, hash.as_str());
@@ -168,13 +185,23 @@ impl Server {
 				);
 				std::process::exit(1);
 			}
+		} else {
+			info!(
+				"No foundation file at {}, using the one embedded for {:?}",
+				foundation_path, config.chain_type
+			);
+			global::clear_foundation_path();
 		}
 
-		let mining_config = config.stratum_mining_config.clone();
-		let enable_test_miner = config.run_test_miner;
+		let (mining_config, enable_test_miner) = if config.light_mode.unwrap_or(false) {
+			// blocks can't be built without the chain state
+			(None, None)
+		} else {
+			(config.stratum_mining_config.clone(), config.run_test_miner)
+		};
 		let test_miner_wallet_url = config.test_miner_wallet_url.clone();
 
-		let serv = Server::new(config, stop_state, api_chan)?;
+		let serv = Server::new(config, network, stop_state, api_chan)?;
 
 		if let Some(c) = mining_config {
 			let enable_stratum_server = c.enable_stratum_server;
@@ -199,6 +226,50 @@ impl Server {
 		Ok(())
 	}
 
+	// Loads the custom network definition and makes the node join it: its
+	// genesis, magic bytes, policies and foundation file are used from then on
+	// and its ports replace the configured ones.
+	fn load_network(
+		config: &mut ServerConfig,
+		network_file: &str,
+	) -> Result<NetworkDefinition, Error> {
+		if config.chain_type != global::ChainTypes::UserTesting {
+			return Err(Error::Configuration(
+				"network_file can only be used with the UserTesting chain type".to_string(),
+			));
+		}
+		let network = NetworkDefinition::load(Path::new(network_file))
+			.map_err(|e| Error::Configuration(format!("{}: {}", network_file, e)))?;
+		info!(
+			"Joining network {} (genesis {}) defined in {}",
+			network.name, network.genesis_hash, network_file
+		);
+		// the network ports win over the configured ones
+		if config.p2p_config.port != network.p2p_port {
+			warn!(
+				"Network {} uses the p2p port {}, not the configured {}",
+				network.name, network.p2p_port, config.p2p_config.port
+			);
+			config.p2p_config.port = network.p2p_port;
+		}
+		let mut api_addr: SocketAddr = config
+			.api_http_addr
+			.parse()
+			.map_err(|e| Error::Configuration(format!("Invalid api_http_addr: {}", e)))?;
+		if api_addr.port() != network.api_port {
+			warn!(
+				"Network {} uses the api port {}, not the configured {}",
+				network.name,
+				network.api_port,
+				api_addr.port()
+			);
+			api_addr.set_port(network.api_port);
+			config.api_http_addr = api_addr.to_string();
+		}
+		global::set_policy_config(network.policy_config.clone());
+		Ok(network)
+	}
+
 	// Exclusive (advisory) lock_file to ensure we do not run multiple
 	// instance of epic server from the same dir.
 	// This uses fs2 and should be safe cross-platform unless somebody abuses the file itself.
@@ -224,9 +295,11 @@ impl Server {
 		Ok(Arc::new(lock_file))
 	}
 
-	/// Instantiates a new server associated with the provided future reactor.
+	/// Instantiates a new server associated with the provided future reactor,
+	/// on the custom network loaded from the configured network_file if any.
 	pub fn new(
-		config: ServerConfig,
+		mut config: ServerConfig,
+		network: Option<Arc<NetworkDefinition>>,
 		//TODO: see if below should be used instead of defining new var in function
 		// had to add underscore to silence compiler warnings
 		stop_state: Option<Arc<StopState>>,
@@ -245,22 +318,76 @@ impl Server {
 			Some(b) => b,
 		};
 
+		// Light nodes follow the headers only, they have no chain state to
+		// serve and no transactions to relay.
+		let light_mode = config.light_mode.unwrap_or(false);
+		if light_mode {
+			if archive_mode {
+				return Err(Error::Configuration(
+					"light_mode and archive_mode can't be both enabled".to_string(),
+				));
+			}
+			config.p2p_config.capabilities &= Capabilities::HEADER_HIST
+				| Capabilities::PEER_LIST
+				| Capabilities::HEADER_FASTSYNC
+				| Capabilities::ENCRYPTED;
+			info!("Running in light mode, following the block headers only");
+		}
+
 		let stop_state = if stop_state.is_some() {
 			stop_state.unwrap()
 		} else {
 			Arc::new(StopState::new())
 		};
 
+		// the policy was set process wide on start, a missing foundation
+		// file means the data embedded for the chain type
+		let params = Arc::new(global::ChainParams {
+			policy_config: Some(global::get_policy_config()),
+			foundation_path: global::get_foundation_path(),
+			header_sync_timeout: global::valid_header_sync_timeout(config.header_sync_timeout),
+			epic_version: global::get_epic_version(),
+			network: network.clone(),
+			..global::ChainParams::new(config.chain_type.clone())
+		});
+
+		// the tor mode can't dial anyone without the SOCKS proxy, onion stem
+		// relaying is only left off when no proxy is configured at all
+		let tor_p2p = config.p2p_config.tor_mode().is_enabled();
+		let onion_stem = config
+			.p2p_config
+			.capabilities
+			.contains(Capabilities::ONIONSTEM);
+		let socks_proxy = if tor_p2p || (onion_stem && config.tor.socks_proxy_addr != "") {
+			let socks_proxy = config
+				.tor
+				.socks_proxy_addr
+				.parse::<SocketAddr>()
+				.map_err(|e| {
+					Error::Configuration(format!(
+						"Invalid tor socks_proxy_addr \"{}\": {}",
+						config.tor.socks_proxy_addr, e
+					))
+				})?;
+			Some(socks_proxy)
+		} else {
+			None
+		};
+
 		let pool_adapter = Arc::new(PoolToChainAdapter::new());
-		let pool_net_adapter = Arc::new(PoolToNetAdapter::new(config.dandelion_config.clone()));
+		let mut pool_net_adapter = PoolToNetAdapter::new(config.dandelion_config.clone());
+		if let (true, Some(socks_proxy)) = (onion_stem, socks_proxy) {
+			pool_net_adapter = pool_net_adapter.with_onion_relay(OnionRelay::new(socks_proxy));
+		}
+		let stem_relay_stats = pool_net_adapter.stem_relay_stats();
+		let pool_net_adapter = Arc::new(pool_net_adapter);
 		let tx_pool = Arc::new(RwLock::new(pool::TransactionPool::new(
 			config.pool_config.clone(),
 			pool_adapter.clone(),
 			pool_net_adapter.clone(),
+			params.clone(),
 		)));
 
-		global::set_header_sync_timeout(config.header_sync_timeout);
-
 		let sync_state = Arc::new(SyncState::new());
 
 		let chain_adapter = Arc::new(ChainToPoolAndNetAdapter::new(
@@ -270,7 +397,12 @@ impl Server {
 
 		let genesis = match config.chain_type {
 			global::ChainTypes::AutomatedTesting => genesis::genesis_dev(),
-			global::ChainTypes::UserTesting => genesis::genesis_dev(),
+			global::ChainTypes::UserTesting => match network.as_ref() {
+				Some(network) => network
+					.genesis()
+					.map_err(|e| Error::Configuration(e.to_string()))?,
+				None => genesis::genesis_dev(),
+			},
 			global::ChainTypes::Floonet => genesis::genesis_floo(),
 			global::ChainTypes::Mainnet => genesis::genesis_main(),
 		};
@@ -280,98 +412,103 @@ impl Server {
 			genesis.hash()
 		);
 
-		let shared_chain = Arc::new(chain::Chain::init(
-			config.db_root.clone(),
-			chain_adapter.clone(),
-			genesis.clone(),
-			pow::verify_size,
-			archive_mode,
-		)?);
+		let shared_chain = Arc::new(
+			chain::Chain::init(
+				config.db_root.clone(),
+				chain_adapter.clone(),
+				genesis.clone(),
+				pow::verify_size,
+				archive_mode,
+				params.clone(),
+			)?
+			.with_light_mode(light_mode),
+		);
 
 		pool_adapter.set_chain(shared_chain.clone());
 
+		let compact_block_stats = Arc::new(RwLock::new(CompactBlockStats::default()));
 		let net_adapter = Arc::new(NetToChainAdapter::new(
 			sync_state.clone(),
 			shared_chain.clone(),
 			tx_pool.clone(),
 			config.clone(),
 			init_net_hooks(&config),
+			compact_block_stats.clone(),
 		));
 
-		// set up tor send process if needed
-
-		let mut tor = TorProcess::new();
+		// publish our API (for onion stem relays) and p2p ports over tor
+		let mut tor = None;
 		let mut onion_api_addr = None;
-		
-
-		if config.tor.socks_proxy_addr != ""
-		&& config.p2p_config.capabilities.contains(Capabilities::ONIONSTEM) {
-			let tor_dir = config.tor.send_config_dir.clone();
-			warn!(
-				"Starting TOR Process for send at {:?}",
-				config.tor.socks_proxy_addr
-			);
-			// Schreibe die torrc-Konfiguration für den Hidden Service
-			let torrc_path = format!("{}/torrc", tor_dir);
-
-			// Erzeuge Hidden Service für den Node (Port 3414)
-			match crate::p2p::tor::config::output_tor_listener_config_auto(
-				&tor_dir,
-				&config.api_http_addr, // Use node address from config
-				&config.tor.socks_proxy_addr,
-			) {
-				Ok(_) => info!("torrc with HiddenService created successfully"),
-				Err(e) => error!("Failed to create torrc with HiddenService: {:?}", e),
+		let mut p2p_config = config.p2p_config.clone();
+		if socks_proxy.is_some() {
+			let api_port = config
+				.api_http_addr
+				.rsplit(':')
+				.next()
+				.and_then(|p| p.parse::<u16>().ok())
+				.unwrap_or(3413);
+			let mut ports = vec![];
+			if onion_stem {
+				ports.push((api_port, SocketAddr::from(([127, 0, 0, 1], api_port))));
 			}
-			debug!("torrc_path: {}, dir: {}", torrc_path, tor_dir);
-			// Starte den Tor-Prozess
-			tor.torrc_path(&torrc_path)
-				.working_dir(&tor_dir)
-				.timeout(20)
-				.completion_percent(100)
-				.launch()
-				.map_err(|e| Error::TorProcess(format!("{:?}", e).into()))?;
-			
-			
-			// Lese die Onion-Adresse aus
-			let onion_base = format!("{}/onion_service_addresses", tor_dir);
-			let api_port = config.api_http_addr
-			.split(':')
-			.last()
-			.unwrap_or("3413"); 
-			let onion_addr = std::fs::read_dir(&onion_base)
-				.ok()
-				.and_then(|mut entries| {
-					entries.next().and_then(|entry| {
-						let hostname_path = entry.ok()?.path().join("hostname");
-						std::fs::read_to_string(hostname_path).ok()
-					})
-				})
-				.map(|s| s.trim().to_string());
-			
-			onion_api_addr = onion_addr
-			.as_ref()
-			.map(|addr| format!("http://{}:{}", addr, api_port));
-		
+			if tor_p2p {
+				ports.push((
+					p2p_config.port,
+					SocketAddr::from(([127, 0, 0, 1], p2p_config.port)),
+				));
+			}
+			let service = TorService::start(&config.tor, &config.db_root, &ports)?;
+			if onion_stem {
+				onion_api_addr = Some(format!(
+					"http://{}.onion:{}",
+					service.onion_address(),
+					api_port
+				));
+			}
+			if tor_p2p {
+				let addr = service.peer_addr(p2p_config.port)?;
+				info!("This peer's onion p2p addr: {}", addr);
+				// advertised to our peers along with our other addresses
+				let mut external_addrs = vec![addr];
+				external_addrs.extend(p2p_config.external_addrs.take().unwrap_or_default());
+				p2p_config.external_addrs = Some(external_addrs);
+			}
+			tor = Some(service);
 		}
 		if let Some(ref addr) = onion_api_addr {
 			info!("This peer's onion addr: {}", addr);
-		} 
-		let p2p_server = Arc::new(p2p::Server::new(
+		}
+		let mut p2p_server = p2p::Server::new(
 			&config.db_root,
 			config.p2p_config.capabilities,
-			config.p2p_config.clone(),
+			p2p_config,
 			net_adapter.clone(),
 			genesis.hash(),
 			stop_state.clone(),
 			onion_api_addr.clone(),
-		)?);
+			params.clone(),
+		)?;
+		if let (true, Some(socks_proxy)) = (tor_p2p, socks_proxy) {
+			p2p_server = p2p_server.with_socks_proxy(socks_proxy);
+		}
+		let p2p_server = Arc::new(p2p_server);
 
 		// Initialize various adapters with our dynamic set of connected peers.
 		chain_adapter.init(p2p_server.peers.clone());
 		pool_net_adapter.init(p2p_server.peers.clone());
 		net_adapter.init(p2p_server.peers.clone());
 
+		let compact_blocks_adapter = net_adapter.clone();
+		let compact_blocks_stop = stop_state.clone();
+		let _ = thread::Builder::new()
+			.name("compact_blocks".to_string())
+			.spawn(move || {
+				while !compact_blocks_stop.is_stopped() {
+					compact_blocks_adapter.expire_pending_compact_blocks();
+					thread::sleep(Duration::from_secs(1));
+				}
+			})?;
+
 		let mut connect_thread = None;
 
 		if config.p2p_config.seeding_type != p2p::Seeding::Programmatic {
@@ -388,7 +525,7 @@ impl Server {
 						));
 					}
 				},
-				p2p::Seeding::DNSSeed => seed::dns_seeds(),
+				p2p::Seeding::DNSSeed => seed::dns_seeds(config.p2p_config.dns_seeds()),
 				_ => unreachable!(),
 			};
 
@@ -425,6 +562,10 @@ impl Server {
 		//info!("Starting rest apis at: {}", &config.api_http_addr);
 		let api_secret = get_first_line(config.api_secret_path.clone());
 		let foreign_api_secret = get_first_line(config.foreign_api_secret_path.clone());
+		let api_tokens = match config.api_tokens_path {
+			Some(ref path) => Some(api::tokens::ApiTokens::load(path)?),
+			None => None,
+		};
 		let tls_conf = match config.tls_certificate_file.clone() {
 			None => None,
 			Some(file) => {
@@ -449,6 +590,11 @@ impl Server {
 			api_secret.clone(),
 			foreign_api_secret.clone(),
 			tls_conf.clone(),
+			api_tokens,
+			config
+				.api_max_batch_size
+				.unwrap_or(api::DEFAULT_MAX_BATCH_SIZE),
+			config.api_rate_limit.clone(),
 			api_chan,
 			stop_state.clone(),
 		)?;
@@ -462,10 +608,11 @@ impl Server {
 		)?;
 
 		info!("Starting the version checker monitor!");
+		let epic_version = params.epic_version.clone();
 		let mut scheduler = Scheduler::new();
-		scheduler.every(15.minutes()).run(|| {
+		scheduler.every(15.minutes()).run(move || {
 			if let Ok(dns_version) = version::get_dns_version() {
-				if let Some(our_version) = global::get_epic_version() {
+				if let Some(our_version) = epic_version.clone() {
 					if !version::is_version_valid(our_version.clone(), dns_version.clone()) {
 						error!(
 							"Your current epic node version {}.{}.X.X is outdated! Please consider updating your code to the newest version {}.{}.X.X!",
@@ -495,6 +642,8 @@ impl Server {
 			tx_pool,
 			sync_state,
 			state_info: ServerStateInfo {
+				compact_block_stats,
+				stem_relay_stats,
 				..Default::default()
 			},
 			stop_state,
@@ -502,7 +651,7 @@ impl Server {
 			connect_thread,
 			sync_thread,
 			dandelion_thread,
-			tor_process: Some(tor),
+			tor,
 		})
 	}
 
@@ -716,12 +865,16 @@ impl Server {
 			stem_pool_kernels: pool.stempool.kernel_count(),
 		});
 
+		let compact_blocks = self.state_info.compact_block_stats.read().clone();
+		let stem_relay_stats = self.state_info.stem_relay_stats.read().clone();
+
 		let head = self.chain.head_header()?;
 		let head_stats = ChainStats {
 			latest_timestamp: head.timestamp,
 			height: head.height,
 			last_block_h: head.prev_hash,
 			total_difficulty: head.total_difficulty(),
+			compact_blocks: compact_blocks.clone(),
 		};
 
 		let header_head = self.chain.header_head()?;
@@ -731,6 +884,7 @@ impl Server {
 			height: header.height,
 			last_block_h: header.prev_hash,
 			total_difficulty: header.total_difficulty(),
+			compact_blocks,
 		};
 
 		let disk_usage_bytes = WalkDir::new(&self.config.db_root)
@@ -754,6 +908,8 @@ impl Server {
 			peer_stats,
 			diff_stats,
 			tx_stats,
+			stem_relay_stats,
+			tor_stats: self.tor.as_ref().and_then(|t| t.stats()),
 		})
 	}
 
@@ -782,7 +938,7 @@ impl Server {
 				Ok(_) => info!("Dandelion monitor thread stopped"),
 			}
 
-			drop(self.tor_process); // Explicitly drop TorProcess to kill Tor
+			drop(self.tor); // Explicitly drop TorService to kill Tor
 		}
 		self.p2p.stop();
 
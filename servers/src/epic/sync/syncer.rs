@@ -17,6 +17,7 @@ use std::sync::Arc;
 use std::time;
 use std::{thread, thread::JoinHandle};
 
+use crate::chain::store::DifficultyIter;
 use crate::chain::{self, SyncState, SyncStatus};
 use crate::core::core::hash::Hashed;
 
@@ -522,6 +523,12 @@ impl SyncRunner {
 							&& sync_head.hash() == header_head.hash()
 							&& sync_head.height == header_head.height
 						{
+							if self.chain.is_light_mode() {
+								info!("Header sync head unchanged, light node in sync.");
+								self.sync_state.update(SyncStatus::NoSync);
+								download_headers = false;
+								continue;
+							}
 							info!("Header sync head unchanged, proceeding directly to BodySync.");
 							self.sync_state.update(SyncStatus::BodySync {
 								current_height: sync_head.height,
@@ -552,6 +559,13 @@ impl SyncRunner {
 							let _ = header_sync.1.send(false);
 						}
 
+						// Light nodes are done once they have the headers
+						if self.chain.is_light_mode() {
+							self.sync_state.update(SyncStatus::NoSync);
+							download_headers = false;
+							continue;
+						}
+
 						// Wechsel zu Body-Synchronisierung
 						self.sync_state.update(SyncStatus::BodySync {
 							current_height: head.height,
@@ -599,7 +613,9 @@ impl SyncRunner {
 	/// Whether we're currently syncing the chain or we're fully caught up and
 	/// just receiving blocks through gossip.
 	fn needs_syncing(&self) -> Result<(bool, u64), chain::Error> {
-		let local_diff = self.chain.head()?.total_difficulty;
+		// light nodes follow the header chain
+		let tip = self.chain.tip()?;
+		let local_diff = tip.total_difficulty;
 		let mut is_syncing = self.sync_state.is_syncing();
 		let peer = self.peers.most_work_peer();
 
@@ -614,10 +630,9 @@ impl SyncRunner {
 		// difficulty than us
 		if is_syncing {
 			if peer_info.total_difficulty() <= local_diff {
-				let ch = self.chain.head()?;
 				info!(
 					"Node synchronized at {} @ {} [{}]",
-					local_diff, ch.height, ch.last_block_h
+					local_diff, tip.height, tip.last_block_h
 				);
 				//set SyncStatus to no sync
 				self.sync_state.update(SyncStatus::NoSync);
@@ -627,14 +642,7 @@ impl SyncRunner {
 		} else {
 			// sum the last 5 difficulties to give us the threshold
 			let threshold = {
-				let diff_iter = match self.chain.difficulty_iter() {
-					Ok(v) => v,
-					Err(e) => {
-						error!("failed to get difficulty iterator: {:?}", e);
-						// we handle 0 height in the caller
-						return Ok((false, 0));
-					}
-				};
+				let diff_iter = DifficultyIter::from(tip.last_block_h, self.chain.store());
 				diff_iter
 					.map(|x| x.difficulty)
 					.take(5)
@@ -0,0 +1,185 @@
+// Copyright 2020 The Epic Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Runs our onion service through the tor control port, on a tor daemon we
+//! spawn or on an already running system tor.
+
+use std::io::BufRead;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use crate::common::stats::TorStats;
+use crate::common::types::{Error, TorConfig};
+use crate::p2p::identity::{self, NodeIdentity};
+use crate::p2p::tor::config as tor_config;
+use crate::p2p::tor::control::{self, TorControl};
+use crate::p2p::tor::process::TorProcess;
+use crate::p2p::types::PeerAddr;
+use crate::util::Mutex;
+
+/// Timeout of the replies of the control port.
+const CONTROL_TIMEOUT_SECS: u64 = 10;
+
+/// Attempts to reach the control port of a tor we just spawned.
+const CONTROL_CONNECT_ATTEMPTS: u32 = 20;
+
+/// How long we wait for tor to bootstrap before publishing our service
+/// anyway, tor keeps bootstrapping in the background.
+const BOOTSTRAP_TIMEOUT_SECS: u64 = 60;
+
+/// Our tor daemon and the onion service publishing our ports on it, taken
+/// down when dropped.
+pub struct TorService {
+	/// Only set when we spawned tor ourselves, killed when dropped
+	_process: Option<TorProcess>,
+	control: Mutex<TorControl>,
+	service_id: String,
+}
+
+impl TorService {
+	/// Starts or reaches tor, then publishes the provided (virtual port,
+	/// local target) pairs as an onion service under our node identity.
+	pub fn start(
+		config: &TorConfig,
+		db_root: &str,
+		ports: &[(u16, SocketAddr)],
+	) -> Result<TorService, Error> {
+		// fail early on a wrong identity password, before starting tor
+		let identity = NodeIdentity::load_or_create(db_root, &identity::password_from_env())?;
+		let (key, _) = identity.onion_keypair()?;
+
+		let control_addr: SocketAddr = config.control_addr().parse().map_err(|e| {
+			Error::TorConfig(format!(
+				"invalid control_addr {}: {:?}",
+				config.control_addr(),
+				e
+			))
+		})?;
+
+		let (process, cookie_file) = if config.use_system_tor() {
+			info!("Using the system tor, control port at {}", control_addr);
+			(None, config.cookie_file.clone())
+		} else {
+			let cookie_file = tor_config::output_tor_control_config(
+				&config.send_config_dir,
+				&config.socks_proxy_addr,
+				&control_addr.to_string(),
+			)
+			.map_err(|e| Error::TorConfig(format!("{:?}", e)))?;
+			(Some(launch(&config.send_config_dir)?), Some(cookie_file))
+		};
+
+		let mut control = connect(control_addr, process.is_some())?;
+		control
+			.authenticate(cookie_file.as_ref().map(Path::new))
+			.map_err(tor_error)?;
+		match control.wait_for_bootstrap(Duration::from_secs(BOOTSTRAP_TIMEOUT_SECS)) {
+			Ok(_) => info!("Tor bootstrapped"),
+			Err(control::Error::Timeout) => warn!("Tor still bootstrapping, going on anyway"),
+			Err(e) => return Err(tor_error(e)),
+		}
+
+		let service_id = control.add_onion(&key, ports).map_err(tor_error)?;
+		info!(
+			"Onion service {}.onion published, ports {:?}",
+			service_id, ports
+		);
+
+		Ok(TorService {
+			_process: process,
+			control: Mutex::new(control),
+			service_id,
+		})
+	}
+
+	/// Our onion address, without ".onion".
+	pub fn onion_address(&self) -> &str {
+		&self.service_id
+	}
+
+	/// Our onion address on the provided port, as advertised to peers.
+	pub fn peer_addr(&self, port: u16) -> Result<PeerAddr, Error> {
+		format!("{}.onion:{}", self.service_id, port)
+			.parse()
+			.map_err(|e| Error::TorConfig(format!("{:?}", e)))
+	}
+
+	/// Bootstrap status and circuit health, if tor still answers.
+	pub fn stats(&self) -> Option<TorStats> {
+		let mut control = self.control.lock();
+		let res = control
+			.bootstrap_status()
+			.and_then(|bootstrap| control.circuit_health().map(|c| (bootstrap, c)));
+		match res {
+			Ok((bootstrap, circuits)) => Some(TorStats {
+				onion_address: self.service_id.clone(),
+				bootstrap,
+				circuits,
+			}),
+			Err(e) => {
+				debug!("Failed to get the tor status: {:?}", e);
+				None
+			}
+		}
+	}
+}
+
+/// Spawns tor, returning as soon as it started bootstrapping, the rest of
+/// the bootstrap is followed through the control port.
+fn launch(tor_dir: &str) -> Result<TorProcess, Error> {
+	let torrc_path = format!("{}/torrc", tor_dir);
+	let mut tor = TorProcess::new();
+	tor.torrc_path(&torrc_path)
+		.working_dir(tor_dir)
+		.timeout(20)
+		.completion_percent(0)
+		.launch()
+		.map_err(|e| Error::TorProcess(format!("{:?}", e)))?;
+
+	// keep consuming the tor logs, they would otherwise pile up in the pipe
+	if let Some(stdout) = tor.stdout.take() {
+		let _ = thread::Builder::new()
+			.name("tor_stdout".to_string())
+			.spawn(move || {
+				for line in stdout.lines().map_while(Result::ok) {
+					debug!("tor: {}", line);
+				}
+			});
+	}
+	Ok(tor)
+}
+
+/// Connects to the control port, giving a freshly spawned tor some time to
+/// open it.
+fn connect(addr: SocketAddr, spawned: bool) -> Result<TorControl, Error> {
+	let attempts = if spawned { CONTROL_CONNECT_ATTEMPTS } else { 1 };
+	let timeout = Duration::from_secs(CONTROL_TIMEOUT_SECS);
+	let mut attempt = 1;
+	loop {
+		match TorControl::connect(addr, timeout) {
+			Ok(control) => return Ok(control),
+			Err(e) if attempt >= attempts => return Err(tor_error(e)),
+			Err(_) => {
+				attempt += 1;
+				thread::sleep(Duration::from_millis(500));
+			}
+		}
+	}
+}
+
+fn tor_error(e: control::Error) -> Error {
+	Error::TorProcess(format!("tor control: {:?}", e))
+}
@@ -95,6 +95,8 @@ fn build_block(
 	key_id: Option<Identifier>,
 	wallet_listener_url: Option<String>,
 ) -> Result<(core::Block, BlockFees, PoWType), Error> {
+	// the difficulty of a custom network comes with the chain parameters
+	let _params = chain.params().enter();
 	let head = chain.head_header()?;
 	let seed = chain
 		.header_pmmr()
@@ -153,7 +155,7 @@ fn build_block(
 	let (output, kernel, block_fees) = get_coinbase(wallet_listener_url, block_fees, height)?;
 
 	let mut b = if is_foundation_height(height) {
-		let cb_data = load_foundation_output(height);
+		let cb_data = load_foundation_output(height).map_err(core::block::Error::from)?;
 		core::Block::from_coinbases(
 			&head,
 			txs,
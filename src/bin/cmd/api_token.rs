@@ -0,0 +1,80 @@
+// Copyright 2020 The Epic Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// API tokens commands processing
+use clap::ArgMatches;
+
+use crate::api;
+use crate::api::tokens::{ApiTokens, Scope};
+use crate::config::GlobalConfig;
+
+pub fn api_token_command(token_args: &ArgMatches, global_config: GlobalConfig) -> i32 {
+	let server_config = global_config.members.unwrap().server;
+	let path = match server_config.api_tokens_path {
+		Some(path) => path,
+		None => {
+			println!(
+				"No api_tokens_path set in the server configuration, API tokens are disabled."
+			);
+			return 1;
+		}
+	};
+
+	let res = ApiTokens::load(&path).and_then(|mut tokens| match token_args.subcommand() {
+		Some(("add", add_args)) => {
+			let name = add_args
+				.get_one::<String>("name")
+				.expect("name argument missing");
+			let scopes = add_args
+				.get_one::<String>("scopes")
+				.expect("scopes argument missing")
+				.split(',')
+				.map(|s| s.trim().parse::<Scope>())
+				.collect::<Result<Vec<_>, api::Error>>()?;
+			let token = tokens.add(name, scopes)?;
+			tokens.save(&path)?;
+			println!("API token {} created, it won't be shown again:", name);
+			println!("{}", token);
+			println!("Restart the node for the token to be accepted.");
+			Ok(())
+		}
+		Some(("list", _)) => {
+			for token in tokens.tokens() {
+				let scopes: Vec<String> = token.scopes.iter().map(|s| s.to_string()).collect();
+				println!("{}: {}", token.name, scopes.join(", "));
+			}
+			Ok(())
+		}
+		Some(("remove", remove_args)) => {
+			let name = remove_args
+				.get_one::<String>("name")
+				.expect("name argument missing");
+			if !tokens.remove(name) {
+				return Err(api::Error::Argument(format!("no token named {}", name)));
+			}
+			tokens.save(&path)?;
+			println!("API token {} removed, restart the node to revoke it.", name);
+			Ok(())
+		}
+		_ => panic!("No api_token command provided, use 'epic api_token --help' for details"),
+	});
+
+	match res {
+		Ok(_) => 0,
+		Err(e) => {
+			println!("API token error: {}", e);
+			1
+		}
+	}
+}
@@ -19,6 +19,7 @@ use clap::ArgMatches;
 
 use crate::api;
 use crate::config::GlobalConfig;
+use crate::core::core::amount_to_hr_string;
 use crate::p2p;
 use crate::servers::ServerConfig;
 use crate::util::file::get_first_line;
@@ -58,6 +59,19 @@ pub fn client_command(client_args: &ArgMatches, global_config: GlobalConfig) ->
 				panic!("Invalid peer address format");
 			}
 		}
+		Some(("rebuild_kernel_index", _)) => {
+			rebuild_kernel_index(&server_config, api_secret);
+		}
+		Some(("foundation_audit", audit_args)) => {
+			let height = |name: &str| {
+				audit_args.get_one::<String>(name).map(|h| {
+					h.parse::<u64>()
+						.unwrap_or_else(|e| panic!("Invalid {}: {}", name, e))
+				})
+			};
+			let (start_height, end_height) = (height("start_height"), height("end_height"));
+			return audit_foundation_levy(&server_config, start_height, end_height, api_secret);
+		}
 		_ => panic!("No client command provided, use 'epic client --help' for details"),
 	}
 	0
@@ -126,6 +140,92 @@ pub fn unban_peer(config: &ServerConfig, peer_addr: &SocketAddr, api_secret: Opt
 	e.reset().unwrap();
 }
 
+pub fn rebuild_kernel_index(config: &ServerConfig, api_secret: Option<String>) {
+	let params = "";
+	let mut e = term::stdout().unwrap();
+	let url = format!(
+		"http://{}/v1/chain/rebuild_kernel_index",
+		config.api_http_addr
+	);
+	match api::client::post_no_ret(url.as_str(), api_secret, &params) {
+		Ok(_) => writeln!(e, "Kernel index rebuilt").unwrap(),
+		Err(err) => writeln!(e, "Failed to rebuild the kernel index: {:?}", err).unwrap(),
+	};
+	e.reset().unwrap();
+}
+
+/// Prints the foundation levy audit of the node, returning 1 when a block
+/// doesn't pay its levy.
+pub fn audit_foundation_levy(
+	config: &ServerConfig,
+	start_height: Option<u64>,
+	end_height: Option<u64>,
+	api_secret: Option<String>,
+) -> i32 {
+	let mut e = term::stdout().unwrap();
+	let mut query = vec![];
+	if let Some(h) = start_height {
+		query.push(format!("start_height={}", h));
+	}
+	if let Some(h) = end_height {
+		query.push(format!("end_height={}", h));
+	}
+	let url = format!(
+		"http://{}/v1/chain/foundation_levy?{}",
+		config.api_http_addr,
+		query.join("&")
+	);
+	let audit = match api::client::get::<api::FoundationLevyAudit>(url.as_str(), api_secret) {
+		Ok(audit) => audit,
+		Err(err) => {
+			writeln!(e, "Failed to audit the foundation levy: {:?}", err).unwrap();
+			return 1;
+		}
+	};
+
+	for block in audit.blocks.iter().filter(|b| !b.status.is_paid()) {
+		writeln!(
+			e,
+			"Height {} ({}): {:?}, levy {}",
+			block.height,
+			block.hash,
+			block.status,
+			amount_to_hr_string(block.levy, false)
+		)
+		.unwrap();
+	}
+	let pruned = audit
+		.blocks
+		.iter()
+		.filter(|b| b.status == api::FoundationLevyStatus::KernelOnly)
+		.count();
+	writeln!(
+		e,
+		"Heights {} to {}: {} foundation blocks, {} with a discrepancy",
+		audit.start_height,
+		audit.end_height,
+		audit.blocks.len(),
+		audit.discrepancies
+	)
+	.unwrap();
+	if pruned > 0 {
+		writeln!(e, "{} pruned blocks only checked by their kernel", pruned).unwrap();
+	}
+	writeln!(
+		e,
+		"Levy paid: {} of {} scheduled",
+		amount_to_hr_string(audit.paid_levy, false),
+		amount_to_hr_string(audit.expected_levy, false)
+	)
+	.unwrap();
+	e.reset().unwrap();
+	if audit.discrepancies > 0 {
+		1
+	} else {
+		0
+	}
+}
+
 pub fn list_connected_peers(config: &ServerConfig, api_secret: Option<String>) {
 	let mut e = term::stdout().unwrap();
 	let url = format!("http://{}/v1/peers/connected", config.api_http_addr);
@@ -163,6 +163,179 @@ pub fn build_cli() -> Command {
                                 .help("Peer ip and port (e.g. 10.12.12.13:13414)")
                                 .required(true),
                         ),
+                )
+                .subcommand(
+                    Command::new("rebuild_kernel_index").about(
+                        "Build the kernel excess index, speeding up kernel lookups by excess",
+                    ),
+                )
+                .subcommand(
+                    Command::new("foundation_audit")
+                        .about("Check the foundation height blocks pay the scheduled foundation levy")
+                        .arg(
+                            Arg::new("start_height")
+                                .long("start-height")
+                                .help("First height to audit, 1 by default")
+                                .value_name("HEIGHT"),
+                        )
+                        .arg(
+                            Arg::new("end_height")
+                                .long("end-height")
+                                .help("Last height to audit, the chain head by default")
+                                .value_name("HEIGHT"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("pow")
+                .about("Proof of work tools, working without a chain database")
+                .subcommand(
+                    Command::new("verify")
+                        .about("Verify the proof of work of a serialized block header and report the difficulty it achieves")
+                        .arg(
+                            Arg::new("header")
+                                .long("header")
+                                .help("Serialized block header, as hex")
+                                .required(true)
+                                .value_name("HEX"),
+                        )
+                        .arg(
+                            Arg::new("seed")
+                                .long("seed")
+                                .help("RandomX seed, the hash of the block at the seed height of the header, as hex")
+                                .value_name("HEX"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("network")
+                .about("Manage custom long lived test networks, run by UserTesting nodes")
+                .subcommand(
+                    Command::new("new")
+                        .about("Mine a genesis block and write the definition of a new network, loaded by nodes through network_file")
+                        .arg(
+                            Arg::new("name")
+                                .short('n')
+                                .long("name")
+                                .help("Name of the network")
+                                .required(true)
+                                .value_name("NAME"),
+                        )
+                        .arg(
+                            Arg::new("output")
+                                .short('o')
+                                .long("output")
+                                .help("File to write the network definition to, must not exist")
+                                .required(true)
+                                .value_name("FILE"),
+                        )
+                        .arg(
+                            Arg::new("magic")
+                                .long("magic")
+                                .help("Magic bytes of the p2p messages as 4 hex digits, random by default")
+                                .value_name("HEX"),
+                        )
+                        .arg(
+                            Arg::new("p2p_port")
+                                .long("p2p-port")
+                                .help("Port the nodes listen to peers on, 23414 by default")
+                                .value_name("PORT"),
+                        )
+                        .arg(
+                            Arg::new("api_port")
+                                .long("api-port")
+                                .help("Port of the node API, 23413 by default")
+                                .value_name("PORT"),
+                        )
+                        .arg(
+                            Arg::new("initial_difficulty")
+                                .long("initial-difficulty")
+                                .help("Difficulty of the genesis block, the first blocks after it starting from it")
+                                .value_name("DIFFICULTY"),
+                        )
+                        .arg(
+                            Arg::new("foundation")
+                                .long("foundation")
+                                .help("Foundation file of the network, the floonet one by default")
+                                .value_name("FILE"),
+                        )
+                        .arg(
+                            Arg::new("policy")
+                                .long("policy")
+                                .help("Proportions of the PoW algorithms: default, no_progpow or only_randomx")
+                                .value_name("POLICY"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("identity")
+                .about("Manage the node identity our onion address is derived from, encrypted with the EPIC_IDENTITY_PASSWORD environment variable")
+                .subcommand(
+                    Command::new("show")
+                        .about("Show our onion address and public key"),
+                )
+                .subcommand(
+                    Command::new("rotate")
+                        .about("Replace the node identity with a new one, changing our onion address"),
+                )
+                .subcommand(
+                    Command::new("backup")
+                        .about("Back up the encrypted node identity")
+                        .arg(
+                            Arg::new("dest")
+                                .short('d')
+                                .long("dest")
+                                .help("File to write the backup to, must not exist")
+                                .required(true)
+                                .value_name("DEST"),
+                        )
+                        .arg(
+                            Arg::new("onion_service_dir")
+                                .short('o')
+                                .long("onion_service_dir")
+                                .help("Also write the onion service keys, as tor expects them in a HiddenServiceDir, under this directory")
+                                .value_name("DIR"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("api_token")
+                .about("Manage the scoped API tokens stored under api_tokens_path")
+                .subcommand(
+                    Command::new("add")
+                        .about("Create a new API token, shown once")
+                        .arg(
+                            Arg::new("name")
+                                .short('n')
+                                .long("name")
+                                .help("Name of the token, as shown in the audit log")
+                                .required(true)
+                                .value_name("NAME"),
+                        )
+                        .arg(
+                            Arg::new("scopes")
+                                .short('s')
+                                .long("scopes")
+                                .help("Comma separated scopes among read_chain, push_tx, mining, peer_admin and chain_admin")
+                                .required(true)
+                                .value_name("SCOPES"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("list")
+                        .about("List the API tokens and their scopes"),
+                )
+                .subcommand(
+                    Command::new("remove")
+                        .about("Remove an API token")
+                        .arg(
+                            Arg::new("name")
+                                .short('n')
+                                .long("name")
+                                .help("Name of the token to remove")
+                                .required(true)
+                                .value_name("NAME"),
+                        ),
                 ),
         )
 }
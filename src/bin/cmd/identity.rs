@@ -0,0 +1,76 @@
+// Copyright 2020 The Epic Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// Node identity commands processing
+use std::path::Path;
+
+use clap::ArgMatches;
+
+use crate::config::GlobalConfig;
+use crate::p2p::identity::{self, NodeIdentity};
+use crate::p2p::tor::config::output_onion_service_config;
+use crate::util::to_hex;
+
+pub fn identity_command(identity_args: &ArgMatches, global_config: GlobalConfig) -> i32 {
+	let server_config = global_config.members.unwrap().server;
+	let db_root = server_config.db_root;
+	let password = identity::password_from_env();
+
+	let res = match identity_args.subcommand() {
+		Some(("show", _)) => {
+			NodeIdentity::load(&identity::identity_path(&db_root), &password).map(|id| show(&id))
+		}
+		Some(("rotate", _)) => NodeIdentity::rotate(&db_root, &password).map(|id| {
+			println!("Node identity rotated, the previous one is kept next to it.");
+			println!("Restart the node to publish the new onion address.");
+			show(&id);
+		}),
+		Some(("backup", backup_args)) => {
+			let dest = backup_args
+				.get_one::<String>("dest")
+				.expect("dest argument missing");
+			NodeIdentity::backup(&db_root, Path::new(dest)).and_then(|_| {
+				println!("Encrypted node identity backed up to {}", dest);
+				match backup_args.get_one::<String>("onion_service_dir") {
+					Some(dir) => {
+						let id = NodeIdentity::load(&identity::identity_path(&db_root), &password)?;
+						output_onion_service_config(dir, id.secret_key())?;
+						println!("Onion service keys written under {}", dir);
+						Ok(())
+					}
+					None => Ok(()),
+				}
+			})
+		}
+		_ => panic!("No identity command provided, use 'epic identity --help' for details"),
+	};
+
+	match res {
+		Ok(_) => 0,
+		Err(e) => {
+			println!("Node identity error: {:?}", e);
+			1
+		}
+	}
+}
+
+fn show(id: &NodeIdentity) {
+	match (id.onion_address(), id.onion_keypair()) {
+		(Ok(address), Ok((_, pub_key))) => {
+			println!("Onion address: {}.onion", address);
+			println!("Public key: {}", to_hex(pub_key.to_bytes().to_vec()));
+		}
+		(Err(e), _) | (_, Err(e)) => println!("Invalid node identity: {:?}", e),
+	}
+}
@@ -12,12 +12,20 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod api_token;
 mod client;
 mod config;
 pub mod epic_args;
+mod identity;
+mod network;
+mod pow;
 mod server;
+pub use self::api_token::api_token_command;
 pub use self::client::client_command;
 pub use self::config::config_command_server;
+pub use self::identity::identity_command;
+pub use self::network::network_command;
+pub use self::pow::pow_command;
 pub use self::server::server_command;
 
 pub mod built_info {
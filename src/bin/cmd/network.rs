@@ -0,0 +1,114 @@
+// Copyright 2020 The Epic Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// Custom network commands processing
+use std::path::Path;
+use std::sync::Arc;
+
+use clap::ArgMatches;
+
+use crate::core::core::feijoada::PolicyConfig;
+use crate::core::global::{self, ChainParams, ChainTypes};
+use crate::core::network::NetworkDefinition;
+use crate::core::pow::{self, Difficulty};
+use crate::util::from_hex;
+
+pub fn network_command(network_args: &ArgMatches) -> i32 {
+	let res = match network_args.subcommand() {
+		Some(("new", new_args)) => new_network(new_args),
+		_ => panic!("No network command provided, use 'epic network --help' for details"),
+	};
+
+	match res {
+		Ok(_) => 0,
+		Err(e) => {
+			println!("Network error: {}", e);
+			1
+		}
+	}
+}
+
+fn new_network(new_args: &ArgMatches) -> Result<(), String> {
+	let name = new_args
+		.get_one::<String>("name")
+		.expect("name argument missing");
+	let output = Path::new(
+		new_args
+			.get_one::<String>("output")
+			.expect("output argument missing"),
+	);
+	if output.exists() {
+		return Err(format!("{} already exists", output.display()));
+	}
+
+	// custom networks run with the usertesting parameters
+	let params = Arc::new(ChainParams::new(ChainTypes::UserTesting));
+	let _params = params.enter();
+
+	let difficulty = match new_args.get_one::<String>("initial_difficulty") {
+		Some(difficulty) => difficulty
+			.parse()
+			.map_err(|e| format!("Invalid initial difficulty: {}", e))?,
+		None => global::TESTING_INITIAL_DIFFICULTY,
+	};
+
+	println!("Mining the genesis block of {}...", name);
+	let genesis =
+		pow::mine_genesis_block_at(Difficulty::from_num(difficulty)).map_err(|e| e.to_string())?;
+	let mut network = NetworkDefinition::new(name.clone(), &genesis).map_err(|e| e.to_string())?;
+
+	if let Some(magic) = new_args.get_one::<String>("magic") {
+		let bytes = from_hex(magic.clone()).map_err(|e| format!("Invalid magic: {}", e))?;
+		if bytes.len() != 2 {
+			return Err(format!("Magic must be 2 bytes, got {}", bytes.len()));
+		}
+		network.magic = [bytes[0], bytes[1]];
+	}
+	if let Some(port) = new_args.get_one::<String>("p2p_port") {
+		network.p2p_port = port
+			.parse()
+			.map_err(|e| format!("Invalid p2p port: {}", e))?;
+	}
+	if let Some(port) = new_args.get_one::<String>("api_port") {
+		network.api_port = port
+			.parse()
+			.map_err(|e| format!("Invalid api port: {}", e))?;
+	}
+	if let Some(foundation) = new_args.get_one::<String>("foundation") {
+		if !Path::new(foundation).exists() {
+			return Err(format!("Foundation file {} not found", foundation));
+		}
+		network.foundation_sha256 = global::get_file_sha256(foundation);
+	}
+	network.policy_config = match new_args.get_one::<String>("policy").map(|p| p.as_str()) {
+		None | Some("default") => PolicyConfig::default(),
+		Some("no_progpow") => PolicyConfig::no_progpow(),
+		Some("only_randomx") => PolicyConfig::only_randomx(),
+		Some(policy) => return Err(format!("Unknown policy {}", policy)),
+	};
+	global::fork_schedule()
+		.validate(&ChainTypes::UserTesting, &network.policy_config)
+		.map_err(|e| format!("Invalid policy: {}", e))?;
+	network.validate().map_err(|e| e.to_string())?;
+	network.save(output).map_err(|e| e.to_string())?;
+
+	println!("Network {} written to {}", network.name, output.display());
+	println!("Genesis: {}", network.genesis_hash);
+	println!(
+		"Magic bytes: {:02x}{:02x}, p2p port: {}, api port: {}",
+		network.magic[0], network.magic[1], network.p2p_port, network.api_port
+	);
+	println!("Run nodes with chain_type = \"UserTesting\" and network_file pointing to it.");
+	Ok(())
+}
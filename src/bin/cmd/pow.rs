@@ -0,0 +1,74 @@
+// Copyright 2020 The Epic Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// Proof of work commands processing
+use clap::ArgMatches;
+
+use crate::core::core::hash::Hashed;
+use crate::core::global::ChainTypes;
+use crate::core::pow;
+use crate::util::from_hex;
+
+pub fn pow_command(pow_args: &ArgMatches, chain_type: &ChainTypes) -> i32 {
+	match pow_args.subcommand() {
+		Some(("verify", verify_args)) => verify(verify_args, chain_type),
+		_ => panic!("No pow command provided, use 'epic pow --help' for details"),
+	}
+}
+
+fn verify(verify_args: &ArgMatches, chain_type: &ChainTypes) -> i32 {
+	let header = verify_args
+		.get_one::<String>("header")
+		.expect("header argument missing");
+	let header = match from_hex(header.trim().to_string()) {
+		Ok(header) => header,
+		Err(e) => {
+			println!("Invalid header hex: {}", e);
+			return 1;
+		}
+	};
+	let seed = match verify_args.get_one::<String>("seed").map(|s| parse_seed(s)) {
+		Some(Ok(seed)) => Some(seed),
+		Some(Err(e)) => {
+			println!("Invalid seed: {}", e);
+			return 1;
+		}
+		None => None,
+	};
+
+	match pow::verify_header(chain_type.clone(), &header, |_| seed) {
+		Ok(verified) => {
+			println!("Proof of work valid");
+			println!("Height: {}", verified.header.height);
+			println!("Hash: {}", verified.header.hash());
+			println!("Algorithm: {:?}", verified.pow_type);
+			println!("Difficulty: {}", verified.difficulty);
+			0
+		}
+		Err(e) => {
+			println!("Proof of work invalid: {}", e);
+			1
+		}
+	}
+}
+
+fn parse_seed(hex: &str) -> Result<[u8; 32], String> {
+	let bytes = from_hex(hex.trim().to_string()).map_err(|e| e.to_string())?;
+	if bytes.len() != 32 {
+		return Err(format!("expected 32 bytes, got {}", bytes.len()));
+	}
+	let mut seed = [0; 32];
+	seed.copy_from_slice(&bytes);
+	Ok(seed)
+}
@@ -163,7 +163,6 @@ pub fn server_command(
         if let Some(seeds) = a.get_many::<String>("seed") {
             let seed_addrs: Vec<PeerAddr> = seeds
                 .filter_map(|x| x.parse().ok())
-                .map(PeerAddr)
                 .collect();
             server_config.p2p_config.seeding_type = Seeding::List;
             server_config.p2p_config.seeds = Some(seed_addrs);
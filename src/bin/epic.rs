@@ -129,6 +129,16 @@ fn real_main() -> i32 {
 		return 0;
 	}
 
+	// Proof of work verification needs neither a config nor a chain
+	if let Some(("pow", pow_args)) = args.subcommand() {
+		return cmd::pow_command(pow_args, &chain_type);
+	}
+
+	// New networks are created before any node config exists
+	if let Some(("network", network_args)) = args.subcommand() {
+		return cmd::network_command(network_args);
+	}
+
 	// Temporary wallet warning message
 	match args.subcommand() {
 		Some(("wallet", _)) => {
@@ -226,6 +236,14 @@ fn real_main() -> i32 {
 		// client commands and options
 		Some(("client", client_args)) => cmd::client_command(client_args, node_config.unwrap()),
 
+		// node identity commands
+		Some(("identity", identity_args)) => {
+			cmd::identity_command(identity_args, node_config.unwrap())
+		}
+
+		// api token commands
+		Some(("api_token", token_args)) => cmd::api_token_command(token_args, node_config.unwrap()),
+
 		// clean command
 		Some(("clean", _)) => {
 			let db_root_path = node_config.unwrap().members.unwrap().server.db_root;
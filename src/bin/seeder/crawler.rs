@@ -0,0 +1,238 @@
+// Copyright 2020 The Epic Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Crawls the network, keeping track of how reliably each node answers.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::p2p::crawl;
+use crate::p2p::handshake::Handshake;
+use crate::p2p::{Capabilities, PeerAddr};
+use crate::util::{RwLock, StopState};
+
+/// Max number of nodes we keep track of, so a flood of bogus addresses
+/// can't exhaust our memory.
+const MAX_NODES: usize = 50_000;
+
+/// How much the last connection attempt weighs in the reliability score.
+const RELIABILITY_WEIGHT: f64 = 0.2;
+
+/// Minimum reliability score of the nodes we hand out.
+const MIN_RELIABILITY: f64 = 0.5;
+
+/// Nodes we haven't been able to connect to for that long aren't handed out.
+const MAX_SUCCESS_AGE_SECS: i64 = 2 * 3600;
+
+/// What we know about a node.
+#[derive(Debug, Clone)]
+pub struct NodeStats {
+	pub capabilities: Capabilities,
+	pub attempts: u32,
+	pub last_try: i64,
+	pub last_success: i64,
+	/// Exponential moving average of the connection attempt outcomes.
+	pub reliability: f64,
+}
+
+impl NodeStats {
+	fn new() -> NodeStats {
+		NodeStats {
+			capabilities: Capabilities::UNKNOWN,
+			attempts: 0,
+			last_try: 0,
+			last_success: 0,
+			reliability: 0.0,
+		}
+	}
+
+	/// A node we can send our DNS clients to.
+	pub fn is_good(&self, now: i64) -> bool {
+		self.reliability >= MIN_RELIABILITY
+			&& now - self.last_success <= MAX_SUCCESS_AGE_SECS
+			&& self.capabilities.contains(Capabilities::FULL_NODE)
+	}
+
+	/// Whether it's time to try this node again, good nodes are checked more
+	/// often than the unreachable ones.
+	fn is_due(&self, now: i64) -> bool {
+		let interval = if self.attempts == 0 {
+			0
+		} else if self.reliability >= MIN_RELIABILITY {
+			10 * 60
+		} else if self.attempts < 10 || self.reliability >= 0.1 {
+			60 * 60
+		} else {
+			24 * 60 * 60
+		};
+		now - self.last_try >= interval
+	}
+
+	fn record(&mut self, success: bool, now: i64) {
+		let outcome = if success { 1.0 } else { 0.0 };
+		self.reliability = if self.attempts == 0 {
+			outcome
+		} else {
+			self.reliability * (1.0 - RELIABILITY_WEIGHT) + outcome * RELIABILITY_WEIGHT
+		};
+		self.attempts += 1;
+		self.last_try = now;
+		if success {
+			self.last_success = now;
+		}
+	}
+}
+
+/// Crawls the network from a set of seed addresses, following the addresses
+/// each node sends back.
+pub struct Crawler {
+	handshake: Handshake,
+	default_port: u16,
+	threads: usize,
+	timeout: Duration,
+	nodes: RwLock<HashMap<PeerAddr, NodeStats>>,
+}
+
+impl Crawler {
+	pub fn new(
+		handshake: Handshake,
+		default_port: u16,
+		threads: usize,
+		timeout: Duration,
+		seeds: Vec<PeerAddr>,
+	) -> Crawler {
+		let crawler = Crawler {
+			handshake,
+			default_port,
+			threads,
+			timeout,
+			nodes: RwLock::new(HashMap::new()),
+		};
+		crawler.add_addrs(seeds);
+		crawler
+	}
+
+	fn add_addrs(&self, addrs: Vec<PeerAddr>) {
+		let mut nodes = self.nodes.write();
+		for addr in addrs {
+			if nodes.len() >= MAX_NODES {
+				break;
+			}
+			if addr.is_routable() && !addr.is_onion() {
+				nodes.entry(addr).or_insert_with(NodeStats::new);
+			}
+		}
+	}
+
+	/// Keeps crawling until stopped.
+	pub fn run(self: Arc<Self>, stop_state: Arc<StopState>) {
+		while !stop_state.is_stopped() {
+			let now = Utc::now().timestamp();
+			let due: Vec<PeerAddr> = self
+				.nodes
+				.read()
+				.iter()
+				.filter(|(_, stats)| stats.is_due(now))
+				.map(|(addr, _)| *addr)
+				.collect();
+
+			if due.is_empty() {
+				thread::sleep(Duration::from_secs(1));
+				continue;
+			}
+
+			debug!("Crawling {} nodes", due.len());
+			let chunk_size = (due.len() + self.threads - 1) / self.threads;
+			let handles: Vec<_> = due
+				.chunks(chunk_size)
+				.map(|chunk| {
+					let crawler = self.clone();
+					let chunk = chunk.to_vec();
+					let stop_state = stop_state.clone();
+					thread::Builder::new()
+						.name("crawler".to_string())
+						.spawn(move || {
+							for addr in chunk {
+								if stop_state.is_stopped() {
+									break;
+								}
+								crawler.crawl(addr);
+							}
+						})
+						.expect("failed to launch crawler thread")
+				})
+				.collect();
+			for h in handles {
+				let _ = h.join();
+			}
+
+			let (good, total) = self.counts();
+			info!("Crawled {} nodes, {}/{} good", due.len(), good, total);
+		}
+	}
+
+	fn crawl(&self, addr: PeerAddr) {
+		let res = crawl::probe(&self.handshake, addr, Capabilities::PEER_LIST, self.timeout);
+		let now = Utc::now().timestamp();
+		let peer_addrs = {
+			let mut nodes = self.nodes.write();
+			let stats = nodes.entry(addr).or_insert_with(NodeStats::new);
+			match res {
+				Ok(probe) => {
+					trace!(
+						"Crawled {}: {:?}, {}, {} addrs",
+						addr,
+						probe.info.capabilities,
+						probe.info.user_agent,
+						probe.peer_addrs.len()
+					);
+					stats.capabilities = probe.info.capabilities;
+					stats.record(true, now);
+					probe.peer_addrs
+				}
+				Err(e) => {
+					trace!("Failed to crawl {}: {:?}", addr, e);
+					stats.record(false, now);
+					vec![]
+				}
+			}
+		};
+		self.add_addrs(peer_addrs);
+	}
+
+	/// Number of good nodes and total number of nodes we know about.
+	pub fn counts(&self) -> (usize, usize) {
+		let now = Utc::now().timestamp();
+		let nodes = self.nodes.read();
+		let good = nodes.values().filter(|s| s.is_good(now)).count();
+		(good, nodes.len())
+	}
+
+	/// The addresses of the good nodes listening on the default port, as DNS
+	/// answers can't carry a port.
+	pub fn good_ips(&self) -> Vec<IpAddr> {
+		let now = Utc::now().timestamp();
+		self.nodes
+			.read()
+			.iter()
+			.filter(|(addr, stats)| addr.port() == self.default_port && stats.is_good(now))
+			.filter_map(|(addr, _)| addr.ip())
+			.collect()
+	}
+}
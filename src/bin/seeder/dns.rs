@@ -0,0 +1,256 @@
+// Copyright 2020 The Epic Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Minimal authoritative DNS server, answering A and AAAA queries for our
+//! seed hostname with the addresses of good nodes.
+
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rand::seq::SliceRandom;
+
+use crate::crawler::Crawler;
+use crate::util::StopState;
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+
+const RCODE_FORMERR: u8 = 1;
+const RCODE_NOTIMP: u8 = 4;
+const RCODE_REFUSED: u8 = 5;
+
+/// Answers are kept under the 512 bytes of a plain UDP DNS message.
+const MAX_A_ANSWERS: usize = 20;
+const MAX_AAAA_ANSWERS: usize = 12;
+
+/// TTL of our answers, short so resolvers come back for fresh nodes.
+const TTL_SECS: u32 = 60;
+
+/// A parsed DNS question.
+#[derive(Debug, PartialEq)]
+struct Question {
+	id: u16,
+	recursion_desired: bool,
+	name: String,
+	qtype: u16,
+	qclass: u16,
+	/// Length of the question section, starting at offset 12.
+	len: usize,
+}
+
+fn parse_query(buf: &[u8]) -> Result<Question, u8> {
+	if buf.len() < 12 {
+		return Err(RCODE_FORMERR);
+	}
+	let id = u16::from_be_bytes([buf[0], buf[1]]);
+	let is_response = buf[2] & 0x80 != 0;
+	let opcode = (buf[2] >> 3) & 0x0f;
+	let qdcount = u16::from_be_bytes([buf[4], buf[5]]);
+	if is_response || qdcount != 1 {
+		return Err(RCODE_FORMERR);
+	}
+	if opcode != 0 {
+		return Err(RCODE_NOTIMP);
+	}
+
+	let mut labels = vec![];
+	let mut pos = 12;
+	loop {
+		let len = *buf.get(pos).ok_or(RCODE_FORMERR)? as usize;
+		pos += 1;
+		if len == 0 {
+			break;
+		}
+		// no compression in questions, labels are at most 63 bytes
+		if len > 63 || pos + len > buf.len() {
+			return Err(RCODE_FORMERR);
+		}
+		labels.push(String::from_utf8_lossy(&buf[pos..pos + len]).to_lowercase());
+		pos += len;
+	}
+	if pos + 4 > buf.len() {
+		return Err(RCODE_FORMERR);
+	}
+	let qtype = u16::from_be_bytes([buf[pos], buf[pos + 1]]);
+	let qclass = u16::from_be_bytes([buf[pos + 2], buf[pos + 3]]);
+
+	Ok(Question {
+		id,
+		recursion_desired: buf[2] & 0x01 != 0,
+		name: labels.join("."),
+		qtype,
+		qclass,
+		len: pos + 4 - 12,
+	})
+}
+
+/// Builds the response to a query, given the addresses to pick answers from.
+fn build_response(query: &[u8], host: &str, ips: &[IpAddr]) -> Vec<u8> {
+	let q = match parse_query(query) {
+		Ok(q) => q,
+		Err(rcode) => return error_response(query, rcode),
+	};
+	if q.name != host || (q.qclass != CLASS_IN && q.qclass != TYPE_ANY) {
+		return error_response(query, RCODE_REFUSED);
+	}
+
+	let want_v4 = q.qtype == TYPE_A || q.qtype == TYPE_ANY;
+	let want_v6 = q.qtype == TYPE_AAAA || q.qtype == TYPE_ANY;
+	let mut v4: Vec<_> = ips.iter().filter(|ip| ip.is_ipv4() && want_v4).collect();
+	let mut v6: Vec<_> = ips.iter().filter(|ip| ip.is_ipv6() && want_v6).collect();
+	let mut rng = rand::rng();
+	v4.shuffle(&mut rng);
+	v6.shuffle(&mut rng);
+	let answers: Vec<&IpAddr> = if want_v4 && want_v6 {
+		v4.into_iter()
+			.take(MAX_A_ANSWERS / 2)
+			.chain(v6.into_iter().take(MAX_AAAA_ANSWERS / 2))
+			.collect()
+	} else {
+		v4.into_iter()
+			.take(MAX_A_ANSWERS)
+			.chain(v6.into_iter().take(MAX_AAAA_ANSWERS))
+			.collect()
+	};
+
+	let mut resp = Vec::with_capacity(512);
+	resp.extend_from_slice(&q.id.to_be_bytes());
+	// QR and AA set, echo RD, NOERROR
+	resp.push(0x84 | q.recursion_desired as u8);
+	resp.push(0x00);
+	resp.extend_from_slice(&1u16.to_be_bytes());
+	resp.extend_from_slice(&(answers.len() as u16).to_be_bytes());
+	resp.extend_from_slice(&0u16.to_be_bytes());
+	resp.extend_from_slice(&0u16.to_be_bytes());
+	resp.extend_from_slice(&query[12..12 + q.len]);
+
+	for ip in answers {
+		// pointer to the name in the question
+		resp.extend_from_slice(&[0xc0, 0x0c]);
+		let (rtype, data) = match ip {
+			IpAddr::V4(ip) => (TYPE_A, ip.octets().to_vec()),
+			IpAddr::V6(ip) => (TYPE_AAAA, ip.octets().to_vec()),
+		};
+		resp.extend_from_slice(&rtype.to_be_bytes());
+		resp.extend_from_slice(&CLASS_IN.to_be_bytes());
+		resp.extend_from_slice(&TTL_SECS.to_be_bytes());
+		resp.extend_from_slice(&(data.len() as u16).to_be_bytes());
+		resp.extend_from_slice(&data);
+	}
+	resp
+}
+
+fn error_response(query: &[u8], rcode: u8) -> Vec<u8> {
+	let mut resp = vec![0u8; 12];
+	if query.len() >= 2 {
+		resp[0] = query[0];
+		resp[1] = query[1];
+	}
+	resp[2] = 0x80;
+	resp[3] = rcode;
+	resp
+}
+
+/// Serves DNS queries for `host` until stopped.
+pub fn serve(
+	addr: SocketAddr,
+	host: String,
+	crawler: Arc<Crawler>,
+	stop_state: Arc<StopState>,
+) -> std::io::Result<()> {
+	let socket = UdpSocket::bind(addr)?;
+	socket.set_read_timeout(Some(Duration::from_secs(1)))?;
+	let host = host.trim_end_matches('.').to_lowercase();
+	info!("Serving DNS seed {} on {}", host, addr);
+
+	let mut buf = [0u8; 512];
+	while !stop_state.is_stopped() {
+		let (len, src) = match socket.recv_from(&mut buf) {
+			Ok(res) => res,
+			Err(ref e)
+				if e.kind() == std::io::ErrorKind::WouldBlock
+					|| e.kind() == std::io::ErrorKind::TimedOut =>
+			{
+				continue
+			}
+			Err(e) => {
+				warn!("DNS receive error: {:?}", e);
+				continue;
+			}
+		};
+		let resp = build_response(&buf[..len], &host, &crawler.good_ips());
+		if let Err(e) = socket.send_to(&resp, src) {
+			debug!("Failed to answer DNS query from {}: {:?}", src, e);
+		}
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn query(name: &str, qtype: u16) -> Vec<u8> {
+		let mut q = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+		for label in name.split('.') {
+			q.push(label.len() as u8);
+			q.extend_from_slice(label.as_bytes());
+		}
+		q.push(0);
+		q.extend_from_slice(&qtype.to_be_bytes());
+		q.extend_from_slice(&CLASS_IN.to_be_bytes());
+		q
+	}
+
+	#[test]
+	fn test_dns_answers() {
+		let ips: Vec<IpAddr> = vec![
+			"1.2.3.4".parse().unwrap(),
+			"5.6.7.8".parse().unwrap(),
+			"2001:db8::1".parse().unwrap(),
+		];
+
+		let q = query("Seed.Example.com", TYPE_A);
+		let parsed = parse_query(&q).unwrap();
+		assert_eq!(parsed.name, "seed.example.com");
+		assert_eq!(parsed.qtype, TYPE_A);
+
+		let resp = build_response(&q, "seed.example.com", &ips);
+		assert_eq!(&resp[0..2], &[0x12, 0x34]);
+		assert_eq!(resp[3] & 0x0f, 0);
+		// two A records
+		assert_eq!(u16::from_be_bytes([resp[6], resp[7]]), 2);
+		assert_eq!(resp.len(), q.len() + 2 * 16);
+
+		let resp = build_response(
+			&query("seed.example.com", TYPE_AAAA),
+			"seed.example.com",
+			&ips,
+		);
+		assert_eq!(u16::from_be_bytes([resp[6], resp[7]]), 1);
+
+		let resp = build_response(
+			&query("other.example.com", TYPE_A),
+			"seed.example.com",
+			&ips,
+		);
+		assert_eq!(resp[3] & 0x0f, RCODE_REFUSED);
+
+		let resp = build_response(&q[..14], "seed.example.com", &ips);
+		assert_eq!(resp[3] & 0x0f, RCODE_FORMERR);
+	}
+}
@@ -0,0 +1,205 @@
+// Copyright 2020 The Epic Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! DNS seeder for the Epic network. Crawls the network and answers DNS
+//! queries with the addresses of reliable full nodes, so nodes can use it
+//! as one of their `dns_seeds`.
+
+#[macro_use]
+extern crate log;
+use epic_core as core;
+use epic_p2p as p2p;
+use epic_util as util;
+
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use clap::{Arg, Command};
+
+use crate::core::core::hash::Hashed;
+use crate::core::{genesis, global};
+use crate::crawler::Crawler;
+use crate::p2p::handshake::Handshake;
+use crate::p2p::{P2PConfig, PeerAddr};
+use crate::util::{init_logger, LoggingConfig, StopState};
+
+mod crawler;
+mod dns;
+
+fn build_cli() -> Command {
+	Command::new("epic-seeder")
+		.about("Crawls the Epic network and serves healthy full nodes over DNS.")
+		.author("The Epic Team")
+		.arg(
+			Arg::new("floonet")
+				.long("floonet")
+				.help("Crawl the Floonet (as opposed to mainnet)")
+				.action(clap::ArgAction::SetTrue),
+		)
+		.arg(
+			Arg::new("host")
+				.long("host")
+				.help("The hostname this seeder is authoritative for, e.g. seed.example.com")
+				.required(true)
+				.value_name("HOST"),
+		)
+		.arg(
+			Arg::new("listen")
+				.long("listen")
+				.help("The address to serve DNS queries on")
+				.default_value("0.0.0.0:53")
+				.value_name("ADDR"),
+		)
+		.arg(
+			Arg::new("seed")
+				.long("seed")
+				.help("A node address to start crawling from, the DNS seeds of the chain are used otherwise")
+				.action(clap::ArgAction::Append)
+				.value_name("PEER_ADDR"),
+		)
+		.arg(
+			Arg::new("threads")
+				.long("threads")
+				.help("Number of nodes crawled in parallel")
+				.default_value("16")
+				.value_name("COUNT"),
+		)
+		.arg(
+			Arg::new("timeout")
+				.long("timeout")
+				.help("Connection timeout in seconds")
+				.default_value("10")
+				.value_name("SECS"),
+		)
+}
+
+fn main() {
+	let exit_code = real_main();
+	std::process::exit(exit_code);
+}
+
+fn real_main() -> i32 {
+	let args = build_cli().get_matches();
+
+	init_logger(
+		Some(LoggingConfig {
+			log_to_file: false,
+			..LoggingConfig::default()
+		}),
+		None,
+	);
+
+	let chain_type = if args.get_flag("floonet") {
+		global::ChainTypes::Floonet
+	} else {
+		global::ChainTypes::Mainnet
+	};
+	global::set_mining_mode(chain_type.clone());
+	let (genesis, default_port) = match chain_type {
+		global::ChainTypes::Floonet => (genesis::genesis_floo(), 13414),
+		_ => (genesis::genesis_main(), 3414),
+	};
+
+	let listen: SocketAddr = match args.get_one::<String>("listen").unwrap().parse() {
+		Ok(addr) => addr,
+		Err(e) => {
+			error!("Invalid listen address: {}", e);
+			return 1;
+		}
+	};
+	let threads: usize = args
+		.get_one::<String>("threads")
+		.unwrap()
+		.parse()
+		.unwrap_or(16)
+		.max(1);
+	let timeout: u64 = args
+		.get_one::<String>("timeout")
+		.unwrap()
+		.parse()
+		.unwrap_or(10);
+
+	let seeds = match args.get_many::<String>("seed") {
+		Some(seeds) => seeds
+			.filter_map(|s| match s.parse::<PeerAddr>() {
+				Ok(addr) => Some(addr),
+				Err(e) => {
+					warn!("Ignoring invalid seed address {}: {:?}", s, e);
+					None
+				}
+			})
+			.collect(),
+		None => resolve_dns_seeds(P2PConfig::default().dns_seeds(), default_port),
+	};
+	if seeds.is_empty() {
+		error!("No node to start crawling from, provide some with --seed.");
+		return 1;
+	}
+
+	let magic = p2p::msg::magic(&global::ChainParams::new(chain_type));
+	let handshake = Handshake::new(genesis.hash(), P2PConfig::default(), magic);
+	let crawler = Arc::new(Crawler::new(
+		handshake,
+		default_port,
+		threads,
+		Duration::from_secs(timeout),
+		seeds,
+	));
+
+	let stop_state = Arc::new(StopState::new());
+	{
+		let stop_state = stop_state.clone();
+		ctrlc::set_handler(move || stop_state.stop()).expect("Error setting Ctrl-C handler");
+	}
+
+	let crawler_thread = {
+		let crawler = crawler.clone();
+		let stop_state = stop_state.clone();
+		thread::Builder::new()
+			.name("crawler".to_string())
+			.spawn(move || crawler.run(stop_state))
+			.expect("failed to launch crawler thread")
+	};
+
+	let host = args.get_one::<String>("host").unwrap().clone();
+	let res = dns::serve(listen, host, crawler, stop_state.clone());
+	stop_state.stop();
+	let _ = crawler_thread.join();
+
+	match res {
+		Ok(_) => 0,
+		Err(e) => {
+			error!("DNS server failed: {:?}", e);
+			1
+		}
+	}
+}
+
+fn resolve_dns_seeds(dns_seeds: Vec<String>, default_port: u16) -> Vec<PeerAddr> {
+	let mut addrs = vec![];
+	for seed in dns_seeds {
+		let addr_str = if seed.contains(':') {
+			seed.clone()
+		} else {
+			format!("{}:{}", seed, default_port)
+		};
+		match addr_str.to_socket_addrs() {
+			Ok(resolved) => addrs.extend(resolved.map(PeerAddr::Ip)),
+			Err(e) => warn!("Failed to resolve seed {}: {:?}", seed, e),
+		}
+	}
+	addrs
+}
@@ -0,0 +1,218 @@
+// Copyright 2020 The Epic Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! In memory chain of simulated blocks, only holding what the difficulty and
+//! feijoada consensus functions look at.
+
+use crate::core::consensus::{self, HeaderInfo};
+use crate::core::core::block::feijoada::{next_block_bottles, Deterministic, Feijoada, Policy};
+use crate::core::core::hash::ZERO_HASH;
+use crate::core::core::BlockHeader;
+use crate::core::global;
+use crate::core::pow::{Difficulty, PoWType};
+
+/// What the consensus rules need to know about a block.
+#[derive(Clone, Debug)]
+pub struct SimBlock {
+	pub height: u64,
+	/// Seconds since the epoch
+	pub timestamp: u64,
+	pub algo: PoWType,
+	/// Difficulty of this block alone, for each algorithm
+	pub difficulty: Difficulty,
+	pub total_difficulty: Difficulty,
+	pub secondary_scaling: u32,
+	pub is_secondary: bool,
+	pub bottles: Policy,
+}
+
+impl SimBlock {
+	/// Block built from a real header, used as the first block of a simulation.
+	pub fn from_header(header: &BlockHeader) -> SimBlock {
+		SimBlock {
+			height: header.height,
+			timestamp: header.timestamp.timestamp() as u64,
+			algo: (&header.pow.proof).into(),
+			difficulty: header.total_difficulty(),
+			total_difficulty: header.total_difficulty(),
+			secondary_scaling: header.pow.secondary_scaling,
+			is_secondary: header.pow.is_secondary(),
+			bottles: header.bottles.clone(),
+		}
+	}
+}
+
+/// Algorithm, bottles and policy the next block has to use.
+pub struct NextAlgo {
+	pub algo: PoWType,
+	pub bottles: Policy,
+	pub policy: u8,
+}
+
+pub struct SimChain {
+	blocks: Vec<SimBlock>,
+	policy_override: Option<u8>,
+}
+
+impl SimChain {
+	pub fn new(first: SimBlock, policy_override: Option<u8>) -> SimChain {
+		SimChain {
+			blocks: vec![first],
+			policy_override,
+		}
+	}
+
+	pub fn head(&self) -> &SimBlock {
+		self.blocks.last().expect("sim chain is never empty")
+	}
+
+	pub fn push(&mut self, block: SimBlock) {
+		self.blocks.push(block);
+	}
+
+	/// Policy index a block at the given height is mined with.
+	pub fn policy(&self, height: u64) -> u8 {
+		self.policy_override
+			.unwrap_or_else(|| global::get_emitted_policy(height))
+	}
+
+	/// Feijoada choice for the block following the head, as done by
+	/// `consensus::next_policy` when building and validating blocks.
+	pub fn next_algo(&self, height: u64) -> Result<NextAlgo, String> {
+		let policy = self.policy(height);
+		let weights = global::get_policies(policy)
+			.ok_or_else(|| format!("no policy {} in the policy config", policy))?;
+		let prev_bottles = &self.head().bottles;
+		let algo = Deterministic::choose_algo(&weights, prev_bottles);
+		let bottles = next_block_bottles(algo, prev_bottles);
+		Ok(NextAlgo {
+			algo,
+			bottles,
+			policy,
+		})
+	}
+
+	/// Difficulty (for every algorithm) and secondary scaling the block
+	/// following the head has to use, as checked in `validate_header`.
+	pub fn next_target(&self, height: u64) -> HeaderInfo {
+		let head = self.head();
+		let iter = SimDifficultyIter::new(&self.blocks);
+		if height < consensus::difficultyfix_height() {
+			consensus::next_difficulty(height, head.algo, iter)
+		} else {
+			consensus::next_difficulty_era1(height, head.algo, iter)
+		}
+	}
+}
+
+/// Same walk as the chain `DifficultyIter`: starts at the head and goes back
+/// through the previous blocks mined with the same algorithm, while the
+/// difficulty and timespan of each block are taken against its direct parent.
+pub struct SimDifficultyIter<'a> {
+	blocks: &'a [SimBlock],
+	next: Option<usize>,
+}
+
+impl<'a> SimDifficultyIter<'a> {
+	pub fn new(blocks: &'a [SimBlock]) -> SimDifficultyIter<'a> {
+		SimDifficultyIter {
+			blocks,
+			next: blocks.len().checked_sub(1),
+		}
+	}
+}
+
+impl<'a> Iterator for SimDifficultyIter<'a> {
+	type Item = HeaderInfo;
+
+	fn next(&mut self) -> Option<HeaderInfo> {
+		let idx = self.next?;
+		let block = &self.blocks[idx];
+		let prev_timespan = match idx {
+			0 => consensus::BLOCK_TIME_SEC,
+			_ => block
+				.timestamp
+				.saturating_sub(self.blocks[idx - 1].timestamp),
+		};
+
+		let mut head = idx;
+		self.next = None;
+		while head > 0 {
+			if self.blocks[head - 1].algo == block.algo {
+				self.next = Some(head - 1);
+				break;
+			}
+			head -= 1;
+		}
+
+		Some(HeaderInfo::new(
+			ZERO_HASH,
+			block.timestamp,
+			block.difficulty.clone(),
+			self.blocks[head].secondary_scaling,
+			block.is_secondary,
+			prev_timespan,
+		))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::core::core::block::feijoada::get_bottles_default;
+
+	fn block(height: u64, timestamp: u64, algo: PoWType, scaling: u32) -> SimBlock {
+		SimBlock {
+			height,
+			timestamp,
+			algo,
+			difficulty: Difficulty::from_num(height + 1),
+			total_difficulty: Difficulty::zero(),
+			secondary_scaling: scaling,
+			is_secondary: false,
+			bottles: get_bottles_default(),
+		}
+	}
+
+	#[test]
+	fn test_difficulty_iter_walks_same_algo() {
+		let blocks = vec![
+			block(0, 100, PoWType::RandomX, 10),
+			block(1, 130, PoWType::Cuckatoo, 11),
+			block(2, 200, PoWType::RandomX, 12),
+			block(3, 250, PoWType::Cuckatoo, 13),
+			block(4, 260, PoWType::Cuckatoo, 14),
+		];
+		let infos: Vec<_> = SimDifficultyIter::new(&blocks).collect();
+		// 4 -> 3 -> 1, then no cuckatoo block before 1
+		assert_eq!(
+			infos.iter().map(|i| i.timestamp).collect::<Vec<_>>(),
+			vec![260, 250, 130]
+		);
+		assert_eq!(
+			infos.iter().map(|i| i.prev_timespan).collect::<Vec<_>>(),
+			vec![10, 50, 30]
+		);
+		assert_eq!(infos[0].difficulty, Difficulty::from_num(5));
+		// scaling comes from the block right after the same algo parent
+		assert_eq!(
+			infos
+				.iter()
+				.map(|i| i.secondary_scaling)
+				.collect::<Vec<_>>(),
+			vec![14, 12, 10]
+		);
+		assert_eq!(SimDifficultyIter::new(&[]).count(), 0);
+	}
+}
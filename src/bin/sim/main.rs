@@ -0,0 +1,275 @@
+// Copyright 2020 The Epic Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Difficulty and feijoada policy simulator. Runs synthetic hashrate
+//! scenarios, or replays blocks exported with the `get_block_summaries` API, through
+//! the consensus functions and writes the per block algorithm, difficulty
+//! and solve time as CSV, to evaluate policy or difficulty changes before a
+//! fork.
+
+use epic_api as api;
+use epic_core as core;
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+
+use clap::{Arg, ArgMatches, Command};
+
+use crate::chain::{SimBlock, SimChain};
+use crate::core::core::block::feijoada::PolicyConfig;
+use crate::core::pow::PoWType;
+use crate::core::{genesis, global};
+use crate::scenario::{HashrateSchedule, Summary, Synthetic};
+
+mod chain;
+mod scenario;
+
+fn build_cli() -> Command {
+	Command::new("epic-sim")
+		.about("Simulates the Epic difficulty adjustment and feijoada algorithm selection.")
+		.author("The Epic Team")
+		.arg(
+			Arg::new("floonet")
+				.long("floonet")
+				.help("Use the Floonet consensus rules (as opposed to mainnet)")
+				.global(true)
+				.action(clap::ArgAction::SetTrue),
+		)
+		.arg(
+			Arg::new("policy_config")
+				.long("policy-config")
+				.help("JSON policy configuration to evaluate instead of the built-in one")
+				.global(true)
+				.value_name("FILE"),
+		)
+		.arg(
+			Arg::new("policy")
+				.long("policy")
+				.help("Use this policy index for every block instead of the scheduled ones")
+				.global(true)
+				.value_name("INDEX"),
+		)
+		.arg(
+			Arg::new("output")
+				.long("output")
+				.short('o')
+				.help("CSV file to write, standard output otherwise")
+				.global(true)
+				.value_name("FILE"),
+		)
+		.subcommand_required(true)
+		.subcommand(
+			Command::new("synthetic")
+				.about("Mines simulated blocks from per algorithm hashrates")
+				.arg(
+					Arg::new("hashrate")
+						.long("hashrate")
+						.help(
+							"Hashrate of an algorithm in difficulty units per second, from \
+							 an optional height, e.g. randomx=5000 or cuckatoo=20@100000",
+						)
+						.required(true)
+						.action(clap::ArgAction::Append)
+						.value_name("ALGO=RATE[@HEIGHT]"),
+				)
+				.arg(
+					Arg::new("blocks")
+						.long("blocks")
+						.short('n')
+						.help("Number of blocks to simulate")
+						.default_value("10080")
+						.value_name("COUNT"),
+				)
+				.arg(
+					Arg::new("start_height")
+						.long("start-height")
+						.help("Height of the first simulated block, mined on top of the genesis")
+						.default_value("1")
+						.value_name("HEIGHT"),
+				)
+				.arg(
+					Arg::new("difficulty")
+						.long("difficulty")
+						.help("Starting difficulty of an algorithm, the genesis one otherwise")
+						.action(clap::ArgAction::Append)
+						.value_name("ALGO=DIFFICULTY"),
+				)
+				.arg(
+					Arg::new("seed")
+						.long("seed")
+						.help("Seed of the solve time random generator")
+						.default_value("0")
+						.value_name("SEED"),
+				)
+				.arg(
+					Arg::new("deterministic")
+						.long("deterministic")
+						.help("Use the expected solve time of every block instead of drawing it")
+						.action(clap::ArgAction::SetTrue),
+				),
+		)
+		.subcommand(
+			Command::new("replay")
+				.about("Replays blocks exported from a node with the get_block_summaries API")
+				.arg(
+					Arg::new("input")
+						.help("get_block_summaries exports (listing, JSON-RPC response or array)")
+						.required(true)
+						.action(clap::ArgAction::Append)
+						.value_name("FILE"),
+				),
+		)
+}
+
+fn main() {
+	let exit_code = match real_main() {
+		Ok(()) => 0,
+		Err(e) => {
+			eprintln!("epic-sim: {}", e);
+			1
+		}
+	};
+	std::process::exit(exit_code);
+}
+
+fn real_main() -> Result<(), String> {
+	let args = build_cli().get_matches();
+
+	let chain_type = if args.get_flag("floonet") {
+		global::ChainTypes::Floonet
+	} else {
+		global::ChainTypes::Mainnet
+	};
+	global::set_mining_mode(chain_type.clone());
+	if let Some(path) = args.get_one::<String>("policy_config") {
+		let json = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+		let config: PolicyConfig =
+			serde_json::from_str(&json).map_err(|e| format!("{}: {}", path, e))?;
+		global::set_policy_config(config);
+	}
+	let policy = args
+		.get_one::<String>("policy")
+		.map(|p| {
+			p.parse::<u8>()
+				.map_err(|e| format!("invalid policy: {}", e))
+		})
+		.transpose()?;
+
+	let mut out: Box<dyn Write> = match args.get_one::<String>("output") {
+		Some(path) => Box::new(BufWriter::new(
+			File::create(path).map_err(|e| format!("{}: {}", path, e))?,
+		)),
+		None => Box::new(BufWriter::new(io::stdout())),
+	};
+	let genesis = match chain_type {
+		global::ChainTypes::Floonet => genesis::genesis_floo(),
+		_ => genesis::genesis_main(),
+	};
+
+	let summary = match args.subcommand() {
+		Some(("synthetic", args)) => {
+			let scenario = synthetic_scenario(args)?;
+			let mut first = SimBlock::from_header(&genesis.header);
+			first.height = scenario.start_height.saturating_sub(1);
+			if let Some(diffs) = args.get_many::<String>("difficulty") {
+				for d in diffs {
+					let (algo, diff) = parse_difficulty(d)?;
+					first.difficulty.num.insert(algo, diff);
+				}
+				first.total_difficulty = first.difficulty.clone();
+			}
+			scenario::run_synthetic(SimChain::new(first, policy), &scenario, &mut out)?
+		}
+		Some(("replay", args)) => {
+			let mut summaries = vec![];
+			for path in args.get_many::<String>("input").unwrap() {
+				let json = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+				summaries
+					.extend(scenario::parse_export(&json).map_err(|e| format!("{}: {}", path, e))?);
+			}
+			let starts_at_genesis = summaries.iter().any(|s| s.header.height == 0);
+			if !starts_at_genesis {
+				eprintln!(
+					"The export does not start at genesis, bottles and difficulty \
+					 windows are rebuilt from its first block."
+				);
+			}
+			let blocks = scenario::replay_blocks(
+				summaries,
+				Some(genesis.header.bottles.clone()).filter(|_| starts_at_genesis),
+			)?;
+			scenario::run_replay(blocks, policy, &mut out)?
+		}
+		_ => unreachable!("subcommand is required"),
+	};
+	out.flush().map_err(|e| e.to_string())?;
+	print_summary(&summary);
+	Ok(())
+}
+
+fn synthetic_scenario(args: &ArgMatches) -> Result<Synthetic, String> {
+	let mut hashrates = HashrateSchedule::default();
+	for step in args.get_many::<String>("hashrate").unwrap() {
+		hashrates.add(step)?;
+	}
+	let number = |name: &str| {
+		args.get_one::<String>(name)
+			.unwrap()
+			.parse::<u64>()
+			.map_err(|e| format!("invalid {}: {}", name, e))
+	};
+	Ok(Synthetic {
+		start_height: number("start_height")?.max(1),
+		blocks: number("blocks")?,
+		hashrates,
+		deterministic: args.get_flag("deterministic"),
+		seed: number("seed")?,
+	})
+}
+
+fn parse_difficulty(arg: &str) -> Result<(PoWType, u64), String> {
+	let (algo, diff) = arg
+		.split_once('=')
+		.ok_or_else(|| format!("invalid difficulty {}, expected ALGO=DIFFICULTY", arg))?;
+	let diff = diff
+		.parse::<u64>()
+		.map_err(|e| format!("invalid difficulty {}: {}", arg, e))?;
+	Ok((scenario::parse_algo(algo)?, diff.max(1)))
+}
+
+fn print_summary(summary: &Summary) {
+	eprintln!("{} blocks", summary.blocks);
+	let total_time: u64 = summary.per_algo.values().map(|(_, time)| time).sum();
+	if summary.blocks > 0 {
+		eprintln!(
+			"average solve time {:.1}s",
+			total_time as f64 / summary.blocks as f64
+		);
+	}
+	for (algo, (count, time)) in &summary.per_algo {
+		eprintln!(
+			"{:>9}: {:>8} blocks ({:>5.1}%), average solve time {:.1}s",
+			scenario::algo_name(*algo),
+			count,
+			100.0 * *count as f64 / summary.blocks as f64,
+			*time as f64 / *count as f64,
+		);
+	}
+	if summary.algo_mismatches > 0 || summary.difficulty_mismatches > 0 {
+		eprintln!(
+			"{} blocks with a different algorithm, {} with a different difficulty",
+			summary.algo_mismatches, summary.difficulty_mismatches
+		);
+	}
+}
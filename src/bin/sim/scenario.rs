@@ -0,0 +1,344 @@
+// Copyright 2020 The Epic Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Synthetic hashrate scenarios and replay of exported headers through the
+//! consensus functions, both writing one CSV row per block.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+
+use chrono::DateTime;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde_json::Value;
+
+use crate::api::{BlockListing, BlockSummaryPrintable};
+use crate::chain::{SimBlock, SimChain};
+use crate::core::consensus::SECOND_POW_EDGE_BITS;
+use crate::core::core::block::feijoada::{get_bottles_default, next_block_bottles, Policy};
+use crate::core::pow::{Difficulty, PoWType};
+
+const ALGOS: [PoWType; 4] = [
+	PoWType::Cuckaroo,
+	PoWType::Cuckatoo,
+	PoWType::RandomX,
+	PoWType::ProgPow,
+];
+
+pub fn algo_name(algo: PoWType) -> &'static str {
+	match algo {
+		PoWType::Cuckaroo => "cuckaroo",
+		PoWType::Cuckatoo => "cuckatoo",
+		PoWType::RandomX => "randomx",
+		PoWType::ProgPow => "progpow",
+	}
+}
+
+pub fn parse_algo(name: &str) -> Result<PoWType, String> {
+	ALGOS
+		.iter()
+		.find(|a| algo_name(**a) == name.to_lowercase())
+		.copied()
+		.ok_or_else(|| format!("unknown proof of work algorithm {}", name))
+}
+
+/// Hashrate of each algorithm as a step function of the height, in difficulty
+/// units per second (hashes per second for RandomX and ProgPow).
+#[derive(Default)]
+pub struct HashrateSchedule {
+	steps: HashMap<PoWType, BTreeMap<u64, f64>>,
+}
+
+impl HashrateSchedule {
+	/// Adds a step given as `algo=rate` or `algo=rate@height`.
+	pub fn add(&mut self, step: &str) -> Result<(), String> {
+		let (algo, rest) = step
+			.split_once('=')
+			.ok_or_else(|| format!("invalid hashrate {}, expected ALGO=RATE[@HEIGHT]", step))?;
+		let (rate, height) = match rest.split_once('@') {
+			Some((rate, height)) => (
+				rate,
+				height
+					.parse::<u64>()
+					.map_err(|e| format!("invalid height in {}: {}", step, e))?,
+			),
+			None => (rest, 0),
+		};
+		let rate = rate
+			.parse::<f64>()
+			.map_err(|e| format!("invalid rate in {}: {}", step, e))?;
+		if rate.is_nan() || rate < 0.0 {
+			return Err(format!("invalid rate in {}", step));
+		}
+		self.steps
+			.entry(parse_algo(algo)?)
+			.or_default()
+			.insert(height, rate);
+		Ok(())
+	}
+
+	/// Hashrate of the algorithm at the given height, if any was set.
+	pub fn rate(&self, algo: PoWType, height: u64) -> Option<f64> {
+		self.steps
+			.get(&algo)
+			.and_then(|s| s.range(..=height).next_back())
+			.map(|(_, rate)| *rate)
+	}
+}
+
+/// Per algorithm totals printed once a run is over.
+#[derive(Default)]
+pub struct Summary {
+	pub blocks: u64,
+	pub per_algo: BTreeMap<PoWType, (u64, u64)>,
+	/// Replay only, blocks where the simulated algorithm differs
+	pub algo_mismatches: u64,
+	/// Replay only, blocks where the recomputed difficulty differs
+	pub difficulty_mismatches: u64,
+}
+
+impl Summary {
+	fn add(&mut self, algo: PoWType, solve_time: u64) {
+		self.blocks += 1;
+		let entry = self.per_algo.entry(algo).or_insert((0, 0));
+		entry.0 += 1;
+		entry.1 += solve_time;
+	}
+}
+
+pub struct Synthetic {
+	pub start_height: u64,
+	pub blocks: u64,
+	pub hashrates: HashrateSchedule,
+	/// Use the expected solve time instead of drawing it at random
+	pub deterministic: bool,
+	pub seed: u64,
+}
+
+/// Mines the scenario blocks on top of the chain head, picking each algorithm
+/// with the feijoada and drawing solve times from the configured hashrates.
+pub fn run_synthetic(
+	mut chain: SimChain,
+	scenario: &Synthetic,
+	out: &mut dyn Write,
+) -> Result<Summary, String> {
+	let mut rng = StdRng::seed_from_u64(scenario.seed);
+	let mut summary = Summary::default();
+	writeln!(
+		out,
+		"height,timestamp,solve_time,policy,algo,difficulty,{},secondary_scaling",
+		ALGOS
+			.iter()
+			.map(|a| format!("{}_difficulty", algo_name(*a)))
+			.collect::<Vec<_>>()
+			.join(",")
+	)
+	.map_err(|e| e.to_string())?;
+
+	for height in scenario.start_height..scenario.start_height + scenario.blocks {
+		let next = chain.next_algo(height)?;
+		let target = chain.next_target(height);
+		let difficulty = target.difficulty.to_num(next.algo);
+		let rate = match scenario.hashrates.rate(next.algo, height) {
+			Some(rate) if rate > 0.0 => rate,
+			_ => {
+				return Err(format!(
+					"no hashrate for {} at height {}",
+					algo_name(next.algo),
+					height
+				))
+			}
+		};
+		let expected = difficulty as f64 / rate;
+		let solve_time = if scenario.deterministic {
+			expected
+		} else {
+			// exponential distribution, the random value is in [0, 1)
+			-(1.0 - rng.random::<f64>()).ln() * expected
+		};
+		let solve_time = (solve_time.round() as u64).max(1);
+
+		let prev = chain.head();
+		let block = SimBlock {
+			height,
+			timestamp: prev.timestamp + solve_time,
+			algo: next.algo,
+			total_difficulty: prev.total_difficulty.clone() + target.difficulty.clone(),
+			difficulty: target.difficulty,
+			secondary_scaling: target.secondary_scaling,
+			is_secondary: next.algo == PoWType::Cuckatoo,
+			bottles: next.bottles,
+		};
+		writeln!(
+			out,
+			"{},{},{},{},{},{},{},{}",
+			height,
+			block.timestamp,
+			solve_time,
+			next.policy,
+			algo_name(next.algo),
+			difficulty,
+			ALGOS
+				.iter()
+				.map(|a| block.difficulty.to_num(*a).to_string())
+				.collect::<Vec<_>>()
+				.join(","),
+			block.secondary_scaling,
+		)
+		.map_err(|e| e.to_string())?;
+		summary.add(next.algo, solve_time);
+		chain.push(block);
+	}
+	Ok(summary)
+}
+
+/// Block summaries from one or more `get_block_summaries` exports, accepting the
+/// listing itself, its JSON-RPC response or a plain array of summaries.
+pub fn parse_export(json: &str) -> Result<Vec<BlockSummaryPrintable>, String> {
+	let value: Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+	let value = match value.get("result") {
+		Some(result) => result.get("Ok").unwrap_or(result).clone(),
+		None => value,
+	};
+	if value.is_array() {
+		serde_json::from_value(value).map_err(|e| e.to_string())
+	} else {
+		serde_json::from_value::<BlockListing>(value)
+			.map(|listing| listing.blocks)
+			.map_err(|e| e.to_string())
+	}
+}
+
+/// Converts exported summaries, sorted by height and without gaps, into
+/// simulated blocks. Bottles are rebuilt from the algorithms, starting from
+/// `first_bottles` (the genesis bottles when the export starts at genesis).
+pub fn replay_blocks(
+	mut summaries: Vec<BlockSummaryPrintable>,
+	first_bottles: Option<Policy>,
+) -> Result<Vec<SimBlock>, String> {
+	summaries.sort_by_key(|s| s.header.height);
+	summaries.dedup_by_key(|s| s.header.height);
+	let mut blocks: Vec<SimBlock> = Vec::with_capacity(summaries.len());
+	for s in summaries {
+		let height = s.header.height;
+		let prev_bottles = match blocks.last() {
+			Some(prev) if prev.height + 1 != height => {
+				return Err(format!(
+					"missing blocks between {} and {}",
+					prev.height, height
+				))
+			}
+			Some(prev) => Some(prev.bottles.clone()),
+			None => None,
+		};
+		let timestamp = DateTime::parse_from_rfc3339(&s.header.timestamp)
+			.map_err(|e| format!("invalid timestamp at height {}: {}", height, e))?
+			.timestamp() as u64;
+		let bottles = match prev_bottles {
+			Some(prev) => next_block_bottles(s.pow_algorithm, &prev),
+			None => first_bottles.clone().unwrap_or_else(get_bottles_default),
+		};
+		blocks.push(SimBlock {
+			height,
+			timestamp,
+			algo: s.pow_algorithm,
+			difficulty: Difficulty::from_dic_number(s.difficulty),
+			total_difficulty: Difficulty::from_dic_number(s.header.total_difficulty),
+			secondary_scaling: s.header.secondary_scaling,
+			is_secondary: s.header.edge_bits == SECOND_POW_EDGE_BITS,
+			bottles,
+		});
+	}
+	Ok(blocks)
+}
+
+/// Recomputes, for every block after the first one, the algorithm the
+/// feijoada picks and the difficulty the consensus rules require, next to
+/// what the real chain did.
+pub fn run_replay(
+	blocks: Vec<SimBlock>,
+	policy_override: Option<u8>,
+	out: &mut dyn Write,
+) -> Result<Summary, String> {
+	let mut summary = Summary::default();
+	writeln!(
+		out,
+		"height,timestamp,solve_time,policy,algo,sim_algo,difficulty,sim_difficulty,\
+		 secondary_scaling,sim_secondary_scaling"
+	)
+	.map_err(|e| e.to_string())?;
+
+	let mut blocks = blocks.into_iter();
+	let first = match blocks.next() {
+		Some(first) => first,
+		None => return Ok(summary),
+	};
+	let mut chain = SimChain::new(first, policy_override);
+	for block in blocks {
+		let height = block.height;
+		let next = chain.next_algo(height)?;
+		let target = chain.next_target(height);
+		let solve_time = block.timestamp.saturating_sub(chain.head().timestamp);
+		let difficulty = block.difficulty.to_num(block.algo);
+		let sim_difficulty = target.difficulty.to_num(block.algo);
+		if next.algo != block.algo {
+			summary.algo_mismatches += 1;
+		}
+		if sim_difficulty != difficulty {
+			summary.difficulty_mismatches += 1;
+		}
+		writeln!(
+			out,
+			"{},{},{},{},{},{},{},{},{},{}",
+			height,
+			block.timestamp,
+			solve_time,
+			next.policy,
+			algo_name(block.algo),
+			algo_name(next.algo),
+			difficulty,
+			sim_difficulty,
+			block.secondary_scaling,
+			target.secondary_scaling,
+		)
+		.map_err(|e| e.to_string())?;
+		summary.add(block.algo, solve_time);
+		chain.push(block);
+	}
+	Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_hashrate_schedule() {
+		let mut schedule = HashrateSchedule::default();
+		schedule.add("randomx=100").unwrap();
+		schedule.add("RandomX=400@1000").unwrap();
+		schedule.add("cuckatoo=2.5@10").unwrap();
+		assert!(schedule.add("sha256=1").is_err());
+		assert!(schedule.add("randomx").is_err());
+		assert!(schedule.add("randomx=-1").is_err());
+		assert!(schedule.add("randomx=1@x").is_err());
+
+		assert_eq!(schedule.rate(PoWType::RandomX, 0), Some(100.0));
+		assert_eq!(schedule.rate(PoWType::RandomX, 999), Some(100.0));
+		assert_eq!(schedule.rate(PoWType::RandomX, 1000), Some(400.0));
+		assert_eq!(schedule.rate(PoWType::Cuckatoo, 9), None);
+		assert_eq!(schedule.rate(PoWType::Cuckatoo, 10), Some(2.5));
+		assert_eq!(schedule.rate(PoWType::ProgPow, 10), None);
+	}
+}
@@ -524,7 +524,7 @@ mod mine_chain {
 					let reward = libtx::reward::output(kc, &ProofBuilder::new(kc), &pk, 0, false, n).unwrap();
 					reward_outputs.push(reward.0.clone());
 					let mut b = if consensus::is_foundation_height(prev.height + 1) {
-						let foundation = load_foundation_output(prev.height + 1);
+						let foundation = load_foundation_output(prev.height + 1).unwrap();
 						core::core::Block::from_coinbases(
 							&prev,
 							vec![],
@@ -752,7 +752,7 @@ mod mine_chain {
 				let key_id = epic_keychain::ExtKeychainPath::new(3, 0, 0, diff as u32, 0).to_identifier();
 				let fees = transactions.iter().map(|tx| tx.fee()).sum();
 				let mining_reward = libtx::reward::output(kc, &ProofBuilder::new(kc), &key_id, fees, false, height).unwrap();
-				let foundation_reward = load_foundation_output(height);
+				let foundation_reward = load_foundation_output(height).unwrap();
 				// Creating the block
 				let hash = chain.header_pmmr().read().get_header_hash_by_height(pow::randomx::rx_current_seed_height(prev.height + 1)).unwrap();
 				let mut block = prepare_block_with_coinbase(&prev, diff, transactions, mining_reward, (foundation_reward.output, foundation_reward.kernel), hash);
@@ -1170,7 +1170,7 @@ mod mine_chain {
 
 			then regex "I try to load the foundation on the height <([0-9]+)> with commit <([A-Za-z0-9]+)>" |_world, matches, _step| {
 				let height = matches[1].parse().unwrap();
-				let cb_data = load_foundation_output(height);
+				let cb_data = load_foundation_output(height).unwrap();
 				let commit = matches[2].as_str();
 				let target_comment = format!("{:?}", cb_data.output.commitment());
 				assert_eq!(format!("Commitment({})", commit), target_comment);
@@ -1291,7 +1291,7 @@ mod mine_chain {
 			  let expected: String = matches[3].parse().unwrap();
 
 				let keyid_dest = epic_keychain::ExtKeychainPath::new(1, 2, 0, 0, 0).to_identifier();
-				let cbdata_foundation = load_foundation_output(height);
+				let cbdata_foundation = load_foundation_output(height).unwrap();
 
 				let pb = ProofBuilder::new(kc_foundation);
 				let foundation_reward = consensus::add_reward_foundation(height) + plus;
@@ -1416,7 +1416,7 @@ mod mine_chain {
 			genesis,
 			pow::verify_size,
 			false,
-			//Arc::new(Mutex::new(StopState::new())),
+			Arc::new(global::ChainParams::current()),
 		)
 		.unwrap()
 	}
@@ -1707,7 +1707,7 @@ mod mine_chain {
 		.unwrap();
 
 		let mut b = if consensus::is_foundation_height(prev.height + 1) {
-			let foundation = load_foundation_output(prev.height + 1);
+			let foundation = load_foundation_output(prev.height + 1).unwrap();
 			core::core::Block::from_coinbases(
 				&prev,
 				txs.into_iter().cloned().collect(),
@@ -1763,7 +1763,7 @@ mod mine_chain {
 		.unwrap();
 
 		let mut b = if consensus::is_foundation_height(prev.height + 1) {
-			let foundation = load_foundation_output(prev.height + 1);
+			let foundation = load_foundation_output(prev.height + 1).unwrap();
 			core::core::Block::from_coinbases(
 				&prev,
 				txs.into_iter().cloned().collect(),
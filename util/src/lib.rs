@@ -28,7 +28,7 @@ extern crate lazy_static;
 #[macro_use]
 extern crate serde_derive;
 // Re-export so only has to be included once
-pub use parking_lot::Mutex;
+pub use parking_lot::{Condvar, Mutex};
 pub use parking_lot::{RwLock, RwLockReadGuard};
 
 // Re-export so only has to be included once